@@ -0,0 +1,63 @@
+// Helpers shared by the front-end binaries
+
+use protochess_engine_rs::{Engine, MoveInfo};
+use protochess_engine_rs::utils::notation::tuple_to_rank_file;
+
+/// Converts a move to coordinate notation ("e2e4", "e7e8q", "P@e4"), as used by UCI and XBoard.
+/// The engine represents castling as the king moving to the square of the rook, which is also
+/// the Chess960 format. Otherwise, castling is represented as the king moving 2 squares.
+/// If the move relocates a neutral piece (like the duck in Duck chess), its destination is appended as in "e2e4,@d5".
+pub fn format_move(engine: &mut Engine, mv: &MoveInfo, chess960: bool) -> String {
+    let neutral = match mv.neutral_to {
+        Some(neutral_to) => format!(",@{}", tuple_to_rank_file(neutral_to)),
+        None => String::new(),
+    };
+    if let Some(drop) = mv.drop {
+        return format!("{}@{}{neutral}", drop.to_ascii_uppercase(), tuple_to_rank_file(mv.to));
+    }
+    let mut to = mv.to;
+    if !chess960 {
+        if let Some(king_to) = castling_destination(engine, mv) {
+            to = king_to;
+        }
+    }
+    let promotion = {
+        if let Some(promo) = mv.promotion { promo.to_lowercase().to_string() }
+        else { String::new() }
+    };
+    format!("{}{}{}{neutral}", tuple_to_rank_file(mv.from), tuple_to_rank_file(to), promotion)
+}
+
+/// Finds the legal move that corresponds to the given coordinate notation string.
+/// Castling is accepted both in standard and Chess960 notation.
+pub fn parse_move(engine: &mut Engine, mv_str: &str, chess960: bool) -> Option<MoveInfo> {
+    let mv_str = mv_str.to_lowercase();
+    let candidates: Vec<MoveInfo> = engine.legal_moves().into_iter().flat_map(|list| list.moves).collect();
+    candidates.into_iter().find(|mv| {
+        format_move(engine, mv, chess960).to_lowercase() == mv_str || format_move(engine, mv, true).to_lowercase() == mv_str
+    })
+}
+
+/// If the move is a castling move, returns the square where the king lands
+fn castling_destination(engine: &mut Engine, mv: &MoveInfo) -> Option<(u8, u8)> {
+    let player = engine.player_to_move();
+    let king_id = engine.get_piece_at(mv.from).ok()?;
+    let rook_id = engine.get_piece_at(mv.to).ok()?;
+    let piece_types = &engine.get_state().initial_state.piece_types;
+    let owner = |id| piece_types.iter().any(|def| def.ids[player as usize] == Some(id));
+    // Castling is the only legal move where a piece lands on a square occupied by a friendly piece
+    if !owner(king_id) || !owner(rook_id) {
+        return None;
+    }
+    let king_def = piece_types.iter().find(|def| def.ids[player as usize] == Some(king_id))?;
+    // In free castling, the destination is part of the move
+    if let Some(king_to) = mv.intermediate {
+        return Some(king_to);
+    }
+    let (queenside, kingside) = king_def.castle_files?;
+    if king_def.castle_vertically {
+        Some((mv.from.0, if mv.to.1 > mv.from.1 { kingside } else { queenside }))
+    } else {
+        Some((if mv.to.0 > mv.from.0 { kingside } else { queenside }, mv.from.1))
+    }
+}
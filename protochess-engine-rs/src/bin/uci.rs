@@ -0,0 +1,327 @@
+// UCI (Universal Chess Interface) front-end for the engine, so that it can be used from chess GUIs
+// and tournament managers like cutechess-cli or Arena.
+// See <https://backscattering.de/chess/uci/> for the protocol specification.
+//
+// Usage: cargo run --release --bin uci
+//
+// Supported variants (UCI_Variant option): chess, atomic, horde, antichess, kingofthehill, racingkings, 3check, 5check, crazyhouse,
+// duck, marseillais, progressive, monster. In Duck chess, the square where the duck is moved is appended to the move, as in
+// "e2e4,@d5". In the variants with several moves per turn, each move is sent separately: after a `bestmove`, the engine
+// still has the move until its turn is over.
+// Dark chess is not offered: the GUI sends the whole position, so the engine would see the pieces that are hidden
+// from its player (see `Engine::get_best_move_sampled()` for searching without that information).
+
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use instant::Duration;
+
+use protochess_engine_rs::{Engine, GameState, SearchLimits, SearchInfo, MakeMoveResultFlag};
+use protochess_engine_rs::position::create::variant_factory::VariantFactory;
+use protochess_engine_rs::types::{GameMode, Player, Depth};
+
+mod common;
+use common::{format_move, parse_move};
+
+const ENGINE_NAME: &str = "Protochess";
+const ENGINE_AUTHOR: &str = "raytr, pol-rivero";
+const VARIANTS: [&str; 13] = [
+    "chess", "atomic", "horde", "antichess", "kingofthehill", "racingkings", "3check", "5check", "crazyhouse", "duck",
+    "marseillais", "progressive", "monster",
+];
+
+pub fn main() {
+    let mut uci = UciEngine::new();
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.handle_command(&line) {
+            break;
+        }
+    }
+    uci.stop_search();
+}
+
+
+struct RunningSearch {
+    stop_flag: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+struct UciEngine {
+    engine: Engine,
+    variant: GameMode,
+    chess960: bool,
+    search: Option<RunningSearch>,
+}
+
+/// Parameters of the `go` command
+#[derive(Debug, Default)]
+pub(crate) struct GoParams {
+    pub(crate) depth: Option<Depth>,
+    pub(crate) movetime: Option<u64>,
+    pub(crate) wtime: Option<u64>,
+    pub(crate) btime: Option<u64>,
+    pub(crate) winc: Option<u64>,
+    pub(crate) binc: Option<u64>,
+    pub(crate) movestogo: Option<u64>,
+    pub(crate) infinite: bool,
+}
+
+impl UciEngine {
+    fn new() -> Self {
+        UciEngine {
+            engine: Engine::default(),
+            variant: GameMode::Standard,
+            chess960: false,
+            search: None,
+        }
+    }
+
+    /// Handles a single line of input. Returns `false` if the engine should quit.
+    fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+        match tokens[0] {
+            "uci" => self.print_id(),
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.reset_position();
+            },
+            "setoption" => {
+                self.stop_search();
+                self.set_option(&tokens[1..]);
+            },
+            "position" => {
+                self.stop_search();
+                if let Err(e) = self.set_position(&tokens[1..]) {
+                    println!("info string Invalid position: {e}");
+                }
+            },
+            "go" => {
+                self.stop_search();
+                self.go(&tokens[1..]);
+            },
+            "stop" => self.stop_search(),
+            "ponderhit" => {},
+            "d" => println!("{}", self.engine),
+            "quit" => return false,
+            _ => println!("info string Unknown command: {line}"),
+        }
+        true
+    }
+
+    fn print_id(&self) {
+        println!("id name {ENGINE_NAME}");
+        println!("id author {ENGINE_AUTHOR}");
+        println!("option name Threads type spin default {} min 1 max {}", Engine::get_max_threads(), Engine::get_max_threads());
+        println!("option name UCI_Chess960 type check default false");
+        let vars: Vec<String> = VARIANTS.iter().map(|v| format!("var {v}")).collect();
+        println!("option name UCI_Variant type combo default chess {}", vars.join(" "));
+        println!("uciok");
+    }
+
+    fn set_option(&mut self, tokens: &[&str]) {
+        // Format: name <id> [value <x>]. Both the name and the value can contain spaces
+        let value_pos = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        if tokens.is_empty() || tokens[0] != "name" {
+            println!("info string Invalid setoption command");
+            return;
+        }
+        let name = tokens[1..value_pos].join(" ");
+        let value = tokens.get(value_pos+1..).map(|v| v.join(" ")).unwrap_or_default();
+        match name.to_lowercase().as_str() {
+            "threads" => {
+                let result = value.parse::<u32>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|n| self.engine.set_num_threads(n));
+                if let Err(e) = result {
+                    println!("info string Invalid number of threads: {e}");
+                }
+            },
+            "uci_chess960" => self.chess960 = value.eq_ignore_ascii_case("true"),
+            "uci_variant" => {
+                match parse_variant(&value) {
+                    Ok(variant) => {
+                        self.variant = variant;
+                        self.reset_position();
+                    },
+                    Err(e) => println!("info string {e}"),
+                }
+            },
+            _ => println!("info string Unknown option: {name}"),
+        }
+    }
+
+    fn reset_position(&mut self) {
+        let initial_state = VariantFactory::new(self.variant).make_initial_state();
+        let state = GameState { initial_state, initial_fen: None, move_history: vec![] };
+        // The hardcoded variants are always valid
+        let _ = self.engine.set_state(state).unwrap();
+    }
+
+    fn set_position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_pos = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+        let initial_fen = match tokens.first() {
+            Some(&"startpos") => None,
+            Some(&"fen") => Some(tokens[1..moves_pos].join(" ")),
+            _ => return Err("Expected 'startpos' or 'fen'".to_string()),
+        };
+        let initial_state = VariantFactory::new(self.variant).make_initial_state();
+        let state = GameState { initial_state, initial_fen, move_history: vec![] };
+        let _ = self.engine.set_state(state)?;
+        for mv_str in tokens.iter().skip(moves_pos + 1) {
+            let mv = parse_move(&mut self.engine, mv_str, self.chess960)
+                .ok_or(format!("Illegal move: {mv_str}"))?;
+            let result = self.engine.make_move(&mv);
+            if result.flag == MakeMoveResultFlag::IllegalMove {
+                return Err(format!("Illegal move: {mv_str}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, tokens: &[&str]) {
+        let params = parse_go(tokens);
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            max_depth: params.depth.unwrap_or(Depth::MAX),
+            max_time: if params.infinite { None } else { allocate_time(&params, self.engine.player_to_move()) },
+            stop_flag: Some(stop_flag.clone()),
+            info_sender: None,
+        };
+        let engine = self.engine.clone();
+        let chess960 = self.chess960;
+        let infinite = params.infinite;
+        let thread_stop_flag = stop_flag.clone();
+        let handle = thread::spawn(move || {
+            run_search(engine, limits, chess960, infinite, &thread_stop_flag);
+        });
+        self.search = Some(RunningSearch { stop_flag, handle });
+    }
+
+    /// Stops the current search (if any) and waits until the best move has been printed
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop_flag.store(true, Ordering::Relaxed);
+            search.handle.join().ok();
+        }
+    }
+}
+
+
+/// Searches the position and prints the `info` lines and the `bestmove` line
+fn run_search(mut engine: Engine, mut limits: SearchLimits, chess960: bool, infinite: bool, stop_flag: &AtomicBool) {
+    let (info_sender, info_receiver) = mpsc::channel();
+    limits.info_sender = Some(info_sender);
+    let mut search_engine = engine.clone();
+    let search_handle = thread::spawn(move || search_engine.get_best_move_limits(&limits));
+
+    // The channel is closed when the search finishes
+    let mut last_info = None;
+    for info in info_receiver {
+        println!("{}", format_info(&engine, &info, chess960));
+        last_info = Some(info);
+    }
+    let result = search_handle.join().unwrap_or_else(|_| Err("The search thread panicked".to_string()));
+
+    // In infinite mode, the best move must not be printed until the GUI sends "stop"
+    while infinite && !stop_flag.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    match result {
+        Ok((best_move, _score, _depth)) => {
+            let best_str = format_move(&mut engine, &best_move, chess960);
+            let ponder = last_info
+                .filter(|info| info.pv.len() > 1 && info.pv[0] == best_move)
+                .map(|info| {
+                    let _ = engine.make_move(&best_move);
+                    let ponder_str = format_move(&mut engine, &info.pv[1], chess960);
+                    format!(" ponder {ponder_str}")
+                })
+                .unwrap_or_default();
+            println!("bestmove {best_str}{ponder}");
+        },
+        Err(e) => {
+            println!("info string {e}");
+            println!("bestmove 0000");
+        },
+    }
+}
+
+fn format_info(engine: &Engine, info: &SearchInfo, chess960: bool) -> String {
+    let score = {
+        if let Some(moves) = info.mate_in() { format!("mate {moves}") }
+        else { format!("cp {}", info.score) }
+    };
+    let millis = info.elapsed.as_millis() as u64;
+    let nps = info.nodes * 1000 / std::cmp::max(millis, 1);
+    // Play the PV on a copy of the engine, since castling moves depend on the position
+    let mut pv_engine = engine.clone();
+    let mut pv = Vec::new();
+    for mv in &info.pv {
+        pv.push(format_move(&mut pv_engine, mv, chess960));
+        if pv_engine.make_move(mv).flag == MakeMoveResultFlag::IllegalMove {
+            break;
+        }
+    }
+    format!("info depth {} score {score} nodes {} nps {nps} time {millis} pv {}", info.depth, info.nodes, pv.join(" "))
+}
+
+
+fn parse_variant(name: &str) -> Result<GameMode, String> {
+    if name.eq_ignore_ascii_case("chess") || name.eq_ignore_ascii_case("standard") {
+        Ok(GameMode::Standard)
+    } else {
+        GameMode::try_from(name)
+    }
+}
+
+pub(crate) fn parse_go(tokens: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut iter = tokens.iter();
+    while let Some(token) = iter.next() {
+        let mut next_num = || iter.next().and_then(|v| v.parse::<i64>().ok()).map(|v| std::cmp::max(v, 0) as u64);
+        match *token {
+            "depth" => params.depth = next_num().map(|d| std::cmp::min(d, Depth::MAX as u64) as Depth),
+            "movetime" => params.movetime = next_num(),
+            "wtime" => params.wtime = next_num(),
+            "btime" => params.btime = next_num(),
+            "winc" => params.winc = next_num(),
+            "binc" => params.binc = next_num(),
+            "movestogo" => params.movestogo = next_num(),
+            "infinite" => params.infinite = true,
+            _ => {},
+        }
+    }
+    params
+}
+
+/// Decides how much time to spend on the current move. Returns `None` if there is no time limit.
+pub(crate) fn allocate_time(params: &GoParams, player: Player) -> Option<Duration> {
+    // Time reserved for communication with the GUI
+    const OVERHEAD_MS: u64 = 30;
+
+    if let Some(movetime) = params.movetime {
+        return Some(Duration::from_millis(movetime.saturating_sub(OVERHEAD_MS).max(1)));
+    }
+    let (time, inc) = {
+        if player == 0 { (params.wtime?, params.winc.unwrap_or(0)) }
+        else { (params.btime?, params.binc.unwrap_or(0)) }
+    };
+    let moves_to_go = params.movestogo.unwrap_or(30).max(1);
+    let budget = time / moves_to_go + inc * 3 / 4;
+    // Never use more than half of the remaining time
+    let max_time = time.saturating_sub(OVERHEAD_MS) / 2;
+    Some(Duration::from_millis(std::cmp::min(budget, max_time).max(1)))
+}
@@ -0,0 +1,687 @@
+// XBoard / WinBoard (CECP v2) front-end for the engine.
+// Unlike UCI, this protocol allows the engine to describe its pieces to the GUI (using Betza notation),
+// so it can be used to play custom variants with fairy pieces.
+// See <https://www.gnu.org/software/xboard/engine-intf.html> for the protocol specification.
+//
+// Usage: cargo run --release --bin xboard [--variant <path>]
+//
+// A custom variant file (see docs/VARIANT_FILE.md) can be loaded with the --variant argument, or from the GUI
+// with the VariantFile option. It's offered as an additional variant, named after the file.
+// Loading variant files requires the `serde` feature.
+
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use instant::Duration;
+
+use protochess_engine_rs::{Engine, GameState, InitialState, MoveInfo, SearchLimits, SearchInfo};
+use protochess_engine_rs::{MakeMoveResult, MakeMoveResultFlag, MakeMoveResultWinner};
+use protochess_engine_rs::piece::betza::to_betza;
+use protochess_engine_rs::position::create::variant_factory::VariantFactory;
+use protochess_engine_rs::position::create::fen::FenData;
+use protochess_engine_rs::types::{GameMode, Player, BCoord, Depth, Centipawns};
+
+mod common;
+use common::{format_move, parse_move};
+
+const ENGINE_NAME: &str = "Protochess";
+// XBoard variant names and the corresponding game modes. Dark chess is not offered: the GUI sends the whole position,
+// so the engine would see the pieces that are hidden from its player.
+const VARIANTS: [(&str, GameMode); 14] = [
+    ("normal", GameMode::Standard),
+    ("atomic", GameMode::Atomic),
+    ("horde", GameMode::Horde),
+    ("antichess", GameMode::Antichess),
+    ("giveaway", GameMode::Antichess),
+    ("kingofthehill", GameMode::KingOfTheHill),
+    ("racingkings", GameMode::RacingKings),
+    ("3check", GameMode::ThreeCheck),
+    ("5check", GameMode::FiveCheck),
+    ("crazyhouse", GameMode::Crazyhouse),
+    ("duck", GameMode::Duck),
+    // The engine sends one "move" command for each move of its turn
+    ("marseillais", GameMode::Marseillais),
+    ("progressive", GameMode::Progressive),
+    ("monster", GameMode::Monster),
+];
+// Internal piece types of XBoard, in the order used by the `setup` command.
+// The first one is treated as a pawn and the last one as a king.
+const XBOARD_PIECE_ORDER: &str = "PNBRQFEACWMOHIJGDVLSUK";
+const XBOARD_PIECE_SLOTS: usize = XBOARD_PIECE_ORDER.len();
+// Score that XBoard interprets as a mate
+const XBOARD_MATE_SCORE: Centipawns = 100000;
+// Longer times sent by the GUI are clamped to this value
+const MAX_TIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let variant_file = args.iter().position(|arg| arg == "--variant").and_then(|i| args.get(i + 1));
+
+    let (sender, receiver) = mpsc::channel();
+    let stdin_sender = sender.clone();
+    thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if stdin_sender.send(Event::Command(line)).is_err() {
+                return;
+            }
+        }
+        stdin_sender.send(Event::Command("quit".to_string())).ok();
+    });
+
+    let mut xboard = XBoardEngine::new(sender);
+    if let Some(path) = variant_file {
+        if let Err(e) = xboard.load_variant_file(path) {
+            eprintln!("Invalid variant file: {e}");
+            std::process::exit(1);
+        }
+    }
+    for event in receiver {
+        let keep_running = match event {
+            Event::Command(line) => xboard.handle_command(&line),
+            Event::SearchDone(id, result) => {
+                xboard.search_done(id, result);
+                true
+            },
+        };
+        if !keep_running {
+            break;
+        }
+    }
+    xboard.stop_search();
+}
+
+
+type SearchResult = Result<(MoveInfo, Centipawns, Depth), String>;
+
+pub(crate) enum Event {
+    /// A line received from the GUI
+    Command(String),
+    /// A search has finished. Contains the id of the search and the result.
+    SearchDone(u64, SearchResult),
+}
+
+struct RunningSearch {
+    id: u64,
+    stop_flag: Arc<AtomicBool>,
+}
+
+/// Time control set with the `level` command
+struct TimeControl {
+    /// Number of moves per session, or 0 for the whole game
+    moves_per_session: u32,
+    base: Duration,
+    increment: Duration,
+}
+
+pub(crate) struct XBoardEngine {
+    pub(crate) engine: Engine,
+    /// The rules of the current variant
+    variant: InitialState,
+    /// Variant loaded from a file, along with its name
+    custom_variant: Option<(String, InitialState)>,
+    /// Player to move and fullmove number at the start of the game, used to count the moves of the engine
+    start_player: Player,
+    start_fullmove: u16,
+    /// The player controlled by the engine, or `None` in force mode
+    engine_player: Option<Player>,
+    game_over: bool,
+    post: bool,
+    time_control: TimeControl,
+    /// Remaining time in the engine's clock (set with the `time` command)
+    remaining_time: Option<Duration>,
+    /// Fixed time per move (set with the `st` command)
+    time_per_move: Option<Duration>,
+    /// Maximum search depth (set with the `sd` command)
+    max_depth: Option<Depth>,
+    search: Option<RunningSearch>,
+    next_search_id: u64,
+    event_sender: Sender<Event>,
+}
+
+impl XBoardEngine {
+    pub(crate) fn new(event_sender: Sender<Event>) -> Self {
+        XBoardEngine {
+            engine: Engine::default(),
+            variant: VariantFactory::new(GameMode::Standard).make_initial_state(),
+            custom_variant: None,
+            start_player: 0,
+            start_fullmove: 1,
+            engine_player: Some(1),
+            game_over: false,
+            post: false,
+            time_control: TimeControl {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::from_secs(0),
+            },
+            remaining_time: None,
+            time_per_move: None,
+            max_depth: None,
+            search: None,
+            next_search_id: 0,
+            event_sender,
+        }
+    }
+
+    /// Handles a single line of input. Returns `false` if the engine should quit.
+    pub(crate) fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            return true;
+        }
+        let args = &tokens[1..];
+        match tokens[0] {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "memory" | "otim" | "draw" | "hint" | "bk" => {},
+            "protover" => self.print_features(),
+            "new" => {
+                self.stop_search();
+                self.variant = VariantFactory::new(GameMode::Standard).make_initial_state();
+                self.reset_position();
+                self.engine_player = Some(1);
+                self.time_per_move = None;
+                self.max_depth = None;
+            },
+            "variant" => {
+                self.stop_search();
+                let name = args.first().copied().unwrap_or_default();
+                match self.custom_variant.as_ref().filter(|(v, _)| v == name) {
+                    Some((_, initial_state)) => {
+                        self.variant = initial_state.clone();
+                        self.reset_position();
+                        self.print_setup();
+                    },
+                    None => match VARIANTS.iter().find(|(v, _)| *v == name) {
+                        Some((_, mode)) => {
+                            self.variant = VariantFactory::new(*mode).make_initial_state();
+                            self.reset_position();
+                            if *mode != GameMode::Standard {
+                                self.print_setup();
+                            }
+                        },
+                        None => println!("Error (unsupported variant): {name}"),
+                    },
+                }
+            },
+            "setboard" => {
+                self.stop_search();
+                if let Err(e) = self.set_board(&args.join(" ")) {
+                    println!("tellusererror Illegal position: {e}");
+                }
+            },
+            "force" => {
+                self.stop_search();
+                self.engine_player = None;
+            },
+            "go" => {
+                self.stop_search();
+                self.engine_player = Some(self.engine.player_to_move());
+                self.start_search_if_needed();
+            },
+            "playother" => {
+                self.stop_search();
+                self.engine_player = Some(1 - self.engine.player_to_move());
+            },
+            "usermove" => {
+                let mv_str = args.first().copied().unwrap_or_default();
+                self.user_move(mv_str);
+            },
+            "?" => {
+                // Move now: the search will return the best move found so far
+                if let Some(search) = &self.search {
+                    search.stop_flag.store(true, Ordering::Relaxed);
+                }
+            },
+            "undo" => {
+                self.stop_search();
+                self.undo(1);
+            },
+            "remove" => {
+                self.stop_search();
+                self.undo(2);
+            },
+            "result" => {
+                self.stop_search();
+                self.engine_player = None;
+            },
+            "level" => {
+                if let Err(e) = self.set_level(args) {
+                    println!("Error ({e}): {line}");
+                }
+            },
+            "st" => match args.first().and_then(|s| parse_seconds(s)) {
+                Some(time) => self.time_per_move = Some(time),
+                None => println!("Error (invalid time): {line}"),
+            },
+            "sd" => match args.first().and_then(|s| s.parse::<Depth>().ok()) {
+                Some(depth) if depth > 0 => self.max_depth = Some(depth),
+                _ => println!("Error (invalid depth): {line}"),
+            },
+            "time" => {
+                // Centiseconds
+                if let Some(cs) = args.first().and_then(|s| s.parse::<i64>().ok()) {
+                    self.remaining_time = Some(Duration::from_millis((cs.max(0) as u64).saturating_mul(10)).min(MAX_TIME));
+                }
+            },
+            "option" => {
+                // Format: option NAME=VALUE
+                let option = args.join(" ");
+                match option.split_once('=') {
+                    Some(("VariantFile", path)) => {
+                        self.stop_search();
+                        match self.load_variant_file(path) {
+                            Ok(()) => self.print_setup(),
+                            Err(e) => println!("tellusererror Invalid variant file: {e}"),
+                        }
+                    },
+                    _ => println!("Error (unknown option): {option}"),
+                }
+            },
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "cores" => {
+                if let Some(n) = args.first().and_then(|s| s.parse::<u32>().ok()) {
+                    let n = n.clamp(1, Engine::get_max_threads());
+                    self.engine.set_num_threads(n).ok();
+                }
+            },
+            "ping" => println!("pong {}", args.first().copied().unwrap_or_default()),
+            "quit" => return false,
+            // Moves can also be sent without the "usermove" prefix
+            cmd if parse_move(&mut self.engine, cmd, false).is_some() => self.user_move(cmd),
+            cmd => println!("Error (unknown command): {cmd}"),
+        }
+        true
+    }
+
+    fn print_features(&self) {
+        let mut variants: Vec<&str> = VARIANTS.iter().map(|(v, _)| *v).collect();
+        if let Some((name, _)) = &self.custom_variant {
+            variants.push(name);
+        }
+        println!("feature done=0");
+        println!("feature myname=\"{ENGINE_NAME}\" setboard=1 usermove=1 ping=1 playother=1 colors=0 analyze=0");
+        println!("feature sigint=0 sigterm=0 reuse=1 time=1 smp=1 memory=0 san=0");
+        println!("feature variants=\"{}\"", variants.join(","));
+        println!("feature option=\"VariantFile -file \"");
+        println!("feature done=1");
+    }
+
+    /// Describes the board and the pieces of the current variant to the GUI
+    fn print_setup(&mut self) {
+        let fen = xboard_fen(&mut self.engine);
+        let initial_state = &self.engine.get_state().initial_state;
+        let letters = match piece_letters(initial_state) {
+            Ok(letters) => letters,
+            Err(e) => {
+                println!("tellusererror {e}");
+                return;
+            },
+        };
+        let white: String = letters.iter().map(|l| l.unwrap_or('.')).collect();
+        let black: String = letters.iter().map(|l| {
+            match l {
+                Some(l) if has_black_piece(initial_state, *l) => l.to_ascii_lowercase(),
+                _ => '.',
+            }
+        }).collect();
+        let width = initial_state.board_width;
+        let height = initial_state.board_height;
+        println!("setup ({white}{black}) {width}x{height}+0_fairy {fen}");
+
+        for letter in letters.iter().flatten() {
+            let white_def = initial_state.piece_types.iter().find(|def| def.ids[0] == Some(*letter));
+            let black_def = initial_state.piece_types.iter().find(|def| def.ids[1] == Some(letter.to_ascii_lowercase()));
+            if let Some(def) = white_def.or(black_def) {
+                println!("piece {letter}& {}", to_betza(def));
+            }
+        }
+    }
+
+    fn reset_position(&mut self) {
+        let state = GameState { initial_state: self.variant.clone(), initial_fen: None, move_history: vec![] };
+        // The hardcoded variants are always valid, and variant files are validated when loaded
+        let _ = self.engine.set_state(state).unwrap();
+        self.start_game();
+    }
+
+    fn set_board(&mut self, fen: &str) -> Result<(), String> {
+        let state = GameState { initial_state: self.variant.clone(), initial_fen: Some(fen.to_string()), move_history: vec![] };
+        let _ = self.engine.set_state(state)?;
+        self.start_game();
+        Ok(())
+    }
+
+    fn start_game(&mut self) {
+        self.game_over = false;
+        self.start_player = self.engine.player_to_move();
+        self.start_fullmove = self.engine.get_fullmove_number();
+    }
+
+    /// Loads a variant file, makes it available as a variant named after the file and selects it
+    pub(crate) fn load_variant_file(&mut self, path: &str) -> Result<(), String> {
+        let path = Path::new(path);
+        let initial_state = load_variant(path)?;
+        // Make sure that the starting position is valid
+        let state = GameState { initial_state: initial_state.clone(), initial_fen: None, move_history: vec![] };
+        let _ = Engine::default().set_state(state)?;
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("custom").to_lowercase();
+        self.custom_variant = Some((name, initial_state.clone()));
+        self.variant = initial_state;
+        self.reset_position();
+        Ok(())
+    }
+
+    fn set_level(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() < 3 {
+            return Err("expected 'level MPS BASE INC'".to_string());
+        }
+        let moves_per_session = args[0].parse::<u32>().map_err(|_| "invalid moves per session")?;
+        // The base time is either "minutes" or "minutes:seconds"
+        let mut base_parts = args[1].split(':');
+        let minutes = base_parts.next().unwrap_or_default().parse::<u64>().map_err(|_| "invalid base time")?;
+        let seconds = base_parts.next().unwrap_or("0").parse::<u64>().map_err(|_| "invalid base time")?;
+        let increment = parse_seconds(args[2]).ok_or("invalid increment")?;
+        self.time_control = TimeControl {
+            moves_per_session,
+            base: Duration::from_secs(minutes.saturating_mul(60).saturating_add(seconds)).min(MAX_TIME),
+            increment,
+        };
+        self.time_per_move = None;
+        Ok(())
+    }
+
+    fn user_move(&mut self, mv_str: &str) {
+        self.stop_search();
+        let mv = match parse_move(&mut self.engine, mv_str, false) {
+            Some(mv) if !self.game_over => mv,
+            _ => {
+                println!("Illegal move: {mv_str}");
+                return;
+            },
+        };
+        let result = self.engine.make_move(&mv);
+        self.game_over = print_result(&result);
+        self.start_search_if_needed();
+    }
+
+    fn undo(&mut self, num_moves: u32) {
+        for _ in 0..num_moves {
+            if let Err(e) = self.engine.undo() {
+                println!("Error ({e}): undo");
+                break;
+            }
+        }
+        self.game_over = false;
+    }
+
+    /// Starts searching if it's the engine's turn
+    fn start_search_if_needed(&mut self) {
+        if self.game_over || self.search.is_some() || self.engine_player != Some(self.engine.player_to_move()) {
+            return;
+        }
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let (info_sender, info_receiver) = mpsc::channel();
+        let limits = SearchLimits {
+            max_depth: self.max_depth.unwrap_or(Depth::MAX),
+            max_time: Some(self.allocate_time()),
+            stop_flag: Some(stop_flag.clone()),
+            info_sender: Some(info_sender),
+        };
+        let id = self.next_search_id;
+        self.next_search_id += 1;
+        self.search = Some(RunningSearch { id, stop_flag });
+
+        let engine = self.engine.clone();
+        let mut search_engine = engine.clone();
+        let post = self.post;
+        let event_sender = self.event_sender.clone();
+        thread::spawn(move || {
+            let search_handle = thread::spawn(move || search_engine.get_best_move_limits(&limits));
+            // The channel is closed when the search finishes
+            for info in info_receiver {
+                if post {
+                    println!("{}", format_thinking(&engine, &info));
+                }
+            }
+            let result = search_handle.join().unwrap_or_else(|_| Err("The search thread panicked".to_string()));
+            event_sender.send(Event::SearchDone(id, result)).ok();
+        });
+    }
+
+    pub(crate) fn search_done(&mut self, id: u64, result: SearchResult) {
+        match &self.search {
+            Some(search) if search.id == id => self.search = None,
+            // The search was cancelled, ignore the result
+            _ => return,
+        }
+        match result {
+            Ok((mv, _score, _depth)) => {
+                let mv_str = format_move(&mut self.engine, &mv, false);
+                let result = self.engine.make_move(&mv);
+                println!("move {mv_str}");
+                self.game_over = print_result(&result);
+                // The engine may have more moves left in the turn
+                self.start_search_if_needed();
+            },
+            Err(e) => println!("Error (search failed): {e}"),
+        }
+    }
+
+    /// Stops the current search (if any) and discards its result
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop_flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of turns that the engine has already played since the start of the game
+    fn engine_moves_played(&self, player: Player) -> u32 {
+        let fullmoves = self.engine.get_fullmove_number().saturating_sub(self.start_fullmove) as u32;
+        // If black starts the game, the first turn of white is in the next fullmove
+        if player == 0 && self.start_player == 1 { fullmoves.saturating_sub(1) } else { fullmoves }
+    }
+
+    /// Decides how much time to spend on the current move
+    pub(crate) fn allocate_time(&mut self) -> Duration {
+        // Time reserved for communication with the GUI
+        const OVERHEAD: Duration = Duration::from_millis(50);
+
+        if let Some(time) = self.time_per_move {
+            return time.saturating_sub(OVERHEAD).max(Duration::from_millis(1));
+        }
+        let remaining = self.remaining_time.unwrap_or(self.time_control.base);
+        let player = self.engine_player.unwrap_or(self.engine.player_to_move());
+        let moves_played = self.engine_moves_played(player);
+        let moves_to_go = {
+            let mps = self.time_control.moves_per_session;
+            if mps == 0 { 30 } else { mps - moves_played % mps }
+        };
+        let budget = remaining / moves_to_go + self.time_control.increment * 3 / 4;
+        // Never use more than half of the remaining time
+        let max_time = remaining.saturating_sub(OVERHEAD) / 2;
+        budget.min(max_time).max(Duration::from_millis(1))
+    }
+}
+
+
+/// Parses a time in seconds sent by the GUI. Negative times are treated as 0, and long times are clamped
+/// to `MAX_TIME`. Returns `None` if the time is not a finite number.
+fn parse_seconds(s: &str) -> Option<Duration> {
+    let secs = s.parse::<f64>().ok().filter(|secs| secs.is_finite())?;
+    Some(Duration::from_secs_f64(secs.clamp(0.0, MAX_TIME.as_secs_f64())))
+}
+
+/// Returns the FEN of the current position in the format expected by XBoard
+pub(crate) fn xboard_fen(engine: &mut Engine) -> String {
+    let fen = engine.get_state_diff().fen;
+    let mut parts = fen.split_whitespace();
+    let placement = parts.next().unwrap_or_default().to_string();
+    let player = if engine.player_to_move() == 0 { "w" } else { "b" };
+    let castling = xboard_castling(&engine.get_state().initial_state, &fen);
+    // Skip the player, castling and en passant fields, which use the custom format
+    let mut clocks = parts.skip(3);
+    let halfmove = clocks.next().unwrap_or("0");
+    let fullmove = clocks.next().unwrap_or("1");
+    format!("{placement} {player} {castling} - {halfmove} {fullmove}")
+}
+
+/// Returns the castling rights of the FEN in the format expected by XBoard: for each unmoved piece that can castle,
+/// the unmoved rooks on its rank, as `K`/`Q` if the rook is on the corner or as the file of the rook otherwise
+/// (like in Shredder-FEN). White's rights go first, kingside before queenside.
+fn xboard_castling(initial_state: &InitialState, fen: &str) -> String {
+    let fen_data = match FenData::parse_fen(fen) {
+        Ok(fen_data) => fen_data,
+        Err(_) => return "-".to_string(),
+    };
+    let unmoved = fen_data.castling_availability.clone().unwrap_or_default();
+    // Returns the definition of the unmoved piece of `player` on the square, if any
+    let unmoved_piece = |player: usize, (x, y): (BCoord, BCoord)| {
+        if !unmoved.contains(&(x, y)) {
+            return None;
+        }
+        let placement = fen_data.piece_placements.iter().find(|p| p.x == x && p.y == y)?;
+        initial_state.piece_types.iter().find(|def| def.ids[player] == Some(placement.piece_id))
+    };
+    let mut castling = String::new();
+    for player in 0..2 {
+        let mut rights = Vec::new();
+        for &(king_x, y) in &unmoved {
+            let is_king = unmoved_piece(player, (king_x, y)).is_some_and(|def| def.can_castle() && !def.castle_vertically);
+            if !is_king {
+                continue;
+            }
+            for &(rook_x, rook_y) in &unmoved {
+                if rook_y != y || !unmoved_piece(player, (rook_x, y)).is_some_and(|def| def.is_castle_rook) {
+                    continue;
+                }
+                let right = match rook_x {
+                    x if x == fen_data.width - 1 => 'K',
+                    0 => 'Q',
+                    x => (b'A' + x) as char,
+                };
+                // Kingside rights first
+                let key = (rook_x < king_x, rook_x.abs_diff(king_x));
+                rights.push((key, if player == 0 { right } else { right.to_ascii_lowercase() }));
+            }
+        }
+        rights.sort();
+        rights.dedup();
+        castling.extend(rights.into_iter().map(|(_, right)| right));
+    }
+    if castling.is_empty() { "-".to_string() } else { castling }
+}
+
+/// Assigns each piece type to one of the internal piece types of XBoard.
+/// The pieces are identified by their uppercase letter.
+fn piece_letters(initial_state: &InitialState) -> Result<Vec<Option<char>>, String> {
+    let mut letters = vec![None; XBOARD_PIECE_SLOTS];
+    let mut others = Vec::new();
+    for def in &initial_state.piece_types {
+        let letter = match def.ids[0].or(def.ids[1]) {
+            Some(id) => id.to_ascii_uppercase(),
+            None => continue,
+        };
+        if letters.contains(&Some(letter)) || others.contains(&letter) {
+            continue;
+        }
+        // Prefer the slot that XBoard uses for this letter, then the king slot for leaders
+        // and the pawn slot for promoting pieces
+        let native_slot = XBOARD_PIECE_ORDER.find(letter).filter(|i| letters[*i].is_none());
+        if let Some(slot) = native_slot {
+            letters[slot] = Some(letter);
+        } else if def.is_leader && letters[XBOARD_PIECE_SLOTS - 1].is_none() {
+            letters[XBOARD_PIECE_SLOTS - 1] = Some(letter);
+        } else if def.can_promote() && letters[0].is_none() {
+            letters[0] = Some(letter);
+        } else {
+            others.push(letter);
+        }
+    }
+    // Fill the remaining slots, leaving the pawn and king slots for last
+    let free_slots: Vec<usize> = (1..XBOARD_PIECE_SLOTS - 1).chain([0, XBOARD_PIECE_SLOTS - 1])
+        .filter(|i| letters[*i].is_none())
+        .collect();
+    let mut free_slots = free_slots.into_iter();
+    for letter in others {
+        match free_slots.next() {
+            Some(slot) => letters[slot] = Some(letter),
+            None => return Err(format!("XBoard supports at most {XBOARD_PIECE_SLOTS} piece types")),
+        }
+    }
+    Ok(letters)
+}
+
+#[cfg(feature = "serde")]
+fn load_variant(path: &Path) -> Result<InitialState, String> {
+    protochess_engine_rs::position::create::variant_file::load_variant(path)
+}
+#[cfg(not(feature = "serde"))]
+fn load_variant(_path: &Path) -> Result<InitialState, String> {
+    Err("Compile with the `serde` feature to load variant files".to_string())
+}
+
+fn has_black_piece(initial_state: &InitialState, letter: char) -> bool {
+    initial_state.piece_types.iter().any(|def| def.ids[1] == Some(letter.to_ascii_lowercase()))
+}
+
+/// Prints the thinking output: ply, score, time (centiseconds), nodes and PV
+fn format_thinking(engine: &Engine, info: &SearchInfo) -> String {
+    let score = match info.mate_in() {
+        Some(moves) if moves > 0 => XBOARD_MATE_SCORE + moves,
+        Some(moves) => -XBOARD_MATE_SCORE + moves,
+        None => info.score,
+    };
+    let centis = info.elapsed.as_millis() / 10;
+    // Play the PV on a copy of the engine, since castling moves depend on the position
+    let mut pv_engine = engine.clone();
+    let mut pv = Vec::new();
+    for mv in &info.pv {
+        pv.push(format_move(&mut pv_engine, mv, false));
+        if pv_engine.make_move(mv).flag == MakeMoveResultFlag::IllegalMove {
+            break;
+        }
+    }
+    format!("{} {score} {centis} {} {}", info.depth, info.nodes, pv.join(" "))
+}
+
+/// If the game is over, prints the result and returns `true`
+fn print_result(result: &MakeMoveResult) -> bool {
+    let winner = match result.winner {
+        MakeMoveResultWinner::White => "White",
+        MakeMoveResultWinner::Black => "Black",
+        MakeMoveResultWinner::None => "Nobody",
+    };
+    let reason = match result.flag {
+        MakeMoveResultFlag::Ok | MakeMoveResultFlag::IllegalMove => return false,
+        MakeMoveResultFlag::Checkmate => format!("{winner} mates"),
+        MakeMoveResultFlag::LeaderCaptured => format!("{winner} captured the leader"),
+        MakeMoveResultFlag::AllPiecesCaptured => format!("{winner} captured all pieces"),
+        MakeMoveResultFlag::Extinction => format!("{winner} eliminated a piece type"),
+        MakeMoveResultFlag::PieceInWinSquare => format!("{winner} reached a winning square"),
+        MakeMoveResultFlag::CheckLimit => format!("{winner} wins by checks"),
+        MakeMoveResultFlag::CapturedMaterial => format!("{winner} captured enough material"),
+        MakeMoveResultFlag::SquaresHeld => format!("{winner} held the squares"),
+        MakeMoveResultFlag::Stalemate => "Stalemate".to_string(),
+        MakeMoveResultFlag::Repetition => "Draw by repetition".to_string(),
+        MakeMoveResultFlag::NMoveRule => "Draw by the N-move rule".to_string(),
+        MakeMoveResultFlag::InsufficientMaterial => "Insufficient material".to_string(),
+        MakeMoveResultFlag::MoveLimit => "Draw by the move limit".to_string(),
+    };
+    let score = match result.winner {
+        MakeMoveResultWinner::White => "1-0",
+        MakeMoveResultWinner::Black => "0-1",
+        MakeMoveResultWinner::None => "1/2-1/2",
+    };
+    println!("{score} {{{reason}}}");
+    true
+}
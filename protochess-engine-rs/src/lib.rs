@@ -13,16 +13,18 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 
 use position::create::position_factory::PositionFactory;
+use position::create::pgn;
 use types::{BCoord, Centipawns, Depth, Player, ZobKey};
 use searcher::Searcher;
 use utils::{to_index, from_index};
+use utils::notation::parse_algebraic_notation;
 
 pub use position::Position;
 pub use position::create::game_state::*;
-pub use position::global_rules::GlobalRules;
+pub use position::global_rules::{GlobalRules, RoyaltyPolicy, GameEndRule, MovesPerTurn, MidTurnCheck};
 pub use move_generator::MoveGen;
 pub use piece::{Piece, PieceId, PieceDefinition};
-pub use types::{MoveInfo, MoveList, MakeMoveResult, MakeMoveResultFlag, MakeMoveResultWinner};
+pub use types::{MoveInfo, MoveList, MakeMoveResult, MakeMoveResultFlag, MakeMoveResultWinner, GameEnd, SearchLimits, SearchInfo};
 
 /// Starting point for the engine
 #[derive(Debug, Clone)]
@@ -56,10 +58,40 @@ impl Engine {
     pub fn get_state_diff(&mut self) -> StateDiff {
         StateDiff::from(&mut self.position)
     }
+    /// Returns the current state as seen by `player` in fog-of-war variants, where the enemy pieces
+    /// outside `visible_squares(player)` are hidden
+    pub fn get_state_diff_for_player(&mut self, player: Player) -> wrap_res!(StateDiff) {
+        err_assert!(player < 2, "Invalid player: {player}");
+        Ok(StateDiff::from_player_view(&mut self.position, player))
+    }
+    /// Returns the squares (x,y) that `player` can see in fog-of-war variants (like Dark chess): the squares
+    /// of their pieces, and the squares where their pieces can move or capture
+    pub fn visible_squares(&mut self, player: Player) -> wrap_res!(Vec<(BCoord, BCoord)>) {
+        err_assert!(player < 2, "Invalid player: {player}");
+        let mut visible = MoveGen::visible_squares(&mut self.position, player);
+        let mut squares = Vec::new();
+        while let Some(index) = visible.lowest_one() {
+            visible.clear_bit(index);
+            squares.push(from_index(index));
+        }
+        Ok(squares)
+    }
     /// Returns the current move history in algebraic notation
     pub fn get_move_history(&mut self) -> &Vec<String> {
         self.factory.get_notation()
     }
+    /// Loads a game in PGN format. The variant is given by the `[Variant]` tag if it's one of the
+    /// hardcoded variants, otherwise the current variant is kept.
+    /// Returns the `MakeMoveResult` of the last move in the game.
+    pub fn load_pgn(&mut self, pgn: &str) -> wrap_res!(MakeMoveResult) {
+        let current_variant = self.factory.get_state().initial_state.clone();
+        let state = pgn::from_pgn(pgn, Some(&current_variant))?;
+        self.set_state(state)
+    }
+    /// Returns the current game in PGN format, including the `[Variant]`, `[FEN]` and `[Result]` tags
+    pub fn to_pgn(&self) -> String {
+        pgn::to_pgn(self.factory.get_state(), self.factory.get_notation(), &self.factory.get_last_result())
+    }
     
     /// Returns the id (can be uppercase or lowercase) of the piece at the given coordinates
     pub fn get_piece_at(&self, position: (BCoord, BCoord)) -> wrap_res!(PieceId) {
@@ -68,7 +100,7 @@ impl Engine {
         Ok(piece.unwrap().get_piece_id())
     }
 
-    /// Adds a new piece on the board. If the piece is not used for castling, `has_moved` is ignored.
+    /// Adds a new piece on the board. If the piece is not used for castling and has no initial moves, `has_moved` is ignored.
     pub fn add_piece(&mut self, piece_id: PieceId, x: BCoord, y: BCoord, has_moved: bool) -> wrap_res!() {
         self.position.public_add_piece(piece_id, to_index(x,y), !has_moved)?;
         Ok(())
@@ -96,6 +128,12 @@ impl Engine {
         Ok(self.make_move(&mv))
     }
 
+    /// Attempts a move on the current board position, given a string in algebraic notation (like "Nf3")
+    pub fn make_move_san(&mut self, target_move: &str) -> wrap_res!(MakeMoveResult) {
+        let mv = parse_algebraic_notation(&mut self.position, target_move)?;
+        Ok(self.make_move(&MoveInfo::from(mv)))
+    }
+
     /// Undoes the most recent move on the current board position
     pub fn undo(&mut self) -> wrap_res!() {
         if !self.position.can_unmake_move() {
@@ -111,6 +149,11 @@ impl Engine {
         self.position.whos_turn
     }
     
+    /// Returns the number of the current turn (starts at 1 and is incremented after black's turn)
+    pub fn get_fullmove_number(&self) -> u16 {
+        self.position.get_fullmove_number()
+    }
+    
     /// Returns the best move for the current position, along with the evaluation score
     pub fn get_best_move(&mut self, depth: Depth) -> wrap_res!(MoveInfo, Centipawns) {
         self.validate_position()?;
@@ -129,12 +172,35 @@ impl Engine {
         Ok((pv[0].into(), score, search_depth))
     }
     
+    /// Returns the best move for the current position, along with the evaluation score and the search depth.
+    /// The search runs until one of the `limits` is reached, or until `limits.stop_flag` is set.
+    pub fn get_best_move_limits(&mut self, limits: &SearchLimits) -> wrap_res!(MoveInfo, Centipawns, Depth) {
+        self.validate_position()?;
+        err_assert!(limits.max_depth != 0, "Depth must be greater than 0");
+        let (pv, score, search_depth) = Searcher::get_best_move_limits(&self.position, limits, self.num_threads);
+        err_assert!(!pv.is_empty(), "No moves found");
+        Ok((pv[0].into(), score, search_depth))
+    }
+    
+    /// Returns the best move for the current position when the player to move can only see `visible_squares()`
+    /// (like in Dark chess), along with the evaluation score. The hidden enemy pieces are placed at random
+    /// in `num_samples` positions, and each one is searched up to `depth`.
+    pub fn get_best_move_sampled(&mut self, depth: Depth, num_samples: u32) -> wrap_res!(MoveInfo, Centipawns) {
+        self.validate_position()?;
+        err_assert!(depth != 0, "Depth must be greater than 0");
+        err_assert!(num_samples != 0, "The number of samples must be greater than 0");
+        let result = Searcher::get_best_move_sampled(&self.position, depth, num_samples, self.num_threads);
+        let (mv, score) = result.ok_or("No moves found")?;
+        Ok((mv.into(), score))
+    }
+    
     /// Returns an error if the current position is invalid
     pub fn validate_position(&mut self) -> wrap_res!() {
         let player = self.position.whos_turn;
         let player_str = if player == 0 { "White" } else { "Black" };
         if self.position.leader_is_captured() {
-            let has_leader = self.position.pieces[player as usize].get_leader().is_some();
+            err_assert!(!self.position.pieces[player as usize].has_extinct_piece(), "A piece type of the player to move ({player_str}) is already extinct");
+            let has_leader = self.position.pieces[player as usize].has_leaders();
             let piece_str = if has_leader { "leaders" } else { "pieces" };
             err!("All the {piece_str} of the player to move ({player_str}) have already been captured");
         }
@@ -142,7 +208,8 @@ impl Engine {
         Ok(())
     }
     
-    /// Returns a list of all squares (x,y) from which the given piece can move, along with the moves themselves
+    /// Returns a list of all squares (x,y) from which the given piece can move, along with the moves themselves.
+    /// Drops are listed under the square where the piece is dropped
     pub fn legal_moves(&mut self) -> Vec<MoveList> {
         let all_moves = MoveGen::get_legal_moves(&mut self.position);
         let mut moves_from_map = HashMap::new();
@@ -159,9 +226,10 @@ impl Engine {
         output
     }
     
-    /// Returns a list of all possible promotions for the given move
+    /// Returns a list of all possible promotions for the given move.
+    /// If promotion is optional on the destination square, the move without promotion may also be legal
     pub fn possible_promotions(&mut self, from: (BCoord, BCoord), to: (BCoord, BCoord)) -> Vec<PieceId> {
-        MoveGen::get_legal_moves(&mut self.position)
+        let mut promotions: Vec<PieceId> = MoveGen::get_legal_moves(&mut self.position)
             .into_iter()
             .filter(|mv| {
                 let mv_from = from_index(mv.get_from());
@@ -169,7 +237,10 @@ impl Engine {
                 mv_from == from && mv_to == to && mv.is_promotion()
             })
             .map(|mv| mv.get_promotion_piece().unwrap())
-            .collect()
+            .collect();
+        // When neutral pieces are relocated, each promotion is repeated for all their destinations
+        promotions.dedup();
+        promotions
     }
     
     
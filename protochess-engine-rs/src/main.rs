@@ -1,10 +1,10 @@
 //#[macro_use] extern crate scan_rules;
 
-use std::io::Write;
+use std::sync::mpsc;
 
-use protochess_engine_rs::{Engine, MoveInfo, MakeMoveResultFlag, MakeMoveResultWinner, GameState};
-use protochess_engine_rs::types::GameMode;
-use protochess_engine_rs::utils::debug::split_debug_fen;
+use instant::Duration;
+
+use protochess_engine_rs::{Engine, MakeMoveResultFlag, MakeMoveResultWinner, GameState, InitialState, SearchLimits};
 
 pub fn main() {
     
@@ -17,14 +17,14 @@ pub fn main() {
     // "rnbqkbnr/pppppppp/8/8/8/8/8/RNBQKBNR w KQkq - 0 1"
     
     
-    // Usage: cargo run -- <depth> <fen> <num_ply>
+    // Usage: cargo run -- <depth> <fen> <num_ply> [--variant <file>]
     // By default, <depth> is 12, <fen> is the starting position, and <num_ply> is 500
     // Example: cargo run -- 4 "1Q6/5pk1/2p3p1/1pbbN2p/4n2P/8/r5P1/5K2 b - - 0 1"
+    // The --variant option loads a variant file (requires the `serde` feature, see docs/VARIANT_FILE.md)
+    // Example: cargo run --features serde -- 4 default 100 --variant capablanca.toml
     
-    let mut pgn_file = std::fs::File::create("pgn.txt").expect("create failed");
-
-    
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let variant_file = take_option(&mut args, "--variant");
     let mut fixed_depth = true;
     let mut depth = 12;
     let mut max_ply = 500;
@@ -35,9 +35,19 @@ pub fn main() {
     
     let mut engine = Engine::default();
     
-    if args.len() > 2 && args[2] != "default" {
+    if let Some(path) = variant_file {
+        let initial_state = load_variant(&path).unwrap_or_else(|e| {
+            println!("Invalid variant file: {e}");
+            std::process::exit(1);
+        });
+        let initial_fen = args.get(2).filter(|fen| *fen != "default").cloned();
+        let state = GameState { initial_state, initial_fen, move_history: vec![] };
+        let _result = engine.set_state(state).unwrap_or_else(|e| {
+            println!("Incorrect FEN: {e}");
+            std::process::exit(1);
+        });
+    } else if args.len() > 2 && args[2] != "default" {
         let state = GameState::from_debug_fen(&args[2]);
-        print_pgn_header(&args[2], &mut pgn_file);
         let _result = engine.set_state(state).unwrap_or_else(|e| {
             println!("Incorrect FEN: {e}");
             std::process::exit(1);
@@ -55,21 +65,27 @@ pub fn main() {
     println!("Start Position:\n{engine}");
     println!("\n----------------------------------------\n");
     
+    let (info_sender, info_receiver) = mpsc::channel();
+    let limits = {
+        if fixed_depth {
+            SearchLimits { max_depth: depth, info_sender: Some(info_sender), ..Default::default() }
+        } else {
+            SearchLimits { max_time: Some(Duration::from_secs(depth as u64)), info_sender: Some(info_sender), ..Default::default() }
+        }
+    };
+    
     let start = instant::Instant::now();
     for ply in 0..max_ply {
-        let mv = {
-            if fixed_depth {
-                engine.get_best_move(depth).unwrap().0
-            } else {
-                engine.get_best_move_timeout(depth as u64).unwrap().0
-            }
-        };
+        let mv = engine.get_best_move_limits(&limits).unwrap().0;
+        for info in info_receiver.try_iter() {
+            println!("{info}");
+        }
         println!("\n========================================\n");
         println!("(Time since start: {:?})", start.elapsed());
         println!("PLY: {ply} Engine plays:\n");
         let result = engine.make_move(&mv);
-        let move_str = result.move_notation.unwrap_or_else(|| "!!!".to_string());
-        print_pgn(&mut pgn_file, ply, &fix_notation(move_str, mv));
+        // Save the game after every move, so that it can be inspected while the engine is running
+        std::fs::write("pgn.txt", engine.to_pgn()).expect("write failed");
         println!("{engine}\n");
         match result.flag {
             MakeMoveResultFlag::Ok => {
@@ -90,6 +106,10 @@ pub fn main() {
                 println!("ALL PIECES HAVE BEEN CAPTURED! {:?} wins!", result.winner);
                 break;
             },
+            MakeMoveResultFlag::Extinction => {
+                println!("A PIECE TYPE HAS BEEN ELIMINATED! {:?} wins!", result.winner);
+                break;
+            },
             MakeMoveResultFlag::PieceInWinSquare => {
                 println!("KING IN WINNING SQUARE! {:?} wins!", result.winner);
                 break;
@@ -98,6 +118,14 @@ pub fn main() {
                 println!("CHECK LIMIT REACHED! {:?} wins!", result.winner);
                 break;
             },
+            MakeMoveResultFlag::CapturedMaterial => {
+                println!("ENOUGH MATERIAL CAPTURED! {:?} wins!", result.winner);
+                break;
+            },
+            MakeMoveResultFlag::SquaresHeld => {
+                println!("SQUARES HELD! {:?} wins!", result.winner);
+                break;
+            },
             MakeMoveResultFlag::Stalemate => {
                 if result.winner == MakeMoveResultWinner::None {
                     println!("DRAW BY STALEMATE!");
@@ -110,37 +138,40 @@ pub fn main() {
                 println!("DRAW BY REPETITION!");
                 break;
             },
+            MakeMoveResultFlag::NMoveRule => {
+                println!("DRAW BY THE {}-MOVE RULE!", engine.get_state().initial_state.global_rules.n_move_rule);
+                break;
+            },
+            MakeMoveResultFlag::MoveLimit => {
+                println!("DRAW BY MOVE LIMIT!");
+                break;
+            },
+            MakeMoveResultFlag::InsufficientMaterial => {
+                println!("DRAW BY INSUFFICIENT MATERIAL!");
+                break;
+            },
         }
     }
 }
 
 
-fn print_pgn_header(fen: &str, pgn_file: &mut std::fs::File) {
-    let (fen, variant) = split_debug_fen(fen);
-    if variant != GameMode::Standard {
-        pgn_file.write_all(format!("[Variant \"{variant}\"]\n").as_bytes()).unwrap();
+/// Removes `option` and its value from the arguments, and returns the value
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == option)?;
+    if index + 1 >= args.len() {
+        println!("Missing value for {option}");
+        std::process::exit(1);
     }
-    pgn_file.write_all(format!("[FEN \"{fen}\"]\n\n").as_bytes()).unwrap();
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Some(value)
 }
 
-fn print_pgn(pgn_file: &mut std::fs::File, ply: u32, move_str: &str) {
-    if (ply % 2) == 0 {
-        let round = format!("{}. ", ply/2 + 1);
-        pgn_file.write_all(round.as_bytes()).expect("write failed");
-    }
-    pgn_file.write_all(move_str.as_bytes()).expect("write failed");
-    pgn_file.write_all(b" ").expect("write failed");
+#[cfg(feature = "serde")]
+fn load_variant(path: &str) -> Result<InitialState, String> {
+    protochess_engine_rs::position::create::variant_file::load_variant(std::path::Path::new(path))
 }
-
-
-// Algebraic notation specifies that a pawn capture includes the file of the capturing pawn,
-// even if the capture is unambiguous without it. This is not generalizable at all, so
-// the engine does not include the file in the notation.
-// If we want Lichess to accept our PGNs, we need to add the file back in.
-fn fix_notation(move_str: String, mv: MoveInfo) -> String {
-    if move_str.starts_with('x') {
-        format!("{}{}", (b'a' + mv.from.0) as char, move_str)
-    } else {
-        move_str
-    }
+#[cfg(not(feature = "serde"))]
+fn load_variant(_path: &str) -> Result<InitialState, String> {
+    Err("Compile with the `serde` feature to load variant files".to_string())
 }
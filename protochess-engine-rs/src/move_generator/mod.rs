@@ -1,9 +1,12 @@
-use crate::piece::Piece;
+use std::iter::once;
+
+use crate::piece::{Piece, hop_landings, indirect_capture_victims};
 use crate::position::piece_set::PieceSet;
-use crate::types::{BCoord, BIndex, Bitboard, Move, MoveType};
+use crate::types::{BCoord, BIndex, Bitboard, Move, MoveType, Player};
 use crate::position::Position;
+use crate::position::global_rules::{RoyaltyPolicy, MidTurnCheck};
 use crate::move_generator::attack_tables::AttackTables;
-use crate::utils::{from_index, to_index};
+use crate::utils::{from_index, to_index, squares_between};
 
 pub mod attack_tables;
 
@@ -23,6 +26,15 @@ impl MoveGen {
     
     pub fn get_legal_moves(position: &mut Position) -> Vec<Move> {
         let mut legal_moves = Vec::new();
+        if position.global_rules.relocate_neutral_pieces && MoveGen::checks_are_impossible(position) {
+            // Relocating the neutral piece can't change if the move is legal, so check each move only once
+            for mv in MoveGen::get_moves_without_neutral(position, true) {
+                if MoveGen::is_move_legal(mv, position) {
+                    legal_moves.push(mv);
+                }
+            }
+            return MoveGen::add_neutral_moves(position, legal_moves);
+        }
         for mv in MoveGen::get_pseudo_moves(position, true) {
             if !MoveGen::is_move_legal(mv, position) {
                 continue;
@@ -34,11 +46,21 @@ impl MoveGen {
 
     /// Iterator that yields pseudo-legal moves from a positon
     pub fn get_pseudo_moves(position: &mut Position, output_translations: bool) -> Vec<Move> {
+        let moves = MoveGen::get_moves_without_neutral(position, output_translations);
+        if position.global_rules.relocate_neutral_pieces {
+            return MoveGen::add_neutral_moves(position, moves);
+        }
+        moves
+    }
+    
+    /// Returns the pseudo-legal moves, without the relocation of the neutral pieces
+    fn get_moves_without_neutral(position: &Position, output_translations: bool) -> Vec<Move> {
         let my_pieces = &position.pieces[position.whos_turn as usize];
 
         let mut out_moves = Vec::with_capacity(50);
 
-        let enemies_or_out_bounds = &position.occ_or_out_bounds & !my_pieces.get_occupied();
+        // Neutral pieces block like any other piece, but they can't be captured
+        let enemies_or_out_bounds = &position.occ_or_out_bounds & !my_pieces.get_occupied() & !position.get_neutral_pieces();
         let occ_or_not_in_bounds = &position.occ_or_out_bounds;
         
         for p in my_pieces.iter() {
@@ -49,44 +71,121 @@ impl MoveGen {
             for p in my_pieces.iter() {
                 p.output_translations(position, &enemies_or_out_bounds, occ_or_not_in_bounds, &mut out_moves);
             }
+            for p in my_pieces.iter() {
+                p.output_drops(occ_or_not_in_bounds, &mut out_moves);
+            }
         }
         out_moves
     }
+    
+    /// Expands each move with every relocation of a neutral piece to a square that is empty after the move.
+    /// If there are neutral pieces in the hand, one of them must be placed on the board instead
+    fn add_neutral_moves(position: &Position, moves: Vec<Move>) -> Vec<Move> {
+        if position.get_neutral_in_hand() == 0 && position.get_neutral_pieces().is_zero() {
+            return moves;
+        }
+        let mut out_moves = Vec::with_capacity(moves.len() * 32);
+        for mv in moves {
+            // The squares of the neutral pieces are occupied, so they must move to a different square
+            let mut empty_squares = position.empty_squares_after(mv);
+            while let Some(to) = empty_squares.lowest_one() {
+                empty_squares.clear_bit(to);
+                out_moves.push(mv.with_neutral_move(to));
+            }
+        }
+        out_moves
+    }
+    
+    /// Returns `true` if no player has leader pieces (or checks are ignored), so no move can be illegal because of
+    /// a check, and the position of the neutral pieces doesn't matter for the legality of a move
+    fn checks_are_impossible(position: &Position) -> bool {
+        position.global_rules.ignore_checks || position.pieces.iter().all(|pieces| pieces.iter().all(|p| !p.is_leader()))
+    }
 
-    /// Checks if the player to move is in check
+    /// Checks if the player to move is in check, according to the royalty policy.
+    /// If `GlobalRules::ignore_checks` is set, the player is never in check
     pub fn in_check(position: &mut Position) -> bool {
-        let my_pieces = &position.pieces[position.whos_turn as usize];
-        if let Some(my_leader) = my_pieces.get_leader() {
-            if my_leader.get_num_pieces() > 1 {
-                // There are multiple leaders, so the position cannot be in check
-                return false;
+        if position.global_rules.ignore_checks {
+            return false;
+        }
+        // If I have no leader, I cannot be in check (only lose when all pieces are captured)
+        let mut leaders = position.pieces[position.whos_turn as usize].get_leader_bitboard();
+        let num_leaders = leaders.count_ones();
+        if num_leaders == 0 {
+            return false;
+        }
+        match position.global_rules.royalty_policy {
+            RoyaltyPolicy::AllCaptured => {
+                if num_leaders > 1 {
+                    // There are multiple leaders, so the position cannot be in check
+                    return false;
+                }
+                // There is only one bit set to 1 in the bitboard
+                let index = leaders.lowest_one().unwrap();
+                MoveGen::index_in_check(index, position)
+            },
+            RoyaltyPolicy::AnyCapturedOrAllAttacked => {
+                while let Some(index) = leaders.lowest_one() {
+                    if !MoveGen::index_in_check(index, position) {
+                        return false;
+                    }
+                    leaders.clear_bit(index);
+                }
+                true
+            },
+            RoyaltyPolicy::EverySafe => {
+                while let Some(index) = leaders.lowest_one() {
+                    if MoveGen::index_in_check(index, position) {
+                        return true;
+                    }
+                    leaders.clear_bit(index);
+                }
+                false
+            },
+        }
+    }
+
+    /// Checks if the opponent of the player to move is in check. Used in the middle of a turn with several moves,
+    /// when the player who made the last move is still the player to move
+    pub fn enemy_in_check(position: &mut Position) -> bool {
+        position.whos_turn = 1 - position.whos_turn;
+        let in_check = MoveGen::in_check(position);
+        position.whos_turn = 1 - position.whos_turn;
+        in_check
+    }
+
+    /// Returns the squares that a player can see in fog-of-war variants (like Dark chess): the squares of their pieces,
+    /// the squares where they can move (ignoring drops), and the pieces they can capture.
+    /// It's computed from the pseudo-legal moves, as if it was the turn of `player`
+    pub fn visible_squares(position: &mut Position, player: Player) -> Bitboard {
+        let whos_turn = position.whos_turn;
+        position.whos_turn = player;
+        let mut visible = position.pieces[player as usize].get_occupied().clone();
+        for mv in MoveGen::get_pseudo_moves(position, true) {
+            if mv.get_move_type() == MoveType::Drop {
+                continue;
+            }
+            visible.set_bit(mv.get_to());
+            if mv.is_capture() {
+                visible.set_bit(mv.get_target());
             }
-            // There is only one bit set to 1 in the bitboard
-            let index = my_leader.get_bitboard().lowest_one().unwrap();
-            MoveGen::index_in_check(index, position)
-        } else {
-            // If I have no leader, I cannot be in check (only lose when all pieces are captured)
-            false
         }
+        position.whos_turn = whos_turn;
+        visible
     }
 
     /// Attempts to make a pseudo-legal move, succeeding and returning true only if the move was legal
     pub fn make_move_if_legal(mv: Move, position: &mut Position) -> bool {
-        // Cannot castle while in check or step through check
-        if mv.is_castling() {
-            let kingside = mv.get_move_type() == MoveType::KingsideCastle;
+        // Cannot castle while in check or step through check (only leaders can be in check)
+        let castling_checks = mv.is_castling() && !position.global_rules.ignore_checks;
+        if castling_checks && position.piece_at(mv.get_from()).is_some_and(|p| p.is_leader()) {
             let from = mv.get_from();
             let to = mv.get_to();
-            // Edge case in chess960 where castling does not move the king,
-            // in which case the loop below will not check the king's position
-            if from == to && MoveGen::index_in_check(from, position) {
-                return false;
-            }
-            let start_index = { if kingside { from } else { to + 1 } };
-            let end_index = { if kingside { to - 1 } else { from } };
+            // Check the squares that the king goes through along its rank or file, from its starting square
+            // (even if it doesn't move) to the one before the destination (checked after making the move)
             // Hide the castling piece from the occupied bitboard so that it doesn't get in the way of check detection
             position.occ_or_out_bounds.clear_bit(from);
-            for step_index in start_index..=end_index {
+            for step_index in once(from).chain(squares_between(from, to)) {
                 if MoveGen::index_in_check(step_index, position) {
                     position.occ_or_out_bounds.set_bit(from);
                     return false;
@@ -98,7 +197,16 @@ impl MoveGen {
         // Try the move and skip a turn, then see if we are in check
         // Also, if after making the move the enemy is in check, the move is illegal if check_is_forbidden
         // If the move captures the last enemy leader, it's still legal (technically, the enemy is not in check)
+        let player = position.whos_turn;
         position.make_move(mv);
+        if position.whos_turn == player {
+            // The turn continues, so the player doesn't need to skip a turn to be the player to move
+            let legal = MoveGen::mid_turn_move_is_legal(position);
+            if !legal {
+                position.unmake_move();
+            }
+            return legal;
+        }
         if position.global_rules.check_is_forbidden && !position.leader_is_captured() && MoveGen::in_check(position) {
             position.unmake_move();
             return false;
@@ -115,6 +223,26 @@ impl MoveGen {
         legal
     }
     
+    /// Checks if the last move is legal, when the player who made it has more moves left in the turn
+    /// (see `GlobalRules::mid_turn_check`). With `MidTurnCheck::EndOfTurn`, turns have at most 2 moves, so
+    /// only the last move of the turn is searched
+    fn mid_turn_move_is_legal(position: &mut Position) -> bool {
+        if position.leader_is_captured() {
+            return false;
+        }
+        let mid_turn_check = position.global_rules.mid_turn_check;
+        let check_is_forbidden = position.global_rules.check_is_forbidden || mid_turn_check == MidTurnCheck::OnlyLastMove;
+        if check_is_forbidden && MoveGen::enemy_in_check(position) {
+            return false;
+        }
+        if !MoveGen::in_check(position) {
+            return true;
+        }
+        // Checks only matter at the end of the turn, so the leader can stay attacked if the turn can be completed
+        mid_turn_check == MidTurnCheck::EndOfTurn &&
+            MoveGen::get_pseudo_moves(position, true).into_iter().any(|mv| MoveGen::is_move_legal(mv, position))
+    }
+    
     /// Checks if a move is legal
     pub fn is_move_legal(mv: Move, position: &mut Position) -> bool {
         let legal = Self::make_move_if_legal(mv, position);
@@ -130,6 +258,7 @@ impl MoveGen {
         let (x, y) = from_index(index);
         let enemy = 1 - position.whos_turn;
         let enemy_pieces = &position.pieces[enemy as usize];
+        let royalty_policy = position.global_rules.royalty_policy;
         let enemy_occupied = enemy_pieces.get_occupied();
         let (inverse_attack, jumps) = enemy_pieces.get_inverse_attack(index);
         // Use inverse attack pattern to get the squares that can potentially attack the square
@@ -153,8 +282,10 @@ impl MoveGen {
             // Found an enemy piece that might attack the last leader
             let enemy_piece = enemy_pieces.piece_at(enemy_piece_index).unwrap();
             // If this attack will kill the remaining enemy leaders, the move is illegal so it is not a check
-            let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index);
-            if !kills_remaining_leaders && MoveGen::slide_targets_coords(x, y, enemy_piece, enemy_piece_index) {
+            let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index, royalty_policy);
+            let attacks = MoveGen::slide_targets_coords(x, y, enemy_piece, enemy_piece_index) &&
+                enemy_piece.get_slide_range_mask(enemy_piece_index).get_bit(index);
+            if !kills_remaining_leaders && enemy_piece.is_allowed_at(index) && attacks {
                 return true;
             }
             slides.clear_bit(enemy_piece_index);
@@ -166,8 +297,12 @@ impl MoveGen {
             // Found an enemy piece that might attack the last leader
             let enemy_piece = enemy_pieces.piece_at(enemy_piece_index).unwrap();
             // If this attack will kill the remaining enemy leaders, the move is illegal so it is not a check
-            let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index);
-            if !kills_remaining_leaders && enemy_piece.get_capture_jumps(enemy_piece_index).get_bit(index) {
+            let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index, royalty_policy);
+            let attacks = enemy_piece.get_capture_jumps(enemy_piece_index).get_bit(index) ||
+                enemy_piece.get_lame_jump_attacks(enemy_piece_index, occ_or_not_in_bounds).get_bit(index) ||
+                MoveGen::multi_leg_targets_index(enemy_piece, enemy_piece_index, index, position) ||
+                enemy_piece.get_bent_rider_attacks(enemy_piece_index, occ_or_not_in_bounds).get_bit(index);
+            if !kills_remaining_leaders && attacks {
                 return true;
             }
             jump_attacks.clear_bit(enemy_piece_index);
@@ -190,8 +325,8 @@ impl MoveGen {
                     // Found an enemy piece that might attack the last leader
                     let enemy_piece = enemy_pieces.piece_at(to).unwrap();
                     // If this attack will kill the remaining enemy leaders, the move is illegal so it is not a check
-                    let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, to);
-                    if !kills_remaining_leaders && MoveGen::sliding_delta_targets_index(enemy_piece, to, index, occ_or_not_in_bounds) {
+                    let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, to, royalty_policy);
+                    if !kills_remaining_leaders && enemy_piece.is_allowed_at(index) && MoveGen::sliding_delta_targets_index(enemy_piece, to, index, occ_or_not_in_bounds) {
                         return true;
                     }
                     break;
@@ -202,6 +337,65 @@ impl MoveGen {
                 }
             }
         }
+        
+        // Check hoppers: the first piece in the opposite direction is the hurdle, and the attacker must be right behind it
+        let bounds = &position.dimensions.bounds;
+        for delta in &inverse_attack.attack_hop_deltas {
+            let (_, behind_hurdle) = hop_landings(index, *delta, occ_or_not_in_bounds, bounds, false);
+            if let Some(enemy_piece_index) = behind_hurdle {
+                if !enemy_occupied.get_bit(enemy_piece_index) {
+                    continue;
+                }
+                // Found an enemy piece that might attack the last leader
+                let enemy_piece = enemy_pieces.piece_at(enemy_piece_index).unwrap();
+                // If this attack will kill the remaining enemy leaders, the move is illegal so it is not a check
+                let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index, royalty_policy);
+                if !kills_remaining_leaders && enemy_piece.get_hop_attacks(enemy_piece_index, occ_or_not_in_bounds, bounds).get_bit(index) {
+                    return true;
+                }
+            }
+        }
+        
+        // Check bent riders: walk the ride backwards through empty squares, the attacker is one step away from each of them
+        for ((sx, sy), (rx, ry)) in &inverse_attack.attack_bent_riders {
+            let (mut x2, mut y2) = (x as i8, y as i8);
+            loop {
+                x2 += *rx;
+                y2 += *ry;
+                if x2 < 0 || y2 < 0 || x2 > 15 || y2 > 15 {
+                    break;
+                }
+                // This square is the step or a square of the ride, so it must be empty
+                if occ_or_not_in_bounds.get_bit(to_index(x2 as BCoord, y2 as BCoord)) {
+                    break;
+                }
+                let (x3, y3) = (x2 + *sx, y2 + *sy);
+                if x3 < 0 || y3 < 0 || x3 > 15 || y3 > 15 {
+                    continue;
+                }
+                let enemy_piece_index = to_index(x3 as BCoord, y3 as BCoord);
+                if !enemy_occupied.get_bit(enemy_piece_index) {
+                    continue;
+                }
+                // Found an enemy piece that might attack the last leader
+                let enemy_piece = enemy_pieces.piece_at(enemy_piece_index).unwrap();
+                // If this attack will kill the remaining enemy leaders, the move is illegal so it is not a check
+                let kills_remaining_leaders = enemy_piece.explodes_on_capture() && explosion_kills_enemy(index, enemy_pieces, enemy_piece, enemy_piece_index, royalty_policy);
+                if !kills_remaining_leaders && enemy_piece.get_bent_rider_attacks(enemy_piece_index, occ_or_not_in_bounds).get_bit(index) {
+                    return true;
+                }
+            }
+        }
+        
+        // Check indirect captures (by withdrawal, approach or custody): the enemy piece must be able to move
+        // to a square from which it captures the target
+        for enemy_piece in enemy_pieces.iter().filter(|p| p.get_movement().has_indirect_captures()) {
+            for enemy_piece_index in enemy_piece.get_indexes() {
+                if MoveGen::indirect_capture_targets_index(enemy_piece, enemy_piece_index, index, position) {
+                    return true;
+                }
+            }
+        }
         false
     }
     fn slide_targets_coords(x: BCoord, y: BCoord, piece: &Piece, piece_index: BIndex) -> bool {
@@ -228,6 +422,31 @@ impl MoveGen {
             else { piece_movement.attack_southwest }
         }
     }
+    fn multi_leg_targets_index(piece: &Piece, piece_index: BIndex, target_index: BIndex, position: &Position) -> bool {
+        if piece.get_movement().multi_leg_deltas.is_empty() {
+            return false;
+        }
+        // The target square is treated as occupied, so that the squares that a castling leader passes through are attacked
+        let mut victims = position.pieces[1 - piece.get_player() as usize].get_occupied().clone();
+        victims.set_bit(target_index);
+        piece.get_multi_leg_captures(piece_index, &victims, &position.occ_or_out_bounds).get_bit(target_index)
+    }
+    fn indirect_capture_targets_index(piece: &Piece, piece_index: BIndex, target_index: BIndex, position: &Position) -> bool {
+        // The target square is treated as occupied, so that the squares that a castling leader passes through are attacked
+        let mut occ_or_not_in_bounds = position.occ_or_out_bounds.clone();
+        occ_or_not_in_bounds.set_bit(target_index);
+        let mut victims = position.pieces[1 - piece.get_player() as usize].get_occupied().clone();
+        victims.set_bit(target_index);
+        let allies = position.pieces[piece.get_player() as usize].get_occupied();
+        let mut destinations = piece.get_translation_squares(piece_index, &occ_or_not_in_bounds, &position.dimensions.bounds);
+        while let Some(to) = destinations.lowest_one() {
+            if indirect_capture_victims(piece.get_movement(), piece_index, to, &victims, allies).get_bit(target_index) {
+                return true;
+            }
+            destinations.clear_bit(to);
+        }
+        false
+    }
     fn sliding_delta_targets_index(piece: &Piece, piece_index: BIndex, target_index: BIndex, occ_or_not_in_bounds: &Bitboard) -> bool {
         let piece_movement = piece.get_movement();
         let (x, y) = from_index(piece_index);
@@ -252,17 +471,23 @@ impl MoveGen {
 }
 
 
-/// Returns true if an explosion in this coordinates would kill all the remaining enemy leaders
-fn explosion_kills_enemy(index: BIndex, enemy_pieces: &PieceSet, enemy_piece: &Piece, enemy_piece_index: u8) -> bool {
-    if let Some(enemy_leader) = enemy_pieces.get_leader() {
+/// Returns true if an explosion in this coordinates would make the enemy lose: kill all the remaining enemy
+/// leaders, or any of them if the royalty policy is not `AllCaptured`
+fn explosion_kills_enemy(index: BIndex, enemy_pieces: &PieceSet, enemy_piece: &Piece, enemy_piece_index: u8, royalty_policy: RoyaltyPolicy) -> bool {
+    if enemy_pieces.has_leaders() {
+        let enemy_leaders = enemy_pieces.get_leader_bitboard();
         let mut killed_enemy_leaders = 0;
-        let affected_squares = enemy_leader.get_bitboard() & enemy_piece.get_explosion(index);
+        let affected_squares = &enemy_leaders & enemy_piece.get_explosion(index);
         // Take into account that the attacking piece might be a leader from far away
         if enemy_piece.is_leader() && !affected_squares.get_bit(enemy_piece_index) {
             killed_enemy_leaders += 1;
         }
         killed_enemy_leaders += affected_squares.count_ones();
-        killed_enemy_leaders == enemy_leader.get_num_pieces()
+        if royalty_policy == RoyaltyPolicy::AllCaptured {
+            killed_enemy_leaders == enemy_leaders.count_ones()
+        } else {
+            killed_enemy_leaders > 0
+        }
     } else {
         // If the enemy has no leaders, then they only lose when all pieces are captured
         let mut killed_enemies = 0;
@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+
+use crate::types::{BCoord, Player};
+use crate::{err, err_assert, wrap_res};
+use super::{PieceDefinition, MultiLegDelta};
+
+// Betza notation: https://www.gnu.org/software/xboard/Betza.html
+// Directions are relative to the owner of the piece ("forward" is north for white and south for black).
+
+// Basic atoms (a, b) with a >= b >= 0, in the order they are printed
+const ATOMS: [((i8, i8), char); 9] = [
+    ((1, 0), 'W'), ((1, 1), 'F'), ((2, 0), 'D'), ((2, 1), 'N'), ((2, 2), 'A'),
+    ((3, 0), 'H'), ((3, 1), 'C'), ((3, 2), 'Z'), ((3, 3), 'G'),
+];
+// Rays longer than this are considered unlimited (the board is at most 16x16)
+const MAX_RANGE: usize = 15;
+
+const CAN_MOVE: u8 = 1;
+const CAN_CAPTURE: u8 = 2;
+
+/// Movement of a single atom: for each direction (relative delta), whether the piece can move/capture
+type AtomDirections = BTreeMap<(i8, i8), u8>;
+
+/// Parses a Betza string (like `"WfcFifmnD"` or `"NN"`) and returns a piece with that movement.
+/// Moves that can only be used to capture are stored in the `attack_*` fields, moves that can't
+/// capture are stored in the `translate_*` fields, and moves without modifiers are stored in both.
+/// 
+/// The directions are relative to `player` ("forward" is north for white and south for black).
+/// Initial moves (`i` modifier) must be non-capturing leaps. Double steps of a non-capturing leap, like `ifmnD`
+/// for a pawn, are allowed from the first 2 ranks of the player. The other ones are only available while the
+/// piece has not moved, and if they are lame (like `ifmnH` for a triple step) they must be in a straight line.
+/// Limited riders of `W` and `F` (like `W2` for a short rook) use the range of the built-in slides.
+/// Multi-leg moves (`a` modifier) must be captures followed by a leap of the same atom in any direction,
+/// like `caK` for the lion in Chu Shogi. Other multi-leg moves return an error: non-capturing first legs
+/// (like `maW`) and second legs with modifiers (like the bent moves of `mafsW`).
+/// All the other fields of the definition (ids, promotions, etc.) have their default values.
+pub fn from_betza(betza: &str, player: Player, width: BCoord, height: BCoord) -> wrap_res!(PieceDefinition) {
+    let flip = player == 1;
+    let orient = |(dx, dy): (i8, i8)| if flip { (-dx, -dy) } else { (dx, dy) };
+    let mut def = PieceDefinition::default();
+    let mut initial_deltas = Vec::new();
+
+    let chars: Vec<char> = betza.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        // Modifiers (lowercase letters), then the atom, then an optional range
+        let mut directions = String::new();
+        let (mut can_move, mut can_capture, mut lame, mut initial) = (false, false, false, false);
+        let mut multi_leg = false;
+        while i < chars.len() && chars[i].is_ascii_lowercase() {
+            err_assert!(!multi_leg,
+                "Multi-leg moves with modifiers in the second leg (like the bent moves of 'mafsW') are not supported in '{betza}'");
+            match chars[i] {
+                'f' | 'b' | 'l' | 'r' | 'v' | 's' => directions.push(chars[i]),
+                'm' => can_move = true,
+                'c' => can_capture = true,
+                'n' => lame = true,
+                'i' => initial = true,
+                'a' => multi_leg = true,
+                c => err!("Unsupported Betza modifier '{c}' in '{betza}'"),
+            }
+            i += 1;
+        }
+        err_assert!(i < chars.len(), "Missing atom at the end of '{betza}'");
+        let letter = chars[i];
+        i += 1;
+        let (atoms, mut range) = match letter {
+            'K' => (vec![(1, 0), (1, 1)], 1),
+            'Q' => (vec![(1, 0), (1, 1)], 0),
+            'R' => (vec![(1, 0)], 0),
+            'B' => (vec![(1, 1)], 0),
+            _ => match ATOMS.iter().find(|(_, l)| *l == letter) {
+                Some((atom, _)) => (vec![*atom], 1),
+                None => err!("Unknown Betza atom '{letter}' in '{betza}'"),
+            },
+        };
+        // A repeated atom is a rider (e.g. NN)
+        if range == 1 && i < chars.len() && chars[i] == letter {
+            range = 0;
+            i += 1;
+        }
+        let digits_start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i > digits_start {
+            let digits: String = chars[digits_start..i].iter().collect();
+            range = match digits.parse::<usize>() {
+                Ok(r) => r,
+                Err(_) => err!("Invalid range '{digits}' in '{betza}'"),
+            };
+        }
+        // Without modifiers, the piece can both move and capture
+        if !can_move && !can_capture {
+            can_move = true;
+            can_capture = true;
+        }
+        err_assert!(!lame || initial, "Lame moves ('n') are only supported as initial moves in '{betza}'");
+        if multi_leg {
+            err_assert!(can_capture && !can_move,
+                "Multi-leg moves ('a') are only supported with a capturing first leg, like 'caK' (not 'maK' or 'aK'), in '{betza}'");
+            err_assert!(range == 1 && !initial && !lame,
+                "Multi-leg moves ('a') are only supported for leapers, like 'caK', in '{betza}'");
+            let second_legs: Vec<(i8, i8)> = atoms.iter().flat_map(|atom| directions_of(*atom)).collect();
+            for atom in &atoms {
+                for delta in parse_directions(&directions, *atom, betza)? {
+                    for second_leg in &second_legs {
+                        def.multi_leg_deltas.push((orient(delta), *second_leg));
+                    }
+                }
+            }
+            continue;
+        }
+
+        for atom in atoms {
+            for delta in parse_directions(&directions, atom, betza)? {
+                let delta = orient(delta);
+                if initial {
+                    err_assert!(!can_capture && range == 1, "Initial moves must be non-capturing leaps in '{betza}'");
+                    initial_deltas.push((delta, lame));
+                    continue;
+                }
+                if can_move {
+                    add_movement(&mut def, delta, range, false);
+                }
+                if can_capture {
+                    add_movement(&mut def, delta, range, true);
+                }
+            }
+        }
+    }
+
+    for ((dx, dy), lame) in initial_deltas {
+        // Double steps of a non-capturing leap are implemented as 2 consecutive jumps (like the pawn)
+        let is_double_jump = dx % 2 == 0 && dy % 2 == 0 && def.translate_jump_deltas.contains(&(dx / 2, dy / 2));
+        if !is_double_jump {
+            def.initial_moves.push(initial_move_path((dx, dy), lame, betza)?);
+        } else if def.double_jump_squares.is_empty() {
+            let ranks = if flip { [height - 1, height - 2] } else { [0, 1] };
+            for x in 0..width {
+                for y in ranks {
+                    def.double_jump_squares.push((x, y));
+                }
+            }
+        }
+    }
+    Ok(def)
+}
+
+/// Returns the path of an initial move to `delta`: the leap itself, or all the squares in between if it's lame
+fn initial_move_path((dx, dy): (i8, i8), lame: bool, betza: &str) -> wrap_res!(Vec<(i8, i8)>) {
+    if !lame {
+        return Ok(vec![(dx, dy)]);
+    }
+    err_assert!(dx == 0 || dy == 0 || dx.abs() == dy.abs(), "Lame initial moves must be in a straight line in '{betza}'");
+    let (ux, uy) = (dx.signum(), dy.signum());
+    Ok((1..=dx.abs().max(dy.abs())).map(|k| (k * ux, k * uy)).collect())
+}
+
+/// Adds a leap (range 1) or a ride (range 0 for unlimited) in the direction of `delta`
+fn add_movement(def: &mut PieceDefinition, delta: (i8, i8), range: usize, capture: bool) {
+    let range = if range >= MAX_RANGE { 0 } else { range };
+    if range != 1 && delta.0.abs() <= 1 && delta.1.abs() <= 1 {
+        // Use the built-in sliding moves for rooks, bishops and their limited versions (like W2),
+        // unless the slide in this direction already has a different range
+        let (translate, attack, max_range) = match delta {
+            (0, 1) => (&mut def.translate_north, &mut def.attack_north, &mut def.range_north),
+            (0, -1) => (&mut def.translate_south, &mut def.attack_south, &mut def.range_south),
+            (1, 0) => (&mut def.translate_east, &mut def.attack_east, &mut def.range_east),
+            (-1, 0) => (&mut def.translate_west, &mut def.attack_west, &mut def.range_west),
+            (1, 1) => (&mut def.translate_northeast, &mut def.attack_northeast, &mut def.range_northeast),
+            (-1, 1) => (&mut def.translate_northwest, &mut def.attack_northwest, &mut def.range_northwest),
+            (1, -1) => (&mut def.translate_southeast, &mut def.attack_southeast, &mut def.range_southeast),
+            _ => (&mut def.translate_southwest, &mut def.attack_southwest, &mut def.range_southwest),
+        };
+        if (!*translate && !*attack) || *max_range as usize == range {
+            *max_range = range as u8;
+            if capture { *attack = true; } else { *translate = true; }
+            return;
+        }
+    }
+    if range == 1 {
+        let jumps = if capture { &mut def.attack_jump_deltas } else { &mut def.translate_jump_deltas };
+        if !jumps.contains(&delta) {
+            jumps.push(delta);
+        }
+    } else {
+        let range = if range == 0 { MAX_RANGE } else { range };
+        let run: Vec<(i8, i8)> = (1..=range as i8).map(|n| (n * delta.0, n * delta.1)).collect();
+        let runs = if capture { &mut def.attack_sliding_deltas } else { &mut def.translate_sliding_deltas };
+        if !runs.contains(&run) {
+            runs.push(run);
+        }
+    }
+}
+
+/// Returns the deltas selected by the direction modifiers. Multiple modifiers are combined
+/// (e.g. `fbW` is the same as `vW`), pairs of letters are matched first.
+fn parse_directions(directions: &str, atom: (i8, i8), betza: &str) -> wrap_res!(Vec<(i8, i8)>) {
+    let groups = direction_groups(atom);
+    if directions.is_empty() {
+        return Ok(groups[0].1.clone());
+    }
+    let find_group = |s: &str| groups.iter().find(|(dir_str, _)| *dir_str == s).map(|(_, g)| g);
+    let mut deltas = Vec::new();
+    let mut i = 0;
+    while i < directions.len() {
+        let group = {
+            if let Some(group) = directions.get(i..i+2).and_then(find_group) {
+                i += 2;
+                group
+            } else if let Some(group) = find_group(&directions[i..i+1]) {
+                i += 1;
+                group
+            } else {
+                err!("Invalid direction '{}' for atom '{}' in '{betza}'", &directions[i..i+1], atom_letter(atom));
+            }
+        };
+        for delta in group {
+            if !deltas.contains(delta) {
+                deltas.push(*delta);
+            }
+        }
+    }
+    Ok(deltas)
+}
+
+/// Returns the Betza string that describes the movement of a piece, like `"fmWfcFifmnD"` for a white pawn.
+/// The directions are relative to the first player that can use the piece (white if `ids[0]` is set).
+/// Leaps that don't correspond to any atom (longer than 3 squares) are not included, and multi-leg moves are
+/// only included if the second leg is a leap of the same atom in any direction (like `caK`).
+pub fn to_betza(def: &PieceDefinition) -> String {
+    // Flip the board for black-only pieces, so that "forward" means south
+    let flip = def.ids[0].is_none() && def.ids[1].is_some();
+    let orient = |(dx, dy): (i8, i8)| if flip { (-dx, -dy) } else { (dx, dy) };
+
+    // Key: (atom, range), where range is 1 for leapers and 0 for unlimited riders
+    let mut atoms: BTreeMap<((i8, i8), usize), AtomDirections> = BTreeMap::new();
+    let mut add = |delta: (i8, i8), range: usize, modality: u8| {
+        let delta = orient(delta);
+        if let Some(atom) = atom_of(delta) {
+            *atoms.entry((atom, range)).or_default().entry(delta).or_default() |= modality;
+        }
+    };
+
+    let slides = [
+        ((0, 1), def.translate_north, def.attack_north, def.range_north),
+        ((0, -1), def.translate_south, def.attack_south, def.range_south),
+        ((1, 0), def.translate_east, def.attack_east, def.range_east),
+        ((-1, 0), def.translate_west, def.attack_west, def.range_west),
+        ((1, 1), def.translate_northeast, def.attack_northeast, def.range_northeast),
+        ((-1, 1), def.translate_northwest, def.attack_northwest, def.range_northwest),
+        ((1, -1), def.translate_southeast, def.attack_southeast, def.range_southeast),
+        ((-1, -1), def.translate_southwest, def.attack_southwest, def.range_southwest),
+    ];
+    for (delta, translate, attack, range) in slides {
+        if translate { add(delta, range as usize, CAN_MOVE); }
+        if attack { add(delta, range as usize, CAN_CAPTURE); }
+    }
+    for delta in &def.translate_jump_deltas {
+        add(*delta, 1, CAN_MOVE);
+    }
+    for delta in &def.attack_jump_deltas {
+        add(*delta, 1, CAN_CAPTURE);
+    }
+    for run in &def.translate_sliding_deltas {
+        if let Some((delta, range)) = rider_of(run) {
+            add(delta, range, CAN_MOVE);
+        }
+    }
+    for run in &def.attack_sliding_deltas {
+        if let Some((delta, range)) = rider_of(run) {
+            add(delta, range, CAN_CAPTURE);
+        }
+    }
+
+    // Unlimited riders first, then leapers, then limited riders
+    let mut keys: Vec<_> = atoms.keys().copied().collect();
+    keys.sort_by_key(|&(atom, range)| (range, atom_index(atom)));
+    let mut tokens = Vec::new();
+    for key in keys {
+        let (atom, range) = key;
+        let letter = atom_letter(atom);
+        let atom_str = match range {
+            0 if atom == (1, 0) => "R".to_string(),
+            0 if atom == (1, 1) => "B".to_string(),
+            0 => format!("{letter}{letter}"),
+            1 => letter.to_string(),
+            r => format!("{letter}{r}"),
+        };
+        push_atom_tokens(&mut tokens, atom, &atom_str, &atoms[&key], "", "");
+    }
+
+    // Double jumps (like the initial pawn move) are lame leaps of twice the distance
+    if def.can_double_jump() {
+        let mut double_atoms: BTreeMap<(i8, i8), AtomDirections> = BTreeMap::new();
+        for &(dx, dy) in &def.translate_jump_deltas {
+            let delta = orient((2 * dx, 2 * dy));
+            if let Some(atom) = atom_of(delta) {
+                double_atoms.entry(atom).or_default().insert(delta, CAN_MOVE);
+            }
+        }
+        for (atom, directions) in &double_atoms {
+            let letter = atom_letter(*atom).to_string();
+            push_atom_tokens(&mut tokens, *atom, &letter, directions, "i", "n");
+        }
+    }
+
+    // Initial moves, lame if they pass through the squares in between
+    let mut initial_atoms: BTreeMap<((i8, i8), bool), AtomDirections> = BTreeMap::new();
+    for path in &def.initial_moves {
+        let (Some(&delta), lame) = (path.last(), path.len() > 1) else { continue };
+        if !initial_move_path(delta, lame, "").is_ok_and(|expected| expected == *path) {
+            continue;
+        }
+        if let Some(atom) = atom_of(orient(delta)) {
+            initial_atoms.entry((atom, lame)).or_default().insert(orient(delta), CAN_MOVE);
+        }
+    }
+    for ((atom, lame), directions) in &initial_atoms {
+        let letter = atom_letter(*atom).to_string();
+        push_atom_tokens(&mut tokens, *atom, &letter, directions, "i", if *lame { "n" } else { "" });
+    }
+
+    // Multi-leg captures
+    if !def.multi_leg_deltas.is_empty() {
+        push_multi_leg_tokens(&mut tokens, &def.multi_leg_deltas, orient);
+    }
+
+    // Use the compound shorthands when possible
+    combine_tokens(&mut tokens, "W", "F", "K");
+    combine_tokens(&mut tokens, "R", "B", "Q");
+    tokens.concat()
+}
+
+/// Appends the tokens of an atom, grouped by modality (move and capture, move only, capture only)
+fn push_atom_tokens(tokens: &mut Vec<String>, atom: (i8, i8), atom_str: &str, directions: &AtomDirections, initial: &str, lame: &str) {
+    let all_directions = directions_of(atom);
+    for (modality, modality_str) in [(CAN_MOVE | CAN_CAPTURE, ""), (CAN_MOVE, "m"), (CAN_CAPTURE, "c")] {
+        let mut remaining: Vec<(i8, i8)> = directions.iter()
+            .filter(|(_, m)| **m == modality)
+            .map(|(d, _)| *d)
+            .collect();
+        if remaining.is_empty() {
+            continue;
+        }
+        if remaining.len() == all_directions.len() {
+            tokens.push(format!("{initial}{modality_str}{lame}{atom_str}"));
+            continue;
+        }
+        // Greedily cover the directions with the largest direction groups
+        for (dir_str, group) in direction_groups(atom).into_iter().skip(1) {
+            if group.iter().all(|d| remaining.contains(d)) {
+                remaining.retain(|d| !group.contains(d));
+                tokens.push(format!("{initial}{dir_str}{modality_str}{lame}{atom_str}"));
+            }
+        }
+    }
+}
+
+/// Appends the tokens of the multi-leg moves whose second leg can go in all the directions of the atom (like `caN`),
+/// or in all the directions of a king if the first leg is a king step (`caK`)
+fn push_multi_leg_tokens(tokens: &mut Vec<String>, multi_leg_deltas: &[MultiLegDelta], orient: impl Fn((i8, i8)) -> (i8, i8)) {
+    let second_legs = |first: (i8, i8)| -> Vec<(i8, i8)> {
+        multi_leg_deltas.iter().filter(|(f, _)| *f == first).map(|(_, second)| *second).collect()
+    };
+    let same_deltas = |a: &[(i8, i8)], b: &[(i8, i8)]| a.len() == b.len() && a.iter().all(|d| b.contains(d));
+    let king_steps: Vec<(i8, i8)> = directions_of((1, 0)).into_iter().chain(directions_of((1, 1))).collect();
+    if king_steps.iter().all(|step| same_deltas(&second_legs(*step), &king_steps)) {
+        tokens.push("caK".to_string());
+        return;
+    }
+    let mut atoms: BTreeMap<(i8, i8), AtomDirections> = BTreeMap::new();
+    for (first, _) in multi_leg_deltas {
+        if let Some(atom) = atom_of(*first) {
+            if same_deltas(&second_legs(*first), &directions_of(atom)) {
+                atoms.entry(atom).or_default().insert(orient(*first), CAN_CAPTURE);
+            }
+        }
+    }
+    for (atom, directions) in &atoms {
+        push_atom_tokens(tokens, *atom, &format!("a{}", atom_letter(*atom)), directions, "", "");
+    }
+}
+
+/// Replaces the tokens `a` and `b` by `combined` if both are present
+fn combine_tokens(tokens: &mut Vec<String>, a: &str, b: &str, combined: &str) {
+    if let (Some(i), Some(j)) = (tokens.iter().position(|t| t == a), tokens.iter().position(|t| t == b)) {
+        tokens[std::cmp::min(i, j)] = combined.to_string();
+        tokens.remove(std::cmp::max(i, j));
+    }
+}
+
+/// If the run is a straight line of equally spaced steps, returns the step and the range (0 if unlimited)
+fn rider_of(run: &[(i8, i8)]) -> Option<((i8, i8), usize)> {
+    let &(dx, dy) = run.first()?;
+    for (i, &(x, y)) in run.iter().enumerate() {
+        let n = i as i8 + 1;
+        if x != n * dx || y != n * dy {
+            return None;
+        }
+    }
+    let range = if run.len() >= MAX_RANGE { 0 } else { run.len() };
+    Some(((dx, dy), range))
+}
+
+/// Returns the atom (a, b) with a >= b >= 0 that corresponds to a delta
+fn atom_of((dx, dy): (i8, i8)) -> Option<(i8, i8)> {
+    let (a, b) = (dx.abs(), dy.abs());
+    let atom = if a >= b { (a, b) } else { (b, a) };
+    atom_index(atom).map(|_| atom)
+}
+fn atom_index(atom: (i8, i8)) -> Option<usize> {
+    ATOMS.iter().position(|(a, _)| *a == atom)
+}
+fn atom_letter(atom: (i8, i8)) -> char {
+    ATOMS.iter().find(|(a, _)| *a == atom).unwrap().1
+}
+
+/// All the deltas of an atom
+fn directions_of(atom: (i8, i8)) -> Vec<(i8, i8)> {
+    direction_groups(atom).swap_remove(0).1
+}
+
+/// Direction modifiers that can be applied to an atom, from largest to smallest.
+/// The first entry (empty modifier) contains all the directions.
+fn direction_groups((a, b): (i8, i8)) -> Vec<(&'static str, Vec<(i8, i8)>)> {
+    if b == 0 {
+        // Orthogonal
+        let (f, bk, r, l) = ((0, a), (0, -a), (a, 0), (-a, 0));
+        vec![
+            ("", vec![f, bk, r, l]),
+            ("v", vec![f, bk]), ("s", vec![r, l]),
+            ("f", vec![f]), ("b", vec![bk]), ("r", vec![r]), ("l", vec![l]),
+        ]
+    } else if a == b {
+        // Diagonal
+        let (fr, fl, br, bl) = ((a, a), (-a, a), (a, -a), (-a, -a));
+        vec![
+            ("", vec![fr, fl, br, bl]),
+            ("f", vec![fr, fl]), ("b", vec![br, bl]), ("r", vec![fr, br]), ("l", vec![fl, bl]),
+            ("fr", vec![fr]), ("fl", vec![fl]), ("br", vec![br]), ("bl", vec![bl]),
+        ]
+    } else {
+        // Oblique. The first letter of a pair is the main direction
+        let (fr, fl, rf, lf) = ((b, a), (-b, a), (a, b), (-a, b));
+        let (br, bl, rb, lb) = ((b, -a), (-b, -a), (a, -b), (-a, -b));
+        vec![
+            ("", vec![fr, fl, rf, lf, br, bl, rb, lb]),
+            ("f", vec![fr, fl, rf, lf]), ("b", vec![br, bl, rb, lb]),
+            ("r", vec![rf, rb, fr, br]), ("l", vec![lf, lb, fl, bl]),
+            ("v", vec![fr, fl, br, bl]), ("s", vec![rf, lf, rb, lb]),
+            ("ff", vec![fr, fl]), ("bb", vec![br, bl]), ("rr", vec![rf, rb]), ("ll", vec![lf, lb]),
+            ("fs", vec![rf, lf]), ("bs", vec![rb, lb]), ("rv", vec![fr, br]), ("lv", vec![fl, bl]),
+            ("fr", vec![fr]), ("fl", vec![fl]), ("rf", vec![rf]), ("lf", vec![lf]),
+            ("br", vec![br]), ("bl", vec![bl]), ("rb", vec![rb]), ("lb", vec![lb]),
+        ]
+    }
+}
@@ -3,6 +3,9 @@ use crate::utils::to_index;
 use crate::MoveGen;
 use crate::types::{Centipawns, BDimensions};
 
+/// Multiplier of the score of a leader piece, also used for the last piece of a type that cannot go extinct
+pub const LEADER_MULTIPLIER: Centipawns = 4;
+
 /// Returns a score value for a piece, given its movement pattern
 pub fn compute_material_score(mp: &PieceDefinition, dims: &BDimensions) -> Centipawns {
     // This function is called only once, so it's worth it to implement a more complex scoring system
@@ -18,26 +21,37 @@ pub fn compute_material_score(mp: &PieceDefinition, dims: &BDimensions) -> Centi
     let diag = 1.4 * average_dimension(dims, false, false, true, false);
     let antidiag = 1.4 * average_dimension(dims, false, false, false, true);
     
+    // The dimensions count the squares in both senses of a line, so a limited range covers about twice as many
+    let limit = |dimension: f32, range: u8| if range == 0 { dimension } else { dimension.min(2.0 * range as f32) };
+    let north = limit(height, mp.range_north);
+    let south = limit(height, mp.range_south);
+    let east = limit(width, mp.range_east);
+    let west = limit(width, mp.range_west);
+    let northeast = limit(diag, mp.range_northeast);
+    let southwest = limit(diag, mp.range_southwest);
+    let northwest = limit(antidiag, mp.range_northwest);
+    let southeast = limit(antidiag, mp.range_southeast);
+    
     // 130 centipawns for each direction (Rook is 4*130 = 520 centipawns, Queen is 8*130 = 1040 centipawns)
-    if mp.attack_north { score += (ATTACK_MUL * height) as Centipawns }
-    if mp.attack_south { score += (ATTACK_MUL * height) as Centipawns }
-    if mp.attack_east  { score += (ATTACK_MUL * width) as Centipawns }
-    if mp.attack_west  { score += (ATTACK_MUL * width) as Centipawns }
-    if mp.translate_north { score += (TRANSLATE_MUL * height) as Centipawns }
-    if mp.translate_south { score += (TRANSLATE_MUL * height) as Centipawns }
-    if mp.translate_east  { score += (TRANSLATE_MUL * width) as Centipawns }
-    if mp.translate_west  { score += (TRANSLATE_MUL * width) as Centipawns }
+    if mp.attack_north { score += (ATTACK_MUL * north) as Centipawns }
+    if mp.attack_south { score += (ATTACK_MUL * south) as Centipawns }
+    if mp.attack_east  { score += (ATTACK_MUL * east) as Centipawns }
+    if mp.attack_west  { score += (ATTACK_MUL * west) as Centipawns }
+    if mp.translate_north { score += (TRANSLATE_MUL * north) as Centipawns }
+    if mp.translate_south { score += (TRANSLATE_MUL * south) as Centipawns }
+    if mp.translate_east  { score += (TRANSLATE_MUL * east) as Centipawns }
+    if mp.translate_west  { score += (TRANSLATE_MUL * west) as Centipawns }
     
-    if mp.attack_northeast { score += (ATTACK_MUL * diag) as Centipawns }
-    if mp.attack_southwest { score += (ATTACK_MUL * diag) as Centipawns }
-    if mp.attack_northwest { score += (ATTACK_MUL * antidiag) as Centipawns }
-    if mp.attack_southeast { score += (ATTACK_MUL * antidiag) as Centipawns }
-    if mp.translate_northeast { score += (TRANSLATE_MUL * diag) as Centipawns }
-    if mp.translate_southwest { score += (TRANSLATE_MUL * diag) as Centipawns }
-    if mp.translate_northwest { score += (TRANSLATE_MUL * antidiag) as Centipawns }
-    if mp.translate_southeast { score += (TRANSLATE_MUL * antidiag) as Centipawns }
+    if mp.attack_northeast { score += (ATTACK_MUL * northeast) as Centipawns }
+    if mp.attack_southwest { score += (ATTACK_MUL * southwest) as Centipawns }
+    if mp.attack_northwest { score += (ATTACK_MUL * northwest) as Centipawns }
+    if mp.attack_southeast { score += (ATTACK_MUL * southeast) as Centipawns }
+    if mp.translate_northeast { score += (TRANSLATE_MUL * northeast) as Centipawns }
+    if mp.translate_southwest { score += (TRANSLATE_MUL * southwest) as Centipawns }
+    if mp.translate_northwest { score += (TRANSLATE_MUL * northwest) as Centipawns }
+    if mp.translate_southeast { score += (TRANSLATE_MUL * southeast) as Centipawns }
     
-    let only_able_to_slide = !mp.can_promote() && !mp.can_jump() && !mp.has_sliding_deltas();
+    let only_able_to_slide = !mp.can_promote() && !mp.can_jump() && !mp.has_sliding_deltas() && !mp.can_hop() && !mp.has_bent_riders();
     
     // Debuff for being limited to a single color of squares
     if !mp.can_slide_main_direction() && only_able_to_slide {
@@ -62,10 +76,39 @@ pub fn compute_material_score(mp: &PieceDefinition, dims: &BDimensions) -> Centi
     // 40 centipawns for each jump (Knight is 8*40 = 320 centipawns)
     score += (mp.translate_jump_deltas.len() * 20) as Centipawns;
     score += (mp.attack_jump_deltas.len() * 20) as Centipawns;
+    // Lame jumps can be blocked, so they are worth a bit less
+    score += (mp.translate_lame_jumps.len() * 15) as Centipawns;
+    score += (mp.attack_lame_jumps.len() * 15) as Centipawns;
+    // Multi-leg moves need a piece to capture in the first leg, but they can capture twice
+    score += (mp.multi_leg_deltas.len() * 5) as Centipawns;
+    // Initial moves can only be made once
+    score += (mp.initial_moves.len() * 5) as Centipawns;
     // 40 centipawns for each delta-based slide group
     for d in mp.translate_sliding_deltas.iter().chain(mp.attack_sliding_deltas.iter()) {
         score += (d.len() * 20) as Centipawns;
     }
+    // Hoppers need a hurdle, so each direction is worth half of a sliding direction
+    let hop_dimension = (width + height + diag + antidiag) / 4.0;
+    score += (0.5 * TRANSLATE_MUL * hop_dimension * mp.translate_hop_deltas.len() as f32) as Centipawns;
+    score += (0.5 * ATTACK_MUL * hop_dimension * mp.attack_hop_deltas.len() as f32) as Centipawns;
+    // Bent riders start their ride one step away, so each one is also worth half of a sliding direction,
+    // and the steps are worth as much as a jump
+    score += (0.5 * TRANSLATE_MUL * hop_dimension * mp.translate_bent_riders.len() as f32) as Centipawns;
+    score += (0.5 * ATTACK_MUL * hop_dimension * mp.attack_bent_riders.len() as f32) as Centipawns;
+    for riders in [&mp.translate_bent_riders, &mp.attack_bent_riders] {
+        let mut steps: Vec<_> = riders.iter().map(|(step, _)| step).collect();
+        steps.sort_unstable();
+        steps.dedup();
+        score += (steps.len() * 20) as Centipawns;
+    }
+
+    // Indirect captures need a piece next to the destination (or behind the starting square), so they are worth
+    // a fraction of the moves without capturing. Rifle captures keep the piece safe on its square
+    let indirect_capture_modes = [mp.capture_by_withdrawal, mp.capture_by_approach, mp.custodian_capture].iter().filter(|m| **m).count();
+    score += (indirect_capture_modes * 50) as Centipawns;
+    if mp.rifle_capture {
+        score += 50;
+    }
     
     // 40 centipawns for being able to promote
     if mp.can_promote() {
@@ -75,7 +118,7 @@ pub fn compute_material_score(mp: &PieceDefinition, dims: &BDimensions) -> Centi
     
     if mp.is_leader {
         // Leader piece is 4x the value of the regular piece
-        score *= 4;
+        score *= LEADER_MULTIPLIER;
     }
     
     // Minimum score is 10
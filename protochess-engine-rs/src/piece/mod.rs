@@ -7,20 +7,26 @@ use crate::{types::*, Position};
 // Note that in Rust a char is 4 bytes (equivalent to u32)
 pub type PieceId = char;
 
+/// Id used for the neutral pieces (like the duck in Duck chess) in FEN strings, which belong to neither player
+pub const NEUTRAL_PIECE_ID: PieceId = '@';
+
 mod piece_definition;
 mod piece_factory;
 mod material_score;
 mod positional_score;
 mod movement;
 mod precomputed_piece_def;
+pub mod betza;
 
 pub use piece_factory::PieceFactory;
-pub use piece_definition::PieceDefinition;
+pub use piece_definition::{PieceDefinition, MultiLegDelta, BentRiderDelta};
 
 use material_score::compute_material_score;
+pub(crate) use material_score::LEADER_MULTIPLIER;
 use positional_score::compute_piece_square_table;
 use precomputed_piece_def::PrecomputedPieceDef;
-use movement::{output_translations, output_captures};
+use movement::{output_translations, output_captures, output_indirect_captures, attacked_squares, translation_squares, hop_attacks, lame_jump_squares, multi_leg_captures, bent_rider_squares, Promotions};
+pub(crate) use movement::{hop_landings, castling_partner_destination, indirect_capture_victims};
 
 /// Represents a piece type (e.g. 'Rook'). Individual instances of this piece are represented by a 1 in the bitboard
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -37,6 +43,8 @@ pub struct Piece {
     player_num: Player,
     /// Zobrist hashes for this piece at each board index
     zobrist_hashes: Vec<ZobKey>,
+    /// Zobrist hashes for each number of pieces of this type in the hand (0 for an empty hand)
+    hand_zobrist_hashes: Vec<ZobKey>,
     
     /// Material score for this piece
     material_score: Centipawns,
@@ -47,34 +55,71 @@ pub struct Piece {
     
     /// Number of `1` bits in the bitboard
     num_pieces: u32,
-    /// Result of `num_pieces * material_score`
+    /// Result of `(num_pieces + num_in_hand) * material_score`
     total_material_score: Centipawns,
+    /// Number of pieces of this type in the hand of the player, that can be dropped on the board
+    num_in_hand: u8,
+    /// Number of pieces of this type in the starting position of the variant.
+    /// Used to know which pieces have been lost, for `promote_to_captured_only`
+    initial_num_pieces: u32,
     
-    /// Positions at which this piece can castle. Used if `can_castle` or `is_castle_rook` are `true`
-    castle_squares: Bitboard,
+    /// Positions of the pieces of this type that have not moved yet (so they can castle or make their
+    /// initial moves). Used if `tracks_unmoved()` is `true`
+    unmoved_squares: Bitboard,
 }
 
 impl Piece {
     pub fn new(definition: PieceDefinition, player_num: Player, dims: &BDimensions) -> Piece {
         let id = definition.ids[player_num as usize]
             .expect("Attempted to create piece for a player that does not have access to this piece");
+        // The squares outside the region of the piece are treated as walls when precomputing its moves.
+        // The material score and the piece-square tables use the whole board
+        let region = &definition.allowed_squares[player_num as usize];
+        let mut region_dims = dims.clone();
+        if !region.is_empty() {
+            region_dims.bounds &= Bitboard::from_coord_list(region);
+        }
         let material_score = compute_material_score(&definition, dims);
-        let zobrist_hashes = Piece::random_zobrist(id, player_num);
+        let (zobrist_hashes, hand_zobrist_hashes) = Piece::random_zobrist(id, player_num);
         let piece_square_table = compute_piece_square_table(&definition, dims, false);
         let piece_square_table_endgame = compute_piece_square_table(&definition, dims, true);
-        Piece {
+        let mut piece = Piece {
             id,
-            precomp: PrecomputedPieceDef::from((&definition, dims)),
+            precomp: PrecomputedPieceDef::from((&definition, &region_dims)),
             type_def: definition,
             player_num,
             zobrist_hashes,
+            hand_zobrist_hashes,
             material_score,
             piece_square_table,
             piece_square_table_endgame,
             bitboard: Bitboard::zero(),
             num_pieces: 0,
             total_material_score: 0,
-            castle_squares: Bitboard::zero(),
+            num_in_hand: 0,
+            initial_num_pieces: 0,
+            unmoved_squares: Bitboard::zero(),
+        };
+        piece.add_forced_promotion_squares();
+        piece
+    }
+    
+    /// Promotion is also forced on the squares of the optional promotion zone where the piece could never move
+    /// again (like a shogi lance on the last rank, or a shogi knight on the last 2 ranks)
+    fn add_forced_promotion_squares(&mut self) {
+        let bounds = self.precomp.allowed_squares.clone();
+        let walls = !&bounds;
+        let mut optional_squares = self.precomp.optional_promotion_squares.clone();
+        while let Some(index) = optional_squares.lowest_one() {
+            optional_squares.clear_bit(index);
+            // Hoppers and multi-leg moves depend on other pieces, so assume that they can always move
+            if self.type_def.can_hop() || !self.precomp.multi_leg_jumps[index as usize].is_empty() {
+                continue;
+            }
+            let moves = self.get_translation_squares(index, &walls, &bounds) | self.get_attacked_squares(index, &walls, &bounds);
+            if moves.is_zero() {
+                self.precomp.promotion_squares.set_bit(index);
+            }
         }
     }
     
@@ -128,14 +173,39 @@ impl Piece {
         self.num_pieces
     }
     
+    /// Returns the number of pieces of this type in the hand of the player
+    pub fn get_num_in_hand(&self) -> u8 {
+        self.num_in_hand
+    }
+    
+    /// Returns the number of pieces of this type in the starting position of the variant
+    pub fn get_initial_num_pieces(&self) -> u32 {
+        self.initial_num_pieces
+    }
+    
+    /// Sets the number of pieces of this type in the starting position of the variant
+    pub fn set_initial_num_pieces(&mut self, num_pieces: u32) {
+        self.initial_num_pieces = num_pieces;
+    }
+    
+    /// Returns `true` if this piece can be at the given index (it's in bounds and inside its region)
+    pub fn is_allowed_at(&self, index: BIndex) -> bool {
+        self.precomp.allowed_squares.get_bit(index)
+    }
+    
+    /// Returns `true` if this piece can be dropped from the hand at the given index (ignoring occupancy)
+    pub fn can_drop_at(&self, index: BIndex) -> bool {
+        self.precomp.drop_squares.get_bit(index)
+    }
+    
     /// Returns `true` if this piece behaves like a rook when castling
     pub fn is_rook(&self) -> bool {
         self.type_def.is_castle_rook
     }
     
-    /// Returns `true` if this piece has not moved from its starting position (can castle)
+    /// Returns `true` if this piece has not moved from its starting position (can castle or make its initial moves)
     pub fn has_not_moved(&self, index: BIndex) -> bool {
-        self.castle_squares.get_bit(index)
+        self.unmoved_squares.get_bit(index)
     }
     
     /// Returns `true` if this piece explodes when it makes a capture
@@ -153,6 +223,21 @@ impl Piece {
         self.precomp.instant_win_squares.get_bit(index)
     }
     
+    /// Returns `true` if the player loses when all the pieces of this type have been captured
+    pub fn loses_on_extinction(&self) -> bool {
+        self.type_def.lose_on_extinction
+    }
+    
+    /// Returns `true` if the player loses because there are no pieces of this type left, on the board or in the hand
+    pub fn is_extinct(&self) -> bool {
+        self.type_def.lose_on_extinction && self.num_pieces == 0 && self.num_in_hand == 0
+    }
+    
+    /// Returns `true` if moving this piece resets the halfmove clock (like pawns in standard chess)
+    pub fn resets_move_clock(&self) -> bool {
+        self.type_def.resets_move_clock
+    }
+    
     /// Returns `true` if this piece is immune to explosions (except when it's the one that explodes)
     pub fn immune_to_explosion(&self) -> bool {
         self.type_def.immune_to_explosion
@@ -163,20 +248,26 @@ impl Piece {
         self.zobrist_hashes[index as usize]
     }
     
-    /// Get the zobrist hash for the castling right of this piece at the given index
+    /// Get the zobrist hash for the castling right (or the initial moves) of this piece at the given index
     pub fn get_castle_zobrist(&self, index: BIndex) -> ZobKey {
         // This could be implemented with a separate random array, but this is simpler
         self.zobrist_hashes[index as usize] >> 1
     }
     
+    /// Get the zobrist hash for the current number of pieces of this type in the hand
+    pub fn get_hand_zobrist(&self) -> ZobKey {
+        self.hand_zobrist_hashes[self.num_in_hand as usize]
+    }
+    
     /// Get the material score for 1 unit of this piece
     pub fn get_material_score(&self) -> Centipawns {
         self.material_score
     }
     
-    /// Returns `true` if this piece is involved in castling (either can castle or is a castle rook)
-    pub fn used_in_castling(&self) -> bool {
-        self.type_def.can_castle() || self.type_def.is_castle_rook
+    /// Returns `true` if this piece needs to know whether it has moved: it's involved in castling
+    /// (either can castle or is a castle rook) or it has initial moves
+    pub fn tracks_unmoved(&self) -> bool {
+        self.type_def.can_castle() || self.type_def.is_castle_rook || self.type_def.has_initial_moves()
     }
     
     /// Move a piece from one index to another
@@ -185,16 +276,16 @@ impl Piece {
     /// **Don't call this directly, use `PieceSet::move_piece()` instead.**
     #[inline]
     pub fn move_piece_(&mut self, from: BIndex, to: BIndex, set_can_castle: bool) -> bool {
-        let could_castle = self.castle_squares.get_bit(from);
+        let could_castle = self.unmoved_squares.get_bit(from);
         self.bitboard.clear_bit(from);
         self.bitboard.set_bit(to);
         
-        if self.used_in_castling() {
-            self.castle_squares.clear_bit(from);
+        if self.tracks_unmoved() {
+            self.unmoved_squares.clear_bit(from);
             if set_can_castle {
-                self.castle_squares.set_bit(to);
+                self.unmoved_squares.set_bit(to);
             } else {
-                self.castle_squares.clear_bit(to);
+                self.unmoved_squares.clear_bit(to);
             }
         }
         could_castle
@@ -208,8 +299,8 @@ impl Piece {
         self.num_pieces += 1;
         self.total_material_score += self.material_score;
         
-        if set_can_castle && self.used_in_castling() {
-            self.castle_squares.set_bit(index);
+        if set_can_castle && self.tracks_unmoved() {
+            self.unmoved_squares.set_bit(index);
         }
     }
     
@@ -218,8 +309,8 @@ impl Piece {
     /// **Don't call this directly, use `PieceSet::remove_piece()` instead.**
     #[inline]
     pub fn remove_piece_(&mut self, index: BIndex) -> bool {
-        let could_castle = self.castle_squares.get_bit(index);
-        self.castle_squares.clear_bit(index);
+        let could_castle = self.unmoved_squares.get_bit(index);
+        self.unmoved_squares.clear_bit(index);
         self.bitboard.clear_bit(index);
         
         self.num_pieces -= 1;
@@ -227,7 +318,23 @@ impl Piece {
         could_castle
     }
     
-    /// Get the material score for all current units of this piece
+    /// Add a piece of this type to the hand of the player.
+    /// **Don't call this directly, use `PieceSet::add_to_hand()` instead.**
+    #[inline]
+    pub fn add_to_hand_(&mut self) {
+        self.num_in_hand += 1;
+        self.total_material_score += self.material_score;
+    }
+    
+    /// Remove a piece of this type from the hand of the player (assuming there is one)
+    /// **Don't call this directly, use `PieceSet::remove_from_hand()` instead.**
+    #[inline]
+    pub fn remove_from_hand_(&mut self) {
+        self.num_in_hand -= 1;
+        self.total_material_score -= self.material_score;
+    }
+    
+    /// Get the material score for all current units of this piece (including the ones in the hand)
     pub fn get_material_score_all(&self) -> Centipawns {
         self.total_material_score
     }
@@ -259,24 +366,45 @@ impl Piece {
     pub fn output_translations(&self, position: &Position, enemies: &Bitboard,
         occ_or_not_in_bounds: &Bitboard, out_moves: &mut Vec<Move>)
     {
+        let promotions = self.get_promotions(position);
+        let start = out_moves.len();
         let mut bb_copy = self.bitboard.clone();
         while let Some(index) = bb_copy.lowest_one() {
-            let can_castle = self.type_def.can_castle() && self.castle_squares.get_bit(index);
+            let has_not_moved = self.unmoved_squares.get_bit(index);
+            let can_castle = self.type_def.can_castle() && has_not_moved;
+            let initial_moves: &[_] = if has_not_moved { &self.precomp.initial_moves[index as usize] } else { &[] };
             output_translations(
                 &self.type_def,
                 index,
                 position,
                 enemies,
-                &self.precomp.promotion_squares,
+                &promotions,
                 occ_or_not_in_bounds,
+                &self.precomp.allowed_squares,
                 can_castle,
                 &self.precomp.double_jump_squares,
+                &self.precomp.slide_range_masks[index as usize],
                 &self.precomp.jump_bitboards_translate,
-                &self.type_def.promo_vals[self.player_num as usize],
+                &self.precomp.lame_jumps_translate[index as usize],
+                &self.precomp.bent_riders_translate[index as usize],
+                initial_moves,
                 out_moves
             );
             bb_copy.clear_bit(index);
         }
+        if self.type_def.has_indirect_captures() {
+            // The moves that capture by withdrawal, approach or custody are generated with the captures
+            let allies = position.pieces[self.player_num as usize].get_occupied();
+            let bounds = &position.dimensions.bounds;
+            // `enemies` also includes the squares out of bounds
+            let enemies = enemies & bounds;
+            let moves: Vec<Move> = out_moves.drain(start..).filter(|mv| {
+                !matches!(mv.get_move_type(), MoveType::Quiet | MoveType::Promotion) ||
+                !self.get_translation_squares(mv.get_from(), &position.occ_or_out_bounds, bounds).get_bit(mv.get_to()) ||
+                indirect_capture_victims(&self.type_def, mv.get_from(), mv.get_to(), &enemies, allies).is_zero()
+            }).collect();
+            out_moves.extend(moves);
+        }
     }
     
     /// Get all the capture moves that all instances of this piece can make.
@@ -284,6 +412,8 @@ impl Piece {
     pub fn output_captures(&self, position: &Position, enemies: &Bitboard,
         occ_or_not_in_bounds: &Bitboard, out_moves: &mut Vec<Move>)
     {
+        let promotions = self.get_promotions(position);
+        let start = out_moves.len();
         let mut bb_copy = self.bitboard.clone();
         while let Some(index) = bb_copy.lowest_one() {
             output_captures(
@@ -291,14 +421,76 @@ impl Piece {
                 index,
                 position,
                 enemies,
-                &self.precomp.promotion_squares,
+                &promotions,
                 occ_or_not_in_bounds,
+                &self.precomp.allowed_squares,
+                &self.precomp.slide_range_masks[index as usize],
                 &self.precomp.jump_bitboards_capture[index as usize],
-                &self.type_def.promo_vals[self.player_num as usize],
+                &self.precomp.lame_jumps_capture[index as usize],
+                &self.precomp.multi_leg_jumps[index as usize],
+                &self.precomp.bent_riders_capture[index as usize],
                 out_moves
             );
             bb_copy.clear_bit(index);
         }
+        if self.type_def.rifle_capture {
+            self.convert_to_rifle_captures(start, out_moves);
+        }
+        // The destinations of the indirect captures are already inside the region
+        if self.type_def.has_indirect_captures() {
+            let allies = position.pieces[self.player_num as usize].get_occupied();
+            let enemies = enemies & &position.dimensions.bounds;
+            let mut bb_copy = self.bitboard.clone();
+            while let Some(index) = bb_copy.lowest_one() {
+                let destinations = self.get_translation_squares(index, &position.occ_or_out_bounds, &position.dimensions.bounds);
+                output_indirect_captures(&self.type_def, index, destinations, &enemies, allies, &promotions, out_moves);
+                bb_copy.clear_bit(index);
+            }
+        }
+    }
+    
+    /// Replaces the captures in `out_moves[start..]` with rifle captures of the same victims, where the piece
+    /// stays on its square. Multi-leg captures are kept as they are
+    fn convert_to_rifle_captures(&self, start: usize, out_moves: &mut Vec<Move>) {
+        let captures: Vec<Move> = out_moves.drain(start..).collect();
+        for mv in captures {
+            if mv.get_intermediate().is_some() {
+                out_moves.push(mv);
+                continue;
+            }
+            // Captures of the same victim with different promotions are the same rifle capture
+            let from = mv.get_from();
+            let rifle_capture = Move::new(from, from, mv.get_target(), MoveType::Capture, None).with_indirect_capture();
+            if !out_moves[start..].contains(&rifle_capture) {
+                out_moves.push(rifle_capture);
+            }
+        }
+    }
+    
+    /// Returns the squares where this piece promotes and the pieces that it can promote to.
+    /// If `promote_to_captured_only` is set, the pieces are filtered when the promotion moves are generated,
+    /// so that nothing is allocated for each call.
+    fn get_promotions<'a>(&'a self, position: &'a Position) -> Promotions<'a> {
+        let captured_only = self.type_def.promote_to_captured_only.then(|| &position.pieces[self.player_num as usize]);
+        Promotions {
+            forced_squares: &self.precomp.promotion_squares,
+            optional_squares: &self.precomp.optional_promotion_squares,
+            pieces: &self.type_def.promo_vals[self.player_num as usize],
+            captured_only,
+        }
+    }
+    
+    /// Get all the drops of this piece type from the hand of the player on empty squares.
+    /// The moves are added to the `out_moves` vector.
+    pub fn output_drops(&self, occ_or_not_in_bounds: &Bitboard, out_moves: &mut Vec<Move>) {
+        if self.num_in_hand == 0 {
+            return;
+        }
+        let mut drops = &self.precomp.drop_squares & !occ_or_not_in_bounds;
+        while let Some(index) = drops.lowest_one() {
+            out_moves.push(Move::drop(self.id, index));
+            drops.clear_bit(index);
+        }
     }
     
     /// Returns the piece definition for this piece type
@@ -310,13 +502,51 @@ impl Piece {
     pub fn get_capture_jumps(&self, index: BIndex) -> &Bitboard {
         &self.precomp.jump_bitboards_capture[index as usize]
     }
+    /// Look up the precomputed bitboard for the squares where the slides of this piece
+    /// can land from the given index, according to their maximum range.
+    pub fn get_slide_range_mask(&self, index: BIndex) -> &Bitboard {
+        &self.precomp.slide_range_masks[index as usize]
+    }
+    /// Returns the squares that a piece of this type at the given index attacks,
+    /// whether they are occupied or not
+    pub fn get_attacked_squares(&self, index: BIndex, occ_or_not_in_bounds: &Bitboard, bounds: &Bitboard) -> Bitboard {
+        let lame_jumps = &self.precomp.lame_jumps_capture[index as usize];
+        let bent_riders = &self.precomp.bent_riders_capture[index as usize];
+        attacked_squares(&self.type_def, index, occ_or_not_in_bounds, bounds, self.get_slide_range_mask(index), self.get_capture_jumps(index), lame_jumps, bent_riders)
+            & &self.precomp.allowed_squares
+    }
+    /// Returns the empty squares that a piece of this type at the given index can move to
+    /// without capturing
+    pub fn get_translation_squares(&self, index: BIndex, occ_or_not_in_bounds: &Bitboard, bounds: &Bitboard) -> Bitboard {
+        let lame_jumps = &self.precomp.lame_jumps_translate[index as usize];
+        let bent_riders = &self.precomp.bent_riders_translate[index as usize];
+        translation_squares(&self.type_def, index, occ_or_not_in_bounds, bounds, self.get_slide_range_mask(index), &self.precomp.jump_bitboards_translate[index as usize], lame_jumps, bent_riders)
+            & &self.precomp.allowed_squares
+    }
+    /// Returns the squares that a piece of this type at the given index attacks with lame jumps
+    pub fn get_lame_jump_attacks(&self, index: BIndex, occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+        lame_jump_squares(&self.precomp.lame_jumps_capture[index as usize], occ_or_not_in_bounds)
+    }
+    /// Returns the squares where a piece of this type at the given index captures with multi-leg moves
+    pub fn get_multi_leg_captures(&self, index: BIndex, enemies: &Bitboard, occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+        multi_leg_captures(&self.precomp.multi_leg_jumps[index as usize], index, enemies, occ_or_not_in_bounds)
+    }
+    /// Returns the squares that a piece of this type at the given index attacks by hopping over a hurdle
+    pub fn get_hop_attacks(&self, index: BIndex, occ_or_not_in_bounds: &Bitboard, bounds: &Bitboard) -> Bitboard {
+        hop_attacks(&self.type_def, index, occ_or_not_in_bounds, bounds) & &self.precomp.allowed_squares
+    }
+    /// Returns the squares that a piece of this type at the given index attacks with bent riders
+    pub fn get_bent_rider_attacks(&self, index: BIndex, occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+        bent_rider_squares(&self.precomp.bent_riders_capture[index as usize], occ_or_not_in_bounds) & &self.precomp.allowed_squares
+    }
     /// Look up the precomputed bitboard for the squares that explode when this
     /// piece explodes at the given index.
     pub fn get_explosion(&self, index: BIndex) -> &Bitboard {
         &self.precomp.explosion_bitboards[index as usize]
     }
     
-    fn random_zobrist(piece_id: PieceId, player: Player) -> Vec<ZobKey> {
+    /// Returns the zobrist hashes for each board index, and for each number of pieces in the hand
+    pub(crate) fn random_zobrist(piece_id: PieceId, player: Player) -> (Vec<ZobKey>, Vec<ZobKey>) {
         // Generate a predictable seed for the rng
         let seed = (player as u64) << 32 | (piece_id as u64);
         let mut rng = StdRng::seed_from_u64(seed);
@@ -325,7 +555,13 @@ impl Piece {
         for _ in 0..=255 {
             zobrist.push(rng.gen::<ZobKey>());
         }
-        zobrist
+        // An empty hand doesn't change the hash, so that positions without drops are not affected
+        let mut hand_zobrist = Vec::with_capacity(256);
+        hand_zobrist.push(0);
+        for _ in 1..=255 {
+            hand_zobrist.push(rng.gen::<ZobKey>());
+        }
+        (zobrist, hand_zobrist)
     }
 }
 
@@ -1,8 +1,31 @@
-use crate::utils::{to_index, from_index};
+use std::iter::{once, successors};
+
+use crate::utils::{to_index, from_index, squares_between};
 use crate::{PieceDefinition, MoveGen, Position, PieceId};
+use crate::position::piece_set::PieceSet;
+use super::precomputed_piece_def::BentRiderRay;
 use crate::types::{Bitboard, Move, MoveType, BCoord, BIndex};
 
 
+/// Squares where a piece promotes, and the pieces that it can promote to
+pub struct Promotions<'a> {
+    /// Squares where the piece must promote
+    pub forced_squares: &'a Bitboard,
+    /// Squares where the piece can choose whether to promote or not
+    pub optional_squares: &'a Bitboard,
+    /// Pieces that it can promote to. If none is available, the piece cannot move to the forced squares
+    pub pieces: &'a [PieceId],
+    /// If set, the piece can only promote to the piece types that the player has lost (see `promote_to_captured_only`)
+    pub captured_only: Option<&'a PieceSet>,
+}
+
+impl Promotions<'_> {
+    /// Returns the pieces that are available for promotion in the current position
+    fn available_pieces(&self) -> impl Iterator<Item = PieceId> + '_ {
+        self.pieces.iter().copied().filter(move |id| self.captured_only.is_none_or(|set| set.has_lost_piece(*id)))
+    }
+}
+
 /// Outputs all pseudo-legal translation (non-capture) moves for a piece at a given index
 #[allow(clippy::too_many_arguments)]
 pub fn output_translations(
@@ -10,12 +33,16 @@ pub fn output_translations(
     index: BIndex,
     position: &Position,
     enemies: &Bitboard,
-    promotion_squares: &Bitboard,
+    promotions: &Promotions,
     occ_or_not_in_bounds: &Bitboard,
+    allowed_squares: &Bitboard,
     can_castle: bool,
     double_jump_squares: &Bitboard,
+    slide_range_mask: &Bitboard,
     jumps_bitboard: &[Bitboard],
-    promotions: &[PieceId],
+    lame_jumps: &[(BIndex, Bitboard)],
+    bent_riders: &[BentRiderRay],
+    initial_moves: &[(BIndex, Bitboard, Option<BIndex>)],
     out_moves: &mut Vec<Move>
 ) {
     let attack_tables = MoveGen::attack_tables();
@@ -34,9 +61,11 @@ pub fn output_translations(
         movement.translate_southeast,
         movement.translate_southwest
     );
-    // Non-attacks (and in bounds) only
+    // Non-attacks (and in bounds) only, in range and inside the region of the piece
     slide_moves &= !&position.occ_or_out_bounds;
-    self::flatten_bb_moves(enemies, slide_moves, index, promotion_squares, promotions, out_moves);
+    slide_moves &= slide_range_mask;
+    slide_moves &= allowed_squares;
+    self::flatten_bb_moves(enemies, slide_moves, index, promotions, out_moves);
 
 
     // JUMP MOVES
@@ -47,12 +76,15 @@ pub fn output_translations(
         let mut jump_moves_copy = jump_moves.clone();
         while let Some(new_index) = jump_moves_copy.lowest_one() {
             let double_jump_moves = &jumps_bitboard[new_index as usize] & !&position.occ_or_out_bounds;
-            self::flatten_bb_moves_doublejump(double_jump_moves, index, new_index, promotion_squares, double_jump_squares, promotions, out_moves);
+            self::flatten_bb_moves_doublejump(double_jump_moves, index, new_index, promotions, double_jump_squares, out_moves);
             jump_moves_copy.clear_bit(new_index);
         }
     }
     // Flatten regular jump moves
-    self::flatten_bb_moves(enemies, jump_moves, index, promotion_squares, promotions, out_moves);
+    self::flatten_bb_moves(enemies, jump_moves, index, promotions, out_moves);
+    // Lame jumps, if their path is not blocked
+    let lame_jump_moves = lame_jump_squares(lame_jumps, &position.occ_or_out_bounds) & !&position.occ_or_out_bounds;
+    self::flatten_bb_moves(enemies, lame_jump_moves, index, promotions, out_moves);
     
     
     // SLIDING DELTAS
@@ -69,82 +101,115 @@ pub fn output_translations(
             if position.occ_or_out_bounds.get_bit(to) {
                 break;
             }
-            if promotion_squares.get_bit(to) {
-                //Add all the promotion moves
-                for c in promotions {
-                    out_moves.push(Move::new(index, to, 0, MoveType::Quiet, Some(*c)));
-                }
-            } else {
-                out_moves.push(Move::new(index, to, 0, MoveType::Quiet, None));
+            if allowed_squares.get_bit(to) {
+                output_move(index, to, to, false, promotions, out_moves);
             }
         }
     }
     
+    // HOPS
+    
+    for delta in &movement.translate_hop_deltas {
+        let (landings, _) = hop_landings(index, *delta, &position.occ_or_out_bounds, &position.dimensions.bounds, movement.hop_lands_behind_hurdle);
+        self::flatten_bb_moves(enemies, landings & allowed_squares, index, promotions, out_moves);
+    }
+    
+    // BENT RIDERS
+    
+    let bent_rider_moves = bent_rider_squares(bent_riders, &position.occ_or_out_bounds) & !&position.occ_or_out_bounds & allowed_squares;
+    self::flatten_bb_moves(enemies, bent_rider_moves, index, promotions, out_moves);
+    
+    // INITIAL MOVES (only passed if the piece has not moved)
+    
+    for (to, path, ep_square) in initial_moves {
+        if position.occ_or_out_bounds.get_bit(*to) || !(path & &position.occ_or_out_bounds).is_zero() {
+            continue;
+        }
+        let mut destination = Bitboard::zero();
+        destination.set_bit(*to);
+        match ep_square {
+            Some(ep_square) => self::flatten_bb_moves_doublejump(destination, index, *ep_square, promotions, &Bitboard::zero(), out_moves),
+            None => self::flatten_bb_moves(enemies, destination, index, promotions, out_moves),
+        }
+    }
+    
     // CASTLING
     
     if can_castle {
-        // Able to castle, check if there is a rook on the correct square
-        let (_, ky) = from_index(index);
-        let rank_visibility = attack_tables.get_rank_slide(index, &position.occ_or_out_bounds);
-        
-        // King side
-        // Index of the closest piece to the east
-        let kingside_rook_index = rank_visibility.highest_one().unwrap();
-        if let Some(rook) = position.player_piece_at(position.whos_turn, kingside_rook_index) {
-            // There is a piece of the same player
-            if rook.is_rook() && rook.has_not_moved(kingside_rook_index) {
-                // Rook is in direct line of sight of the king and hasn't moved
-                let kingside_file = movement.castle_files.unwrap().1;
-                let to_index = to_index(kingside_file, ky);
-                // Check that the squares between x=(rook_x + 1) and x=6 (both included) are empty
-                let mut empty = true;
-                for i in (kingside_rook_index as i16 + 1)..=(to_index as i16) {
-                    if position.occ_or_out_bounds.get_bit(i as BIndex) {
-                        empty = false;
-                        break;
-                    }
-                }
-                // Check that the squares between x=5 and x=(king_x - 1) (both included) are empty
-                for i in (to_index as i16 - 1)..(index as i16) {
-                    if position.occ_or_out_bounds.get_bit(i as BIndex) {
-                        empty = false;
-                        break;
-                    }
-                }
-                if empty {
-                    out_moves.push(Move::new(index, to_index, kingside_rook_index, MoveType::KingsideCastle, None));
-                }
+        self::output_castling(movement, index, position, allowed_squares, out_moves);
+    }
+}
+
+
+
+/// Outputs the castling moves of a piece that has not moved. The partners are the castle rooks of the same player
+/// that have not moved, in either direction along the rank (or the file if the piece castles vertically).
+/// The squares that both pieces go through must be empty, except for the squares where they start (like in Chess960)
+fn output_castling(movement: &PieceDefinition, index: BIndex, position: &Position, allowed_squares: &Bitboard, out_moves: &mut Vec<Move>) {
+    let free_castling = position.global_rules.free_castling;
+    let (x, y) = from_index(index);
+    let square_at = |coord: i8| {
+        if movement.castle_vertically { to_index(x, coord as BCoord) }
+        else { to_index(coord as BCoord, y) }
+    };
+    let coord = if movement.castle_vertically { y } else { x } as i8;
+    let (queenside_coord, kingside_coord) = movement.castle_files.unwrap();
+    
+    for (move_type, dir, castle_coord) in [(MoveType::KingsideCastle, 1, kingside_coord), (MoveType::QueensideCastle, -1, queenside_coord)] {
+        let kingside = move_type == MoveType::KingsideCastle;
+        let partner_coords = successors(Some(coord + dir), |c| Some(c + dir)).take_while(|c| (0..16).contains(c));
+        for partner_coord in partner_coords {
+            let partner_index = square_at(partner_coord);
+            match position.player_piece_at(position.whos_turn, partner_index) {
+                Some(partner) if partner.is_rook() && partner.has_not_moved(partner_index) => {},
+                _ => continue,
             }
-        }
-        // Queen side (same logic as kingside)
-        let queenside_rook_index = rank_visibility.lowest_one().unwrap();
-        if let Some(rook) = position.player_piece_at(position.whos_turn, queenside_rook_index) {
-            if rook.is_rook() && rook.has_not_moved(queenside_rook_index) {
-                let queenside_file = movement.castle_files.unwrap().0;
-                let to_index = to_index(queenside_file, ky);
-                // Check that the squares between x=2 and x=(rook_x - 1) (both included) are empty
-                let mut empty = true;
-                for i in (to_index as i16)..(queenside_rook_index as i16) {
-                    if position.occ_or_out_bounds.get_bit(i as BIndex) {
-                        empty = false;
-                        break;
-                    }
+            // The pieces can go through each other's squares
+            let mut occupied = position.occ_or_out_bounds.clone();
+            occupied.clear_bit(index);
+            occupied.clear_bit(partner_index);
+            // With free castling, the piece can land on any square up to the partner's square
+            let destinations = {
+                if !free_castling { castle_coord as i8..=castle_coord as i8 }
+                else if kingside { coord + 1..=partner_coord }
+                else { partner_coord..=coord - 1 }
+            };
+            for king_coord in destinations {
+                let king_to = square_at(king_coord);
+                if !allowed_squares.get_bit(king_to) {
+                    continue;
                 }
-                // Check that the squares between x=(king_x + 1) and x=3 (both included) are empty
-                for i in (index as i16 + 1)..=(to_index as i16 + 1) {
-                    if position.occ_or_out_bounds.get_bit(i as BIndex) {
-                        empty = false;
-                        break;
-                    }
-                }
-                if empty {
-                    out_moves.push(Move::new(index, to_index, queenside_rook_index, MoveType::QueensideCastle, None));
+                let partner_to = match castling_partner_destination(movement, king_to, kingside, free_castling) {
+                    Some(partner_to) => partner_to,
+                    None => continue,
+                };
+                let mut path = squares_between(index, king_to).chain(once(king_to))
+                    .chain(squares_between(partner_index, partner_to)).chain(once(partner_to));
+                if path.any(|sq| occupied.get_bit(sq)) {
+                    continue;
                 }
+                let mv = Move::new(index, king_to, partner_index, move_type, None);
+                out_moves.push(if free_castling { mv.with_free_castling() } else { mv });
             }
         }
     }
 }
 
+/// Returns the square where the castling partner lands when a piece with this definition castles to `king_to`,
+/// or `None` if it would be outside of the 16x16 board
+pub fn castling_partner_destination(movement: &PieceDefinition, king_to: BIndex, kingside: bool, free_castling: bool) -> Option<BIndex> {
+    let (x, y) = from_index(king_to);
+    let coord = if movement.castle_vertically { y } else { x };
+    let partner_coord = match movement.castle_partner_files {
+        Some((queenside, kingside_coord)) if !free_castling => if kingside { kingside_coord } else { queenside },
+        // Next to the piece, on the side it came from
+        _ if kingside => coord.checked_sub(1)?,
+        _ => Some(coord + 1).filter(|c| *c < 16)?,
+    };
+    if movement.castle_vertically { Some(to_index(x, partner_coord)) }
+    else { Some(to_index(partner_coord, y)) }
+}
+
 
 
 /// Outputs all the pseudo-legal capture moves for a piece at a given index
@@ -154,10 +219,14 @@ pub fn output_captures(
     index: BIndex,
     position: &Position,
     enemies: &Bitboard,
-    promotion_squares: &Bitboard,
+    promotions: &Promotions,
     occ_or_not_in_bounds: &Bitboard,
+    allowed_squares: &Bitboard,
+    slide_range_mask: &Bitboard,
     jumps_bitboard: &Bitboard,
-    promotions: &[PieceId],
+    lame_jumps: &[(BIndex, Bitboard)],
+    multi_leg_jumps: &[(BIndex, BIndex)],
+    bent_riders: &[BentRiderRay],
     out_moves: &mut Vec<Move>
 ) {
     let attack_tables = MoveGen::attack_tables();
@@ -178,20 +247,25 @@ pub fn output_captures(
     );
     // Attacks only
     slide_moves &= enemies;
-    // Keep only in bounds
-    slide_moves &= &position.dimensions.bounds;
-    self::flatten_bb_moves(enemies, slide_moves, index, promotion_squares, promotions, out_moves);
+    // Keep only in range and inside the region of the piece (which is in bounds)
+    slide_moves &= allowed_squares;
+    slide_moves &= slide_range_mask;
+    self::flatten_bb_moves(enemies, slide_moves, index, promotions, out_moves);
 
     
     // JUMP MOVES
     
     let jump_moves = jumps_bitboard & enemies;
-    self::flatten_bb_moves(enemies, jump_moves, index, promotion_squares, promotions, out_moves);
+    self::flatten_bb_moves(enemies, jump_moves, index, promotions, out_moves);
+    let lame_jump_moves = lame_jump_squares(lame_jumps, &position.occ_or_out_bounds) & enemies;
+    self::flatten_bb_moves(enemies, lame_jump_moves, index, promotions, out_moves);
     // En passant capture
-    if let Some(ep_square) = position.get_ep_square() {
-        if movement.can_double_jump() && jumps_bitboard.get_bit(ep_square) {
-            let target = position.get_ep_victim();
-            out_moves.push(Move::new(index, ep_square, target, MoveType::Capture, None));
+    if movement.can_capture_en_passant() && position.en_passant_available() {
+        for &ep_square in position.get_ep_squares() {
+            if jumps_bitboard.get_bit(ep_square) {
+                let target = position.get_ep_victim();
+                out_moves.push(Move::new(index, ep_square, target, MoveType::Capture, None));
+            }
         }
     }
     
@@ -211,15 +285,10 @@ pub fn output_captures(
             if !position.in_bounds(x2 as BCoord, y2 as BCoord) {
                 break;
             }
-            //If there is an enemy here, we can add an attack move
+            //If there is an enemy here, we can add an attack move (if it's inside the region of the piece)
             if enemies.get_bit(to) {
-                if promotion_squares.get_bit(to) {
-                    //Add all the promotion moves
-                    for c in promotions {
-                        out_moves.push(Move::new(index, to, to, MoveType::PromotionCapture, Some(*c)));
-                    }
-                } else {
-                    out_moves.push(Move::new(index, to, to, MoveType::Capture, None));
+                if allowed_squares.get_bit(to) {
+                    output_move(index, to, to, true, promotions, out_moves);
                 }
                 break;
             }
@@ -229,37 +298,129 @@ pub fn output_captures(
             }
         }
     }
+    
+    // HOPS
+    for delta in &movement.attack_hop_deltas {
+        let (_, target) = hop_landings(index, *delta, &position.occ_or_out_bounds, &position.dimensions.bounds, movement.hop_lands_behind_hurdle);
+        if let Some(to) = target {
+            if enemies.get_bit(to) && allowed_squares.get_bit(to) {
+                output_move(index, to, to, true, promotions, out_moves);
+            }
+        }
+    }
+    
+    // BENT RIDERS
+    let bent_rider_moves = bent_rider_squares(bent_riders, &position.occ_or_out_bounds) & enemies & allowed_squares;
+    self::flatten_bb_moves(enemies, bent_rider_moves, index, promotions, out_moves);
+    
+    // MULTI-LEG MOVES
+    for &(mid, to) in multi_leg_jumps {
+        if !multi_leg_is_possible(index, mid, to, enemies, &position.occ_or_out_bounds) {
+            continue;
+        }
+        // The target is the piece captured at the destination, or the one of the first leg if there is none
+        let target = if enemies.get_bit(to) { to } else { mid };
+        let start = out_moves.len();
+        output_move(index, to, target, true, promotions, out_moves);
+        for mv in &mut out_moves[start..] {
+            *mv = mv.with_intermediate(mid);
+        }
+    }
+}
+
+/// Outputs the moves of a piece at `index` to the empty squares in `destinations` that capture indirectly
+/// (by withdrawal, approach or custody). The moves that don't capture anything are skipped
+pub fn output_indirect_captures(
+    movement: &PieceDefinition,
+    index: BIndex,
+    mut destinations: Bitboard,
+    enemies: &Bitboard,
+    allies: &Bitboard,
+    promotions: &Promotions,
+    out_moves: &mut Vec<Move>
+) {
+    while let Some(to) = destinations.lowest_one() {
+        destinations.clear_bit(to);
+        if let Some(target) = indirect_capture_victims(movement, index, to, enemies, allies).lowest_one() {
+            let start = out_moves.len();
+            output_move(index, to, target, true, promotions, out_moves);
+            for mv in &mut out_moves[start..] {
+                *mv = mv.with_indirect_capture();
+            }
+        }
+    }
+}
+
+/// Returns the enemy pieces captured by withdrawal, approach or custody when a piece with this definition
+/// moves from `from` to the empty square `to`. `allies` are the pieces of the same player as the moving piece
+pub fn indirect_capture_victims(movement: &PieceDefinition, from: BIndex, to: BIndex, enemies: &Bitboard, allies: &Bitboard) -> Bitboard {
+    let square = |x: i8, y: i8| {
+        if (0..16).contains(&x) && (0..16).contains(&y) { Some(to_index(x as BCoord, y as BCoord)) }
+        else { None }
+    };
+    let mut victims = Bitboard::zero();
+    let (x1, y1) = from_index(from);
+    let (x2, y2) = from_index(to);
+    let (x1, y1, x2, y2) = (x1 as i8, y1 as i8, x2 as i8, y2 as i8);
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    // Withdrawal and approach need a move in a straight line
+    if dx == 0 || dy == 0 || dx.abs() == dy.abs() {
+        let (dx, dy) = (dx.signum(), dy.signum());
+        let mut candidates = Vec::with_capacity(2);
+        if movement.capture_by_withdrawal {
+            candidates.push(square(x1 - dx, y1 - dy));
+        }
+        if movement.capture_by_approach {
+            candidates.push(square(x2 + dx, y2 + dy));
+        }
+        for victim in candidates.into_iter().flatten() {
+            if enemies.get_bit(victim) {
+                victims.set_bit(victim);
+            }
+        }
+    }
+    if movement.custodian_capture {
+        for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
+            if let (Some(victim), Some(ally)) = (square(x2 + dx, y2 + dy), square(x2 + 2 * dx, y2 + 2 * dy)) {
+                // The moving piece has left its starting square, so it cannot be the other side of the sandwich
+                if enemies.get_bit(victim) && allies.get_bit(ally) && ally != from {
+                    victims.set_bit(victim);
+                }
+            }
+        }
+    }
+    victims
+}
+
+/// Returns `true` if a piece at `from` can make a multi-leg move through `mid` to `to`: the first leg
+/// must capture an enemy piece, and the second one must land on an empty square, an enemy piece or `from`
+fn multi_leg_is_possible(from: BIndex, mid: BIndex, to: BIndex, enemies: &Bitboard, occ_or_not_in_bounds: &Bitboard) -> bool {
+    enemies.get_bit(mid) && (to == from || enemies.get_bit(to) || !occ_or_not_in_bounds.get_bit(to))
+}
+
+/// Returns the squares where a piece at `from` captures with multi-leg moves, in the first or the second leg
+pub fn multi_leg_captures(multi_leg_jumps: &[(BIndex, BIndex)], from: BIndex, enemies: &Bitboard, occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+    let mut captures = Bitboard::zero();
+    for &(mid, to) in multi_leg_jumps {
+        if multi_leg_is_possible(from, mid, to, enemies, occ_or_not_in_bounds) {
+            captures.set_bit(mid);
+            if enemies.get_bit(to) {
+                captures.set_bit(to);
+            }
+        }
+    }
+    captures
 }
 
 pub fn flatten_bb_moves(
     enemies: &Bitboard,
     mut moves: Bitboard,
     from_index: BIndex,
-    promotion_squares: &Bitboard,
-    promotions: &[PieceId],
+    promotions: &Promotions,
     out_moves: &mut Vec<Move>
 ) {
     while let Some(to) = moves.lowest_one() {
-        let promo_here = promotion_squares.get_bit(to);
-        let capture_here = enemies.get_bit(to);
-        let move_type = {
-            if capture_here {
-                if promo_here { MoveType::PromotionCapture }
-                else { MoveType::Capture }
-            } else if promo_here {
-                MoveType::Promotion
-            } else {
-                MoveType::Quiet
-            }
-        };
-        if promo_here {
-            for promo_val in promotions {
-                out_moves.push(Move::new(from_index, to, to, move_type, Some(*promo_val)));
-            }
-        } else {
-            //No promotion chars left, go to next after this
-            out_moves.push(Move::new(from_index, to, to, move_type, None));
-        }
+        output_move(from_index, to, to, enemies.get_bit(to), promotions, out_moves);
         moves.clear_bit(to);
     }
 }
@@ -267,16 +428,19 @@ pub fn flatten_bb_moves_doublejump(
     mut moves: Bitboard,
     from_index: BIndex,
     ep_square: BIndex,
-    promotion_squares: &Bitboard,
+    promotions: &Promotions,
     double_jump_squares: &Bitboard,
-    promotions: &[PieceId],
     out_moves: &mut Vec<Move>
 ) {
     while let Some(to) = moves.lowest_one() {
-        if promotion_squares.get_bit(to) {
-            for promo_val in promotions {
-                out_moves.push(Move::new(from_index, to, 0, MoveType::Promotion, Some(*promo_val)));
+        let forced = promotions.forced_squares.get_bit(to);
+        if forced || promotions.optional_squares.get_bit(to) {
+            for promo_val in promotions.available_pieces() {
+                out_moves.push(Move::new(from_index, to, 0, MoveType::Promotion, Some(promo_val)));
             }
+        }
+        if forced {
+            // Cannot move without promoting
         } else if double_jump_squares.get_bit(ep_square) {
             // In double jump, the first jump index (to) is an en passant square (unless it's also a double jump square)
             out_moves.push(Move::new(from_index, to, 0, MoveType::Quiet, None));
@@ -286,3 +450,238 @@ pub fn flatten_bb_moves_doublejump(
         moves.clear_bit(to);
     }
 }
+
+/// Outputs a move (capture or not) to a square, with all the possible promotions.
+/// If the promotion is optional, the move without promotion is also added.
+fn output_move(from: BIndex, to: BIndex, target: BIndex, is_capture: bool, promotions: &Promotions, out_moves: &mut Vec<Move>) {
+    let forced = promotions.forced_squares.get_bit(to);
+    if forced || promotions.optional_squares.get_bit(to) {
+        let move_type = if is_capture { MoveType::PromotionCapture } else { MoveType::Promotion };
+        for promo_val in promotions.available_pieces() {
+            out_moves.push(Move::new(from, to, target, move_type, Some(promo_val)));
+        }
+        if forced {
+            return;
+        }
+    }
+    let move_type = if is_capture { MoveType::Capture } else { MoveType::Quiet };
+    out_moves.push(Move::new(from, to, target, move_type, None));
+}
+
+
+/// Returns the squares that a piece at a given index attacks (whether they are occupied or not).
+/// Used to reason about the movement of a piece without generating moves.
+#[allow(clippy::too_many_arguments)]
+pub fn attacked_squares(
+    movement: &PieceDefinition,
+    index: BIndex,
+    occ_or_not_in_bounds: &Bitboard,
+    bounds: &Bitboard,
+    slide_range_mask: &Bitboard,
+    jumps_bitboard: &Bitboard,
+    lame_jumps: &[(BIndex, Bitboard)],
+    bent_riders: &[BentRiderRay]
+) -> Bitboard {
+    let mut attacks = MoveGen::attack_tables().get_sliding_moves_bb(
+        index,
+        occ_or_not_in_bounds,
+        movement.attack_north,
+        movement.attack_east,
+        movement.attack_south,
+        movement.attack_west,
+        movement.attack_northeast,
+        movement.attack_northwest,
+        movement.attack_southeast,
+        movement.attack_southwest
+    );
+    attacks &= slide_range_mask;
+    attacks |= jumps_bitboard;
+    attacks |= lame_jump_squares(lame_jumps, occ_or_not_in_bounds);
+    self::add_sliding_deltas(&movement.attack_sliding_deltas, index, occ_or_not_in_bounds, true, &mut attacks);
+    attacks |= hop_attacks(movement, index, occ_or_not_in_bounds, bounds);
+    attacks |= bent_rider_squares(bent_riders, occ_or_not_in_bounds);
+    attacks & bounds
+}
+
+/// Returns the squares that a hopper at a given index attacks (whether they are occupied or not)
+pub fn hop_attacks(
+    movement: &PieceDefinition,
+    index: BIndex,
+    occ_or_not_in_bounds: &Bitboard,
+    bounds: &Bitboard
+) -> Bitboard {
+    let mut attacks = Bitboard::zero();
+    for delta in &movement.attack_hop_deltas {
+        let (landings, target) = hop_landings(index, *delta, occ_or_not_in_bounds, bounds, movement.hop_lands_behind_hurdle);
+        attacks |= landings;
+        if let Some(to) = target {
+            attacks.set_bit(to);
+        }
+    }
+    attacks
+}
+
+/// Returns the empty squares that a piece at a given index can move to without capturing
+/// (ignoring castling and double jumps)
+#[allow(clippy::too_many_arguments)]
+pub fn translation_squares(
+    movement: &PieceDefinition,
+    index: BIndex,
+    occ_or_not_in_bounds: &Bitboard,
+    bounds: &Bitboard,
+    slide_range_mask: &Bitboard,
+    jumps_bitboard: &Bitboard,
+    lame_jumps: &[(BIndex, Bitboard)],
+    bent_riders: &[BentRiderRay]
+) -> Bitboard {
+    let mut moves = MoveGen::attack_tables().get_sliding_moves_bb(
+        index,
+        occ_or_not_in_bounds,
+        movement.translate_north,
+        movement.translate_east,
+        movement.translate_south,
+        movement.translate_west,
+        movement.translate_northeast,
+        movement.translate_northwest,
+        movement.translate_southeast,
+        movement.translate_southwest
+    );
+    moves &= slide_range_mask;
+    moves |= jumps_bitboard;
+    moves |= lame_jump_squares(lame_jumps, occ_or_not_in_bounds);
+    self::add_sliding_deltas(&movement.translate_sliding_deltas, index, occ_or_not_in_bounds, false, &mut moves);
+    for delta in &movement.translate_hop_deltas {
+        moves |= hop_landings(index, *delta, occ_or_not_in_bounds, bounds, movement.hop_lands_behind_hurdle).0;
+    }
+    moves |= bent_rider_squares(bent_riders, occ_or_not_in_bounds);
+    moves & !occ_or_not_in_bounds
+}
+
+/// Returns the squares where the slides of a piece at a given index are allowed to land, according to the
+/// maximum range in each direction. The squares that are not in the line of a slide are also included.
+pub fn slide_range_mask(movement: &PieceDefinition, index: BIndex) -> Bitboard {
+    let mut mask = !&Bitboard::zero();
+    let (x, y) = from_index(index);
+    for ((dx, dy), range) in movement.slide_ranges() {
+        if range == 0 {
+            continue;
+        }
+        // Remove the squares of the ray that are farther than the range
+        let mut distance = range as i16 + 1;
+        loop {
+            let (x2, y2) = (x as i16 + distance * dx as i16, y as i16 + distance * dy as i16);
+            if !(0..=15).contains(&x2) || !(0..=15).contains(&y2) {
+                break;
+            }
+            mask.clear_bit(to_index(x2 as BCoord, y2 as BCoord));
+            distance += 1;
+        }
+    }
+    mask
+}
+
+/// Returns the destinations of the lame jumps whose path is not blocked
+pub fn lame_jump_squares(lame_jumps: &[(BIndex, Bitboard)], occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+    let mut squares = Bitboard::zero();
+    for (to, path) in lame_jumps {
+        if (path & occ_or_not_in_bounds).is_zero() {
+            squares.set_bit(*to);
+        }
+    }
+    squares
+}
+
+/// Returns the squares that bent riders reach: the step of each one, and if it's empty, the squares of the ride
+/// up to the first occupied one (included). The occupied squares can be walls or pieces of any player.
+pub fn bent_rider_squares(bent_riders: &[BentRiderRay], occ_or_not_in_bounds: &Bitboard) -> Bitboard {
+    let mut squares = Bitboard::zero();
+    for (step, ray, increasing) in bent_riders {
+        squares.set_bit(*step);
+        if occ_or_not_in_bounds.get_bit(*step) {
+            continue;
+        }
+        let blockers = ray & occ_or_not_in_bounds;
+        let first_blocker = if *increasing { blockers.lowest_one() } else { blockers.highest_one() };
+        match first_blocker {
+            Some(blocker) => {
+                // Squares with an index greater than or equal to the blocker
+                let from_blocker = !Bitboard::zero() << blocker;
+                if *increasing {
+                    squares |= ray & !from_blocker;
+                    squares.set_bit(blocker);
+                } else {
+                    squares |= ray & from_blocker;
+                }
+            },
+            None => squares |= ray,
+        }
+    }
+    squares
+}
+
+/// Returns the squares where a hopper at a given index can land when riding in the direction `(dx, dy)`:
+/// the empty squares behind the first piece that it finds (the hurdle), and the first occupied square
+/// behind the hurdle, if any. Walls and the edges of the board cannot be used as hurdles.
+pub fn hop_landings(
+    index: BIndex,
+    (dx, dy): (i8, i8),
+    occ_or_not_in_bounds: &Bitboard,
+    bounds: &Bitboard,
+    lands_behind_hurdle: bool
+) -> (Bitboard, Option<BIndex>) {
+    let mut landings = Bitboard::zero();
+    let (x, y) = from_index(index);
+    let (mut x2, mut y2) = (x as i8, y as i8);
+    let mut found_hurdle = false;
+    loop {
+        x2 += dx;
+        y2 += dy;
+        if x2 < 0 || y2 < 0 || x2 > 15 || y2 > 15 {
+            break;
+        }
+        let to = to_index(x2 as BCoord, y2 as BCoord);
+        if !bounds.get_bit(to) {
+            break;
+        }
+        if !found_hurdle {
+            found_hurdle = occ_or_not_in_bounds.get_bit(to);
+            continue;
+        }
+        if occ_or_not_in_bounds.get_bit(to) {
+            return (landings, Some(to));
+        }
+        landings.set_bit(to);
+        if lands_behind_hurdle {
+            break;
+        }
+    }
+    (landings, None)
+}
+
+/// Adds the squares of each run of sliding deltas until the first occupied square,
+/// which is only included if `include_blocker` is `true`
+fn add_sliding_deltas(
+    runs: &[Vec<(i8, i8)>],
+    index: BIndex,
+    occ_or_not_in_bounds: &Bitboard,
+    include_blocker: bool,
+    out: &mut Bitboard
+) {
+    let (x, y) = from_index(index);
+    for run in runs {
+        for (dx, dy) in run {
+            let (x2, y2) = (x as i8 + *dx, y as i8 + *dy);
+            if x2 < 0 || y2 < 0 || x2 > 15 || y2 > 15 {
+                break;
+            }
+            let to = to_index(x2 as BCoord, y2 as BCoord);
+            if occ_or_not_in_bounds.get_bit(to) {
+                if include_blocker {
+                    out.set_bit(to);
+                }
+                break;
+            }
+            out.set_bit(to);
+        }
+    }
+}
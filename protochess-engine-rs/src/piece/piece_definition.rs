@@ -2,32 +2,78 @@ use crate::types::BCoord;
 use crate::utils::debug::eq_anyorder;
 use super::PieceId;
 
+/// Move in two legs: the delta of the first leg, and the delta of the second leg (from the end of the first one)
+pub type MultiLegDelta = ((i8, i8), (i8, i8));
+
+/// Bent ride: the delta of the first step, and the direction of the ride that starts after it
+pub type BentRiderDelta = ((i8, i8), (i8, i8));
 
 /// External representation of a piece
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default, deny_unknown_fields))]
 #[must_use]
 pub struct PieceDefinition {
     // The id of this piece for white and black. None if this piece is not available for that color.
+    #[cfg_attr(feature = "serde", serde(with = "crate::position::create::variant_file::per_player"))]
     pub ids: [Option<PieceId>; 2],
     // Override the id of the piece when printing the algebraic notation
+    #[cfg_attr(feature = "serde", serde(with = "crate::position::create::variant_file::per_player"))]
     pub notation_prefix: [Option<String>; 2],
     
     pub is_leader: bool,
-    // Either None (no castle) or (queenside, kingside) (files where this piece moves when castling)
+    // Either None (no castle) or (queenside, kingside) (files where this piece moves when castling, or ranks if
+    // `castle_vertically` is true). Queenside is towards the west (or south), kingside towards the east (or north)
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub castle_files: Option<(BCoord, BCoord)>,
+    // Files (or ranks) where the castling partner lands, as (queenside, kingside). If None, the partner lands
+    // next to this piece, on the side it came from. Ignored with free castling
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub castle_partner_files: Option<(BCoord, BCoord)>,
+    // If true, this piece castles along its file instead of its rank
+    pub castle_vertically: bool,
     // True if this piece works as a rook for castling purposes
     pub is_castle_rook: bool,
     pub explode_on_capture: bool,
     pub explosion_deltas: Vec<(i8, i8)>,
     pub immune_to_explosion: bool,
     
-    // Places where this piece can promote, as well as PieceId for the promotion pieces on each side
+    // Captures of pieces that are not on the destination square (a piece can combine several of them):
+    // Moving in a straight line away from an adjacent enemy piece captures it (like the withdrawer in Ultima)
+    pub capture_by_withdrawal: bool,
+    // Moving in a straight line to a square next to an enemy piece captures the piece in front (like in Fanorona)
+    pub capture_by_approach: bool,
+    // Moving next to an enemy piece captures it if there is a friendly piece right behind it, orthogonally
+    // (like the pincer pawn in Ultima)
+    pub custodian_capture: bool,
+    // The piece captures without moving: it stays on its square and removes the victim (like in Rifle chess).
+    // Multi-leg captures are not affected
+    pub rifle_capture: bool,
+    
+    // Places where this piece must promote, as well as PieceId for the promotion pieces on each side
     pub promotion_squares: Vec<(BCoord, BCoord)>,
     pub promo_vals: [Vec<PieceId>; 2],
+    // Places where this piece can choose whether to promote or not (promotion zone)
+    pub optional_promotion_squares: Vec<(BCoord, BCoord)>,
+    // If true, the piece can only promote to a piece type that the player has lost (there are fewer pieces of
+    // that type than in the initial position), like in Grand Chess. If there is none, it cannot move to
+    // a square where promotion is forced
+    pub promote_to_captured_only: bool,
     
     // Places where this piece can double move
     pub double_jump_squares: Vec<(BCoord, BCoord)>,
+    // Moves without capturing that are only available while the piece has not moved (like the initial leap of the
+    // king in Courier chess). Each one is a path of deltas like in the lame jumps: the last delta is the destination,
+    // and the squares of the previous ones must be empty. If there is more than one delta, the move creates
+    // en passant squares on the square of the second to last delta and the squares skipped in a straight line
+    pub initial_moves: Vec<Vec<(i8, i8)>>,
+    
+    // Places where this piece can be dropped from the hand. If empty, it can be dropped on any empty square
+    pub drop_squares: Vec<(BCoord, BCoord)>,
+    
+    // Squares where this piece can be for each player, like the palace in Xiangqi. Moves that land outside
+    // of this region are not allowed. If empty, the piece can go anywhere
+    pub allowed_squares: [Vec<(BCoord, BCoord)>; 2],
 
     // Ways the piece can capture (but not move without capturing)
     pub attack_sliding_deltas: Vec<Vec<(i8, i8)>>,
@@ -53,8 +99,50 @@ pub struct PieceDefinition {
     pub translate_southeast: bool,
     pub translate_southwest: bool,
     
+    // Maximum number of squares that the piece can slide in each direction, both when moving and when
+    // capturing (like the short rook). 0 means unlimited
+    pub range_north: u8,
+    pub range_south: u8,
+    pub range_east: u8,
+    pub range_west: u8,
+    pub range_northeast: u8,
+    pub range_northwest: u8,
+    pub range_southeast: u8,
+    pub range_southwest: u8,
+    
+    // Leaps that are blocked by a piece on an intermediate square (like the Xiangqi horse). Each one is a path of
+    // deltas: the last delta is the destination, and the squares of the previous ones must be empty
+    pub translate_lame_jumps: Vec<Vec<(i8, i8)>>,
+    pub attack_lame_jumps: Vec<Vec<(i8, i8)>>,
+    
+    // Hopper moves: the piece rides in each direction (dx, dy), jumps over the first piece it finds (the hurdle)
+    // and lands on any square behind it, up to the next piece (like the capture of the Xiangqi cannon)
+    pub translate_hop_deltas: Vec<(i8, i8)>,
+    pub attack_hop_deltas: Vec<(i8, i8)>,
+    // If true, hoppers can only land on the square right behind the hurdle (like the grasshopper)
+    pub hop_lands_behind_hurdle: bool,
+    
+    // Moves in two legs (like the lion in Chu Shogi): the piece leaps by the first delta and captures the enemy
+    // piece there, then leaps by the second delta to an empty square, back to its starting square, or to another
+    // enemy piece that is also captured. The moves where the first leg doesn't capture are not included
+    pub multi_leg_deltas: Vec<MultiLegDelta>,
+    
+    // Bent riders (like the griffon): the piece steps by the first delta, and if that square is empty it keeps
+    // riding in the direction of the second delta until it finds a piece. Both the step and the squares of the
+    // ride are destinations. Entries with the same step share it, so each branch is a separate entry
+    pub translate_bent_riders: Vec<BentRiderDelta>,
+    pub attack_bent_riders: Vec<BentRiderDelta>,
+    
     // Successfully moving the piece to a win square is an instant win
     pub win_squares: Vec<(BCoord, BCoord)>,
+    
+    // The player loses when all the pieces of this type have been captured or promoted, like every piece type
+    // in Extinction chess. The pieces in the hand still count
+    pub lose_on_extinction: bool,
+    
+    // Moving this piece resets the halfmove clock of the N-move rule, like pawns in standard chess.
+    // Captures always reset the clock
+    pub resets_move_clock: bool,
 }
 
 impl PieceDefinition {
@@ -97,33 +185,99 @@ impl PieceDefinition {
     pub fn can_slide_west_indirectly(&self) -> bool {
         self.can_slide_west() || self.can_slide_northwest() || self.can_slide_southwest()
     }
+    /// Returns the direction of each slide and its maximum range (0 if unlimited)
+    pub fn slide_ranges(&self) -> [((i8, i8), u8); 8] {
+        [
+            ((0, 1), self.range_north),
+            ((0, -1), self.range_south),
+            ((1, 0), self.range_east),
+            ((-1, 0), self.range_west),
+            ((1, 1), self.range_northeast),
+            ((-1, 1), self.range_northwest),
+            ((1, -1), self.range_southeast),
+            ((-1, -1), self.range_southwest),
+        ]
+    }
     pub fn can_promote(&self) -> bool {
-        !self.promotion_squares.is_empty()
+        !self.promotion_squares.is_empty() || !self.optional_promotion_squares.is_empty()
     }
     pub fn can_jump(&self) -> bool {
-        !self.translate_jump_deltas.is_empty() || !self.attack_jump_deltas.is_empty()
+        !self.translate_jump_deltas.is_empty() || !self.attack_jump_deltas.is_empty() ||
+        !self.translate_lame_jumps.is_empty() || !self.attack_lame_jumps.is_empty() ||
+        !self.multi_leg_deltas.is_empty()
+    }
+    pub fn can_hop(&self) -> bool {
+        !self.translate_hop_deltas.is_empty() || !self.attack_hop_deltas.is_empty()
+    }
+    pub fn has_bent_riders(&self) -> bool {
+        !self.translate_bent_riders.is_empty() || !self.attack_bent_riders.is_empty()
     }
     pub fn can_double_jump(&self) -> bool {
         !self.double_jump_squares.is_empty()
     }
+    pub fn has_initial_moves(&self) -> bool {
+        !self.initial_moves.is_empty()
+    }
+    /// Returns `true` if the piece can capture en passant: it makes double jumps or initial moves
+    /// of more than one step, which create en passant squares (like a pawn)
+    pub fn can_capture_en_passant(&self) -> bool {
+        self.can_double_jump() || self.initial_moves.iter().any(|path| path.len() > 1)
+    }
     pub fn has_sliding_deltas(&self) -> bool {
         !self.translate_sliding_deltas.is_empty() || !self.attack_sliding_deltas.is_empty()
     }
+    /// Returns `true` if the moves of the piece can capture pieces next to its destination
+    /// (by withdrawal, by approach or custodian captures)
+    pub fn has_indirect_captures(&self) -> bool {
+        self.capture_by_withdrawal || self.capture_by_approach || self.custodian_capture
+    }
     pub fn can_castle(&self) -> bool {
         self.castle_files.is_some()
     }
+    /// Returns `true` if every move of the piece keeps it on squares of the same colour (like a bishop)
+    pub fn is_color_bound(&self) -> bool {
+        let same_color = |(dx, dy): &(i8, i8)| (dx + dy) % 2 == 0;
+        let mut lame_destinations = self.translate_lame_jumps.iter().chain(&self.attack_lame_jumps).filter_map(|path| path.last());
+        !self.can_slide_main_direction() &&
+        self.translate_jump_deltas.iter().all(same_color) &&
+        self.attack_jump_deltas.iter().all(same_color) &&
+        lame_destinations.all(same_color) &&
+        self.multi_leg_deltas.iter().all(|((dx1, dy1), (dx2, dy2))| same_color(&(dx1 + dx2, dy1 + dy2))) &&
+        self.translate_bent_riders.iter().chain(&self.attack_bent_riders).all(|(step, ride)| same_color(step) && same_color(ride)) &&
+        self.translate_sliding_deltas.iter().flatten().all(same_color) &&
+        self.attack_sliding_deltas.iter().flatten().all(same_color)
+    }
+    /// Returns `true` if a position with this piece on the board is never a draw by insufficient material
+    pub fn prevents_insufficient_material(&self) -> bool {
+        // Hoppers, multi-leg moves and indirect captures need other pieces, so their range cannot be computed on an empty board
+        self.can_promote() || self.can_hop() || !self.multi_leg_deltas.is_empty() || self.has_indirect_captures() ||
+        // Other ways of winning or changing the material
+        self.lose_on_extinction || self.explode_on_capture || !self.win_squares.is_empty()
+    }
     
     pub fn eq_ignore_order(&self, other: &PieceDefinition) -> bool {
         self.ids == other.ids &&
         self.is_leader == other.is_leader &&
         self.castle_files == other.castle_files &&
+        self.castle_partner_files == other.castle_partner_files &&
+        self.castle_vertically == other.castle_vertically &&
         self.is_castle_rook == other.is_castle_rook &&
         self.explode_on_capture == other.explode_on_capture &&
         eq_anyorder(&self.explosion_deltas, &other.explosion_deltas) &&
         self.immune_to_explosion == other.immune_to_explosion &&
+        self.capture_by_withdrawal == other.capture_by_withdrawal &&
+        self.capture_by_approach == other.capture_by_approach &&
+        self.custodian_capture == other.custodian_capture &&
+        self.rifle_capture == other.rifle_capture &&
         eq_anyorder(&self.promotion_squares, &other.promotion_squares) &&
         eq_anyorder(&self.promo_vals, &other.promo_vals) &&
+        eq_anyorder(&self.optional_promotion_squares, &other.optional_promotion_squares) &&
+        self.promote_to_captured_only == other.promote_to_captured_only &&
         eq_anyorder(&self.double_jump_squares, &other.double_jump_squares) &&
+        eq_anyorder(&self.initial_moves, &other.initial_moves) &&
+        eq_anyorder(&self.drop_squares, &other.drop_squares) &&
+        eq_anyorder(&self.allowed_squares[0], &other.allowed_squares[0]) &&
+        eq_anyorder(&self.allowed_squares[1], &other.allowed_squares[1]) &&
         eq_anyorder(&self.attack_sliding_deltas, &other.attack_sliding_deltas) &&
         eq_anyorder(&self.attack_jump_deltas, &other.attack_jump_deltas) &&
         self.attack_north == other.attack_north &&
@@ -144,6 +298,24 @@ impl PieceDefinition {
         self.translate_northwest == other.translate_northwest &&
         self.translate_southeast == other.translate_southeast &&
         self.translate_southwest == other.translate_southwest &&
-        eq_anyorder(&self.win_squares, &other.win_squares)
+        self.range_north == other.range_north &&
+        self.range_south == other.range_south &&
+        self.range_east == other.range_east &&
+        self.range_west == other.range_west &&
+        self.range_northeast == other.range_northeast &&
+        self.range_northwest == other.range_northwest &&
+        self.range_southeast == other.range_southeast &&
+        self.range_southwest == other.range_southwest &&
+        eq_anyorder(&self.translate_lame_jumps, &other.translate_lame_jumps) &&
+        eq_anyorder(&self.attack_lame_jumps, &other.attack_lame_jumps) &&
+        eq_anyorder(&self.translate_hop_deltas, &other.translate_hop_deltas) &&
+        eq_anyorder(&self.attack_hop_deltas, &other.attack_hop_deltas) &&
+        self.hop_lands_behind_hurdle == other.hop_lands_behind_hurdle &&
+        eq_anyorder(&self.multi_leg_deltas, &other.multi_leg_deltas) &&
+        eq_anyorder(&self.translate_bent_riders, &other.translate_bent_riders) &&
+        eq_anyorder(&self.attack_bent_riders, &other.attack_bent_riders) &&
+        eq_anyorder(&self.win_squares, &other.win_squares) &&
+        self.lose_on_extinction == other.lose_on_extinction &&
+        self.resets_move_clock == other.resets_move_clock
     }
 }
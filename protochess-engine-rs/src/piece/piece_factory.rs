@@ -28,10 +28,15 @@ impl PieceFactory {
         let double_move_rank2 = { if is_white { 0 } else { height-1 } }; // Needed for horde
         let mut promotion_squares = vec![];
         let mut double_jump_squares = vec![];
+        let mut drop_squares = vec![];
         for i in 0..width {
             promotion_squares.push((i, promotion_rank));
             double_jump_squares.push((i, double_move_rank1));
             double_jump_squares.push((i, double_move_rank2));
+            // Pawns cannot be dropped on the first or last rank
+            for j in 1..height-1 {
+                drop_squares.push((i, j));
+            }
         }
         let move_dir = { if is_white { 1 } else { -1 } };
         let ids = {
@@ -54,13 +59,24 @@ impl PieceFactory {
             notation_prefix: [Some("".to_string()), Some("".to_string())],
             is_leader: false,
             castle_files: None,
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: false,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: true,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares,
             double_jump_squares,
+            initial_moves: vec![],
+            drop_squares,
+            allowed_squares: [vec![], vec![]],
             promo_vals,
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![(-1, move_dir), (1, move_dir)],
             attack_north: false,
@@ -81,7 +97,25 @@ impl PieceFactory {
             translate_northwest: false,
             translate_southeast: false,
             translate_southwest: false,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares: vec![],
+            lose_on_extinction: false,
+            resets_move_clock: true,
         }
     }
     
@@ -91,13 +125,24 @@ impl PieceFactory {
             notation_prefix: [Some("N".to_string()), Some("N".to_string())],
             is_leader: false,
             castle_files: None,
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: false,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: false,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares: vec![],
             double_jump_squares: vec![],
+            initial_moves: vec![],
+            drop_squares: vec![],
+            allowed_squares: [vec![], vec![]],
             promo_vals: [vec![], vec![]],
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![(1, 2), (1, -2), (-1, 2), (-1, -2), (2, 1), (2, -1), (-2, 1), (-2, -1)],
             attack_north: false,
@@ -118,7 +163,25 @@ impl PieceFactory {
             translate_northwest: false,
             translate_southeast: false,
             translate_southwest: false,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares: vec![],
+            lose_on_extinction: false,
+            resets_move_clock: false,
         }
     }
     
@@ -128,13 +191,24 @@ impl PieceFactory {
             notation_prefix: [Some("B".to_string()), Some("B".to_string())],
             is_leader: false,
             castle_files: None,
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: false,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: false,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares: vec![],
             double_jump_squares: vec![],
+            initial_moves: vec![],
+            drop_squares: vec![],
+            allowed_squares: [vec![], vec![]],
             promo_vals: [vec![], vec![]],
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![],
             attack_north: false,
@@ -155,7 +229,25 @@ impl PieceFactory {
             translate_northwest: true,
             translate_southeast: true,
             translate_southwest: true,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares: vec![],
+            lose_on_extinction: false,
+            resets_move_clock: false,
         }
     }
     
@@ -165,13 +257,24 @@ impl PieceFactory {
             notation_prefix: [Some("R".to_string()), Some("R".to_string())],
             is_leader: false,
             castle_files: None,
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: true,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: false,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares: vec![],
             double_jump_squares: vec![],
+            initial_moves: vec![],
+            drop_squares: vec![],
+            allowed_squares: [vec![], vec![]],
             promo_vals: [vec![], vec![]],
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![],
             attack_north: true,
@@ -192,7 +295,25 @@ impl PieceFactory {
             translate_northwest: false,
             translate_southeast: false,
             translate_southwest: false,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares: vec![],
+            lose_on_extinction: false,
+            resets_move_clock: false,
         }
     }
     
@@ -218,15 +339,27 @@ impl PieceFactory {
         PieceDefinition {
             ids,
             notation_prefix: [Some("K".to_string()), Some("K".to_string())],
-            is_leader: self.mode != GameMode::Antichess,
+            // In Duck chess there is no check, the king must be captured
+            is_leader: self.mode != GameMode::Antichess && self.mode != GameMode::Duck,
             castle_files: if can_castle { Some((2, 6)) } else { None },
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: false,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: false,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares: vec![],
             double_jump_squares: vec![],
+            initial_moves: vec![],
+            drop_squares: vec![],
+            allowed_squares: [vec![], vec![]],
             promo_vals: [vec![], vec![]],
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             attack_north: false,
@@ -247,7 +380,25 @@ impl PieceFactory {
             translate_northwest: false,
             translate_southeast: false,
             translate_southwest: false,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares,
+            lose_on_extinction: self.mode == GameMode::Duck,
+            resets_move_clock: false,
         }
     }
     
@@ -257,13 +408,24 @@ impl PieceFactory {
             notation_prefix: [Some("Q".to_string()), Some("Q".to_string())],
             is_leader: false,
             castle_files: None,
+            castle_partner_files: None,
+            castle_vertically: false,
             is_castle_rook: false,
             explode_on_capture: self.mode == GameMode::Atomic,
             explosion_deltas: vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
             immune_to_explosion: false,
+            capture_by_withdrawal: false,
+            capture_by_approach: false,
+            custodian_capture: false,
+            rifle_capture: false,
             promotion_squares: vec![],
             double_jump_squares: vec![],
+            initial_moves: vec![],
+            drop_squares: vec![],
+            allowed_squares: [vec![], vec![]],
             promo_vals: [vec![], vec![]],
+            optional_promotion_squares: vec![],
+            promote_to_captured_only: false,
             attack_sliding_deltas: vec![],
             attack_jump_deltas: vec![],
             attack_north: true,
@@ -284,7 +446,25 @@ impl PieceFactory {
             translate_northwest: true,
             translate_southeast: true,
             translate_southwest: true,
+            range_north: 0,
+            range_south: 0,
+            range_east: 0,
+            range_west: 0,
+            range_northeast: 0,
+            range_northwest: 0,
+            range_southeast: 0,
+            range_southwest: 0,
+            translate_lame_jumps: vec![],
+            attack_lame_jumps: vec![],
+            translate_hop_deltas: vec![],
+            attack_hop_deltas: vec![],
+            hop_lands_behind_hurdle: false,
+            multi_leg_deltas: vec![],
+            translate_bent_riders: vec![],
+            attack_bent_riders: vec![],
             win_squares: vec![],
+            lose_on_extinction: false,
+            resets_move_clock: false,
         }
     }
 }
@@ -1,6 +1,7 @@
 use crate::types::{Centipawns, BIndex, Bitboard, BCoord, BDimensions};
 use crate::utils::{from_index, to_index, distance_to_one};
 use crate::{MoveGen, PieceDefinition};
+use super::movement::slide_range_mask;
 
 const BASE_MULT: Centipawns = 5;
 const EDGE_DIST_MULT: Centipawns = 5;
@@ -12,7 +13,8 @@ pub fn compute_piece_square_table(piece: &PieceDefinition, dims: &BDimensions, e
     let mut return_vec = Vec::with_capacity(256);
     let center_squares_bb = get_center_squares(dims.width, dims.height);
     // Keep promotion squares in bounds
-    let promotion_squares_bb = Bitboard::from_coord_list(&piece.promotion_squares) & &dims.bounds;
+    let promotion_squares_bb = (Bitboard::from_coord_list(&piece.promotion_squares) |
+        Bitboard::from_coord_list(&piece.optional_promotion_squares)) & &dims.bounds;
     // Keep win squares in bounds
     let win_squares_bb = Bitboard::from_coord_list(&piece.win_squares) & &dims.bounds;
     
@@ -50,11 +52,13 @@ pub fn compute_piece_square_table(piece: &PieceDefinition, dims: &BDimensions, e
         abs_score += points_for_distance_to_one(x, y, piece, dims, &win_squares_bb, avg_board, WIN_DIST_MULT);
         
         // Extra points for castling a leader
-        if piece.is_leader && !endgame && piece.can_castle() && (y == 0 || y == dims.height - 1) {
-            let (queenside_x, kingside_x) = piece.castle_files.unwrap();
-            if x == queenside_x || x == kingside_x {
+        // (coordinates along the castling direction and across it, so that vertical castling works the same)
+        let (along, across, across_len) = if piece.castle_vertically { (y, x, dims.width) } else { (x, y, dims.height) };
+        if piece.is_leader && !endgame && piece.can_castle() && (across == 0 || across == across_len - 1) {
+            let (queenside, kingside) = piece.castle_files.unwrap();
+            if along == queenside || along == kingside {
                 abs_score += 40;
-            } else if x < queenside_x || x > kingside_x {
+            } else if along < queenside || along > kingside {
                 abs_score += 20;
             }
         }
@@ -75,10 +79,10 @@ fn get_center_squares(width: BCoord, height: BCoord) -> Bitboard {
     // For odd dimensions, the center is the 9 squares in the middle
     let x1 = width / 2 - 1;
     let x2 = width / 2;
-    let x3 = (width + 1) / 2;
+    let x3 = width.div_ceil(2);
     let y1 = height / 2 - 1;
     let y2 = height / 2;
-    let y3 = (height + 1) / 2;
+    let y3 = height.div_ceil(2);
     
     center_squares.set_bit_at(x1, y1);
     center_squares.set_bit_at(x1, y2);
@@ -134,16 +138,33 @@ fn get_moves_on_empty_board(mp: &PieceDefinition, index: BIndex, dims: &BDimensi
         mp.translate_southeast || (mp.attack_southeast && include_attacks),
         mp.translate_southwest || (mp.attack_southwest && include_attacks),
     );
+    moves &= slide_range_mask(mp, index);
 
     // Delta based moves (sliding, non sliding)
     let (x, y) = from_index(index);
-    let jumps = {
+    let mut jumps = {
         if include_attacks {
             mp.translate_jump_deltas.iter().chain(mp.attack_jump_deltas.iter()).collect()
         } else {
             mp.translate_jump_deltas.iter().collect::<Vec<_>>()
         }
     };
+    // On an empty board, lame jumps are only blocked by walls (ignored here)
+    jumps.extend(mp.translate_lame_jumps.iter().filter_map(|path| path.last()));
+    if include_attacks {
+        jumps.extend(mp.attack_lame_jumps.iter().filter_map(|path| path.last()));
+    }
+    // Multi-leg moves need a piece to capture in the first leg, assume that there is one
+    let multi_leg_destinations: Vec<(i8, i8)> = {
+        if include_attacks {
+            mp.multi_leg_deltas.iter().map(|((dx1, dy1), (dx2, dy2))| (dx1 + dx2, dy1 + dy2))
+                .filter(|delta| *delta != (0, 0))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    };
+    jumps.extend(multi_leg_destinations.iter());
     for (dx, dy) in jumps {
         let (x2, y2) = (x as i8 + *dx, y as i8 + *dy);
         if x2 < 0 || y2 < 0 || x2 > 15 || y2 > 15 {
@@ -177,7 +198,49 @@ fn get_moves_on_empty_board(mp: &PieceDefinition, index: BIndex, dims: &BDimensi
             moves.set_bit(to);
         }
     }
-    
+
+    // Hoppers need a hurdle, assume that one can always be found in their directions
+    let hop_deltas = {
+        if include_attacks {
+            mp.translate_hop_deltas.iter().chain(mp.attack_hop_deltas.iter()).collect()
+        } else {
+            mp.translate_hop_deltas.iter().collect::<Vec<_>>()
+        }
+    };
+    for (dx, dy) in hop_deltas {
+        let (mut x2, mut y2) = (x as i8 + *dx, y as i8 + *dy);
+        while x2 >= 0 && y2 >= 0 && x2 <= 15 && y2 <= 15 {
+            let to = to_index(x2 as BCoord, y2 as BCoord);
+            if !dims.bounds.get_bit(to) {
+                break;
+            }
+            moves.set_bit(to);
+            x2 += *dx;
+            y2 += *dy;
+        }
+    }
+
+    // Bent riders: the step, and then the ride until the edge of the board
+    let bent_riders = {
+        if include_attacks {
+            mp.translate_bent_riders.iter().chain(mp.attack_bent_riders.iter()).collect()
+        } else {
+            mp.translate_bent_riders.iter().collect::<Vec<_>>()
+        }
+    };
+    for ((sx, sy), (rx, ry)) in bent_riders {
+        let (mut x2, mut y2) = (x as i8 + *sx, y as i8 + *sy);
+        while x2 >= 0 && y2 >= 0 && x2 <= 15 && y2 <= 15 {
+            let to = to_index(x2 as BCoord, y2 as BCoord);
+            if !dims.bounds.get_bit(to) {
+                break;
+            }
+            moves.set_bit(to);
+            x2 += *rx;
+            y2 += *ry;
+        }
+    }
+
     //Keep only in bounds
     moves &= &dims.bounds;
     moves
@@ -1,20 +1,46 @@
-use crate::types::{Bitboard, BDimensions, BCoord};
+use crate::types::{Bitboard, BDimensions, BCoord, BIndex};
 use crate::PieceDefinition;
-use crate::utils::from_index;
+use super::{MultiLegDelta, BentRiderDelta};
+use super::movement::slide_range_mask;
+use crate::utils::{from_index, to_index};
 
 
+/// A bent rider from a given square: the square of the step, the squares of the ride after it, and whether the ride
+/// goes towards higher indexes (then the first square where the ride stops is the lowest occupied one of the ray)
+pub type BentRiderRay = (BIndex, Bitboard, bool);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PrecomputedPieceDef {
-    // Positions at which this piece can promote
+    // Positions at which this piece must promote
     pub promotion_squares: Bitboard,
+    // Positions at which this piece can choose whether to promote or not
+    pub optional_promotion_squares: Bitboard,
     // Positions at which this piece can double jump
     pub double_jump_squares: Bitboard,
     // Positions at which the game is won instantly
     pub instant_win_squares: Bitboard,
+    // Positions at which this piece can be dropped from the hand
+    pub drop_squares: Bitboard,
+    // Positions at which this piece can be (the bounds of the board, or its region)
+    pub allowed_squares: Bitboard,
+    
+    // Squares where the slides of this piece can land from each index, according to their maximum range
+    pub slide_range_masks: Vec<Bitboard>,
     
     // Jump bitboards for this piece
     pub jump_bitboards_translate: Vec<Bitboard>,
     pub jump_bitboards_capture: Vec<Bitboard>,
+    // Lame jumps for this piece: for each index, the destination of each jump and the squares that must be empty
+    pub lame_jumps_translate: Vec<Vec<(BIndex, Bitboard)>>,
+    pub lame_jumps_capture: Vec<Vec<(BIndex, Bitboard)>>,
+    // Multi-leg moves for this piece: for each index, the square of the first leg and the destination
+    pub multi_leg_jumps: Vec<Vec<(BIndex, BIndex)>>,
+    // Bent riders for this piece: for each index, the square of the step and the ray of the ride after it
+    pub bent_riders_translate: Vec<Vec<BentRiderRay>>,
+    pub bent_riders_capture: Vec<Vec<BentRiderRay>>,
+    // Initial moves for this piece: for each index, the destination of each move, the squares that must be empty
+    // and the en passant square that it creates (if any)
+    pub initial_moves: Vec<Vec<(BIndex, Bitboard, Option<BIndex>)>>,
     
     // Explosion bitboards for this piece
     pub explosion_bitboards: Vec<Bitboard>,
@@ -24,10 +50,23 @@ impl From<(&PieceDefinition, &BDimensions)> for PrecomputedPieceDef {
     fn from((definition, dims): (&PieceDefinition, &BDimensions)) -> Self {
         PrecomputedPieceDef { 
             promotion_squares: Bitboard::from_coord_list(&definition.promotion_squares) & &dims.bounds,
+            optional_promotion_squares: Bitboard::from_coord_list(&definition.optional_promotion_squares) & &dims.bounds,
             double_jump_squares: Bitboard::from_coord_list(&definition.double_jump_squares) & &dims.bounds,
             instant_win_squares: Bitboard::from_coord_list(&definition.win_squares) & &dims.bounds,
+            drop_squares: {
+                if definition.drop_squares.is_empty() { dims.bounds.clone() }
+                else { Bitboard::from_coord_list(&definition.drop_squares) & &dims.bounds }
+            },
+            allowed_squares: dims.bounds.clone(),
+            slide_range_masks: (0..=255).map(|index| slide_range_mask(definition, index)).collect(),
             jump_bitboards_translate: Self::precompute_jumps(&definition.translate_jump_deltas, dims),
             jump_bitboards_capture: Self::precompute_jumps(&definition.attack_jump_deltas, dims),
+            lame_jumps_translate: Self::precompute_lame_jumps(&definition.translate_lame_jumps, dims),
+            lame_jumps_capture: Self::precompute_lame_jumps(&definition.attack_lame_jumps, dims),
+            multi_leg_jumps: Self::precompute_multi_leg_jumps(&definition.multi_leg_deltas, dims),
+            bent_riders_translate: Self::precompute_bent_riders(&definition.translate_bent_riders),
+            bent_riders_capture: Self::precompute_bent_riders(&definition.attack_bent_riders),
+            initial_moves: Self::precompute_initial_moves(&definition.initial_moves, dims),
             explosion_bitboards: Self::precompute_jumps(&definition.explosion_deltas, dims),
         }
     }
@@ -52,4 +91,104 @@ impl PrecomputedPieceDef {
         }
         jumps
     }
+    
+    fn precompute_lame_jumps(paths: &[Vec<(i8, i8)>], dims: &BDimensions) -> Vec<Vec<(BIndex, Bitboard)>> {
+        let mut jumps = Vec::with_capacity(256);
+        for index in 0..=255 {
+            let (x, y) = from_index(index);
+            let mut jumps_from_index = Vec::new();
+            'paths: for path in paths {
+                let mut squares = Vec::with_capacity(path.len());
+                for (dx, dy) in path {
+                    let (x2, y2) = (x as i8 + *dx, y as i8 + *dy);
+                    if x2 < 0 || y2 < 0 || x2 > 15 || y2 > 15 {
+                        continue 'paths;
+                    }
+                    squares.push((x2 as BCoord, y2 as BCoord));
+                }
+                match squares.pop() {
+                    // The intermediate squares can be walls, but then the jump is always blocked
+                    Some((x2, y2)) if dims.in_bounds(x2, y2) => {
+                        jumps_from_index.push((to_index(x2, y2), Bitboard::from_coord_list(&squares)));
+                    },
+                    _ => {},
+                }
+            }
+            jumps.push(jumps_from_index);
+        }
+        jumps
+    }
+    
+    fn precompute_initial_moves(paths: &[Vec<(i8, i8)>], dims: &BDimensions) -> Vec<Vec<(BIndex, Bitboard, Option<BIndex>)>> {
+        let mut moves = Vec::with_capacity(256);
+        for index in 0..=255 {
+            let (x, y) = from_index(index);
+            let mut moves_from_index = Vec::new();
+            'paths: for path in paths {
+                let mut squares = Vec::with_capacity(path.len());
+                for (dx, dy) in path {
+                    let (x2, y2) = (x as i8 + *dx, y as i8 + *dy);
+                    if x2 < 0 || y2 < 0 || !dims.in_bounds(x2 as BCoord, y2 as BCoord) {
+                        continue 'paths;
+                    }
+                    squares.push((x2 as BCoord, y2 as BCoord));
+                }
+                if let Some((x2, y2)) = squares.pop() {
+                    // The square passed right before the destination is the en passant square
+                    let ep_square = squares.last().map(|(x1, y1)| to_index(*x1, *y1));
+                    moves_from_index.push((to_index(x2, y2), Bitboard::from_coord_list(&squares), ep_square));
+                }
+            }
+            moves.push(moves_from_index);
+        }
+        moves
+    }
+    
+    fn precompute_multi_leg_jumps(legs: &[MultiLegDelta], dims: &BDimensions) -> Vec<Vec<(BIndex, BIndex)>> {
+        let in_bounds = |x: i8, y: i8| x >= 0 && y >= 0 && dims.in_bounds(x as BCoord, y as BCoord);
+        let mut jumps = Vec::with_capacity(256);
+        for index in 0..=255 {
+            let (x, y) = from_index(index);
+            let mut jumps_from_index = Vec::new();
+            for ((dx1, dy1), (dx2, dy2)) in legs {
+                let (x1, y1) = (x as i8 + *dx1, y as i8 + *dy1);
+                let (x2, y2) = (x1 + *dx2, y1 + *dy2);
+                if in_bounds(x1, y1) && in_bounds(x2, y2) {
+                    jumps_from_index.push((to_index(x1 as BCoord, y1 as BCoord), to_index(x2 as BCoord, y2 as BCoord)));
+                }
+            }
+            jumps.push(jumps_from_index);
+        }
+        jumps
+    }
+    
+    fn precompute_bent_riders(riders: &[BentRiderDelta]) -> Vec<Vec<BentRiderRay>> {
+        // The squares outside the board (and walls) are checked when generating the moves, using the occupancy
+        let on_board = |x: i8, y: i8| (0..=15).contains(&x) && (0..=15).contains(&y);
+        let mut riders_per_index = Vec::with_capacity(256);
+        for index in 0..=255 {
+            let (x, y) = from_index(index);
+            let mut riders_from_index = Vec::new();
+            for ((sx, sy), (rx, ry)) in riders {
+                let (mut x2, mut y2) = (x as i8 + *sx, y as i8 + *sy);
+                if !on_board(x2, y2) {
+                    continue;
+                }
+                let step = to_index(x2 as BCoord, y2 as BCoord);
+                let mut ray = Bitboard::zero();
+                loop {
+                    x2 += *rx;
+                    y2 += *ry;
+                    if !on_board(x2, y2) {
+                        break;
+                    }
+                    ray.set_bit_at(x2 as BCoord, y2 as BCoord);
+                }
+                let increasing = *ry > 0 || (*ry == 0 && *rx > 0);
+                riders_from_index.push((step, ray, increasing));
+            }
+            riders_per_index.push(riders_from_index);
+        }
+        riders_per_index
+    }
 }
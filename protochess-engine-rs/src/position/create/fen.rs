@@ -3,8 +3,10 @@ use scan_fmt::scan_fmt;
 
 use crate::utils::{from_index, to_index};
 use crate::utils::notation::tuple_to_rank_file;
-use crate::{wrap_res, err_assert, err, PieceId, Position};
+use crate::{wrap_res, err_assert, err, PieceId, Position, MoveGen};
+use crate::piece::NEUTRAL_PIECE_ID;
 use crate::types::{BCoord, Player};
+use crate::position::position_properties::MAX_EP_SQUARES;
 
 
 #[derive(Debug, Clone)]
@@ -12,8 +14,13 @@ pub struct PiecePlacement {
     pub x: BCoord,
     pub y: BCoord,
     pub piece_id: PieceId,
+    /// `true` if the piece is the result of a promotion (marked with `~` after the piece)
+    pub promoted: bool,
 }
 
+/// En passant squares, and the square of the en passant victim
+pub type EpSquaresAndVictim = (Vec<(BCoord, BCoord)>, (BCoord, BCoord));
+
 /// Summary of the data encoded in a FEN string. Used as an intermediate step when converting between FEN and Position.
 /// 
 /// See [this document](https://github.com/pol-rivero/protochess-engine/tree/master/docs/FEN.md) for the custom FEN format.
@@ -30,18 +37,30 @@ pub struct FenData {
     pub height: BCoord,
     pub piece_placements: Vec<PiecePlacement>,
     pub walls: Vec<(BCoord, BCoord)>,
+    /// Pieces in the hands of the players (in the `[...]` pocket after the piece placement),
+    /// each one identified by its id
+    pub pockets: Vec<PieceId>,
+    /// Squares with neutral pieces (written as `@`), which belong to neither player
+    pub neutral_pieces: Vec<(BCoord, BCoord)>,
+    /// Number of neutral pieces that are not on the board yet (written as `@` in the pocket)
+    pub neutral_in_hand: u8,
     pub player_to_move: Player,
     /// List of squares that have not been moved. `None` means that the castling has not been specified
     /// in the string. Assume that no pieces have moved (everyone can castle).
     pub castling_availability: Option<Vec<(BCoord, BCoord)>>, 
-    /// The EP square, and the square of the EP victim (piece that just did a double move and will be captured)
+    /// The EP squares, and the square of the EP victim (piece that just did a double move and will be captured)
     /// `None` means that EP is not available in this position
-    pub ep_square_and_victim: Option<((BCoord, BCoord), (BCoord, BCoord))>,
+    pub ep_squares_and_victim: Option<EpSquaresAndVictim>,
     /// Number of times each player has been in check. `None` means that this information is not available
     /// in the FEN string (not aplicable to this variant): assume that no player has been in check (`[0,0]`).
     pub times_in_check: Option<[u8; 2]>,
-    
-    // Fullmove and halfmove clocks are not used
+    /// Number of plies since the last capture or move of a piece with `resets_move_clock`. Defaults to `0`.
+    pub halfmove_clock: u16,
+    /// Number of the current move, starting at 1. Defaults to `1`.
+    pub fullmove_number: u16,
+    /// Number of moves that the player to move has already made in the current turn, in variants with several
+    /// moves per turn (written as `mN`). Defaults to `0`.
+    pub submove: u8,
 }
 
 
@@ -54,18 +73,41 @@ impl FenData {
             err!("Invalid FEN string, it must have at least 1 part");
         }
         
+        // Pockets (pieces in hand), like "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Nn]"
+        let (placement, mut pockets) = match fen_parts[0].split_once('[') {
+            Some((placement, pocket)) => {
+                let pocket = pocket.strip_suffix(']').ok_or("Invalid pocket in FEN string, it must end with ']'")?;
+                (placement, pocket.chars().filter(|c| *c != '-').collect::<Vec<_>>())
+            },
+            None => (fen_parts[0], Vec::new()),
+        };
+        // Neutral pieces in the pocket are not owned by any player
+        let neutral_in_hand = pockets.iter().filter(|c| **c == NEUTRAL_PIECE_ID).count();
+        err_assert!(neutral_in_hand <= u8::MAX as usize, "Too many neutral pieces in the pocket");
+        let neutral_in_hand = neutral_in_hand as u8;
+        pockets.retain(|c| *c != NEUTRAL_PIECE_ID);
+        
         // Count the number of ranks
-        let board_height = fen_parts[0].chars().filter(|c| *c == '/').count() + 1;
+        let board_height = placement.chars().filter(|c| *c == '/').count() + 1;
         err_assert!(board_height <= 16, "The FEN string has {board_height} ranks, but the limit is 16");
         
         // Piece placement
         let mut piece_placements = Vec::new();
         let mut walls = Vec::new();
+        let mut neutral_pieces = Vec::new();
         let mut x = 0;
         let mut y = board_height as BCoord - 1;
         let mut skip_x = 0;
         let mut board_width = 0;
-        for c in fen_parts[0].chars() {
+        for c in placement.chars() {
+            if c == '~' {
+                // The previous piece is the result of a promotion
+                match piece_placements.last_mut() {
+                    Some(PiecePlacement { promoted, .. }) => *promoted = true,
+                    None => err!("Invalid FEN string, '~' must follow a piece"),
+                }
+                continue;
+            }
             if c == '/' {
                 board_width = std::cmp::max(board_width, x + skip_x);
                 x = 0;
@@ -80,8 +122,10 @@ impl FenData {
             skip_x = 0;
             if c == '*' {
                 walls.push((x as BCoord, y));
+            } else if c == NEUTRAL_PIECE_ID {
+                neutral_pieces.push((x as BCoord, y));
             } else {
-                piece_placements.push(PiecePlacement { x: x as BCoord, y, piece_id: c });
+                piece_placements.push(PiecePlacement { x: x as BCoord, y, piece_id: c, promoted: false });
             }
             x += 1;
         }
@@ -94,8 +138,8 @@ impl FenData {
         // Player to move
         let player_to_move = {
             // By default, white moves first
-            if fen_parts.len() <= 1 || fen_parts[1].to_ascii_lowercase() == "w" { 0 }
-            else if fen_parts[1].to_ascii_lowercase() == "b" { 1 }
+            if fen_parts.len() <= 1 || fen_parts[1].eq_ignore_ascii_case("w") { 0 }
+            else if fen_parts[1].eq_ignore_ascii_case("b") { 1 }
             else { err!("The player to move must be 'w' or 'b'") }
         };
         
@@ -109,70 +153,109 @@ impl FenData {
             else { Some(parse_castling(fen_parts[2], board_height, board_width)?) }
         };
         
-        let ep_square_and_victim = {
+        let ep_squares_and_victim = {
             if fen_parts.len() <= 3 || fen_parts[3] == "-" {
                 None
             } else {
-                // Expected formats: a1, a1(b2)
-                const EXPECTED_REGEX: &str = r"^[a-p][0-9]+(\([a-p][0-9]+\))?$";
+                // Expected formats: a1, a1(b2), a1,a2(b3)
+                const EXPECTED_REGEX: &str = r"^[a-p][0-9]+(,[a-p][0-9]+)*(\([a-p][0-9]+\))?$";
                 err_assert!(Regex::new(EXPECTED_REGEX).unwrap().is_match(fen_parts[3]), "Invalid en passant square in FEN string");
-                let (ep_x, ep_y) = match scan_fmt!(fen_parts[3], "{[a-p]}{d}", char, isize) {
-                    Ok(parts) => parts,
-                    Err(_) => err!("Invalid en passant square in FEN string")
+                let parse_square = |square: &str| -> wrap_res!((BCoord, isize)) {
+                    match scan_fmt!(square, "{[a-p]}{d}", char, isize) {
+                        // x is guaranteed to be a valid character between 'a' and 'p'
+                        Ok((x, y)) => Ok((x.to_digit(36).unwrap() as BCoord - 10, y - 1)),
+                        Err(_) => err!("Invalid en passant square in FEN string")
+                    }
+                };
+                let (squares, victim) = match fen_parts[3].split_once('(') {
+                    Some((squares, victim)) => (squares, Some(victim.trim_end_matches(')'))),
+                    None => (fen_parts[3], None),
                 };
-                let (vic_x, vic_y) = match scan_fmt!(fen_parts[3], "{*[a-p]}{*d}({[a-p]}{d})", char, isize) {
-                    Ok(parts) => parts,
-                    Err(_) => {
+                let mut ep_squares = Vec::new();
+                for square in squares.split(',') {
+                    let (ep_x, ep_y) = parse_square(square)?;
+                    // Only need to check the y coordinates, since the x coordinates are already guaranteed to be valid
+                    err_assert!(ep_y >= 0 && ep_y < board_height as isize, "Invalid en passant square in FEN string");
+                    ep_squares.push((ep_x, ep_y as BCoord));
+                }
+                err_assert!(ep_squares.len() <= MAX_EP_SQUARES, "Too many en passant squares in FEN string");
+                let (vic_x, vic_y) = match victim {
+                    Some(victim) => parse_square(victim)?,
+                    None => {
+                        err_assert!(ep_squares.len() == 1, "The en passant victim is required with several en passant squares");
                         // If it's white's turn, the victim is the black pawn that just did a double move
                         // (victim is 1 square below the EP square).
-                        if player_to_move == 0 { (ep_x, ep_y - 1) }
-                        else { (ep_x, ep_y + 1) }
+                        let (ep_x, ep_y) = ep_squares[0];
+                        if player_to_move == 0 { (ep_x, ep_y as isize - 1) }
+                        else { (ep_x, ep_y as isize + 1) }
                     }
                 };
-                // ep_x and ev_x are guaranteed to be a valid character between 'a' and 'p'
-                let ep_x = ep_x.to_digit(36).unwrap() as BCoord - 10;
-                let ep_y = ep_y - 1;
-                let vic_x = vic_x.to_digit(36).unwrap() as BCoord - 10;
-                let vic_y = vic_y - 1;
-                // Only need to check the y coordinates, since the x coordinates are already guaranteed to be valid
-                err_assert!(ep_y >= 0 && ep_y < board_height as isize, "Invalid en passant square in FEN string");
                 err_assert!(vic_y >= 0 && vic_y < board_height as isize, "Invalid en passant victim in FEN string");
                 
-                Some(((ep_x, ep_y as BCoord), (vic_x, vic_y as BCoord)))
+                Some((ep_squares, (vic_x, vic_y as BCoord)))
             }
         };
         
         // Times in check: search all remaining parts for a +W+B format
+        // Sub-move counter: search all remaining parts for a mN format
+        // Halfmove and fullmove clocks: the remaining parts that are numbers, in this order
         let mut times_in_check = None;
+        let mut submove = 0;
+        let mut clocks = Vec::new();
+        const TIMES_IN_CHECK_REGEX: &str = r"^\+([0-9]+)\+([0-9]+)$";
+        const WRONG_FORMAT_REGEX: &str = r"^([0-9]+)\+([0-9]+)$";
+        let times_in_check_regex = Regex::new(TIMES_IN_CHECK_REGEX).unwrap();
+        let wrong_format_regex = Regex::new(WRONG_FORMAT_REGEX).unwrap();
         for part in fen_parts.iter().skip(4) {
-            const TIMES_IN_CHECK_REGEX: &str = r"^\+([0-9]+)\+([0-9]+)$";
-            const WRONG_FORMAT_REGEX: &str = r"^([0-9]+)\+([0-9]+)$";
-            if !Regex::new(TIMES_IN_CHECK_REGEX).unwrap().is_match(part) {
+            if part.chars().all(|c| c.is_ascii_digit()) {
+                match part.parse::<u16>() {
+                    Ok(clock) => clocks.push(clock),
+                    Err(_) => err!("Invalid move clock in FEN string: '{part}'"),
+                }
+                continue;
+            }
+            if let Some(count) = part.strip_prefix('m') {
+                submove = match count.parse::<u8>() {
+                    Ok(count) => count,
+                    Err(_) => err!("Invalid sub-move counter in FEN string: '{part}'"),
+                };
+                continue;
+            }
+            if !times_in_check_regex.is_match(part) {
                 // Check if this is an alternative check count format
-                if Regex::new(WRONG_FORMAT_REGEX).unwrap().is_match(part) {
+                if wrong_format_regex.is_match(part) {
                     err!("Invalid check count format, use +W+B, where W is the number of times White put Black in check.
                     In 3-Check, '3+1' is equivalent to '+0+2'");
                 }
                 continue;
             }
             // Some of the parts match the check count format
-            let (white_checks, black_checks) = match scan_fmt!(fen_parts[6], "+{d}+{d}", u8, u8) {
+            let (white_checks, black_checks) = match scan_fmt!(part, "+{d}+{d}", u8, u8) {
                 Ok(parts) => parts,
                 Err(_) => err!("Invalid check format, make sure it's between +0+0 and +255+255"),
             };
             // Important: Note that the order is reversed
             times_in_check = Some([black_checks, white_checks]);
         }
+        err_assert!(clocks.len() <= 2, "Too many move clocks in FEN string");
+        let halfmove_clock = clocks.first().copied().unwrap_or(0);
+        let fullmove_number = clocks.get(1).copied().unwrap_or(1).max(1);
         
         Ok(Self {
             width: board_width,
             height: board_height,
             piece_placements,
             walls,
+            pockets,
+            neutral_pieces,
+            neutral_in_hand,
             times_in_check,
             player_to_move,
             castling_availability,
-            ep_square_and_victim,
+            ep_squares_and_victim,
+            halfmove_clock,
+            fullmove_number,
+            submove,
         })
     }
 }
@@ -244,6 +327,7 @@ impl From<&Position> for FenData {
         let mut piece_placements = Vec::new();
         let mut castling = Vec::new();
         let mut walls = Vec::new();
+        let mut neutral_pieces = Vec::new();
         let width = pos.dimensions.width;
         let height = pos.dimensions.height;
         for x in 0..width {
@@ -251,24 +335,34 @@ impl From<&Position> for FenData {
                 let index = to_index(x, y);
                 if let Some(piece) = pos.piece_at(index) {
                     // Piece found, add it to the list
-                    piece_placements.push(PiecePlacement { x, y, piece_id: piece.get_piece_id() });
+                    piece_placements.push(PiecePlacement { x, y, piece_id: piece.get_piece_id(), promoted: pos.is_promoted(index) });
                     // If this square can be used for castling, add it to the list
-                    if piece.has_not_moved(index) && piece.used_in_castling() {
+                    if piece.has_not_moved(index) && piece.tracks_unmoved() {
                         castling.push((x, y));
                     }
                 }
+                if pos.get_neutral_pieces().get_bit(index) {
+                    neutral_pieces.push((x, y));
+                }
                 if !pos.dimensions.in_bounds(x, y) {
                     walls.push((x, y));
                 }
             }
         }        
-        // Extract EP square
-        let ep_square_and_victim = {
-            if let Some(ep_square) = pos.get_ep_square() {
-                let ep_victim = pos.get_ep_victim();
-                Some((from_index(ep_square), from_index(ep_victim)))
-            } else {
+        // Extract the pockets, white pieces first
+        let mut pockets = Vec::new();
+        for player in 0..2 {
+            for (piece_id, count) in pos.get_pocket(player) {
+                pockets.extend(std::iter::repeat_n(piece_id, count as usize));
+            }
+        }
+        // Extract EP squares
+        let ep_squares_and_victim = {
+            if pos.get_ep_squares().is_empty() {
                 None
+            } else {
+                let ep_squares = pos.get_ep_squares().iter().map(|ep_square| from_index(*ep_square)).collect();
+                Some((ep_squares, from_index(pos.get_ep_victim())))
             }
         };
         
@@ -277,14 +371,44 @@ impl From<&Position> for FenData {
             height,
             piece_placements,
             walls,
+            pockets,
+            neutral_pieces,
+            neutral_in_hand: pos.get_neutral_in_hand(),
             times_in_check: pos.get_times_checked().copied(),
             player_to_move: pos.whos_turn,
             castling_availability: Some(castling),
-            ep_square_and_victim,
+            ep_squares_and_victim,
+            halfmove_clock: pos.get_halfmove_clock(),
+            fullmove_number: pos.get_fullmove_number(),
+            submove: pos.get_submove(),
         }
     }
 }
 
+impl FenData {
+    /// Extracts the FEN data that `player` knows in fog-of-war variants (see `MoveGen::visible_squares()`).
+    /// The enemy pieces on squares that the player can't see are removed, along with their castling rights,
+    /// the pieces in the hand of the enemy, and the en passant squares if the victim is hidden
+    pub fn from_player_view(pos: &mut Position, player: Player) -> Self {
+        let visible = MoveGen::visible_squares(pos, player);
+        let is_visible = |x: BCoord, y: BCoord| visible.get_bit(to_index(x, y));
+        let mut fen = FenData::from(&*pos);
+        fen.piece_placements.retain(|p| is_visible(p.x, p.y));
+        if let Some(castling) = &mut fen.castling_availability {
+            castling.retain(|&(x, y)| is_visible(x, y));
+        }
+        if let Some((_, (x, y))) = fen.ep_squares_and_victim {
+            if !is_visible(x, y) {
+                fen.ep_squares_and_victim = None;
+            }
+        }
+        fen.pockets = pos.get_pocket(player).into_iter()
+            .flat_map(|(piece_id, count)| std::iter::repeat_n(piece_id, count as usize))
+            .collect();
+        fen
+    }
+}
+
 
 
 /// Outputs the FEN data as a FEN-formatted string
@@ -304,6 +428,15 @@ impl std::fmt::Display for FenData {
                     write!(f, "*")?;
                     continue;
                 }
+                // Add neutral pieces as '@'
+                if self.neutral_pieces.contains(&(x, y)) {
+                    if empty_count > 0 {
+                        write!(f, "{empty_count}")?;
+                        empty_count = 0;
+                    }
+                    write!(f, "{NEUTRAL_PIECE_ID}")?;
+                    continue;
+                }
                 let mut found = false;
                 // Find the piece placed on that square
                 for piece in &self.piece_placements {
@@ -313,6 +446,9 @@ impl std::fmt::Display for FenData {
                             empty_count = 0;
                         }
                         write!(f, "{}", piece.piece_id)?;
+                        if piece.promoted {
+                            write!(f, "~")?;
+                        }
                         found = true;
                         break;
                     }
@@ -332,6 +468,12 @@ impl std::fmt::Display for FenData {
             }
         }
         
+        // Pockets are omitted if empty
+        if !self.pockets.is_empty() || self.neutral_in_hand > 0 {
+            let neutral = std::iter::repeat_n(NEUTRAL_PIECE_ID, self.neutral_in_hand as usize);
+            write!(f, "[{}]", self.pockets.iter().copied().chain(neutral).collect::<String>())?;
+        }
+        
         // STEP 2: Player to move
         let player_char = if self.player_to_move == 0 { 'w' } else { 'b' };
         write!(f, " {player_char}")?;
@@ -354,17 +496,30 @@ impl std::fmt::Display for FenData {
         }
         
         // STEP 4: EP square
-        if let Some((ep_square, ep_victim)) = self.ep_square_and_victim {
-            write!(f, " {}({})", tuple_to_rank_file(ep_square), tuple_to_rank_file(ep_victim))?;
+        if let Some((ep_squares, ep_victim)) = &self.ep_squares_and_victim {
+            write!(f, " ")?;
+            for (i, square) in ep_squares.iter().enumerate() {
+                if i > 0 { write!(f, ",")?; }
+                write!(f, "{}", tuple_to_rank_file(*square))?;
+            }
+            write!(f, "({})", tuple_to_rank_file(*ep_victim))?;
         } else {
             write!(f, " -")?;
         }
         
-        // STEP 5: Times in check
+        // STEP 5: Halfmove and fullmove clocks
+        write!(f, " {} {}", self.halfmove_clock, self.fullmove_number)?;
+        
+        // STEP 6: Times in check
         // Important: Note that the order is reversed
         if let Some(times_in_check) = self.times_in_check {
             write!(f, " +{}+{}", times_in_check[1], times_in_check[0])?;
         }
+        
+        // STEP 7: Sub-move counter, omitted at the start of a turn
+        if self.submove > 0 {
+            write!(f, " m{}", self.submove)?;
+        }
         Ok(())
     }
 }
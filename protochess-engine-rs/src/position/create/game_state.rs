@@ -9,8 +9,8 @@ use super::variant_factory::VariantFactory;
 /// Full state of the game, including:
 /// - **Initial state:** Defines the rules of the game and starting position.
 /// - **Initial fen (optional):** User-provided fen that further defines the starting position, overriding 
-/// the initial state. It's applied to the initial state before playing the moves in `move_history`.
-/// See [this document](https://github.com/pol-rivero/protochess-engine/tree/master/docs/FEN.md) for the custom FEN format.
+///   the initial state. It's applied to the initial state before playing the moves in `move_history`.
+///   See [this document](https://github.com/pol-rivero/protochess-engine/tree/master/docs/FEN.md) for the custom FEN format.
 /// - **Move history:** Defines the current position and allows enforcing the repetition rules.
 /// 
 /// All games of a given chess variant have the same initial state, but can have different initial FENs.
@@ -39,6 +39,7 @@ impl GameState {
 /// Defines a chess variant. Includes the rules of the game and the starting position.
 #[must_use]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default, deny_unknown_fields))]
 pub struct InitialState {
     /// Initial position in FEN format. It includes the walls as `'*'`, which cannot 
     /// be overridden by the user when loading another FEN.
@@ -66,7 +67,7 @@ impl Default for InitialState {
 #[must_use]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StateDiff {
-    /// Current position in FEN format. The walls are represented as `'*'`.
+    /// Current position in FEN format. The walls are represented as `'*'`, and the neutral pieces as `'@'`.
     pub fen: String,
     pub in_check: bool,
     pub player_to_move: Player,
@@ -83,3 +84,17 @@ impl From<&mut Position> for StateDiff {
         StateDiff { fen, in_check, player_to_move }
     }
 }
+
+impl StateDiff {
+    /// Returns the state as seen by `player` in fog-of-war variants, with the hidden enemy pieces removed
+    /// from the FEN (see `FenData::from_player_view()`). Checks are only reported to the player to move
+    pub fn from_player_view(pos: &mut Position, player: Player) -> Self {
+        let fen = FenData::from_player_view(pos, player).to_string();
+        let in_check = {
+            if pos.whos_turn != player || pos.leader_is_captured() { false }
+            else { MoveGen::in_check(pos) }
+        };
+        let player_to_move = pos.whos_turn;
+        StateDiff { fen, in_check, player_to_move }
+    }
+}
@@ -4,3 +4,6 @@ pub mod fen;
 pub mod game_state;
 pub mod variant_factory;
 pub mod position_factory;
+pub mod pgn;
+#[cfg(feature = "serde")]
+pub mod variant_file;
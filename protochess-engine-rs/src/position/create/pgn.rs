@@ -0,0 +1,284 @@
+// Importing and exporting games in PGN (Portable Game Notation) format.
+// See <https://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm> for the specification.
+
+use std::convert::TryFrom;
+
+use crate::{MakeMoveResult, MakeMoveResultFlag, MakeMoveResultWinner, InitialState, MoveInfo, wrap_res, err, err_assert};
+use crate::types::{GameMode, Player};
+use crate::utils::notation::parse_algebraic_notation;
+
+use super::fen::FenData;
+use super::game_state::GameState;
+use super::position_factory::PositionFactory;
+use super::variant_factory::VariantFactory;
+
+/// Maximum length of a line of movetext (the PGN standard recommends 80 characters)
+const MAX_LINE_LENGTH: usize = 80;
+
+const ALL_GAME_MODES: [GameMode; 14] = [
+    GameMode::Standard,
+    GameMode::Atomic,
+    GameMode::Horde,
+    GameMode::Antichess,
+    GameMode::KingOfTheHill,
+    GameMode::RacingKings,
+    GameMode::ThreeCheck,
+    GameMode::FiveCheck,
+    GameMode::Crazyhouse,
+    GameMode::Duck,
+    GameMode::Marseillais,
+    GameMode::Progressive,
+    GameMode::Monster,
+    GameMode::Dark,
+];
+
+/// Builds a PGN from the `GameState`, the move history in algebraic notation and the
+/// result of the last move.
+/// The tags that are unknown to the engine (event, players, etc.) are set to `"?"`.
+pub fn to_pgn(state: &GameState, move_notation: &[String], last_result: &MakeMoveResult) -> String {
+    let result = result_tag(last_result);
+
+    let mut pgn = String::new();
+    // Seven Tag Roster
+    pgn.push_str("[Event \"?\"]\n");
+    pgn.push_str("[Site \"?\"]\n");
+    pgn.push_str("[Date \"????.??.??\"]\n");
+    pgn.push_str("[Round \"?\"]\n");
+    pgn.push_str("[White \"?\"]\n");
+    pgn.push_str("[Black \"?\"]\n");
+    pgn.push_str(&format!("[Result \"{result}\"]\n"));
+
+    let variant = variant_name(&state.initial_state);
+    if variant != GameMode::Standard.to_string() {
+        pgn.push_str(&format!("[Variant \"{variant}\"]\n"));
+    }
+    if let Some(fen) = &state.initial_fen {
+        pgn.push_str(&format!("[FEN \"{fen}\"]\n"));
+        pgn.push_str("[SetUp \"1\"]\n");
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::with_capacity(move_notation.len() * 3 / 2 + 1);
+    // All the moves of a turn share the same number
+    for (ply, (mv, (player, move_number, submove))) in move_notation.iter().zip(move_numbers(state)).enumerate() {
+        if player == 0 && submove == 0 {
+            tokens.push(format!("{move_number}."));
+        } else if ply == 0 {
+            tokens.push(format!("{move_number}..."));
+        }
+        // The " e.p." suffix is not part of SAN, and would be parsed as a separate token
+        tokens.push(mv.replace(" e.p.", ""));
+    }
+    tokens.push(result.to_string());
+
+    // Wrap the movetext
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+    pgn
+}
+
+/// Reads a game in PGN format and returns the corresponding `GameState`. Only the first game of
+/// the PGN is read.
+/// 
+/// The variant is determined by the `[Variant]` tag, if it's the name of one of the hardcoded
+/// variants. Otherwise, `default_variant` is used (or standard chess, if it's `None`).
+/// The `[FEN]` tag is used as the initial FEN.
+/// 
+/// Returns an error if a move is illegal or cannot be parsed. The error message includes the
+/// move number.
+pub fn from_pgn(pgn: &str, default_variant: Option<&InitialState>) -> wrap_res!(GameState) {
+    let (tags, movetext) = split_tags(pgn)?;
+    let tag = |name: &str| tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    
+    let initial_state = match (tag("Variant").and_then(parse_variant_name), default_variant) {
+        (Some(mode), _) => VariantFactory::new(mode).make_initial_state(),
+        (None, Some(state)) => state.clone(),
+        (None, None) => {
+            if let Some(name) = tag("Variant") {
+                err!("Unknown variant '{name}'");
+            }
+            VariantFactory::new(GameMode::Standard).make_initial_state()
+        },
+    };
+    let initial_fen = tag("FEN").map(|fen| fen.to_string());
+    let mut state = GameState { initial_state, initial_fen, move_history: vec![] };
+    let mut pos = PositionFactory::default().set_state(state.clone(), None)?
+        .expect("set_state() without a reused position always creates a new one");
+    
+    for san in movetext_moves(movetext)? {
+        let move_number = pos.get_fullmove_number();
+        let dots = if pos.whos_turn == 0 { "." } else { "..." };
+        let mv = parse_algebraic_notation(&mut pos, san)
+            .map_err(|e| format!("Move {move_number}{dots} {san}: {e}"))?;
+        let mv = MoveInfo::from(mv);
+        if pos.pub_make_move(&mv).flag == MakeMoveResultFlag::IllegalMove {
+            err!("Move {move_number}{dots} {san}: Illegal move: '{san}'");
+        }
+        state.move_history.push(mv);
+    }
+    Ok(state)
+}
+
+/// Splits the PGN into the list of (name, value) tags and the movetext
+fn split_tags(pgn: &str) -> wrap_res!(Vec<(String, String)>, &str) {
+    let mut tags = Vec::new();
+    let mut rest = pgn.trim_start_matches('\u{feff}').trim_start();
+    while let Some(tag) = rest.strip_prefix('[') {
+        let end = tag.find(']').ok_or("Unterminated tag in PGN")?;
+        let (name, value) = tag[..end].trim().split_once(char::is_whitespace)
+            .ok_or_else(|| format!("Invalid tag in PGN: [{}]", &tag[..end]))?;
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+            .ok_or_else(|| format!("Invalid tag in PGN: [{}]", &tag[..end]))?;
+        tags.push((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")));
+        rest = tag[end+1..].trim_start();
+    }
+    Ok((tags, rest))
+}
+
+/// Returns the moves in the movetext, skipping comments, variations, move numbers, NAGs and the result
+fn movetext_moves(movetext: &str) -> wrap_res!(Vec<&str>) {
+    let mut moves = Vec::new();
+    let mut token_start = None;
+    let mut variation_depth = 0;
+    let mut chars = movetext.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let is_separator = c.is_whitespace() || matches!(c, '{' | '}' | ';' | '(' | ')');
+        if is_separator {
+            if let Some(start) = token_start.take() {
+                if variation_depth == 0 && push_token(&movetext[start..i], &mut moves) {
+                    return Ok(moves);
+                }
+            }
+        } else if token_start.is_none() {
+            token_start = Some(i);
+        }
+        match c {
+            '{' => {
+                // Skip until the end of the comment
+                let closed = chars.by_ref().any(|(_, c)| c == '}');
+                err_assert!(closed, "Unterminated comment in PGN");
+            },
+            ';' => {
+                // Skip until the end of the line
+                chars.by_ref().any(|(_, c)| c == '\n');
+            },
+            '(' => variation_depth += 1,
+            ')' => {
+                if variation_depth == 0 {
+                    err!("Unexpected ')' in PGN");
+                }
+                variation_depth -= 1;
+            },
+            _ => {},
+        }
+    }
+    if let Some(start) = token_start {
+        if variation_depth == 0 {
+            push_token(&movetext[start..], &mut moves);
+        }
+    }
+    Ok(moves)
+}
+
+/// Adds the move in the token (if any) to `moves`. Returns `true` if the token is a game result,
+/// which marks the end of the game.
+fn push_token<'a>(token: &'a str, moves: &mut Vec<&'a str>) -> bool {
+    if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return true;
+    }
+    if token.starts_with('$') || token == "e.p." {
+        return false;
+    }
+    // Remove the move number ("12." or "12..."), which can be attached to the move
+    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let token = {
+        if digits.len() < token.len() && digits.starts_with('.') { digits.trim_start_matches('.') }
+        else { token }
+    };
+    if !token.is_empty() {
+        moves.push(token);
+    }
+    false
+}
+
+/// Returns the hardcoded variant with the given name, ignoring case, spaces and hyphens
+/// (for example, `"King of the Hill"` or `"Three-check"`)
+fn parse_variant_name(name: &str) -> Option<GameMode> {
+    let normalize = |s: &str| s.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    let name = normalize(name);
+    if name == "chess" || name == "fromposition" {
+        return Some(GameMode::Standard);
+    }
+    ALL_GAME_MODES.iter().copied()
+        .find(|mode| normalize(&mode.to_string()) == name)
+        .or_else(|| GameMode::try_from(name.as_str()).ok())
+}
+
+/// Returns the value of the `[Result]` tag: `1-0`, `0-1`, `1/2-1/2` or `*` (game in progress)
+fn result_tag(last_result: &MakeMoveResult) -> &'static str {
+    match last_result.winner {
+        MakeMoveResultWinner::White => "1-0",
+        MakeMoveResultWinner::Black => "0-1",
+        MakeMoveResultWinner::None => {
+            match last_result.flag {
+                MakeMoveResultFlag::Ok | MakeMoveResultFlag::IllegalMove => "*",
+                _ => "1/2-1/2",
+            }
+        }
+    }
+}
+
+/// Returns the name of the hardcoded variant that matches the `InitialState`, or `"Custom"`
+fn variant_name(initial_state: &InitialState) -> String {
+    for mode in ALL_GAME_MODES {
+        if VariantFactory::new(mode).make_initial_state() == *initial_state {
+            return mode.to_string();
+        }
+    }
+    "Custom".to_string()
+}
+
+/// Returns the player, the fullmove number and the sub-move of each move in the game. The game is replayed,
+/// since a turn can have several moves (see `GlobalRules::moves_per_turn`) and in some variants it ends early
+fn move_numbers(state: &GameState) -> Vec<(Player, u16, u8)> {
+    let initial = GameState { move_history: vec![], ..state.clone() };
+    let mut pos = PositionFactory::default().set_state(initial, None).ok().flatten();
+    let (mut player, mut move_number, mut submove) = match &state.initial_fen {
+        Some(fen) => FenData::parse_fen(fen).map_or((state.initial_state.player_to_move, 1, 0),
+            |fen_data| (fen_data.player_to_move, fen_data.fullmove_number, fen_data.submove)),
+        None => (state.initial_state.player_to_move, 1, 0),
+    };
+    let mut numbers = Vec::with_capacity(state.move_history.len());
+    for mv in &state.move_history {
+        if let Some(pos) = &pos {
+            (player, move_number, submove) = (pos.whos_turn, pos.get_fullmove_number(), pos.get_submove());
+        }
+        numbers.push((player, move_number, submove));
+        if pos.as_mut().is_some_and(|pos| pos.pub_make_move(mv).flag == MakeMoveResultFlag::IllegalMove) {
+            pos = None;
+        }
+        if pos.is_none() {
+            // The game can't be replayed, assume that every turn has all its moves
+            let moves_in_turn = state.initial_state.global_rules.moves_per_turn.moves_in_turn(player, move_number);
+            submove += 1;
+            if submove as u32 >= moves_in_turn {
+                submove = 0;
+                move_number += player as u16;
+                player = 1 - player;
+            }
+        }
+    }
+    numbers
+}
@@ -1,7 +1,8 @@
 use crate::position::position_properties::PositionProperties;
 use crate::{InitialState, Position, wrap_res, err_assert, MakeMoveResultFlag, MoveInfo, MakeMoveResult};
 use crate::utils::to_index;
-use crate::types::BDimensions;
+use crate::position::global_rules::{MovesPerTurn, MidTurnCheck};
+use crate::types::{BDimensions, ZobKey};
 
 use super::fen::FenData;
 use super::game_state::GameState;
@@ -23,7 +24,7 @@ impl PositionFactory {
     /// Returns:
     /// - `Err` If the state is invalid
     /// - `Ok(None)` If the current position can be reused. In this case, `reused_position` parameter
-    /// will be updated.
+    ///   will be updated.
     /// - `Ok(Some(position))` If a new position was created.
     pub fn set_state(&mut self, new_state: GameState, reused_position: Option<&mut Position>) -> wrap_res!(Option<Position>) {
         // No current state, just create a new position
@@ -113,6 +114,7 @@ impl PositionFactory {
         
         // Apply the move history
         self.move_notation.clear();
+        self.last_result = None;
         for m in &state.move_history {
             let result = pos.pub_make_move(m);
             err_assert!(result.flag != MakeMoveResultFlag::IllegalMove, "Invalid move: {}", m);
@@ -145,7 +147,7 @@ impl PositionFactory {
         if let Some(result) = &self.last_result {
             result.clone()
         } else {
-            MakeMoveResult::ok(vec![], "??".to_string())
+            MakeMoveResult::ok(vec![], "??".to_string(), false)
         }
     }
     
@@ -171,6 +173,7 @@ impl PositionFactory {
         } else {
             panic!("No current state, call make_position() first");
         }
+        self.move_notation.pop();
         self.last_result = None;
     }
     
@@ -178,11 +181,12 @@ impl PositionFactory {
     /// - **Board height and width:** From `InitialState`
     /// - **Piece definitions:** From `InitialState`
     /// - **Global rules:** From `InitialState`
-    /// - **Piece placements and Walls:** From `FenData`
+    /// - **Piece placements, Walls, Pockets and Neutral pieces:** From `FenData`
     /// - **Player to move:** From `FenData`
     /// - **Castling availability:** From `FenData`
     /// - **EP square and victim:** From `FenData`
     /// - **Times in check:** From `FenData`
+    /// - **Halfmove and fullmove clocks, sub-move counter:** From `FenData`
     fn create_new_position(state: &InitialState, fen: FenData) -> wrap_res!(Position) {
         
         let dims = BDimensions::from_walls(state.board_width, state.board_height, &fen.walls)?;
@@ -194,16 +198,38 @@ impl PositionFactory {
         
         // Update props
         let mut props = PositionProperties::default();
-        if let Some(((sx,sy),(vx,vy))) = fen.ep_square_and_victim {
-            err_assert!(dims.in_bounds(sx, sy), "Invalid EP square: ({sx}, {sy})");
+        if let Some((ep_squares, (vx, vy))) = fen.ep_squares_and_victim {
+            for &(sx, sy) in &ep_squares {
+                err_assert!(dims.in_bounds(sx, sy), "Invalid EP square: ({sx}, {sy})");
+            }
             err_assert!(dims.in_bounds(vx, vy), "Invalid EP victim: ({vx}, {vy})");
-            props.set_ep_square(to_index(sx, sy), to_index(vx, vy));
+            props.set_ep_squares(ep_squares.iter().map(|(sx, sy)| to_index(*sx, *sy)), to_index(vx, vy));
         }
         if fen.player_to_move == 1 {
             // Use the lowest bit as player zobrist key
             props.zobrist_key ^= 1;
         }
         props.times_in_check = fen.times_in_check.unwrap_or([0,0]);
+        props.halfmove_clock = fen.halfmove_clock;
+        props.fullmove_number = fen.fullmove_number;
+        let rules = &state.global_rules;
+        err_assert!(!(rules.stalemated_player_loses && rules.stalemated_player_wins), "A stalemated player cannot both lose and win");
+        let moves_per_turn = rules.moves_per_turn;
+        if let MovesPerTurn::Fixed { moves, .. } = moves_per_turn {
+            err_assert!(moves.iter().all(|m| *m > 0), "Each player must make at least one move per turn");
+        }
+        // Checking if the turn can be completed searches all the remaining moves of the turn, so limit its length
+        let max_moves_in_turn = match moves_per_turn {
+            MovesPerTurn::Fixed { moves, .. } => moves.iter().copied().max().unwrap_or(1),
+            MovesPerTurn::Progressive => u8::MAX,
+        };
+        err_assert!(rules.mid_turn_check != MidTurnCheck::EndOfTurn || max_moves_in_turn <= 2,
+            "Checks at the end of the turn are only supported with at most 2 moves per turn");
+        let moves_in_turn = moves_per_turn.moves_in_turn(fen.player_to_move, fen.fullmove_number);
+        err_assert!((fen.submove as u32) < moves_in_turn, "Invalid sub-move counter in FEN string, the turn only has {moves_in_turn} moves");
+        props.submove = fen.submove;
+        // Use bits 32-39 as sub-move zobrist key
+        props.zobrist_key ^= (fen.submove as ZobKey) << 32;
 
         // Instantiate position and register piecetypes
         let mut pos = Position::new(dims, fen.player_to_move, props, state.global_rules.clone());
@@ -211,14 +237,37 @@ impl PositionFactory {
             pos.register_piecetype(definition)?;
         }
         pos.assert_promotion_consistency()?;
-        
+        pos.assert_game_end_rules()?;
+
+        // Count the pieces in the starting position of the variant, to know which ones have been lost
+        if state.piece_types.iter().any(|def| def.promote_to_captured_only) {
+            let initial_placements = FenData::parse_fen(&state.fen)?.piece_placements;
+            for piece in pos.pieces.iter_mut().flat_map(|set| set.iter_mut()) {
+                let id = piece.get_piece_id();
+                piece.set_initial_num_pieces(initial_placements.iter().filter(|p| p.piece_id == id).count() as u32);
+            }
+        }
+
         // Add pieces
         for p in fen.piece_placements {
             let can_castle = {
-                if fen.castling_availability.is_none() { true }
-                else { fen.castling_availability.as_ref().unwrap().contains(&(p.x, p.y)) }
+                if let Some(castling) = &fen.castling_availability { castling.contains(&(p.x, p.y)) }
+                else { true }
             };
             pos.public_add_piece(p.piece_id, to_index(p.x, p.y), can_castle)?;
+            if p.promoted && pos.global_rules.demote_promoted_pieces {
+                pos.promoted.set_bit(to_index(p.x, p.y));
+            }
+        }
+        for piece_id in fen.pockets {
+            pos.public_add_to_hand(piece_id)?;
+        }
+        for (x, y) in fen.neutral_pieces {
+            err_assert!(pos.in_bounds(x, y), "The neutral piece at ({x}, {y}) is out of bounds");
+            pos.public_add_neutral_piece(to_index(x, y))?;
+        }
+        for _ in 0..fen.neutral_in_hand {
+            pos.public_add_neutral_to_hand()?;
         }
         Ok(pos)
     }
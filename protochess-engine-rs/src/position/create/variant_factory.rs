@@ -23,6 +23,10 @@ impl VariantFactory {
         let fen = match self.mode {
             GameMode::Horde => "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP",
             GameMode::RacingKings => "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ",
+            // The duck starts off the board, and white places it after the first move
+            GameMode::Duck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[@]",
+            // White has only the king and 4 pawns, but makes 2 moves per turn
+            GameMode::Monster => "rnbqkbnr/pppppppp/8/8/8/8/2PPPP2/4K3",
             _ => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
         }.to_string();
         
@@ -0,0 +1,148 @@
+// Loading and saving variants (`InitialState`) as TOML or JSON files.
+// See docs/VARIANT_FILE.md for a description of the format.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{InitialState, GameState, PieceDefinition, err, err_assert, wrap_res};
+use crate::types::BCoord;
+
+use super::position_factory::PositionFactory;
+
+
+/// Loads a variant from a file. The format is determined by the extension (`.toml` or `.json`).
+pub fn load_variant(path: &Path) -> wrap_res!(InitialState) {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read '{}': {e}", path.display()))?;
+    match extension(path)?.as_str() {
+        "toml" => variant_from_toml(&contents),
+        _ => variant_from_json(&contents),
+    }
+}
+
+/// Saves a variant to a file. The format is determined by the extension (`.toml` or `.json`).
+pub fn save_variant(state: &InitialState, path: &Path) -> wrap_res!() {
+    let contents = match extension(path)?.as_str() {
+        "toml" => variant_to_toml(state)?,
+        _ => variant_to_json(state)?,
+    };
+    std::fs::write(path, contents).map_err(|e| format!("Cannot write '{}': {e}", path.display()))
+}
+
+/// Parses and validates a variant in TOML format
+pub fn variant_from_toml(contents: &str) -> wrap_res!(InitialState) {
+    let deserializer = toml::Deserializer::new(contents);
+    let state: InitialState = serde_path_to_error::deserialize(deserializer)
+        .map_err(|e| format!("{}: {}", e.path(), e.inner().message()))?;
+    validate_variant(&state)?;
+    Ok(state)
+}
+
+/// Parses and validates a variant in JSON format
+pub fn variant_from_json(contents: &str) -> wrap_res!(InitialState) {
+    let mut deserializer = serde_json::Deserializer::from_str(contents);
+    let state: InitialState = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| format!("{}: {}", e.path(), e.inner()))?;
+    validate_variant(&state)?;
+    Ok(state)
+}
+
+pub fn variant_to_toml(state: &InitialState) -> wrap_res!(String) {
+    toml::to_string(state).map_err(|e| e.to_string())
+}
+
+pub fn variant_to_json(state: &InitialState) -> wrap_res!(String) {
+    serde_json::to_string_pretty(state).map_err(|e| e.to_string())
+}
+
+/// Returns an error if the variant is not valid. The error message starts with the name of the
+/// offending field, like `piece_types[2].promo_vals`.
+pub fn validate_variant(state: &InitialState) -> wrap_res!() {
+    let (width, height) = (state.board_width, state.board_height);
+    err_assert!(width > 0 && width <= 16, "board_width: must be between 1 and 16 (found {width})");
+    err_assert!(height > 0 && height <= 16, "board_height: must be between 1 and 16 (found {height})");
+    err_assert!(state.player_to_move <= 1, "player_to_move: must be 0 (white) or 1 (black)");
+    let placement = state.fen.split_whitespace().next().unwrap_or_default();
+    let num_ranks = placement.split('/').count();
+    err_assert!(num_ranks == height as usize, "fen: expected {height} ranks, found {num_ranks}");
+
+    let mut used_ids = HashMap::new();
+    for (i, def) in state.piece_types.iter().enumerate() {
+        let field = |name: &str| format!("piece_types[{i}].{name}");
+        for id in def.ids.iter().flatten() {
+            if let Some(j) = used_ids.insert(*id, i) {
+                err!("{}: id '{id}' is already used by piece_types[{j}]", field("ids"));
+            }
+        }
+        // Pieces that castle vertically use ranks instead of files
+        let (castle_limit, castle_limit_name) = if def.castle_vertically { (height, "board_height") } else { (width, "board_width") };
+        if let Some((queenside, kingside)) = def.castle_files {
+            err_assert!(queenside < castle_limit && kingside < castle_limit, "{}: must be less than {castle_limit_name}", field("castle_files"));
+        }
+        if let Some((queenside, kingside)) = def.castle_partner_files {
+            err_assert!(queenside < castle_limit && kingside < castle_limit, "{}: must be less than {castle_limit_name}", field("castle_partner_files"));
+        }
+        check_squares(&def.promotion_squares, width, height, &field("promotion_squares"))?;
+        check_squares(&def.optional_promotion_squares, width, height, &field("optional_promotion_squares"))?;
+        check_squares(&def.double_jump_squares, width, height, &field("double_jump_squares"))?;
+        check_squares(&def.win_squares, width, height, &field("win_squares"))?;
+        check_squares(&def.drop_squares, width, height, &field("drop_squares"))?;
+        check_squares(&def.allowed_squares[0], width, height, &field("allowed_squares[0]"))?;
+        check_squares(&def.allowed_squares[1], width, height, &field("allowed_squares[1]"))?;
+    }
+    for (i, def) in state.piece_types.iter().enumerate() {
+        for (player, promo_vals) in def.promo_vals.iter().enumerate() {
+            for id in promo_vals {
+                let exists = state.piece_types.iter().any(|other: &PieceDefinition| other.ids[player] == Some(*id));
+                err_assert!(exists, "piece_types[{i}].promo_vals: unknown piece '{id}'");
+            }
+        }
+    }
+
+    // Any other error is detected when creating the position
+    let game_state = GameState { initial_state: state.clone(), initial_fen: None, move_history: vec![] };
+    PositionFactory::default().set_state(game_state, None)
+        .map_err(|e| format!("Invalid variant: {e}"))?;
+    Ok(())
+}
+
+fn check_squares(squares: &[(BCoord, BCoord)], width: BCoord, height: BCoord, field: &str) -> wrap_res!() {
+    for (x, y) in squares {
+        err_assert!(*x < width && *y < height, "{field}: ({x}, {y}) is outside the {width}x{height} board");
+    }
+    Ok(())
+}
+
+fn extension(path: &Path) -> wrap_res!(String) {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    err_assert!(ext == "toml" || ext == "json", "Unknown variant file extension '{ext}' (expected .toml or .json)");
+    Ok(ext)
+}
+
+
+/// Serializes a `[Option<T>; 2]` as `{ white = ..., black = ... }`, omitting the `None` values
+/// (TOML cannot represent `None` inside an array).
+pub(crate) mod per_player {
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct PerPlayer<T> {
+        #[serde(skip_serializing_if = "Option::is_none", default = "none")]
+        white: Option<T>,
+        #[serde(skip_serializing_if = "Option::is_none", default = "none")]
+        black: Option<T>,
+    }
+    fn none<T>() -> Option<T> {
+        None
+    }
+
+    pub fn serialize<S: Serializer, T: Serialize + Clone>(value: &[Option<T>; 2], serializer: S) -> Result<S::Ok, S::Error> {
+        PerPlayer { white: value[0].clone(), black: value[1].clone() }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<[Option<T>; 2], D::Error> {
+        let value = PerPlayer::deserialize(deserializer)?;
+        Ok([value.white, value.black])
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{Position, MoveGen, MakeMoveResultFlag, GameEnd, wrap_res, err_assert};
+use crate::types::{Player, ZobKey};
+use crate::utils::to_index;
+
+use super::global_rules::GameEndRule;
+
+impl Position {
+    /// Checks the rules that end the game after the last move, in order of precedence. This is the only place where
+    /// the rules are evaluated, so that `pub_make_move()`, the search and perft always agree on when the game ends.
+    ///
+    /// Must be called exactly once after each move, since it updates the counters of some rules (the checks received
+    /// and the turns holding squares). If `detect_no_moves` is `false`, checkmate and stalemate are not checked, since
+    /// the search already counts the legal moves (see `no_moves_result()`). If `include_draws` is `false`, only the
+    /// rules with a winner are checked (like in perft). `known_check` is whether the player to move is in check, if the
+    /// caller already knows it (otherwise it's computed only if a rule needs it).
+    ///
+    /// In the middle of a turn with several moves, the game only ends if the player can't complete the turn. The rest
+    /// of the rules are checked when the turn ends (which happens right away if the move wins by captures or by
+    /// moving to a winning square).
+    pub fn game_end(&mut self, detect_no_moves: bool, include_draws: bool, known_check: Option<bool>) -> Option<GameEnd> {
+        if self.get_submove() > 0 {
+            if detect_no_moves && MoveGen::get_legal_moves(self).is_empty() {
+                let in_check = known_check.unwrap_or_else(|| MoveGen::in_check(self));
+                return Some(self.no_moves_result(in_check));
+            }
+            return None;
+        }
+        // Leader captured (atomic chess, or playing without a king), or a piece type is extinct
+        if self.leader_is_captured() {
+            let piece_set = &self.pieces[self.whos_turn as usize];
+            let flag = {
+                if piece_set.has_extinct_piece() { MakeMoveResultFlag::Extinction }
+                else if !piece_set.has_leaders() { MakeMoveResultFlag::AllPiecesCaptured }
+                else { MakeMoveResultFlag::LeaderCaptured }
+            };
+            return Some(self.win(flag));
+        }
+        // Piece moved to winning square (king of the hill, racing kings)
+        if self.piece_is_on_winning_square() {
+            return Some(self.win(MakeMoveResultFlag::PieceInWinSquare));
+        }
+        if let Some(flag) = self.custom_win() {
+            return Some(self.win(flag));
+        }
+        // The player to move is only checked for some rules, avoid computing it twice
+        let mut in_check = known_check;
+        let mut is_in_check = |pos: &mut Position| *in_check.get_or_insert_with(|| MoveGen::in_check(pos));
+        // No legal moves, checkmate or stalemate
+        if detect_no_moves && MoveGen::get_legal_moves(self).is_empty() {
+            let in_check = is_in_check(self);
+            return Some(self.no_moves_result(in_check));
+        }
+        // Checked N times (N=3 in 3-check)
+        if self.global_rules.checks_to_lose != 0 && is_in_check(self) && self.increment_num_checks() {
+            return Some(self.win(MakeMoveResultFlag::CheckLimit));
+        }
+        
+        if !include_draws {
+            return None;
+        }
+        // Neither player can checkmate
+        if self.draw_by_insufficient_material() {
+            return Some(GameEnd::draw(MakeMoveResultFlag::InsufficientMaterial));
+        }
+        // Threefold Repetition
+        if self.draw_by_repetition() {
+            return Some(GameEnd::draw(MakeMoveResultFlag::Repetition));
+        }
+        // N-move rule (checkmate on the last move takes precedence)
+        if self.draw_by_move_rule() {
+            if !detect_no_moves && is_in_check(self) && MoveGen::get_legal_moves(self).is_empty() {
+                return Some(self.win(MakeMoveResultFlag::Checkmate));
+            }
+            return Some(GameEnd::draw(MakeMoveResultFlag::NMoveRule));
+        }
+        let move_limit = self.global_rules.game_end_rules.iter().find_map(|rule| match rule {
+            GameEndRule::MoveLimit(limit) => Some(*limit),
+            _ => None,
+        });
+        if let Some(limit) = move_limit {
+            // The fullmove number starts at 1, and it's incremented after each move of black
+            if self.get_fullmove_number() > limit {
+                return Some(GameEnd::draw(MakeMoveResultFlag::MoveLimit));
+            }
+        }
+        None
+    }
+    
+    /// Returns an error if the custom rules in `GlobalRules::game_end_rules` are invalid for this board
+    pub fn assert_game_end_rules(&self) -> wrap_res!() {
+        let mut num_hold_squares = 0;
+        for rule in &self.global_rules.game_end_rules {
+            match rule {
+                GameEndRule::CapturedMaterial(material) => {
+                    err_assert!(*material > 0, "The captured material to win must be positive");
+                },
+                GameEndRule::HoldSquares { squares, turns } => {
+                    num_hold_squares += 1;
+                    err_assert!(*turns > 0, "The number of turns to hold the squares must be positive");
+                    for &(x, y) in squares.iter().flatten() {
+                        err_assert!(self.dimensions.in_bounds(x, y), "Invalid square to hold: ({x}, {y})");
+                    }
+                },
+                GameEndRule::MoveLimit(limit) => {
+                    err_assert!(*limit > 0, "The move limit must be positive");
+                },
+            }
+        }
+        err_assert!(num_hold_squares <= 1, "There can only be one rule to hold squares");
+        Ok(())
+    }
+    
+    /// Returns the result of the game when the player to move has no legal moves
+    pub fn no_moves_result(&self, in_check: bool) -> GameEnd {
+        if in_check {
+            self.win(MakeMoveResultFlag::Checkmate)
+        } else if self.global_rules.stalemated_player_loses {
+            self.win(MakeMoveResultFlag::Stalemate)
+        } else if self.global_rules.stalemated_player_wins {
+            self.loss(MakeMoveResultFlag::Stalemate)
+        } else {
+            GameEnd::draw(MakeMoveResultFlag::Stalemate)
+        }
+    }
+    
+    /// Returns a win of the player who made the last move (or of the opponent, if the win conditions are inverted)
+    fn win(&self, flag: MakeMoveResultFlag) -> GameEnd {
+        let winner = {
+            if self.global_rules.invert_win_conditions {
+                self.whos_turn
+            } else {
+                1 - self.whos_turn
+            }
+        };
+        GameEnd { flag, winner: Some(winner) }
+    }
+    
+    /// Returns a loss of the player who made the last move (or of the opponent, if the win conditions are inverted)
+    fn loss(&self, flag: MakeMoveResultFlag) -> GameEnd {
+        let winner = {
+            if self.global_rules.invert_win_conditions {
+                1 - self.whos_turn
+            } else {
+                self.whos_turn
+            }
+        };
+        GameEnd { flag, winner: Some(winner) }
+    }
+    
+    /// Checks the custom rules in `GlobalRules::game_end_rules` that can make the player who made the last move win
+    fn custom_win(&mut self) -> Option<MakeMoveResultFlag> {
+        let player = 1 - self.whos_turn;
+        let mut won_by = None;
+        let mut holding_squares = None;
+        for rule in &self.global_rules.game_end_rules {
+            match rule {
+                GameEndRule::CapturedMaterial(material) => {
+                    if self.get_properties().captured_material[player as usize] >= *material {
+                        won_by = won_by.or(Some(MakeMoveResultFlag::CapturedMaterial));
+                    }
+                },
+                GameEndRule::HoldSquares { squares, turns } => {
+                    let holding = squares[player as usize].iter()
+                        .any(|&(x, y)| self.pieces[player as usize].index_has_piece(to_index(x, y)));
+                    holding_squares = Some((holding, *turns));
+                },
+                GameEndRule::MoveLimit(_) => {},
+            }
+        }
+        if let Some((holding, turns)) = holding_squares {
+            if self.update_turns_holding_squares(player, holding) >= turns {
+                won_by = won_by.or(Some(MakeMoveResultFlag::SquaresHeld));
+            }
+        }
+        won_by
+    }
+    
+    /// Updates the number of consecutive turns that the player has ended with a piece on the squares
+    /// of `GameEndRule::HoldSquares`, and returns the new value
+    fn update_turns_holding_squares(&mut self, player: Player, holding: bool) -> u8 {
+        let i = self.properties_stack.len() - 1;
+        // Count from the position before the move, so that calling this again doesn't count the turn twice
+        let old_turns = self.properties_stack[i-1].turns_holding_squares[player as usize];
+        let new_turns = if holding { old_turns.saturating_add(1) } else { 0 };
+        
+        let current_turns = self.properties_stack[i].turns_holding_squares[player as usize];
+        self.properties_stack[i].turns_holding_squares[player as usize] = new_turns;
+        // Update the zobrist key (use bits 12-19 for white, 20-27 for black)
+        let shift = 12 + 8 * player as ZobKey;
+        self.properties_stack[i].zobrist_key ^= ((current_turns ^ new_turns) as ZobKey) << shift;
+        new_turns
+    }
+}
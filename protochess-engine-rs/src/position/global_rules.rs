@@ -1,14 +1,20 @@
-use crate::types::GameMode;
+use crate::types::{GameMode, BCoord, Centipawns, Player};
 
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default, deny_unknown_fields))]
 pub struct GlobalRules {
     // If true, a player must capture if they can
     pub capturing_is_forced: bool,
     // If true, a player cannot put the opponent in check
     pub check_is_forbidden: bool,
+    // If true, there is no check: moves can leave the leader attacked (also when castling), and a player
+    // loses when their leader is captured instead of checkmated (like in Dark chess)
+    pub ignore_checks: bool,
     // If true, a player who is stalemated loses. If false, the game is a draw
     pub stalemated_player_loses: bool,
+    // If true, a player who is stalemated wins (like in Duck chess). Cannot be combined with `stalemated_player_loses`
+    pub stalemated_player_wins: bool,
     // If true, what would be a win for white is a win for black, and vice versa
     pub invert_win_conditions: bool,
     // Number of times that the same position is reached to draw by repetition
@@ -17,6 +23,111 @@ pub struct GlobalRules {
     // Number of times that a player is put in check to lose instantly
     // 0 means checks are not counted
     pub checks_to_lose: u8,
+    // Number of moves by each player without captures or moves of a piece with `resets_move_clock`
+    // to draw (50 in standard chess). 0 means no N-move rule
+    pub n_move_rule: u16,
+    // If true, captured pieces go to the hand of the capturing player, who can later drop them
+    // on the board instead of moving (like in crazyhouse)
+    pub captures_to_hand: bool,
+    // If true, promoted pieces go to the hand as the piece they promoted from (usually a pawn)
+    // when they are captured. Only used if `captures_to_hand` is true
+    pub demote_promoted_pieces: bool,
+    // If true, castling pieces can land on any square between their position and the castling partner (both
+    // included), and the partner lands next to them on the other side (like in free castling)
+    pub free_castling: bool,
+    // If true, after each move the player must also move a neutral piece (the one with the lowest index) to another
+    // empty square, or place one on the board if there are neutral pieces in the hand (like the duck in Duck chess)
+    pub relocate_neutral_pieces: bool,
+    // When a player with several leader pieces loses, and which leaders must be kept out of check
+    pub royalty_policy: RoyaltyPolicy,
+    // Number of moves that each player makes in a turn
+    pub moves_per_turn: MovesPerTurn,
+    // How checks work in the middle of a turn with several moves
+    pub mid_turn_check: MidTurnCheck,
+    // If true, in turns with several moves, a double step made in the last move of the opponent's turn can be captured
+    // en passant in any move of the turn (while the pawn stays on its square). If false, only in the first move
+    pub en_passant_any_move_of_turn: bool,
+    // Additional conditions that end the game, checked after every move (after the ones above)
+    pub game_end_rules: Vec<GameEndRule>,
+}
+
+/// Rules for the leader pieces of a player, when there can be more than one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+#[must_use]
+pub enum RoyaltyPolicy {
+    // The player loses when all the leaders are captured, and can only be in check if there is a single leader left
+    AllCaptured,
+    // The player loses when any leader is captured, and is in check when all the leaders are attacked
+    // at the same time (so that any of them can be left attacked while another one is safe)
+    AnyCapturedOrAllAttacked,
+    // The player loses when any leader is captured, and is in check when any of them is attacked,
+    // so every leader must be kept safe
+    EverySafe,
+}
+
+/// Number of moves that each player makes in a turn (several consecutive moves of the same player)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+#[must_use]
+pub enum MovesPerTurn {
+    // Each player makes this many moves per turn (for white and for black). If `single_first_move` is true,
+    // the first turn of white only has one move (like in Marseillais chess)
+    Fixed { moves: [u8; 2], single_first_move: bool },
+    // Each turn has one more move than the previous one: white makes 1 move, black 2, white 3... (like in
+    // Progressive chess)
+    Progressive,
+}
+
+impl MovesPerTurn {
+    /// Returns the number of moves in the turn of `player` that starts at the given fullmove number
+    pub fn moves_in_turn(&self, player: Player, fullmove_number: u16) -> u32 {
+        match self {
+            MovesPerTurn::Fixed { moves, single_first_move } => {
+                if *single_first_move && player == 0 && fullmove_number <= 1 { 1 } else { moves[player as usize] as u32 }
+            },
+            MovesPerTurn::Progressive => 2 * (fullmove_number.max(1) as u32 - 1) + player as u32 + 1,
+        }
+    }
+}
+
+/// How checks work in the middle of a turn with several moves. At the end of the turn, the player must
+/// always leave their leader out of check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+#[must_use]
+pub enum MidTurnCheck {
+    // Every move must leave the leader out of check, and giving check ends the turn (like in Marseillais chess)
+    EndsTurn,
+    // Every move must leave the leader out of check, and only the last move of the turn can give check
+    // (like in Progressive chess)
+    OnlyLastMove,
+    // Checks only matter at the end of the turn: a move can leave the leader attacked if the rest of the turn
+    // gets it out of check, and the opponent can be attacked (like in Monster chess). Only allowed with at most
+    // 2 moves per turn, since checking if the turn can be completed searches all the remaining moves
+    EndOfTurn,
+}
+
+/// A custom condition that ends the game, in addition to the built-in ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+#[must_use]
+pub enum GameEndRule {
+    // A player wins after capturing enemy pieces (including explosions) worth at least this many centipawns,
+    // according to the material value of the piece types
+    CapturedMaterial(Centipawns),
+    // A player wins after ending this many of their turns in a row with a piece on one of the squares
+    // (for white and for black). With 1 turn, moving any piece to one of the squares wins
+    HoldSquares { squares: [Vec<(BCoord, BCoord)>; 2], turns: u8 },
+    // The game is a draw after this many moves by each player
+    MoveLimit(u16),
+}
+
+impl GameEndRule {
+    /// Returns `true` if this rule can make a player win (instead of ending the game in a draw)
+    pub fn can_win(&self) -> bool {
+        !matches!(self, GameEndRule::MoveLimit(_))
+    }
 }
 
 
@@ -27,35 +138,83 @@ impl GlobalRules {
             GameMode::FiveCheck => 5,
             _ => 0,
         };
+        let captures_to_hand = mode == GameMode::Crazyhouse;
+        let moves_per_turn = match mode {
+            GameMode::Marseillais => MovesPerTurn::Fixed { moves: [2, 2], single_first_move: true },
+            GameMode::Progressive => MovesPerTurn::Progressive,
+            GameMode::Monster => MovesPerTurn::Fixed { moves: [2, 1], single_first_move: false },
+            _ => MovesPerTurn::Fixed { moves: [1, 1], single_first_move: false },
+        };
+        let mid_turn_check = match mode {
+            GameMode::Progressive => MidTurnCheck::OnlyLastMove,
+            GameMode::Monster => MidTurnCheck::EndOfTurn,
+            _ => MidTurnCheck::EndsTurn,
+        };
         match mode {
             GameMode::Antichess => {
                 GlobalRules {
                     capturing_is_forced: true,
                     check_is_forbidden: false,
+                    ignore_checks: false,
                     stalemated_player_loses: true,
+                    stalemated_player_wins: false,
                     invert_win_conditions: true,
                     repetitions_draw: 3,
                     checks_to_lose: 0,
+                    n_move_rule: 50,
+                    captures_to_hand: false,
+                    demote_promoted_pieces: false,
+                    free_castling: false,
+                    relocate_neutral_pieces: false,
+                    royalty_policy: RoyaltyPolicy::AllCaptured,
+                    moves_per_turn: MovesPerTurn::Fixed { moves: [1, 1], single_first_move: false },
+                    mid_turn_check: MidTurnCheck::EndsTurn,
+                    en_passant_any_move_of_turn: false,
+                    game_end_rules: Vec::new(),
                 }
             },
             GameMode::RacingKings => {
                 GlobalRules {
                     capturing_is_forced: false,
                     check_is_forbidden: true,
+                    ignore_checks: false,
                     stalemated_player_loses: false,
+                    stalemated_player_wins: false,
                     invert_win_conditions: false,
                     repetitions_draw: 3,
                     checks_to_lose: 0,
+                    n_move_rule: 50,
+                    captures_to_hand: false,
+                    demote_promoted_pieces: false,
+                    free_castling: false,
+                    relocate_neutral_pieces: false,
+                    royalty_policy: RoyaltyPolicy::AllCaptured,
+                    moves_per_turn: MovesPerTurn::Fixed { moves: [1, 1], single_first_move: false },
+                    mid_turn_check: MidTurnCheck::EndsTurn,
+                    en_passant_any_move_of_turn: false,
+                    game_end_rules: Vec::new(),
                 }
             },
             _ => {
                 GlobalRules {
                     capturing_is_forced: false,
                     check_is_forbidden: false,
+                    ignore_checks: mode == GameMode::Dark,
                     stalemated_player_loses: false,
+                    stalemated_player_wins: mode == GameMode::Duck,
                     invert_win_conditions: false,
                     repetitions_draw: 3,
                     checks_to_lose,
+                    n_move_rule: 50,
+                    captures_to_hand,
+                    demote_promoted_pieces: captures_to_hand,
+                    free_castling: false,
+                    relocate_neutral_pieces: mode == GameMode::Duck,
+                    royalty_policy: RoyaltyPolicy::AllCaptured,
+                    moves_per_turn,
+                    mid_turn_check,
+                    en_passant_any_move_of_turn: false,
+                    game_end_rules: Vec::new(),
                 }
             },
         }
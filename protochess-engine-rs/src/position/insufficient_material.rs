@@ -0,0 +1,185 @@
+use crate::Position;
+use crate::position::global_rules::GameEndRule;
+use crate::piece::Piece;
+use crate::types::{Bitboard, BIndex, Player};
+use crate::utils::from_index;
+
+/// Maximum number of pieces on the board (not counting the leaders) for the position to be checked for insufficient
+/// material. The check is expensive and runs after every capture, and positions with more pieces (like several
+/// bishops of the same colour) are almost never a draw, so they are not detected.
+const MAX_PIECES_FOR_INSUFFICIENT_MATERIAL: u32 = 4;
+
+impl Position {
+    /// Returns `true` if neither player can win the game anymore, for example with only the
+    /// kings, king and knight against king, or bishops of the same colour
+    #[inline]
+    pub fn draw_by_insufficient_material(&self) -> bool {
+        self.get_properties().insufficient_material
+    }
+
+    /// Checks if neither player can checkmate the opponent, based on the movement of the pieces
+    /// on the board. Must be called whenever the material changes (captures and promotions).
+    ///
+    /// Only applies to games where the only way to win is checkmating a single leader, where no piece
+    /// can promote, and with at most `MAX_PIECES_FOR_INSUFFICIENT_MATERIAL` pieces besides the leaders.
+    pub(crate) fn compute_insufficient_material(&self) -> bool {
+        let rules = &self.global_rules;
+        if rules.invert_win_conditions || rules.checks_to_lose != 0 || rules.stalemated_player_loses || rules.stalemated_player_wins ||
+            rules.game_end_rules.iter().any(GameEndRule::can_win)
+        {
+            return false;
+        }
+        let num_pieces: u32 = self.pieces.iter()
+            .flat_map(|piece_set| piece_set.iter())
+            .filter(|p| !p.is_leader())
+            .map(|p| p.get_num_pieces())
+            .sum();
+        if num_pieces > MAX_PIECES_FOR_INSUFFICIENT_MATERIAL {
+            return false;
+        }
+        for piece_set in &self.pieces {
+            // The pieces in the hand can be dropped, and any captured piece would go to the hand
+            if piece_set.has_pieces_in_hand() || (rules.captures_to_hand && piece_set.iter().any(|p| !p.is_leader() && p.get_num_pieces() > 0)) {
+                return false;
+            }
+            match piece_set.get_leader() {
+                Some(leader) if leader.get_num_pieces() == 1 => {},
+                _ => return false,
+            }
+            if piece_set.iter().any(|p| p.get_num_pieces() > 0 && p.get_movement().prevents_insufficient_material()) {
+                return false;
+            }
+        }
+        !self.can_checkmate(0) && !self.can_checkmate(1)
+    }
+
+    /// Returns `false` if the player can never checkmate the enemy leader, even with the help of the enemy
+    fn can_checkmate(&self, player: Player) -> bool {
+        let enemy = 1 - player as usize;
+        let enemy_leader = self.pieces[enemy].get_leader().unwrap();
+        let leader_index = enemy_leader.get_bitboard().lowest_one().unwrap();
+        let (leader_squares, _) = self.movement_range(enemy_leader, leader_index);
+
+        // Pieces that can attack at least one of the squares that the enemy leader can reach
+        let mut useful_pieces = Vec::new();
+        for p in self.pieces[player as usize].iter() {
+            if p.is_leader() {
+                continue;
+            }
+            for index in p.get_indexes() {
+                let (_, attacked) = self.movement_range(p, index);
+                if (&attacked & &leader_squares).is_zero() {
+                    continue;
+                }
+                useful_pieces.push((p, index));
+                if useful_pieces.len() > 1 && !same_color_bound(&useful_pieces) {
+                    return true;
+                }
+            }
+        }
+
+        let enemy_has_only_leader = self.pieces[enemy].iter().all(|p| p.is_leader() || p.get_num_pieces() == 0);
+        match useful_pieces[..] {
+            [] => false,
+            [(p, _)] if !p.get_movement().is_color_bound() => {
+                !enemy_has_only_leader || self.can_mate_alone(player, p)
+            },
+            _ => {
+                // The pieces only attack squares of one colour, so the enemy leader can always escape
+                // to a square of the other colour, unless it's blocked by an enemy piece
+                let color = square_color(useful_pieces[0].1);
+                self.pieces[enemy].iter()
+                    .filter(|p| !p.is_leader())
+                    .any(|p| {
+                        let color_bound = p.get_movement().is_color_bound();
+                        p.get_indexes().into_iter().any(|index| !color_bound || square_color(index) != color)
+                    })
+            },
+        }
+    }
+
+    /// Returns `true` if the leader of `player` and the piece can checkmate a lone enemy leader.
+    /// Only the squares where the enemy leader has the fewest moves (the corners, in standard
+    /// chess) are considered.
+    fn can_mate_alone(&self, player: Player, piece: &Piece) -> bool {
+        let my_leader = self.pieces[player as usize].get_leader().unwrap();
+        let enemy_leader = self.pieces[1 - player as usize].get_leader().unwrap();
+        let bounds = &self.dimensions.bounds;
+        let walls = !bounds;
+
+        let mut squares = Vec::new();
+        let mut bb = bounds.clone();
+        while let Some(index) = bb.lowest_one() {
+            bb.clear_bit(index);
+            squares.push(index);
+        }
+        let num_moves = |index: BIndex| {
+            let moves = enemy_leader.get_translation_squares(index, &walls, bounds) | enemy_leader.get_attacked_squares(index, &walls, bounds);
+            moves.count_ones()
+        };
+        let min_moves = squares.iter().map(|index| num_moves(*index)).min().unwrap_or(0);
+
+        for &king in squares.iter().filter(|index| num_moves(**index) == min_moves) {
+            // Squares from which the piece can give check (ignoring the position of my leader)
+            let checking_squares = squares.iter().filter(|square| {
+                **square != king && piece.get_attacked_squares(**square, &walls, bounds).get_bit(king)
+            });
+            for &square in checking_squares {
+                for &leader in &squares {
+                    if leader == king || leader == square {
+                        continue;
+                    }
+                    // The enemy leader is not on the occupied bitboard, so that it cannot hide behind itself
+                    let mut occ = walls.clone();
+                    occ.set_bit(square);
+                    occ.set_bit(leader);
+                    let piece_attacks = piece.get_attacked_squares(square, &occ, bounds);
+                    let leader_attacks = my_leader.get_attacked_squares(leader, &occ, bounds);
+                    let enemy_attacks = enemy_leader.get_attacked_squares(king, &occ, bounds);
+                    // The leaders cannot attack each other
+                    if !piece_attacks.get_bit(king) || leader_attacks.get_bit(king) || enemy_attacks.get_bit(leader) {
+                        continue;
+                    }
+                    // The enemy leader cannot move to a safe square or capture the piece
+                    let escapes = enemy_leader.get_translation_squares(king, &occ, bounds) & !(&piece_attacks | &leader_attacks);
+                    let can_capture = enemy_attacks.get_bit(square) && !leader_attacks.get_bit(square);
+                    if escapes.is_zero() && !can_capture {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns the squares that a piece can reach from `index` on an empty board (except for
+    /// the walls), and the squares that it attacks from any of them
+    fn movement_range(&self, piece: &Piece, index: BIndex) -> (Bitboard, Bitboard) {
+        let bounds = &self.dimensions.bounds;
+        let walls = !bounds;
+        let mut reachable = Bitboard::zero();
+        reachable.set_bit(index);
+        let mut attacked = Bitboard::zero();
+        let mut pending = reachable.clone();
+        while let Some(from) = pending.lowest_one() {
+            pending.clear_bit(from);
+            let attacks = piece.get_attacked_squares(from, &walls, bounds);
+            let new_squares = (&attacks | piece.get_translation_squares(from, &walls, bounds)) & !&reachable;
+            attacked |= attacks;
+            reachable |= &new_squares;
+            pending |= new_squares;
+        }
+        (reachable, attacked)
+    }
+}
+
+fn square_color(index: BIndex) -> u8 {
+    let (x, y) = from_index(index);
+    (x + y) % 2
+}
+
+/// Returns `true` if all the pieces are colour-bound, and on squares of the same colour
+fn same_color_bound(pieces: &[(&Piece, BIndex)]) -> bool {
+    let color = square_color(pieces[0].1);
+    pieces.iter().all(|(p, index)| p.get_movement().is_color_bound() && square_color(*index) == color)
+}
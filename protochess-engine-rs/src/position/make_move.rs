@@ -1,8 +1,11 @@
 use crate::{Position, MoveInfo, MoveGen, MakeMoveResult};
-use crate::types::{Move, MoveType};
+use crate::piece::{Piece, PieceId, castling_partner_destination, indirect_capture_victims};
+use crate::types::{Move, MoveType, BIndex, Player, Bitboard, ZobKey};
 use crate::utils::notation::{get_algebraic_notation, add_suffix};
+use crate::utils::squares_between;
 
-use super::position_properties::PositionProperties;
+use super::global_rules::MidTurnCheck;
+use super::position_properties::{PositionProperties, MAX_CAPTURES_TO_HAND};
 
 impl Position {
     
@@ -14,61 +17,31 @@ impl Position {
                 continue;
             }
             // Found the move, try to play it
+            let player = self.whos_turn;
             let exploded = mv.get_potential_explosion(self);
             let mut move_notation = get_algebraic_notation(self, *mv, &moves);
             if !MoveGen::make_move_if_legal(*mv, self) {
                 continue;
             }
             
-            let winner = {
-                if self.global_rules.invert_win_conditions {
-                    self.whos_turn
-                } else {
-                    1 - self.whos_turn
-                }
-            };
-            
-            // Leader captured (atomic chess, or playing without a king)
-            if self.leader_is_captured() {
-                move_notation = add_suffix(move_notation, "#");
-                if self.pieces[self.whos_turn as usize].get_leader().is_none() {
-                    return MakeMoveResult::all_pieces_captured(winner, exploded, move_notation);
-                }
-                return MakeMoveResult::leader_captured(winner, exploded, move_notation);
-            }
-            // Piece moved to winning square (king of the hill, racing kings)
-            if self.piece_is_on_winning_square() {
-                move_notation = add_suffix(move_notation, "#");
-                return MakeMoveResult::piece_in_win_square(winner, exploded, move_notation);
-            }
-            let in_check = MoveGen::in_check(self);
-            // No legal moves, check if it's checkmate or stalemate
-            if MoveGen::get_legal_moves(self).is_empty() {
-                if in_check {
+            // In the middle of a turn, the player to move is the one who made the move
+            let turn_continues = self.whos_turn == player;
+            let in_check = if turn_continues { MoveGen::enemy_in_check(self) } else { MoveGen::in_check(self) };
+            // The check on the enemy doesn't tell if the player to move can complete the turn
+            let known_check = if turn_continues { None } else { Some(in_check) };
+            if let Some(game_end) = self.game_end(true, true, known_check) {
+                if game_end.winner.is_some() {
                     move_notation = add_suffix(move_notation, "#");
-                    return MakeMoveResult::checkmate(winner, exploded, move_notation);
+                } else if in_check {
+                    move_notation = add_suffix(move_notation, "+");
                 }
-                if self.global_rules.stalemated_player_loses {
-                    move_notation = add_suffix(move_notation, "#");
-                    return MakeMoveResult::stalemate(Some(winner), exploded, move_notation);
-                }
-                // Don't add "#" since it's a draw
-                return MakeMoveResult::stalemate(None, exploded, move_notation);
-            }
-            // Checked N times (N=3 in 3-check)
-            if in_check && self.increment_num_checks() {
-                move_notation = add_suffix(move_notation, "#");
-                return MakeMoveResult::check_limit(winner, exploded, move_notation);
-            }
-            // Threefold Repetition
-            if self.draw_by_repetition() {
-                return MakeMoveResult::repetition(move_notation);
+                return MakeMoveResult::game_over(game_end, exploded, move_notation);
             }
             
             if in_check {
                 move_notation = add_suffix(move_notation, "+");
             }
-            return MakeMoveResult::ok(exploded, move_notation);
+            return MakeMoveResult::ok(exploded, move_notation, turn_continues);
         }
         MakeMoveResult::illegal_move()
     }
@@ -79,40 +52,66 @@ impl Position {
         let my_player_num = self.whos_turn;
         let mut new_props = *self.get_properties(); // Copy the current properties
         new_props.num_captures = 0;
+        new_props.captured_to_hand = [None; MAX_CAPTURES_TO_HAND];
         let move_type = mv.get_move_type();
         
-        // Update the player
-        self.whos_turn = 1 - self.whos_turn;
-        // Update the player zobrist key
-        // For simplicity, use the lowest bit to represent the player
-        new_props.zobrist_key ^= 1;
+        // Update the halfmove clock (it's reset below if needed). The player and the fullmove number are
+        // updated at the end, in update_turn()
+        new_props.halfmove_clock = new_props.halfmove_clock.saturating_add(1);
         
         // In the special case of the null move, don't do anything except update whos_turn
         // And update props
         if move_type == MoveType::Null {
-            // Update props
-            // Since we're passing, there cannot be an ep square
-            new_props.clear_ep_square();
             new_props.move_played = mv;
             self.properties_stack.push(new_props);
+            self.update_turn(mv);
+            return;
+        }
+        
+        // Relocate the neutral piece (the rest of the move doesn't depend on it, since it lands on a square that
+        // is empty after the move). It comes from the hand if there are any there, or else it's the one on the
+        // lowest square
+        if let Some(neutral_to) = mv.get_neutral_to() {
+            let neutral_from = if self.get_neutral_in_hand() > 0 { None } else { self.get_neutral_pieces().lowest_one() };
+            new_props.neutral_from = neutral_from;
+            new_props.zobrist_key ^= self.relocate_neutral_piece(neutral_from, neutral_to);
+        }
+        
+        // Drops only place a piece from the hand on the board
+        if let Some(piece_id) = mv.get_drop_piece() {
+            let to = mv.get_to();
+            new_props.zobrist_key ^= self.pieces[my_player_num as usize].remove_from_hand(piece_id);
+            self.pieces[my_player_num as usize].add_piece(piece_id, to, false);
+            let dropped_piece = self.player_piece_at(my_player_num, to).unwrap();
+            new_props.zobrist_key ^= dropped_piece.get_zobrist(to);
+            if dropped_piece.resets_move_clock() {
+                new_props.halfmove_clock = 0;
+            }
+            new_props.move_played = mv;
+            self.properties_stack.push(new_props);
+            self.update_occupied();
+            self.update_turn(mv);
             return;
         }
 
         // If this move is a capture, remove the captured piece before moving
         if move_type == MoveType::Capture || move_type == MoveType::PromotionCapture {
-            let capt_index = mv.get_target();
-            let captured_piece = self.player_piece_at(self.whos_turn, capt_index).unwrap();
-            let piece_id = captured_piece.get_piece_id();
-            let capt_player = captured_piece.get_player();
-            let castling_zob = captured_piece.get_castle_zobrist(capt_index);
-            new_props.zobrist_key ^= captured_piece.get_zobrist(capt_index);
-    
-            let could_castle = self.pieces[capt_player as usize].remove_piece(capt_index);
-            if could_castle {
-                new_props.zobrist_key ^= castling_zob;
+            if mv.is_indirect_capture() {
+                let mut victims = self.indirect_capture_victims(mv, my_player_num);
+                while let Some(victim) = victims.lowest_one() {
+                    victims.clear_bit(victim);
+                    self.capture_piece(victim, my_player_num, &mut new_props);
+                }
+            } else {
+                // Multi-leg moves first capture the piece of the first leg
+                if let Some(intermediate) = mv.get_intermediate() {
+                    self.capture_piece(intermediate, my_player_num, &mut new_props);
+                }
+                if mv.get_intermediate() != Some(mv.get_target()) {
+                    self.capture_piece(mv.get_target(), my_player_num, &mut new_props);
+                }
             }
-            self.captures_stack.push((piece_id, capt_player, could_castle, capt_index));
-            new_props.num_captures += 1;
+            new_props.halfmove_clock = 0;
     
             // Check if the capturing piece explodes
             self.explode_piece(mv, my_player_num, &mut new_props);
@@ -120,25 +119,29 @@ impl Position {
         
         // If this move is a castle, first remove the rook (in chess960 the king could move to the rook's square
         // and the rook would be overwritten)
-        let mut rook_id = None;
-        if move_type == MoveType::KingsideCastle || move_type == MoveType::QueensideCastle {
+        let from = mv.get_from();
+        let to = mv.get_to();
+        let mut rook_id_and_to = None;
+        if mv.is_castling() {
             let rook_from = mv.get_target();
+            let rook_to = self.castling_partner_destination(mv, from);
             let rook_piece = self.player_piece_at(my_player_num, rook_from).unwrap();
             new_props.zobrist_key ^= rook_piece.get_zobrist(rook_from);
             new_props.zobrist_key ^= rook_piece.get_castle_zobrist(rook_from);
-            rook_id = Some(rook_piece.get_piece_id());
+            rook_id_and_to = Some((rook_piece.get_piece_id(), rook_to));
             self.pieces[my_player_num as usize].remove_piece(rook_from);
         }
 
-        let from = mv.get_from();
-        let to = mv.get_to();
-        // Move the piece (only if it hasn't exploded)
+        // Move the piece (only if it hasn't exploded). In rifle captures, from and to are the same square
         if self.pieces[my_player_num as usize].index_has_piece(from) {
             // Move piece to location
             new_props.moved_piece_castle = self.pieces[my_player_num as usize].move_piece(from, to, false);
             let moved_piece = self.player_piece_at(my_player_num, to).unwrap();
             new_props.zobrist_key ^= moved_piece.get_zobrist(from);
             new_props.zobrist_key ^= moved_piece.get_zobrist(to);
+            if moved_piece.resets_move_clock() {
+                new_props.halfmove_clock = 0;
+            }
             if new_props.moved_piece_castle {
                 // A castling piece was moved, so it cannot castle anymore
                 // Remove the castling ability from the zobrist key
@@ -156,26 +159,34 @@ impl Position {
                 self.pieces[my_player_num as usize].add_piece(promo, to, false);
                 let piece = self.player_piece_at(my_player_num, to).unwrap();
                 new_props.zobrist_key ^= piece.get_zobrist(to);
+                if self.global_rules.demote_promoted_pieces {
+                    self.promoted.set_bit(to);
+                }
+            } else if self.promoted.get_bit(from) {
+                self.promoted.clear_bit(from);
+                self.promoted.set_bit(to);
             }
         }
         
         // If this move is a castle, add the rook back
-        if move_type == MoveType::KingsideCastle || move_type == MoveType::QueensideCastle {
-            let rook_to = {
-                if move_type == MoveType::KingsideCastle { to - 1 }
-                else { to + 1 }
-            };
-            self.pieces[my_player_num as usize].add_piece(rook_id.unwrap(), rook_to, false);
+        if let Some((rook_id, rook_to)) = rook_id_and_to {
+            self.pieces[my_player_num as usize].add_piece(rook_id, rook_to, false);
             let rook_piece = self.player_piece_at(my_player_num, rook_to).unwrap();
             new_props.zobrist_key ^= rook_piece.get_zobrist(rook_to);
         }
 
         // Pawn en-passant
-        // Check for a pawn double push to set ep square
+        // Check for a pawn double push to set the ep squares: the target of the move,
+        // and every square skipped in a straight line (like in a triple step).
+        // After other moves, the ep squares are cleared in update_turn()
         if move_type == MoveType::DoubleJump {
-            new_props.set_ep_square(mv.get_target(), mv.get_to());
-        } else {
-            new_props.clear_ep_square();
+            let ep_squares = squares_between(mv.get_from(), mv.get_to()).chain(std::iter::once(mv.get_target()));
+            new_props.set_ep_squares(ep_squares, mv.get_to());
+        }
+        
+        // The material only changes with captures and promotions
+        if new_props.num_captures > 0 || mv.get_promotion_piece().is_some() {
+            new_props.insufficient_material = self.compute_insufficient_material();
         }
         
         // Update props
@@ -184,6 +195,88 @@ impl Position {
         
         // Update occupied bbs for future calculations
         self.update_occupied();
+        self.update_turn(mv);
+    }
+    
+    /// Passes the turn to the opponent after making a move, unless the player still has moves left in the current
+    /// turn (see `GlobalRules::moves_per_turn`). Also clears the ep squares if they can't be used anymore.
+    /// Must be called at the end of `make_move()`, after pushing the new properties and updating the occupied bitboards
+    fn update_turn(&mut self, mv: Move) {
+        let player = self.whos_turn;
+        let turn_continues = mv.get_move_type() != MoveType::Null && self.turn_continues();
+        let keep_ep_squares = mv.get_move_type() == MoveType::DoubleJump || (turn_continues && self.keeps_ep_squares());
+        
+        let props = self.properties_stack.last_mut().unwrap();
+        if !keep_ep_squares {
+            props.clear_ep_squares();
+        }
+        // Update the zobrist key of the sub-move (use bits 32-39)
+        props.zobrist_key ^= (props.submove as ZobKey) << 32;
+        if turn_continues {
+            props.submove += 1;
+        } else {
+            props.submove = 0;
+            // Update the player and its zobrist key
+            // For simplicity, use the lowest bit to represent the player
+            self.whos_turn = 1 - player;
+            props.zobrist_key ^= 1;
+            if player == 1 {
+                props.fullmove_number = props.fullmove_number.saturating_add(1);
+            }
+        }
+        props.zobrist_key ^= (props.submove as ZobKey) << 32;
+    }
+    
+    /// Returns `true` if the player who just moved has moves left in the current turn. The turn ends early if the
+    /// move wins the game by captures or by moving to a winning square, or if it gives check and checks end the turn
+    fn turn_continues(&mut self) -> bool {
+        let props = self.get_properties();
+        let moves_in_turn = self.global_rules.moves_per_turn.moves_in_turn(self.whos_turn, props.fullmove_number);
+        if props.submove as u32 + 1 >= moves_in_turn {
+            return false;
+        }
+        if self.enemy_leader_is_captured() || self.piece_is_on_winning_square() {
+            return false;
+        }
+        self.global_rules.mid_turn_check != MidTurnCheck::EndsTurn || !MoveGen::enemy_in_check(self)
+    }
+    
+    /// Returns `true` if the ep squares set by the opponent can still be used in the rest of the turn, because
+    /// the victim is still on its square and the ep squares are empty (see `GlobalRules::en_passant_any_move_of_turn`)
+    fn keeps_ep_squares(&self) -> bool {
+        self.global_rules.en_passant_any_move_of_turn && self.en_passant_available() &&
+            self.get_ep_squares().iter().all(|sq| !self.occ_or_out_bounds.get_bit(*sq))
+    }
+
+    /// Removes the enemy piece at `capt_index`, captured by `my_player_num`
+    #[inline]
+    fn capture_piece(&mut self, capt_index: BIndex, my_player_num: Player, new_props: &mut PositionProperties) {
+        let captured_piece = self.player_piece_at(1 - my_player_num, capt_index).unwrap();
+        let piece_id = captured_piece.get_piece_id();
+        let capt_player = captured_piece.get_player();
+        let castling_zob = captured_piece.get_castle_zobrist(capt_index);
+        let was_promoted = self.promoted.get_bit(capt_index);
+        let hand_id = self.captured_to_hand_id(captured_piece, was_promoted);
+        new_props.zobrist_key ^= captured_piece.get_zobrist(capt_index);
+        new_props.captured_material[my_player_num as usize] += captured_piece.get_material_score();
+        if captured_piece.is_leader() {
+            new_props.leaders_captured[capt_player as usize] += 1;
+        }
+
+        let could_castle = self.pieces[capt_player as usize].remove_piece(capt_index);
+        if could_castle {
+            new_props.zobrist_key ^= castling_zob;
+        }
+        self.promoted.clear_bit(capt_index);
+        self.captures_stack.push((piece_id, capt_player, could_castle, capt_index, was_promoted));
+        new_props.num_captures += 1;
+        // The capturing player gets the captured piece in the hand
+        if let Some(hand_id) = hand_id {
+            // Explosions don't go to the hand, so there is always a free slot
+            let slot = new_props.captured_to_hand.iter().position(Option::is_none).unwrap();
+            new_props.captured_to_hand[slot] = Some(hand_id);
+            new_props.zobrist_key ^= self.pieces[my_player_num as usize].add_to_hand(hand_id);
+        }
     }
 
     #[inline]
@@ -199,12 +292,17 @@ impl Position {
         new_props.zobrist_key ^= moved_piece.get_zobrist(from);
         let moved_piece_castle_zob = moved_piece.get_castle_zobrist(from);
         let moved_piece_id = moved_piece.get_piece_id();
+        if moved_piece.is_leader() {
+            new_props.leaders_captured[my_player_num as usize] += 1;
+        }
         // Remove the capturing piece
         let capturing_could_castle = self.pieces[my_player_num as usize].remove_piece(from);
         if capturing_could_castle {
             new_props.zobrist_key ^= moved_piece_castle_zob;
         }
-        self.captures_stack.push((moved_piece_id, my_player_num, capturing_could_castle, from));
+        let capturing_was_promoted = self.promoted.get_bit(from);
+        self.promoted.clear_bit(from);
+        self.captures_stack.push((moved_piece_id, my_player_num, capturing_could_castle, from, capturing_was_promoted));
         new_props.num_captures += 1;
         // Remove all pieces in the explosion radius
         while let Some(nindex) = explosion.lowest_one() {
@@ -217,11 +315,19 @@ impl Position {
                 let exploded_id = exploded_piece.get_piece_id();
                 let exploded_player = exploded_piece.get_player();
                 let exploded_castle_zob = exploded_piece.get_castle_zobrist(nindex);
+                if exploded_piece.is_leader() {
+                    new_props.leaders_captured[exploded_player as usize] += 1;
+                }
+                if exploded_player != my_player_num {
+                    new_props.captured_material[my_player_num as usize] += exploded_piece.get_material_score();
+                }
                 let could_castle = self.pieces[exploded_player as usize].remove_piece(nindex);
                 if could_castle {
                     new_props.zobrist_key ^= exploded_castle_zob;
                 }
-                self.captures_stack.push((exploded_id, exploded_player, could_castle, nindex));
+                let was_promoted = self.promoted.get_bit(nindex);
+                self.promoted.clear_bit(nindex);
+                self.captures_stack.push((exploded_id, exploded_player, could_castle, nindex, was_promoted));
                 new_props.num_captures += 1;
             }
         }
@@ -229,6 +335,106 @@ impl Position {
 
 
 
+    /// Returns the id of the piece that the capturing player gets in the hand when `captured` is
+    /// captured, or `None` if captures don't go to the hand or the piece cannot be dropped.
+    /// `was_promoted` is `true` if the captured piece was the result of a promotion.
+    fn captured_to_hand_id(&self, captured: &Piece, was_promoted: bool) -> Option<PieceId> {
+        if !self.global_rules.captures_to_hand || captured.is_leader() {
+            return None;
+        }
+        let victim = captured.get_player();
+        let capturer = 1 - victim;
+        let mut definition = captured.get_movement();
+        let mut victim_id = captured.get_piece_id();
+        if was_promoted && self.global_rules.demote_promoted_pieces {
+            // Find the piece type that promoted to the captured piece
+            let base_piece = self.pieces[victim as usize].iter()
+                .find(|p| p.get_movement().promo_vals[victim as usize].contains(&victim_id));
+            if let Some(base_piece) = base_piece {
+                definition = base_piece.get_movement();
+                victim_id = base_piece.get_piece_id();
+            }
+        }
+        // Use the id of the same piece type for the capturer. If it's not available for the capturer
+        // (like the pawns, which are different piece types for each player), try swapping the case
+        let hand_id = definition.ids[capturer as usize].unwrap_or_else(|| {
+            if victim_id.is_uppercase() { victim_id.to_ascii_lowercase() } else { victim_id.to_ascii_uppercase() }
+        });
+        if self.pieces[capturer as usize].contains_piece(hand_id) {
+            Some(hand_id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pieces captured by the indirect capture `mv` (by withdrawal, approach, custody or rifle).
+    /// Must be called before making the move
+    fn indirect_capture_victims(&self, mv: Move, my_player_num: Player) -> Bitboard {
+        let mut victims = Bitboard::zero();
+        if mv.is_rifle_capture() {
+            victims.set_bit(mv.get_target());
+            return victims;
+        }
+        let piece = self.player_piece_at(my_player_num, mv.get_from()).unwrap();
+        let enemies = self.pieces[1 - my_player_num as usize].get_occupied();
+        let allies = self.pieces[my_player_num as usize].get_occupied();
+        indirect_capture_victims(piece.get_movement(), mv.get_from(), mv.get_to(), enemies, allies)
+    }
+
+    /// Returns the square where the castling partner lands in the castling move `mv`,
+    /// given the current square of the castling piece (`from` before the move, `to` after it)
+    fn castling_partner_destination(&self, mv: Move, king_index: BIndex) -> BIndex {
+        let king = self.piece_at(king_index).unwrap();
+        let kingside = mv.get_move_type() == MoveType::KingsideCastle;
+        castling_partner_destination(king.get_movement(), mv.get_to(), kingside, mv.is_free_castling()).unwrap()
+    }
+
+    /// Returns the squares that are empty after making `mv`, without making it
+    pub(crate) fn empty_squares_after(&self, mv: Move) -> Bitboard {
+        let my_player_num = self.whos_turn;
+        let mut occupied = self.occ_or_out_bounds.clone();
+        if mv.is_null() {
+            return !occupied;
+        }
+        let from = mv.get_from();
+        let to = mv.get_to();
+        if mv.is_castling() {
+            let partner_to = self.castling_partner_destination(mv, from);
+            occupied.clear_bit(from);
+            occupied.clear_bit(mv.get_target());
+            occupied.set_bit(to);
+            occupied.set_bit(partner_to);
+            return !occupied;
+        }
+        if mv.is_capture() {
+            if mv.is_indirect_capture() {
+                occupied &= !self.indirect_capture_victims(mv, my_player_num);
+            } else {
+                if let Some(intermediate) = mv.get_intermediate() {
+                    occupied.clear_bit(intermediate);
+                }
+                occupied.clear_bit(mv.get_target());
+            }
+            let moved_piece = self.player_piece_at(my_player_num, from).unwrap();
+            if moved_piece.explodes_on_capture() {
+                // The capturing piece explodes as well, so it doesn't land on the destination
+                let mut explosion = moved_piece.get_explosion(to) & &occupied;
+                while let Some(index) = explosion.lowest_one() {
+                    explosion.clear_bit(index);
+                    if self.piece_at(index).is_some_and(|p| !p.immune_to_explosion()) {
+                        occupied.clear_bit(index);
+                    }
+                }
+                occupied.clear_bit(from);
+                return !occupied;
+            }
+        }
+        // In rifle captures and drops, from and to are the same square
+        occupied.clear_bit(from);
+        occupied.set_bit(to);
+        !occupied
+    }
+
     /// Returns true if there is a move to undo
     pub fn can_unmake_move(&self) -> bool {
         // We always have at least one move in the stack
@@ -241,8 +447,10 @@ impl Position {
         // Consume prev props; never to return again
         let props = self.properties_stack.pop().expect("No move to undo");
         
-        // Update player turn
-        self.whos_turn = 1 - self.whos_turn;
+        // Update player turn (only if the move ended the turn)
+        if props.submove == 0 {
+            self.whos_turn = 1 - self.whos_turn;
+        }
 
         let my_player_num = self.whos_turn;
         let mv = props.move_played;
@@ -252,16 +460,24 @@ impl Position {
         if move_type == MoveType::Null {
             return;
         }
+        if let Some(neutral_to) = mv.get_neutral_to() {
+            self.undo_neutral_relocation(props.neutral_from, neutral_to);
+        }
         let from = mv.get_from();
         let to = mv.get_to();
         
+        // Undo drops
+        if let Some(piece_id) = mv.get_drop_piece() {
+            self.pieces[my_player_num as usize].remove_piece(to);
+            self.pieces[my_player_num as usize].add_to_hand(piece_id);
+            self.update_occupied();
+            return;
+        }
+        
         // If this move is a castle, remove the rook
         let mut rook_id = None;
-        if move_type == MoveType::KingsideCastle || move_type == MoveType::QueensideCastle {
-            let rook_to = {
-                if move_type == MoveType::KingsideCastle { to - 1 }
-                else { to + 1 }
-            };
+        if mv.is_castling() {
+            let rook_to = self.castling_partner_destination(mv, to);
             let rook_piece = self.pieces[my_player_num as usize].piece_at_mut(rook_to).unwrap();
             rook_id = Some(rook_piece.get_piece_id());
             self.pieces[my_player_num as usize].remove_piece(rook_to);
@@ -270,14 +486,20 @@ impl Position {
         // Undo move piece to location
         if self.pieces[my_player_num as usize].index_has_piece(to) {
             self.pieces[my_player_num as usize].move_piece(to, from, props.moved_piece_castle);
+            if self.promoted.get_bit(to) {
+                self.promoted.clear_bit(to);
+                if !mv.is_promotion() {
+                    self.promoted.set_bit(from);
+                }
+            }
             
             // Undo Promotion
             if move_type == MoveType::Promotion || move_type == MoveType::PromotionCapture {
                 // Remove old piece
                 self.pieces[my_player_num as usize].remove_piece(from);
                 let promoted_from = props.promote_from;
-                // Assume that the piece that promoted must have moved, so it can't castle
-                self.pieces[my_player_num as usize].add_piece(promoted_from, from, false);
+                // The piece may have promoted in its initial move, so restore whether it had moved
+                self.pieces[my_player_num as usize].add_piece(promoted_from, from, props.moved_piece_castle);
             }
         }
 
@@ -286,8 +508,14 @@ impl Position {
         match move_type {
             MoveType::Capture | MoveType::PromotionCapture => {
                 for _ in 0..props.num_captures {
-                    let (piece_id, owner, captured_can_castle, capt_index) = self.captures_stack.pop().unwrap();
+                    let (piece_id, owner, captured_can_castle, capt_index, was_promoted) = self.captures_stack.pop().unwrap();
                     self.pieces[owner as usize].add_piece(piece_id, capt_index, captured_can_castle);
+                    if was_promoted {
+                        self.promoted.set_bit(capt_index);
+                    }
+                }
+                for hand_id in props.captured_to_hand.iter().flatten() {
+                    self.pieces[my_player_num as usize].remove_from_hand(*hand_id);
                 }
             },
             MoveType::KingsideCastle | MoveType::QueensideCastle => {
@@ -2,15 +2,17 @@ use std::fmt;
 
 use crate::{types::*, PieceDefinition, err_assert, wrap_res, err};
 use crate::utils::to_index;
-use crate::piece::{Piece, PieceId};
+use crate::piece::{Piece, PieceId, NEUTRAL_PIECE_ID};
 
 mod position_properties;
 mod make_move;
+mod insufficient_material;
+mod game_end;
 pub mod global_rules;
 pub mod create;
 pub mod piece_set;
 
-use global_rules::GlobalRules;
+use global_rules::{GlobalRules, RoyaltyPolicy};
 use position_properties::PositionProperties;
 use piece_set::PieceSet;
 
@@ -28,8 +30,19 @@ pub struct Position {
     properties_stack: Vec<PositionProperties>,
     // Full id (piece type + player num) of the captured pieces, if any.
     // Also store whether the captured piece could castle and the index where it was captured.
+    // Also store whether the captured piece was promoted (see `promoted`).
     // In regular chess, this will be a maximum of 1 piece. In atomic chess, there can be up to 9.
-    captures_stack: Vec<(PieceId, Player, bool, BIndex)>,
+    captures_stack: Vec<(PieceId, Player, bool, BIndex, bool)>,
+    // Squares with pieces that are the result of a promotion. Only used if `demote_promoted_pieces`
+    // is true, so that they go to the hand as the piece they promoted from when captured
+    promoted: Bitboard,
+    // Squares with neutral pieces, which belong to neither player (like the duck in Duck chess).
+    // They block both players and can't be captured
+    neutral_pieces: Bitboard,
+    // Number of neutral pieces that are not on the board yet
+    neutral_in_hand: u8,
+    // Zobrist hashes for a neutral piece at each board index, and for each number of neutral pieces in the hand
+    neutral_zobrist: (Vec<ZobKey>, Vec<ZobKey>),
     // Global rules of the game
     pub global_rules: GlobalRules,
 }
@@ -47,6 +60,11 @@ impl Position {
             occ_or_out_bounds,
             properties_stack,
             captures_stack: Vec::with_capacity(128),
+            promoted: Bitboard::zero(),
+            neutral_pieces: Bitboard::zero(),
+            neutral_in_hand: 0,
+            // Use a player number that doesn't exist, so that the hashes are different from those of the pieces
+            neutral_zobrist: Piece::random_zobrist(NEUTRAL_PIECE_ID, 2),
             global_rules: rules,
         }
     }
@@ -59,9 +77,25 @@ impl Position {
             let id = id.unwrap();
             
             // Make sure that the promotion squares and pieces are specified together
-            err_assert!(definition.promotion_squares.is_empty() == definition.promo_vals[player].is_empty(), 
+            err_assert!(definition.can_promote() != definition.promo_vals[player].is_empty(), 
                 "Promotion squares and pieces must be specified together");
                 
+            let mut all_hops = definition.translate_hop_deltas.iter().chain(&definition.attack_hop_deltas);
+            err_assert!(all_hops.all(|delta| *delta != (0, 0)), "Hop deltas cannot be (0, 0)");
+            let mut all_legs = definition.multi_leg_deltas.iter().flat_map(|(first, second)| [first, second]);
+            err_assert!(all_legs.all(|delta| *delta != (0, 0)), "Multi-leg deltas cannot be (0, 0)");
+            let mut all_bent_riders = definition.translate_bent_riders.iter().chain(&definition.attack_bent_riders);
+            err_assert!(all_bent_riders.all(|(step, ride)| *step != (0, 0) && *ride != (0, 0)), "Bent rider deltas cannot be (0, 0)");
+            let valid_initial_move = |path: &Vec<(i8, i8)>| path.last().is_some_and(|delta| *delta != (0, 0));
+            err_assert!(definition.initial_moves.iter().all(valid_initial_move), "Initial moves must end in a delta other than (0, 0)");
+            err_assert!(definition.castle_partner_files.is_none() || definition.can_castle(), "Castle partner files require castle files");
+            if let (Some((queenside, kingside)), Some((partner_queenside, partner_kingside))) = (definition.castle_files, definition.castle_partner_files) {
+                err_assert!(queenside != partner_queenside && kingside != partner_kingside, "A castling piece and its partner cannot land on the same square");
+            }
+            let indirect_captures = definition.has_indirect_captures() || definition.rifle_capture;
+            err_assert!(!(indirect_captures && definition.explode_on_capture), "Pieces that explode on capture cannot capture by withdrawal, approach, custody or rifle");
+            
+            err_assert!(id != NEUTRAL_PIECE_ID, "The piece id {NEUTRAL_PIECE_ID} is reserved for neutral pieces");
             // Make sure that the piece is uniquely identifiable for this player
             for set in &self.pieces {
                 err_assert!(!set.contains_piece(id), "Piece id {id} already exists");
@@ -96,9 +130,8 @@ impl Position {
             if p.zobrist_key == my_zob {
                 num_reps += 1;
             }
-            // A capture breaks the repetition
-            // We could also break on pawn moves, but the concept of "pawn" doesn't exist in a custom game
-            if p.num_captures > 0 {
+            // A capture or a move that resets the halfmove clock (pawn move) breaks the repetition
+            if p.halfmove_clock == 0 {
                 break;
             }
             i -= 1;
@@ -106,14 +139,41 @@ impl Position {
         num_reps >= self.global_rules.repetitions_draw
     }
     
+    /// Returns `true` if the game is drawn by the N-move rule (fifty-move rule in standard chess)
+    #[inline]
+    pub fn draw_by_move_rule(&self) -> bool {
+        let n_move_rule = self.global_rules.n_move_rule;
+        n_move_rule != 0 && self.get_properties().halfmove_clock as u32 >= 2 * n_move_rule as u32
+    }
+    
+    #[inline]
+    pub fn get_halfmove_clock(&self) -> u16 {
+        self.get_properties().halfmove_clock
+    }
     #[inline]
-    pub fn get_ep_square(&self) -> Option<BIndex> {
-        self.get_properties().get_ep_square()
+    pub fn get_fullmove_number(&self) -> u16 {
+        self.get_properties().fullmove_number
+    }
+    /// Number of moves that the player to move has already made in the current turn
+    #[inline]
+    pub fn get_submove(&self) -> u8 {
+        self.get_properties().submove
+    }
+    
+    #[inline]
+    pub fn get_ep_squares(&self) -> &[BIndex] {
+        self.get_properties().get_ep_squares()
     }
     #[inline]
     pub fn get_ep_victim(&self) -> BIndex {
         self.get_properties().get_ep_victim()
     }
+    /// Returns `true` if the player to move can capture the en passant victim. In turns with several moves,
+    /// the victim can be a piece of the player to move, which moved earlier in the turn
+    #[inline]
+    pub fn en_passant_available(&self) -> bool {
+        !self.get_ep_squares().is_empty() && self.player_piece_at(1 - self.whos_turn, self.get_ep_victim()).is_some()
+    }
     
     #[inline]
     pub fn get_times_checked(&self) -> Option<&[u8; 2]> {
@@ -143,19 +203,29 @@ impl Position {
     
     #[inline]
     pub fn leader_is_captured(&self) -> bool {
-        self.get_num_leader_pieces(self.whos_turn) == 0
+        self.has_lost_by_captures(self.whos_turn)
     }
     #[inline]
     pub fn enemy_leader_is_captured(&self) -> bool {
-        self.get_num_leader_pieces(1 - self.whos_turn) == 0
+        self.has_lost_by_captures(1 - self.whos_turn)
     }
+    /// Returns `true` if the player has lost because their leaders were captured (according to the royalty policy),
+    /// or one of their piece types with `lose_on_extinction` is extinct.
+    /// A player without leader pieces loses when all their pieces are captured
     #[inline]
-    fn get_num_leader_pieces(&self, player: Player) -> u32 {
-        if let Some(leader) = self.pieces[player as usize].get_leader() {
-            leader.get_num_pieces()
-        } else {
-            self.pieces[player as usize].get_occupied().count_ones()
+    fn has_lost_by_captures(&self, player: Player) -> bool {
+        let piece_set = &self.pieces[player as usize];
+        if piece_set.has_extinct_piece() {
+            return true;
+        }
+        if !piece_set.has_leaders() {
+            return piece_set.get_occupied().is_zero();
         }
+        if piece_set.get_leader_bitboard().is_zero() {
+            return true;
+        }
+        self.global_rules.royalty_policy != RoyaltyPolicy::AllCaptured &&
+            self.get_properties().leaders_captured[player as usize] > 0
     }
 
     pub fn piece_at(&self, index: BIndex) -> Option<&Piece> {
@@ -204,18 +274,22 @@ impl Position {
         }
         err_assert!(owner.is_some(), "Attempted to add piece with ID={piece_id}, which doesn't exist");
         let owner = owner.unwrap();
+        let allowed = self.pieces[owner as usize].lookup_piece(piece_id).unwrap().is_allowed_at(index);
+        err_assert!(allowed, "Attempted to add piece {piece_id} to a square where it's not allowed: {index}");
         
         let mut zob = self.get_zobrist();
         self.pieces[owner as usize].add_piece(piece_id, index, can_castle);
         let piece = self.player_piece_at(owner, index).unwrap();
         // Update the zobrist key
         zob ^= piece.get_zobrist(index);
-        if can_castle && piece.used_in_castling() {
+        if can_castle && piece.tracks_unmoved() {
             zob ^= piece.get_castle_zobrist(index);
         }
         self.update_occupied();
+        let insufficient_material = self.compute_insufficient_material();
         let stack_len = self.properties_stack.len();
         self.properties_stack[stack_len - 1].zobrist_key = zob;
+        self.properties_stack[stack_len - 1].insufficient_material = insufficient_material;
         Ok(())
     }
 
@@ -230,18 +304,113 @@ impl Position {
         let piece = self.piece_at_mut(index).unwrap();
         // Update the zobrist key
         zob ^= piece.get_zobrist(index);
-        let used_in_castling = piece.used_in_castling();
+        let tracks_unmoved = piece.tracks_unmoved();
         let castle_zob = piece.get_castle_zobrist(index);
         let could_casle = self.pieces[owner].remove_piece(index);
-        if could_casle && used_in_castling {
+        if could_casle && tracks_unmoved {
             zob ^= castle_zob;
         }
         self.update_occupied();
+        let insufficient_material = self.compute_insufficient_material();
         let stack_len = self.properties_stack.len();
         self.properties_stack[stack_len - 1].zobrist_key = zob;
+        self.properties_stack[stack_len - 1].insufficient_material = insufficient_material;
+        Ok(())
+    }
+    
+    /// Adds a piece to the hand of the player that owns the piece type
+    pub fn public_add_to_hand(&mut self, piece_id: PieceId) -> wrap_res!() {
+        let owner = self.pieces.iter().position(|ps| ps.contains_piece(piece_id));
+        err_assert!(owner.is_some(), "Attempted to add piece with ID={piece_id} to the hand, which doesn't exist");
+        let owner = owner.unwrap();
+        err_assert!(self.pieces[owner].lookup_piece(piece_id).unwrap().get_num_in_hand() < u8::MAX,
+            "Too many pieces with ID={piece_id} in the hand");
+        let hand_zob = self.pieces[owner].add_to_hand(piece_id);
+        let insufficient_material = self.compute_insufficient_material();
+        let stack_len = self.properties_stack.len();
+        self.properties_stack[stack_len - 1].zobrist_key ^= hand_zob;
+        self.properties_stack[stack_len - 1].insufficient_material = insufficient_material;
         Ok(())
     }
     
+    /// Adds a neutral piece (which belongs to neither player) to an empty square
+    pub fn public_add_neutral_piece(&mut self, index: BIndex) -> wrap_res!() {
+        err_assert!(!self.occ_or_out_bounds.get_bit(index), "Attempted to add a neutral piece to a square that is not empty: {index}");
+        let zob = self.neutral_zobrist.0[index as usize];
+        self.neutral_pieces.set_bit(index);
+        self.update_occupied();
+        let stack_len = self.properties_stack.len();
+        self.properties_stack[stack_len - 1].zobrist_key ^= zob;
+        Ok(())
+    }
+    
+    /// Adds a neutral piece to the ones that are not on the board yet
+    pub fn public_add_neutral_to_hand(&mut self) -> wrap_res!() {
+        err_assert!(self.neutral_in_hand < u8::MAX, "Too many neutral pieces in the hand");
+        let hand_zob = self.set_neutral_in_hand(self.neutral_in_hand + 1);
+        let stack_len = self.properties_stack.len();
+        self.properties_stack[stack_len - 1].zobrist_key ^= hand_zob;
+        Ok(())
+    }
+    
+    /// Returns the squares with neutral pieces
+    #[inline]
+    pub fn get_neutral_pieces(&self) -> &Bitboard {
+        &self.neutral_pieces
+    }
+    
+    /// Returns the number of neutral pieces that are not on the board yet
+    #[inline]
+    pub fn get_neutral_in_hand(&self) -> u8 {
+        self.neutral_in_hand
+    }
+    
+    /// Moves a neutral piece from `from` (or from the hand, if `None`) to `to`, and returns the change of the zobrist key.
+    /// Doesn't update the occupied bitboard
+    fn relocate_neutral_piece(&mut self, from: Option<BIndex>, to: BIndex) -> ZobKey {
+        let mut zob = self.neutral_zobrist.0[to as usize];
+        match from {
+            Some(from) => {
+                self.neutral_pieces.clear_bit(from);
+                zob ^= self.neutral_zobrist.0[from as usize];
+            },
+            None => zob ^= self.set_neutral_in_hand(self.neutral_in_hand - 1),
+        }
+        self.neutral_pieces.set_bit(to);
+        zob
+    }
+    
+    /// Undoes `relocate_neutral_piece()`. Doesn't update the occupied bitboard
+    fn undo_neutral_relocation(&mut self, from: Option<BIndex>, to: BIndex) {
+        self.neutral_pieces.clear_bit(to);
+        match from {
+            Some(from) => self.neutral_pieces.set_bit(from),
+            None => { self.set_neutral_in_hand(self.neutral_in_hand + 1); },
+        }
+    }
+    
+    /// Sets the number of neutral pieces in the hand, and returns the change of the zobrist key
+    fn set_neutral_in_hand(&mut self, num: u8) -> ZobKey {
+        let hand_zobrist = &self.neutral_zobrist.1;
+        let zob = hand_zobrist[self.neutral_in_hand as usize] ^ hand_zobrist[num as usize];
+        self.neutral_in_hand = num;
+        zob
+    }
+    
+    /// Returns the pieces in the hand of a player, as a list of (piece id, number of pieces)
+    pub fn get_pocket(&self, player: Player) -> Vec<(PieceId, u8)> {
+        self.pieces[player as usize].iter()
+            .filter(|p| p.get_num_in_hand() > 0)
+            .map(|p| (p.get_piece_id(), p.get_num_in_hand()))
+            .collect()
+    }
+    
+    /// Returns `true` if the piece at the given index is the result of a promotion
+    /// (only tracked if `demote_promoted_pieces` is enabled)
+    pub fn is_promoted(&self, index: BIndex) -> bool {
+        self.promoted.get_bit(index)
+    }
+    
     /// Returns true if any of the pieces on the board is on a winning square
     pub fn piece_is_on_winning_square(&self) -> bool {
         for piece_set in &self.pieces {
@@ -265,8 +434,8 @@ impl Position {
     /// Updates the occupied bitboard
     /// Must be called after every position update/modification
     fn update_occupied(&mut self) {
-        self.occ_or_out_bounds = !&self.dimensions.bounds;
-        for (_i, ps) in self.pieces.iter_mut().enumerate() {
+        self.occ_or_out_bounds = !&self.dimensions.bounds | &self.neutral_pieces;
+        for ps in self.pieces.iter_mut() {
             ps.update_occupied();
             self.occ_or_out_bounds |= ps.get_occupied();
         }
@@ -280,6 +449,8 @@ impl fmt::Display for Position {
             for x in 0..self.dimensions.width {
                 if let Some(piece) = self.piece_at(to_index(x,y)) {
                     write!(f, "{} ", piece)?;
+                } else if self.neutral_pieces.get_bit(to_index(x,y)) {
+                    write!(f, "{NEUTRAL_PIECE_ID} ")?;
                 } else if self.dimensions.in_bounds(x, y) {
                     write!(f, ". ")?;
                 } else {
@@ -308,6 +479,9 @@ impl PartialEq for Position {
         self.whos_turn == other.whos_turn &&
         self.pieces == other.pieces &&
         self.occ_or_out_bounds == other.occ_or_out_bounds &&
+        self.promoted == other.promoted &&
+        self.neutral_pieces == other.neutral_pieces &&
+        self.neutral_in_hand == other.neutral_in_hand &&
         // Only compare the top of the stack, since the history may be different
         self.get_properties().zobrist_key == other.get_properties().zobrist_key &&
         // Don't compare captures stack, since the history may be different
@@ -3,8 +3,8 @@ use std::slice::{Iter, IterMut};
 use crate::utils::debug::eq_anyorder;
 use crate::{PieceDefinition, wrap_res, err_assert};
 //Pieces that a player has
-use crate::types::{Bitboard, BIndex, Player, BDimensions, Centipawns, BCoord};
-use crate::piece::{Piece, PieceId};
+use crate::types::{Bitboard, BIndex, Player, BDimensions, Centipawns, BCoord, ZobKey};
+use crate::piece::{Piece, PieceId, LEADER_MULTIPLIER};
 use crate::utils::from_index;
 
 /// Represents a set of pieces for a player
@@ -12,7 +12,8 @@ use crate::utils::from_index;
 #[derive(Clone, Debug)]
 pub struct PieceSet {
     pieces: Vec<Piece>,
-    leader_piece_index: isize,
+    // Indexes of the leader piece types in `pieces`
+    leader_piece_indexes: Vec<usize>,
     occupied: Bitboard,
     player_num: Player,
     // Inverse attack pattern of all the pieces in the set
@@ -31,7 +32,7 @@ impl PieceSet {
         PieceSet {
             occupied: Bitboard::zero(),
             pieces: Vec::new(),
-            leader_piece_index: -1,
+            leader_piece_indexes: Vec::new(),
             player_num,
             inverse_attack: PieceDefinition::default(),
             inverse_attack_jumps,
@@ -44,9 +45,7 @@ impl PieceSet {
     /// - This piece is available for the player (i.e. `ids[player_num]` is not `None`)
     pub fn register_piecetype(&mut self, definition: &PieceDefinition, dims: &BDimensions) -> wrap_res!() {
         if definition.is_leader {
-            let player = if self.player_num == 0 { "White" } else { "Black" };
-            err_assert!(self.leader_piece_index == -1, "{player} has more than 1 leader piece");
-            self.leader_piece_index = self.pieces.len() as isize;
+            self.leader_piece_indexes.push(self.pieces.len());
         }
         
         let piece = Piece::new(definition.clone(), self.player_num, dims);
@@ -75,14 +74,22 @@ impl PieceSet {
     pub fn lookup_piece(&self, piece_id: PieceId) -> Option<&Piece> {
         self.pieces.iter().find(|p| p.get_piece_id() == piece_id)
     }
-    pub fn contains_piece(&self, piece_id: PieceId) -> bool {
+    /// Returns `true` if the player has fewer pieces of this type (on the board and in the hand) than in the
+    /// starting position, so that other pieces can promote to it if `promote_to_captured_only` is set
+    pub fn has_lost_piece(&self, piece_id: PieceId) -> bool {
+        match self.lookup_piece(piece_id) {
+            Some(p) => p.get_num_pieces() + (p.get_num_in_hand() as u32) < p.get_initial_num_pieces(),
+            None => false,
+        }
+    }
+        pub fn contains_piece(&self, piece_id: PieceId) -> bool {
         self.lookup_piece(piece_id).is_some()
     }
     
-    pub fn iter(&self) -> Iter<Piece> {
+    pub fn iter(&self) -> Iter<'_, Piece> {
         self.pieces.iter()
     }
-    pub fn iter_mut(&mut self) -> IterMut<Piece> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, Piece> {
         self.pieces.iter_mut()
     }
     
@@ -115,11 +122,24 @@ impl PieceSet {
         }
     }
     
+    /// Returns the leader piece type, or `None` if there are no leader piece types or there are several of them
     pub fn get_leader(&self) -> Option<&Piece> {
-        if self.leader_piece_index == -1 {
-            return None;
+        match self.leader_piece_indexes[..] {
+            [index] => self.pieces.get(index),
+            _ => None,
         }
-        self.pieces.get(self.leader_piece_index as usize)
+    }
+    /// Returns `true` if at least one of the piece types is a leader
+    pub fn has_leaders(&self) -> bool {
+        !self.leader_piece_indexes.is_empty()
+    }
+    /// Returns the positions of all the leader pieces of all types
+    pub fn get_leader_bitboard(&self) -> Bitboard {
+        let mut leaders = Bitboard::zero();
+        for &index in &self.leader_piece_indexes {
+            leaders |= self.pieces[index].get_bitboard();
+        }
+        leaders
     }
     
     pub fn get_inverse_attack(&self, index: BIndex) -> (&PieceDefinition, &Bitboard) {
@@ -167,14 +187,47 @@ impl PieceSet {
         could_castle
     }
     
-    /// Returns the material score of all pieces in the set, and of only the leader pieces
+    /// Add a piece of the given type to the hand of the player (assuming the piece type exists).
+    /// Returns the zobrist key that must be XORed with the hash of the position.
+    pub fn add_to_hand(&mut self, piece_id: PieceId) -> ZobKey {
+        let piece = self.pieces.iter_mut().find(|p| p.get_piece_id() == piece_id).unwrap();
+        let old_key = piece.get_hand_zobrist();
+        piece.add_to_hand_();
+        old_key ^ piece.get_hand_zobrist()
+    }
+    
+    /// Remove a piece of the given type from the hand of the player (assuming there is one).
+    /// Returns the zobrist key that must be XORed with the hash of the position.
+    pub fn remove_from_hand(&mut self, piece_id: PieceId) -> ZobKey {
+        let piece = self.pieces.iter_mut().find(|p| p.get_piece_id() == piece_id).unwrap();
+        let old_key = piece.get_hand_zobrist();
+        piece.remove_from_hand_();
+        old_key ^ piece.get_hand_zobrist()
+    }
+    
+    /// Returns `true` if the player has at least one piece in the hand
+    pub fn has_pieces_in_hand(&self) -> bool {
+        self.pieces.iter().any(|p| p.get_num_in_hand() > 0)
+    }
+    
+    /// Returns `true` if one of the piece types that cannot go extinct has no pieces left
+    pub fn has_extinct_piece(&self) -> bool {
+        self.pieces.iter().any(|p| p.is_extinct())
+    }
+    
+    /// Returns the material score of all pieces in the set, and of only the leader pieces.
+    /// The last piece of a type that cannot go extinct is valued like a leader
     pub fn get_material_score(&self) -> (Centipawns, Centipawns) {
         let mut score = 0;
         let mut leader_score = 0;
         for piece in &self.pieces {
-            let piece_total_score = piece.get_material_score_all();
+            let mut piece_total_score = piece.get_material_score_all();
+            let is_last_survivor = piece.loses_on_extinction() && piece.get_num_pieces() + piece.get_num_in_hand() as u32 == 1;
+            if is_last_survivor && !piece.is_leader() {
+                piece_total_score *= LEADER_MULTIPLIER;
+            }
             score += piece_total_score;
-            if piece.is_leader() {
+            if piece.is_leader() || is_last_survivor {
                 leader_score += piece_total_score;
             }
         }
@@ -203,15 +256,19 @@ impl PieceSet {
         
         for delta in &other.attack_jump_deltas {
             self.inverse_attack.attack_jump_deltas.push((-delta.0, -delta.1));
-            
-            for i in 0..=255 {
-                let (x, y) = from_index(i);
-                let nx = x as i8 - delta.0;
-                let ny = y as i8 - delta.1;
-                if nx < 0 || ny < 0 || !dims.in_bounds(nx as BCoord, ny as BCoord) {
-                    continue;
-                }
-                self.inverse_attack_jumps[i as usize].set_bit_at(nx as BCoord, ny as BCoord);
+            self.add_inverse_jump(*delta, dims);
+        }
+        // Lame jumps can only attack the squares where a regular jump would land, the path is checked later
+        for path in &other.attack_lame_jumps {
+            if let Some(delta) = path.last() {
+                self.add_inverse_jump(*delta, dims);
+            }
+        }
+        // Multi-leg moves capture on the square of the first leg and the destination
+        for ((dx1, dy1), (dx2, dy2)) in &other.multi_leg_deltas {
+            self.add_inverse_jump((*dx1, *dy1), dims);
+            if (dx1 + dx2, dy1 + dy2) != (0, 0) {
+                self.add_inverse_jump((dx1 + dx2, dy1 + dy2), dims);
             }
         }
         
@@ -222,6 +279,34 @@ impl PieceSet {
             }
             self.inverse_attack.attack_sliding_deltas.push(new_delta);
         }
+        
+        for (dx, dy) in &other.attack_hop_deltas {
+            if !self.inverse_attack.attack_hop_deltas.contains(&(-dx, -dy)) {
+                self.inverse_attack.attack_hop_deltas.push((-dx, -dy));
+            }
+        }
+        
+        // Bent riders capture on the step like a jump, the rides are walked backwards from the target later
+        for ((sx, sy), (rx, ry)) in &other.attack_bent_riders {
+            self.add_inverse_jump((*sx, *sy), dims);
+            let inverse = ((-sx, -sy), (-rx, -ry));
+            if !self.inverse_attack.attack_bent_riders.contains(&inverse) {
+                self.inverse_attack.attack_bent_riders.push(inverse);
+            }
+        }
+    }
+    
+    /// Marks the squares from which a jump with the given delta would land on each index
+    fn add_inverse_jump(&mut self, delta: (i8, i8), dims: &BDimensions) {
+        for i in 0..=255 {
+            let (x, y) = from_index(i);
+            let nx = x as i8 - delta.0;
+            let ny = y as i8 - delta.1;
+            if nx < 0 || ny < 0 || !dims.in_bounds(nx as BCoord, ny as BCoord) {
+                continue;
+            }
+            self.inverse_attack_jumps[i as usize].set_bit_at(nx as BCoord, ny as BCoord);
+        }
     }
 }
 
@@ -243,11 +328,9 @@ impl PartialEq for PieceSet {
             }
             true
         } && {
-            let i1 = self.leader_piece_index;
-            let i2 = other.leader_piece_index;
-            if i1 == -1 && i2 == -1 { true }
-            else if i1 == -1 || i2 == -1 { false }
-            else { self.pieces[i1 as usize] == other.pieces[i2 as usize] }
+            let leaders1: Vec<&Piece> = self.leader_piece_indexes.iter().map(|&i| &self.pieces[i]).collect();
+            let leaders2: Vec<&Piece> = other.leader_piece_indexes.iter().map(|&i| &other.pieces[i]).collect();
+            eq_anyorder(&leaders1, &leaders2)
         }
     }
 }
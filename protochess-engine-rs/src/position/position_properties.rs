@@ -1,4 +1,4 @@
-use crate::types::{BIndex, Move, ZobKey};
+use crate::types::{BIndex, Move, ZobKey, Centipawns};
 
 use crate::piece::PieceId;
 
@@ -9,37 +9,77 @@ pub struct PositionProperties {
     pub move_played: Move,
     //If the last move was a promotion, promote_from is the previous piecetype
     pub promote_from: PieceId,
-    //EP square (square behind a double pawn push)
-    ep_square: Option<BIndex>,
-    ep_victim: BIndex, // Only valid if ep_square is Some
+    //EP squares (squares passed by a double pawn push or a longer jump), only the first num_ep_squares are valid
+    ep_squares: [BIndex; MAX_EP_SQUARES],
+    num_ep_squares: u8,
+    ep_victim: BIndex, // Only valid if num_ep_squares > 0
     // true if the piece that moved could castle
     pub moved_piece_castle: bool,
     pub num_captures: u8,
     // Number of times that each player has been in check
     pub times_in_check: [u8; 2],
+    // Number of plies since the last capture or move of a piece with `resets_move_clock`
+    pub halfmove_clock: u16,
+    // Starts at 1 and is incremented after black's move
+    pub fullmove_number: u16,
+    // Number of moves that the player to move has already made in the current turn (always 0 if every turn has a
+    // single move, see `GlobalRules::moves_per_turn`)
+    pub submove: u8,
+    // true if neither player can win with the material on the board
+    pub insufficient_material: bool,
+    // If the last move was a capture and the captured pieces went to the hand of the capturing
+    // player, the ids of the pieces in the hand (filled from the start)
+    pub captured_to_hand: [Option<PieceId>; MAX_CAPTURES_TO_HAND],
+    // Number of leader pieces of each player that have been captured or exploded since the start of the game
+    pub leaders_captured: [u8; 2],
+    // Material value of the enemy pieces that each player has captured since the start of the game
+    pub captured_material: [Centipawns; 2],
+    // Number of consecutive turns that each player has ended with a piece on the squares of `GameEndRule::HoldSquares`
+    pub turns_holding_squares: [u8; 2],
+    // If the last move relocated a neutral piece, the square where it was (or `None` if it came from the hand)
+    pub neutral_from: Option<BIndex>,
 }
 
+/// Maximum number of EP squares of a move (the squares between the ends of a 16-square line, and the target of the move)
+pub const MAX_EP_SQUARES: usize = 15;
+
+/// Maximum number of pieces that a move can capture without explosions (by withdrawal, approach
+/// and custody at the same time)
+pub const MAX_CAPTURES_TO_HAND: usize = 6;
+
 impl PositionProperties {
-    // Access EP square
-    pub fn set_ep_square(&mut self, ep_square: BIndex, ep_victim: BIndex) {
-        self.clear_ep_square();
-        self.ep_square = Some(ep_square);
+    // Access EP squares
+    pub fn set_ep_squares(&mut self, ep_squares: impl IntoIterator<Item = BIndex>, ep_victim: BIndex) {
+        self.clear_ep_squares();
+        for sq in ep_squares {
+            if self.get_ep_squares().contains(&sq) {
+                continue;
+            }
+            assert!((self.num_ep_squares as usize) < MAX_EP_SQUARES, "Attempted to set more than {} ep squares", MAX_EP_SQUARES);
+            self.ep_squares[self.num_ep_squares as usize] = sq;
+            self.num_ep_squares += 1;
+            self.zobrist_key ^= Self::ep_zobrist(sq);
+        }
         self.ep_victim = ep_victim;
-        // Update zobrist. For simplicity, use the ep index as the zobrist key
-        self.zobrist_key ^= ep_square as ZobKey;
     }
-    pub fn clear_ep_square(&mut self) {
-        if let Some(sq) = self.ep_square {
-            // If the last prop had some ep square then we want to clear zob by xoring again
-            self.zobrist_key ^= sq as ZobKey;
+    pub fn clear_ep_squares(&mut self) {
+        // If the last prop had some ep squares then we want to clear zob by xoring again
+        for i in 0..self.num_ep_squares as usize {
+            self.zobrist_key ^= Self::ep_zobrist(self.ep_squares[i]);
         }
-        self.ep_square = None;
+        self.num_ep_squares = 0;
     }
-    pub fn get_ep_square(&self) -> Option<BIndex> {
-        self.ep_square
+    pub fn get_ep_squares(&self) -> &[BIndex] {
+        &self.ep_squares[..self.num_ep_squares as usize]
     }
     pub fn get_ep_victim(&self) -> BIndex {
-        assert!(self.ep_square.is_some(), "Attempted to get ep victim when ep square is None");
+        assert!(self.num_ep_squares > 0, "Attempted to get ep victim when there are no ep squares");
         self.ep_victim
     }
+    
+    // For simplicity, derive the zobrist key from the ep index. Use an odd multiplier so that the keys of several
+    // ep squares don't cancel each other out, and keep the lowest bit for the player to move
+    fn ep_zobrist(ep_square: BIndex) -> ZobKey {
+        (ep_square as ZobKey + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15) << 1
+    }
 }
@@ -1,10 +1,9 @@
-use instant::Instant;
-
-#[cfg(feature = "parallel")]
 use std::sync::atomic::Ordering;
 
-use crate::MoveGen;
-use crate::types::{Move, Depth, Centipawns, SearchTimeout, ZobKey};
+use instant::Instant;
+
+use crate::{MoveGen, GameEnd};
+use crate::types::{Move, Depth, Centipawns, SearchTimeout, ZobKey, Player};
 
 use super::Searcher;
 use super::eval;
@@ -112,6 +111,7 @@ impl Searcher {
         // Null move pruning
         if  !IS_PV && depth > 3 && // Don't skip a turn in PV nodes or close to the leaves
             do_null && // Don't do 2 null moves in a row
+            self.pos.get_submove() == 0 && // Don't skip the rest of a turn with several moves
             !self.pos.global_rules.capturing_is_forced && // Don't skip a turn if capturing is forced
             eval::can_do_null_move(&self.pos) && // Don't skip a turn in endgame
             !MoveGen::in_check(&mut self.pos) // Don't skip a turn in check
@@ -129,12 +129,7 @@ impl Searcher {
         let old_alpha = alpha;
         let mut best_score = -Centipawns::MAX; // Use -MAX instead of MIN to avoid overflow when negating
         let in_check = known_check || MoveGen::in_check(&mut self.pos);
-        if !IS_ROOT && in_check && self.pos.increment_num_checks() {
-            // If the player has been checked N times, the game is over.
-            // Don't increment the check counter in the root node
-            self.end_pv::<IS_PV>(pv_index);
-            return Ok(self.checkmate_score(pv_index));
-        }
+        let player = self.pos.whos_turn;
         if IS_PV && in_check && !known_check && search_depth < self.max_searching_depth {
             // If in check, extend search by 1 ply. Limit the extension to 2x the original depth.
             depth += 1;
@@ -150,11 +145,11 @@ impl Searcher {
                 self.pos.make_move(mv);
                 num_legal_moves += 1;
                 let score: Centipawns;
-                if let Some(end_score) = self.is_game_over(mv, pv_index+1) {
+                if let Some(end_score) = self.is_game_over(player, pv_index+1) {
                     self.end_pv::<IS_PV>(pv_index);
-                    score = -end_score;
+                    score = end_score;
                 } else {
-                    score = -self.alphabeta::<IS_PV,false>(depth-1, search_depth, pv_index+1, -beta, -alpha, true, hint)?;
+                    score = self.search_child::<IS_PV>(player, depth-1, search_depth, pv_index+1, alpha, beta, hint)?;
                 }
                 self.pos.unmake_move();
                 // This is the leftmost branch, we know that best_score = -INF && alpha = -INF
@@ -177,12 +172,12 @@ impl Searcher {
 
             num_legal_moves += 1;
             let mut score: Centipawns;
-            if let Some(end_score) = self.is_game_over(mv, pv_index+1) {
+            if let Some(end_score) = self.is_game_over(player, pv_index+1) {
                 self.end_pv::<IS_PV>(pv_index);
-                score = -end_score;
+                score = end_score;
             } else if num_legal_moves == 1 {
                 // Leftmost child when the hint is not available
-                score = -self.alphabeta::<IS_PV,false>(depth-1, search_depth, pv_index+1, -beta, -alpha, true, None)?;
+                score = self.search_child::<IS_PV>(player, depth-1, search_depth, pv_index+1, alpha, beta, None)?;
             } else {
                 // Try late move reduction
                 if !IS_PV && num_legal_moves > 4 && mv.is_quiet() && depth >= 5 && !in_check {
@@ -191,7 +186,7 @@ impl Searcher {
                         if num_legal_moves > 10 { depth - 4 }
                         else { depth - 3 }
                     };
-                    score = -self.alphabeta::<false,false>(reduced_depth, search_depth, pv_index+1, -alpha-1, -alpha, true, None)?;
+                    score = self.search_child::<false>(player, reduced_depth, search_depth, pv_index+1, alpha, alpha+1, None)?;
                 } else {
                     // Cannot reduce, proceed with standard PVS
                     score = alpha + 1;
@@ -200,10 +195,10 @@ impl Searcher {
                 if IS_PV || score > alpha {
                     // PVS
                     // Null window search
-                    score = -self.alphabeta::<false,false>(depth-1, search_depth, pv_index+1, -alpha-1, -alpha, true, None)?;
+                    score = self.search_child::<false>(player, depth-1, search_depth, pv_index+1, alpha, alpha+1, None)?;
                     // Re-search if necessary
                     if score > alpha && score < beta {
-                        score = -self.alphabeta::<IS_PV,false>(depth-1, search_depth, pv_index+1, -beta, -alpha, true, None)?;
+                        score = self.search_child::<IS_PV>(player, depth-1, search_depth, pv_index+1, alpha, beta, None)?;
                     }
                 }
             }
@@ -238,15 +233,10 @@ impl Searcher {
         }
 
         if num_legal_moves == 0 {
-            return if in_check || self.pos.global_rules.stalemated_player_loses {
-                // No legal moves and in check: Checkmate
-                self.end_pv::<IS_PV>(pv_index);
-                Ok(self.checkmate_score(pv_index))
-            } else {
-                // No legal moves but also not in check: Stalemate
-                self.end_pv::<IS_PV>(pv_index);
-                Ok(0)
-            };
+            // No legal moves: checkmate or stalemate
+            self.end_pv::<IS_PV>(pv_index);
+            let game_end = self.pos.no_moves_result(in_check);
+            return Ok(self.game_end_score(&game_end, player, pv_index));
         }
 
         if IS_PV && alpha != old_alpha {
@@ -272,6 +262,26 @@ impl Searcher {
         
         Ok(alpha)
     }
+    
+    /// Searches the position after a move of `player`, and returns its score from the perspective of `player`.
+    /// In turns with several moves, `player` can also be the player to move in that position
+    #[allow(clippy::too_many_arguments)]
+    fn search_child<const IS_PV: bool>(&mut self,
+            player: Player,
+            depth: Depth,
+            search_depth: Depth,
+            pv_index: usize,
+            alpha: Centipawns,
+            beta: Centipawns,
+            hint: Option<&Vec<Move>>
+        ) -> Result<Centipawns, SearchTimeout>
+    {
+        if self.pos.whos_turn == player {
+            self.alphabeta::<IS_PV,false>(depth, search_depth, pv_index, alpha, beta, true, hint)
+        } else {
+            Ok(-self.alphabeta::<IS_PV,false>(depth, search_depth, pv_index, -beta, -alpha, true, hint)?)
+        }
+    }
 
 
     // Keep seaching, but only consider capture moves (avoid horizon effect)
@@ -292,13 +302,18 @@ impl Searcher {
         }
 
         // Get only captures, sorted by move ordering heuristics (try the most promising moves first)
+        let player = self.pos.whos_turn;
         let moves = MoveGen::get_pseudo_moves(&mut self.pos, false);
         for (_move_score, mv) in self.sort_moves_by_score(moves, 0) {
             // This is a capture move, so there is no need to check for repetition
             if !MoveGen::make_move_if_legal(mv, &mut self.pos) {
                 continue;
             }
-            let score = -self.quiesce(-beta, -alpha, pv_index+1)?;
+            // In turns with several moves, the same player can move again
+            let score = {
+                if self.pos.whos_turn == player { self.quiesce(alpha, beta, pv_index+1)? }
+                else { -self.quiesce(-beta, -alpha, pv_index+1)? }
+            };
             self.pos.unmake_move();
 
             if score >= beta {
@@ -319,10 +334,10 @@ impl Searcher {
     #[inline]
     fn increment_num_nodes(&mut self) -> Result<(), SearchTimeout> {
         self.nodes_searched += 1;
-        // Check for timeout periodically (every 2^19 nodes)
-        #[allow(clippy::collapsible_if)]
-        if self.nodes_searched.trailing_zeros() >= 19 {
-            #[cfg(feature = "parallel")]
+        // Check for timeout periodically (every 2^14 nodes, about 25 ms at 650k nodes/s), so that short time limits
+        // and the stop flag are honoured quickly. Reading the clock at this rate has no measurable cost
+        if self.nodes_searched.trailing_zeros() >= 14 {
+            // The stop flag is honoured at every depth (the search falls back to any legal move if needed)
             if self.stop_flag.load(Ordering::Relaxed) {
                 return Err(SearchTimeout);
             }
@@ -338,26 +353,21 @@ impl Searcher {
     }
     
     #[inline]
-    // Check for instant game over conditions (does not check for checkmate or stalemate)
-    fn is_game_over(&mut self, mv: Move, pv_index: usize) -> Option<Centipawns> {
-        // There is repetition, the result is always a draw
-        if self.pos.draw_by_repetition() {
-            return Some(0);
-        }
-        // The leader is captured
-        if self.pos.leader_is_captured() {
-            return Some(self.checkmate_score(pv_index));
-        }
-        // The opponent has moved the leader to a winning position
-        let opponent = 1 - self.pos.whos_turn;
-        let to = mv.get_to();
-        // Piece could have exploded when capturing in the win square, in that case the square is empty
-        if let Some(enemy_piece) = self.pos.player_piece_at(opponent, to) {
-            if enemy_piece.wins_at(to) {
-                return Some(self.checkmate_score(pv_index));
-            }
+    // Check for instant game over conditions (does not check for checkmate or stalemate, unless the N-move rule applies)
+    // Returns the score from the perspective of `player`
+    fn is_game_over(&mut self, player: Player, pv_index: usize) -> Option<Centipawns> {
+        let game_end = self.pos.game_end(false, true, None)?;
+        Some(self.game_end_score(&game_end, player, pv_index))
+    }
+    
+    /// Returns the score of a finished game from the perspective of `player`
+    fn game_end_score(&self, game_end: &GameEnd, player: Player, pv_index: usize) -> Centipawns {
+        match game_end.winner {
+            None => 0,
+            // Like in checkmate_score(), prefer the shortest win (or the longest loss)
+            Some(winner) if winner == player => -(GAME_OVER_SCORE + pv_index as Centipawns),
+            Some(_) => GAME_OVER_SCORE + pv_index as Centipawns,
         }
-        None
     }
 
     #[inline]
@@ -398,7 +408,7 @@ impl Searcher {
         }
         
         // Sort moves by decreasing score
-        moves_and_score.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+        moves_and_score.sort_unstable_by_key(|a| std::cmp::Reverse(a.0));
         
         if self.pos.global_rules.invert_win_conditions {
             moves_and_score.reverse();
@@ -9,6 +9,11 @@ pub fn evaluate(position: &Position) -> Centipawns {
     // Arbitrary threshold of roughly 2 queens and 2 rooks, feel free to experiment
     const ENDGAME_THRESHOLD: Centipawns = 3000;
     
+    // Neither player can win, the material is irrelevant
+    if position.draw_by_insufficient_material() {
+        return 0;
+    }
+    
     let player_num = position.whos_turn;
     // Material score (opponent pieces are negative)
     let mut score = 0;
@@ -72,7 +77,11 @@ pub fn score_move(search: &Searcher, depth: usize, mv: Move) -> Centipawns {
         let victim = search.pos.player_piece_at(1-current_player, mv.get_target()).unwrap();
 
         let attacker_score = attacker.get_material_score();
-        let victim_score = victim.get_material_score();
+        let mut victim_score = victim.get_material_score();
+        // Multi-leg moves can capture a second piece in the first leg
+        if let Some(intermediate) = mv.get_intermediate().filter(|i| *i != mv.get_target()) {
+            victim_score += search.pos.player_piece_at(1-current_player, intermediate).unwrap().get_material_score();
+        }
 
         score += CAPTURE_BASE_SCORE;
         score += 8 * victim_score - attacker_score;
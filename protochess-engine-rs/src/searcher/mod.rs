@@ -1,19 +1,25 @@
 use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicBool};
+use std::sync::mpsc::Sender;
 
 #[cfg(feature = "parallel")]
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 #[cfg(feature = "parallel")]
-use std::sync::atomic::{Ordering, AtomicBool, AtomicU8};
+use std::sync::atomic::AtomicU8;
 
 use instant::{Instant, Duration};
 
-use crate::types::{Move, Depth, Centipawns, SearchTimeout, ZobKey};
-use crate::Position;
+use crate::types::{Move, Depth, Centipawns, SearchTimeout, ZobKey, SearchLimits, SearchInfo};
+use crate::{Position, MoveInfo, MoveGen};
 
 mod alphabeta;
+mod sampling;
 pub mod transposition_table;
 pub mod eval;
 
+pub use alphabeta::GAME_OVER_SCORE;
+
 use transposition_table::{TranspositionTable, TranspositionHandle};
 
 #[derive(Debug, Clone)]
@@ -28,17 +34,21 @@ pub struct Searcher {
     transposition_table: TranspositionHandle,
     // Stats
     nodes_searched: u64,
+    total_nodes_searched: u64,
     max_searching_depth: Depth,
+    start_time: Instant,
     end_time: Instant,
     principal_variation: [Move; Depth::MAX as usize + 1],
     known_checks: BTreeSet<ZobKey>,
+    // When set to true, the search stops as soon as possible
+    stop_flag: Arc<AtomicBool>,
+    // Progress reports are sent here after each iteration
+    info_sender: Option<Sender<SearchInfo>>,
     
     // Attributes for parallel search
     #[cfg(feature = "parallel")]
     thread_num: u32,
     #[cfg(feature = "parallel")]
-    stop_flag: Arc<AtomicBool>,
-    #[cfg(feature = "parallel")]
     current_searched_depth: Arc<AtomicU8>,
 }
 
@@ -52,52 +62,61 @@ impl Searcher {
             history_moves: [[0;256];256],
             transposition_table,
             nodes_searched: 0,
+            total_nodes_searched: 0,
             max_searching_depth: 0,
+            start_time: Instant::now(),
             end_time: Instant::now(),
             principal_variation: [Move::null(); Depth::MAX as usize + 1],
             known_checks: BTreeSet::new(),
+            stop_flag: Arc::default(),
+            info_sender: None,
             
             #[cfg(feature = "parallel")]
             thread_num: 0,
             #[cfg(feature = "parallel")]
-            stop_flag: Arc::default(),
-            #[cfg(feature = "parallel")]
             current_searched_depth: Arc::default(),
         }
     }
     
     pub fn get_best_move(position: &Position, depth: Depth, num_threads: u32) -> SearchRes {
-        // Create a new copy of the heuristics for each search
-        // Cannot use u64::MAX due to overflow, 1_000_000 seconds is 11.5 days
-        Searcher::get_best_move_impl(position, depth, 1_000_000, num_threads)
+        let limits = SearchLimits { max_depth: depth, ..Default::default() };
+        Searcher::get_best_move_limits(position, &limits, num_threads)
     }
 
     pub fn get_best_move_timeout(position: &Position, time_sec: u64, num_threads: u32) -> SearchRes {
-        // Create a new copy of the heuristics for each search
-        Searcher::get_best_move_impl(position, Depth::MAX, time_sec, num_threads)
+        let limits = SearchLimits { max_time: Some(Duration::from_secs(time_sec)), ..Default::default() };
+        Searcher::get_best_move_limits(position, &limits, num_threads)
     }
     
-    // Run for some time, then return the PV, the position score, and the depth
-    fn get_best_move_impl(position: &Position, max_depth: Depth, time_sec: u64, num_threads: u32) -> SearchRes {
+    // Run until one of the limits is reached, then return the PV, the position score, and the depth
+    pub fn get_best_move_limits(position: &Position, limits: &SearchLimits, num_threads: u32) -> SearchRes {
+        // Create a new copy of the heuristics for each search
         // Limit the max depth to 127 to avoid overflow when doubling
-        let max_depth = std::cmp::min(max_depth, 127);
+        let max_depth = std::cmp::min(limits.max_depth, 127);
+        // Cannot use Duration::MAX due to overflow, 1_000_000 seconds is 11.5 days
+        let max_time = limits.max_time.unwrap_or(Duration::from_secs(1_000_000));
+        let stop_flag = limits.stop_flag.clone().unwrap_or_default();
         #[cfg(not(feature = "parallel"))] {
             assert!(num_threads == 1);
             let table = TranspositionTable::default();
-            Searcher::new(position, table.into()).search(max_depth, time_sec)
+            let mut searcher = Searcher::new(position, table.into());
+            searcher.stop_flag = stop_flag;
+            searcher.info_sender = limits.info_sender.clone();
+            searcher.search(max_depth, max_time)
         }
         #[cfg(feature = "parallel")] {
-            Self::search_multi_thread(position, max_depth, time_sec, num_threads)
+            Self::search_multi_thread(position, max_depth, max_time, num_threads, stop_flag, &limits.info_sender)
         }
     }
     
     #[cfg(feature = "parallel")]
-    fn search_multi_thread(position: &Position, max_depth: Depth, time_sec: u64, num_threads: u32) -> SearchRes {
+    fn search_multi_thread(position: &Position, max_depth: Depth, max_time: Duration, num_threads: u32,
+        stop_arc: Arc<AtomicBool>, info_sender: &Option<Sender<SearchInfo>>) -> SearchRes
+    {
         // Arc pointer to a vector of results
         let res = vec![Default::default(); num_threads as usize];
         let results_arc = Arc::new(Mutex::new(res));
         // Arc pointers to global search state
-        let stop_arc = Arc::new(AtomicBool::new(false));
         let depth_arc = Arc::new(AtomicU8::new(0));
         // Global transposition table
         let table = Arc::new(TranspositionTable::default());
@@ -108,6 +127,7 @@ impl Searcher {
                 let stop_arc = stop_arc.clone();
                 let depth_arc = depth_arc.clone();
                 let table = table.clone();
+                let info_sender = info_sender.clone();
                 // Spawn a new task in the thread pool, take ownership of the pointers
                 scope.spawn(move |_scope| {
                     // Create a new searcher (with cloned position) for each thread
@@ -115,7 +135,8 @@ impl Searcher {
                     searcher.thread_num = thread_num;
                     searcher.stop_flag = stop_arc;
                     searcher.current_searched_depth = depth_arc;
-                    let thread_result = searcher.search(max_depth, time_sec);
+                    searcher.info_sender = info_sender;
+                    let thread_result = searcher.search(max_depth, max_time);
                     // When the thread is done, store the result in the results vector
                     let mut results_vec = results_arc.lock().unwrap();
                     results_vec[thread_num as usize] = thread_result;
@@ -141,12 +162,14 @@ impl Searcher {
         (best_pv, best_score, best_depth)
     }
     
-    fn search(&mut self, max_depth: Depth, time_sec: u64) -> SearchRes {
+    fn search(&mut self, max_depth: Depth, max_time: Duration) -> SearchRes {
         let mut pv = Vec::with_capacity(max_depth as usize);
         let mut pv_score: Centipawns = 0;
         let mut pv_depth: Depth = 0;
         self.known_checks.clear();
-        self.end_time = Instant::now() + Duration::from_secs(time_sec);
+        self.total_nodes_searched = 0;
+        self.start_time = Instant::now();
+        self.end_time = self.start_time + max_time;
         
         let mut search_depth;
         #[cfg(not(feature = "parallel"))] {
@@ -167,10 +190,12 @@ impl Searcher {
             self.max_searching_depth = 2 * search_depth;
             match self.start_alphabeta(search_depth, &pv) {
                 Ok(score) => {
-                    // Update the current searched depth
-                    #[cfg(feature = "parallel")] {
-                        self.current_searched_depth.fetch_max(search_depth, Ordering::Relaxed);
-                    }
+                    self.total_nodes_searched += self.nodes_searched;
+                    // Update the current searched depth. Only the first thread to complete a depth reports it
+                    #[cfg(not(feature = "parallel"))]
+                    let new_depth = true;
+                    #[cfg(feature = "parallel")]
+                    let new_depth = self.current_searched_depth.fetch_max(search_depth, Ordering::Relaxed) < search_depth;
                     // If there have been transposition table hits, the new pv won't be complete.
                     // It might be shorter than the previous pv, in which case we can keep the old pv
                     // as long as it's consistent with the new pv.
@@ -198,8 +223,9 @@ impl Searcher {
                     }
                     pv_depth = search_depth;
                     pv_score = score;
-                    // Print PV info
-                    println!("{}", self.format_result(score, &pv, search_depth));
+                    if new_depth {
+                        self.send_info(score, &pv, search_depth);
+                    }
                 },
                 Err(SearchTimeout) => {
                     // Thread timed out, return the best move found so far
@@ -207,7 +233,6 @@ impl Searcher {
                 },
             }
 
-            #[cfg(feature = "parallel")]
             if self.stop_flag.load(Ordering::Relaxed) {
                 // Stop flag set, return the best move found so far
                 break;
@@ -230,31 +255,25 @@ impl Searcher {
                 search_depth = std::cmp::min(next_depth, max_depth);
             }
         }
+        // If the search was stopped before completing depth 1, play any legal move
+        if pv.is_empty() {
+            pv.extend(MoveGen::get_legal_moves(&mut self.pos).first());
+        }
         (pv, pv_score, pv_depth)
     }
     
-    // Format the result as a string in order to print it all at once. This prevents 2 threads from printing at the same time.
-    fn format_result(&self, score: i32, pv: &Vec<Move>, depth: Depth) -> String {
-        #[cfg(feature = "parallel")]
-        let thread_str = format!("T{:<2} ", self.thread_num);
-        #[cfg(not(feature = "parallel"))]
-        let thread_str = String::new();
-        
-        let diff = -(score.abs() + alphabeta::GAME_OVER_SCORE);
-        let score_str = {
-            if diff < 200 {
-                let sign = if score > 0 { "" } else { "-" };
-                format!("MATE {}{}", sign, (diff+1) / 2)
-            } else {
-                format!("cp {:<4}", score)
-            }
-        };
-        let mut pv_str = String::new();
-        for m in pv {
-            pv_str.push_str(&format!("{} ", m));
+    // Send a progress report to the listener, if any
+    fn send_info(&self, score: Centipawns, pv: &[Move], depth: Depth) {
+        if let Some(sender) = &self.info_sender {
+            let info = SearchInfo {
+                depth,
+                score,
+                nodes: self.total_nodes_searched,
+                elapsed: Instant::now() - self.start_time,
+                pv: pv.iter().map(|mv| MoveInfo::from(*mv)).collect(),
+            };
+            // The receiver may have been dropped, in which case the report is simply discarded
+            sender.send(info).ok();
         }
-        
-        format!("{thread_str}Depth {depth:<2} Score: {score_str} [nodes: {n}] PV: {pv_str}", n=self.nodes_searched)
     }
 }
-
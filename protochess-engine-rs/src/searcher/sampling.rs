@@ -0,0 +1,104 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::{MoveGen, Position, PieceId};
+use crate::types::{Bitboard, BIndex, Move, Depth, Centipawns};
+use crate::utils::to_index;
+
+use super::Searcher;
+
+/// Maximum number of attempts to generate each sample (some samples are rejected, see `sample_hidden_position()`)
+const ATTEMPTS_PER_SAMPLE: u32 = 4;
+
+impl Searcher {
+    /// Searches a position where the player to move only knows the squares returned by `MoveGen::visible_squares()`
+    /// (like in Dark chess). Each sample places the hidden enemy pieces at random on the squares that the player
+    /// can't see, and it's searched up to `depth` with the regular search. Only the samples whose best move is
+    /// legal in the real position count toward `num_samples`.
+    ///
+    /// Returns the move that is the best in most samples (breaking ties by the average score), along with
+    /// its average score, or `None` if no sample has a legal move in the real position.
+    pub fn get_best_move_sampled(position: &Position, depth: Depth, num_samples: u32, num_threads: u32) -> Option<(Move, Centipawns)> {
+        let mut position = position.clone();
+        let player = position.whos_turn;
+        let visible = MoveGen::visible_squares(&mut position, player);
+        // The hidden pieces can only block some moves of the player, so the best move of a sample must also be legal
+        // in the real position (in the actual game, the player would find out when trying to make the move)
+        let legal_moves = MoveGen::get_legal_moves(&mut position);
+        // Use a predictable seed, so that the search is reproducible and doesn't depend on the hidden pieces
+        let seed = (position.get_fullmove_number() as u64) << 8 | player as u64;
+        let mut rng = StdRng::seed_from_u64(seed);
+        
+        // For each candidate move: number of samples where it's the best, and the sum of its scores
+        let mut votes: Vec<(Move, u32, i64)> = Vec::new();
+        let mut samples = 0;
+        for _ in 0..num_samples * ATTEMPTS_PER_SAMPLE {
+            if samples == num_samples {
+                break;
+            }
+            let sample = match sample_hidden_position(&position, &visible, &mut rng) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let (pv, score, _depth) = Searcher::get_best_move(&sample, depth, num_threads);
+            // Only count the samples that vote for a legal move
+            let best_move = match pv.first() {
+                Some(mv) if legal_moves.contains(mv) => *mv,
+                _ => continue,
+            };
+            samples += 1;
+            match votes.iter_mut().find(|(mv, _, _)| *mv == best_move) {
+                Some((_, count, score_sum)) => {
+                    *count += 1;
+                    *score_sum += score as i64;
+                },
+                None => votes.push((best_move, 1, score as i64)),
+            }
+        }
+        votes.into_iter()
+            .map(|(mv, count, score_sum)| (mv, count, (score_sum / count as i64) as Centipawns))
+            .max_by_key(|&(_, count, score)| (count, score))
+            .map(|(mv, _, score)| (mv, score))
+    }
+}
+
+/// Returns a copy of the position where the enemy pieces on squares that the player to move can't see are moved
+/// to random hidden squares. Returns `None` if some piece can't be placed, or if the player to move could
+/// capture the enemy leader (unless `GlobalRules::ignore_checks` is set)
+fn sample_hidden_position(position: &Position, visible: &Bitboard, rng: &mut StdRng) -> Option<Position> {
+    let mut sample = position.clone();
+    let enemy = 1 - position.whos_turn;
+    let mut hidden_pieces: Vec<(PieceId, BIndex, bool)> = Vec::new();
+    for piece in sample.pieces[enemy as usize].iter() {
+        for index in piece.get_indexes() {
+            if !visible.get_bit(index) {
+                let can_castle = piece.has_not_moved(index) && piece.tracks_unmoved();
+                hidden_pieces.push((piece.get_piece_id(), index, can_castle));
+            }
+        }
+    }
+    for &(_, index, _) in &hidden_pieces {
+        sample.public_remove_piece(index).ok()?;
+    }
+    
+    let mut free_squares: Vec<BIndex> = (0..=BIndex::MAX)
+        .filter(|&index| !visible.get_bit(index) && !sample.occ_or_out_bounds.get_bit(index))
+        .collect();
+    free_squares.shuffle(rng);
+    for (piece_id, old_index, can_castle) in hidden_pieces {
+        let piece = sample.pieces[enemy as usize].lookup_piece(piece_id)?;
+        let promotion_squares = &piece.get_movement().promotion_squares;
+        // Don't place the piece where it can't be, or where it should have promoted (like a pawn on the last rank)
+        let i = free_squares.iter().position(|&index| {
+            piece.is_allowed_at(index) && !promotion_squares.iter().any(|&(x, y)| to_index(x, y) == index)
+        })?;
+        let index = free_squares.swap_remove(i);
+        // The castling rights are kept only if the piece stays on the same square
+        sample.public_add_piece(piece_id, index, can_castle && index == old_index).ok()?;
+    }
+    if MoveGen::enemy_in_check(&mut sample) {
+        return None;
+    }
+    Some(sample)
+}
@@ -17,7 +17,7 @@ pub use handle_singlethread::TranspositionHandle;
 
 
 // Since we will be computing zobrist_key % TABLE_SIZE, we want it to be a power of 2
-// 2^21 clusters is about 2 million clusters. Each cluster is 4*24 = 96 bytes, so this is about 192 MB
+// 2^21 clusters is about 2 million clusters. Each cluster is 4*32 = 128 bytes, so this is about 256 MB
 const TABLE_SIZE: usize = 2_usize.pow(21);
 const ENTRIES_PER_CLUSTER: usize = 4;
 
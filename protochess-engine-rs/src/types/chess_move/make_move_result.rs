@@ -8,10 +8,16 @@ pub enum MakeMoveResultFlag {
     Checkmate,
     LeaderCaptured,
     AllPiecesCaptured,
+    Extinction,
     PieceInWinSquare,
     CheckLimit,
+    CapturedMaterial,
+    SquaresHeld,
     Stalemate,
     Repetition,
+    NMoveRule,
+    InsufficientMaterial,
+    MoveLimit,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -32,6 +38,19 @@ pub struct MakeMoveResult {
     pub exploded: Vec<(BCoord, BCoord)>,
     /// If `flag != IllegalMove`, contains the move in algebraic notation
     pub move_notation: Option<String>,
+    /// If `true`, the player who made the move still has moves left in the current turn, and is the player
+    /// to move again (in variants with several moves per turn)
+    pub turn_continues: bool,
+}
+
+/// How the game ended after a move, see `Position::game_end()`
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[must_use]
+pub struct GameEnd {
+    /// The rule that ended the game
+    pub flag: MakeMoveResultFlag,
+    /// The winner of the game, or `None` if it ended in a draw
+    pub winner: Option<Player>,
 }
 
 
@@ -49,10 +68,16 @@ impl From<String> for MakeMoveResultFlag {
             "Checkmate" => Self::Checkmate,
             "LeaderCaptured" => Self::LeaderCaptured,
             "AllPiecesCaptured" => Self::AllPiecesCaptured,
+            "Extinction" => Self::Extinction,
             "PieceInWinSquare" => Self::PieceInWinSquare,
             "CheckLimit" => Self::CheckLimit,
+            "CapturedMaterial" => Self::CapturedMaterial,
+            "SquaresHeld" => Self::SquaresHeld,
             "Stalemate" => Self::Stalemate,
             "Repetition" => Self::Repetition,
+            "NMoveRule" => Self::NMoveRule,
+            "InsufficientMaterial" => Self::InsufficientMaterial,
+            "MoveLimit" => Self::MoveLimit,
             _ => panic!("Invalid flag"),
         }
     }
@@ -91,12 +116,13 @@ impl From<String> for MakeMoveResultWinner {
 
 
 impl MakeMoveResult {
-    pub fn ok(exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
+    pub fn ok(exploded: Vec<(BCoord, BCoord)>, move_notation: String, turn_continues: bool) -> Self {
         Self {
             flag: MakeMoveResultFlag::Ok,
             winner: None.into(),
             exploded,
             move_notation: Some(move_notation),
+            turn_continues,
         }
     }
     pub fn illegal_move() -> Self {
@@ -105,63 +131,22 @@ impl MakeMoveResult {
             winner: None.into(),
             exploded: Vec::new(),
             move_notation: None,
+            turn_continues: false,
         }
     }
-    pub fn checkmate(winner: Player, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::Checkmate,
-            winner: Some(winner).into(),
-            exploded,
-            move_notation: Some(move_notation),
-        }
-    }
-    pub fn leader_captured(winner: Player, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::LeaderCaptured,
-            winner: Some(winner).into(),
-            exploded,
-            move_notation: Some(move_notation),
-        }
-    }
-    pub fn all_pieces_captured(winner: Player, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::AllPiecesCaptured,
-            winner: Some(winner).into(),
-            exploded,
-            move_notation: Some(move_notation),
-        }
-    }
-    pub fn piece_in_win_square(winner: Player, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::PieceInWinSquare,
-            winner: Some(winner).into(),
-            exploded,
-            move_notation: Some(move_notation),
-        }
-    }
-    pub fn check_limit(winner: Player, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
+    pub fn game_over(game_end: GameEnd, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
         Self {
-            flag: MakeMoveResultFlag::CheckLimit,
-            winner: Some(winner).into(),
+            flag: game_end.flag,
+            winner: game_end.winner.into(),
             exploded,
             move_notation: Some(move_notation),
+            turn_continues: false,
         }
     }
-    pub fn stalemate(winner: Option<Player>, exploded: Vec<(BCoord, BCoord)>, move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::Stalemate,
-            winner: winner.into(),
-            exploded,
-            move_notation: Some(move_notation),
-        }
-    }
-    pub fn repetition(move_notation: String) -> Self {
-        Self {
-            flag: MakeMoveResultFlag::Repetition,
-            winner: None.into(),
-            // Since this is a repetition, this move cannot be a capture, so there is no explosion
-            exploded: Vec::new(),
-            move_notation: Some(move_notation),
-        }
+}
+
+impl GameEnd {
+    pub fn draw(flag: MakeMoveResultFlag) -> Self {
+        Self { flag, winner: None }
     }
-}
\ No newline at end of file
+}
@@ -30,6 +30,9 @@ pub enum MoveType {
     DoubleJump = 0b1000,
     // Skip 0b1001 because there is no DoubleJumpCapture
     Null = 0b1010,
+    // Skip 0b1011 because there is no NullCapture
+    // Place a piece from the hand on an empty square. From, to and target are all the drop square
+    Drop = 0b1100,
 }
 
 #[derive(Eq, PartialEq, Copy, Clone)]
@@ -40,21 +43,88 @@ pub struct Move {
     ///8-15:  to index:u8
     ///16-23: target index:u8
     ///24-27 : movetype (see MoveType above)
+    ///28:    multi-leg move flag
+    ///29:    the second leg of the multi-leg move also captures
+    ///30:    free castling flag (the destination of the king is chosen by the player)
+    ///31:    indirect capture flag (the victims are not on the destination square)
     /// In captures, target is the index of the captured piece (usually the same as to, except for en passant)
     /// In DoubleJump, target is the index of the generated En Passant square
+    /// In multi-leg moves, the target bits store the square where the first leg captures a piece, and the
+    /// target is either that square or to (if the second leg captures another piece)
+    /// In indirect captures (by withdrawal, approach, custody or rifle), target is one of the victims. Rifle
+    /// captures don't move the piece, so from and to are the same square
     move_fields: u32, 
-    // Promotion piece
-    promotion: PieceId
+    /// Promotion piece (or dropped piece in drops), and the relocation of a neutral piece at the end of the move
+    /// (like the duck in Duck chess)
+    ///0-20:  promotion piece:char
+    ///21-28: destination of the neutral piece:u8
+    ///29:    the move relocates a neutral piece
+    /// The square where the neutral piece comes from is stored in the position properties when the move is made
+    promotion: u32,
 }
 
+const MULTI_LEG_FLAG: u32 = 1 << 28;
+const CAPTURES_TWICE_FLAG: u32 = 1 << 29;
+const FREE_CASTLING_FLAG: u32 = 1 << 30;
+const INDIRECT_CAPTURE_FLAG: u32 = 1 << 31;
+const PROMOTION_MASK: u32 = (1 << 21) - 1;
+const NEUTRAL_MOVE_FLAG: u32 = 1 << 29;
+
 impl Move {
     #[inline]
     pub fn new(from: BIndex, to: BIndex, target: BIndex, move_type: MoveType, promotion: Option<PieceId>) -> Move {
         Move {
             move_fields: (from as u32) | (to as u32) << 8 | (target as u32) << 16 | (move_type as u32) << 24,
-            promotion: promotion.unwrap_or('_')
+            promotion: promotion.unwrap_or('_') as u32,
         }
     }
+    
+    /// Returns a copy of this capture that first captures the piece at `intermediate` and then
+    /// continues to `to` (like the moves of the lion in Chu Shogi)
+    #[inline]
+    pub fn with_intermediate(self, intermediate: BIndex) -> Move {
+        debug_assert!(self.is_capture(), "Multi-leg moves must capture in the first leg");
+        let captures_twice = if self.get_target() == intermediate { 0 } else { CAPTURES_TWICE_FLAG };
+        let move_fields = (self.move_fields & !(0xFF << 16)) | (intermediate as u32) << 16 | MULTI_LEG_FLAG | captures_twice;
+        Move { move_fields, ..self }
+    }
+
+    /// Returns a copy of this castling move where the player chose where the king lands (free castling),
+    /// so it must be told apart from the other castling moves with the same partner
+    #[inline]
+    pub fn with_free_castling(self) -> Move {
+        debug_assert!(self.is_castling(), "Only castling moves can be free castling moves");
+        Move { move_fields: self.move_fields | FREE_CASTLING_FLAG, ..self }
+    }
+
+    /// Returns a copy of this capture where the victims are not on the destination square: they are captured
+    /// by withdrawal, approach or custody, or by a rifle capture (if `from` and `to` are the same square)
+    #[inline]
+    pub fn with_indirect_capture(self) -> Move {
+        debug_assert!(self.is_capture(), "Only captures can be indirect captures");
+        Move { move_fields: self.move_fields | INDIRECT_CAPTURE_FLAG, ..self }
+    }
+
+    /// Returns a copy of this move that also relocates a neutral piece to the empty square `to`
+    #[inline]
+    pub fn with_neutral_move(self, to: BIndex) -> Move {
+        let promotion = (self.promotion & PROMOTION_MASK) | (to as u32) << 21 | NEUTRAL_MOVE_FLAG;
+        Move { promotion, ..self }
+    }
+    
+    /// Returns a copy of this move without the relocation of a neutral piece
+    #[inline]
+    pub fn without_neutral_move(self) -> Move {
+        Move { promotion: self.promotion & PROMOTION_MASK, ..self }
+    }
+    
+    /// Returns the square where this move relocates a neutral piece, if any
+    pub fn get_neutral_to(&self) -> Option<BIndex> {
+        if self.promotion & NEUTRAL_MOVE_FLAG == 0 {
+            return None;
+        }
+        Some(((self.promotion >> 21) & 0xFF) as BIndex)
+    }
 
     #[inline]
     pub fn null() -> Move {
@@ -66,8 +136,14 @@ impl Move {
         self.get_move_type() == MoveType::Null
     }
     
+    #[inline]
+    pub fn drop(piece_id: PieceId, to: BIndex) -> Move {
+        Move::new(to, to, to, MoveType::Drop, Some(piece_id))
+    }
+    
     pub fn is_quiet(&self) -> bool {
-        self.get_move_type() == MoveType::Quiet || self.get_move_type() == MoveType::DoubleJump
+        let move_type = self.get_move_type();
+        move_type == MoveType::Quiet || move_type == MoveType::DoubleJump || move_type == MoveType::Drop
     }
 
     pub fn get_from(&self) -> BIndex{
@@ -81,16 +157,39 @@ impl Move {
     // Get the index of the victim piece, if any. Usually the same as get_to(), except for en passant
     // In double jump, this is the index of the generated en passant square
     pub fn get_target(&self) -> BIndex {
+        if self.move_fields & CAPTURES_TWICE_FLAG != 0 {
+            return self.get_to();
+        }
         ((self.move_fields >> 16) & 0xFF) as BIndex
     }
 
+    /// Returns the square captured by the first leg of a multi-leg move, if this is one
+    pub fn get_intermediate(&self) -> Option<BIndex> {
+        if self.move_fields & MULTI_LEG_FLAG == 0 {
+            return None;
+        }
+        Some(((self.move_fields >> 16) & 0xFF) as BIndex)
+    }
+
     pub fn is_capture(&self) -> bool {
         // The least significant bit of the move type is used to indicate capture
         ((self.move_fields >> 24) & 1) != 0
     }
     
     pub fn is_en_passant(&self) -> bool {
-        self.get_move_type() == MoveType::Capture && self.get_target() != self.get_to()
+        self.get_move_type() == MoveType::Capture && self.get_target() != self.get_to() && self.get_intermediate().is_none()
+            && !self.is_indirect_capture()
+    }
+    
+    /// Returns `true` if this move captures pieces that are not on its destination square
+    /// (by withdrawal, approach, custody or rifle)
+    pub fn is_indirect_capture(&self) -> bool {
+        self.move_fields & INDIRECT_CAPTURE_FLAG != 0
+    }
+    
+    /// Returns `true` if this move captures the target without moving the piece
+    pub fn is_rifle_capture(&self) -> bool {
+        self.is_indirect_capture() && self.get_from() == self.get_to()
     }
     
     pub fn is_promotion(&self) -> bool {
@@ -102,35 +201,60 @@ impl Move {
         let move_type = self.get_move_type();
         move_type == MoveType::KingsideCastle || move_type == MoveType::QueensideCastle
     }
+    
+    pub fn is_free_castling(&self) -> bool {
+        self.move_fields & FREE_CASTLING_FLAG != 0
+    }
+    
+    pub fn is_drop(&self) -> bool {
+        self.get_move_type() == MoveType::Drop
+    }
 
     #[inline]
     pub fn get_move_type(&self) -> MoveType {
         // Output a match statement that maps from "x if x == MoveType::XX as u32" to "MoveType::XX"
         macro_rules! match_move_type {
             ($($x:ident),*) => {
-                match self.move_fields >> 24 {
+                match (self.move_fields >> 24) & 0xF {
                     // For each argument x, generate a line of the match
                     $( x if x == MoveType::$x as u32 => { MoveType::$x } )*
                     _ => { panic!("Invalid move type") }
                 }
             }
         }
-        match_move_type!(Quiet, Capture, KingsideCastle, QueensideCastle, Promotion, PromotionCapture, DoubleJump, Null)
+        match_move_type!(Quiet, Capture, KingsideCastle, QueensideCastle, Promotion, PromotionCapture, DoubleJump, Null, Drop)
     }
 
     pub fn get_promotion_piece(&self) -> Option<PieceId> {
         if self.is_promotion() {
-            Some(self.promotion)
+            Some(self.promotion_id())
+        } else {
+            None
+        }
+    }
+    
+    /// Returns the id of the piece placed on the board, if this move is a drop
+    pub fn get_drop_piece(&self) -> Option<PieceId> {
+        if self.is_drop() {
+            Some(self.promotion_id())
         } else {
             None
         }
     }
 
+    // Id stored in the promotion bits (the promotion piece or the dropped piece)
+    fn promotion_id(&self) -> PieceId {
+        char::from_u32(self.promotion & PROMOTION_MASK).unwrap()
+    }
+
     /// Returns the squares that would explode if this move was made.
     /// **Important**: Call this function **before** making the move
     pub fn get_potential_explosion(&self, pos: &Position) -> Vec<(BCoord, BCoord)> {
+        if !self.is_capture() {
+            return Vec::new();
+        }
         let piece = pos.piece_at(self.get_from()).unwrap();
-        if !piece.explodes_on_capture() || !self.is_capture() {
+        if !piece.explodes_on_capture() {
             return Vec::new();
         }
         let to = self.get_to();
@@ -153,24 +277,51 @@ impl fmt::Display for Move {
         if self.is_null() {
             return write!(f, "[NULL]");
         }
+        if self.is_drop() {
+            let (x, y) = from_index(self.get_to());
+            write!(f, "{}@{}", self.promotion_id(), to_rank_file(x, y))?;
+            return self.fmt_neutral_move(f);
+        }
         let (x1, y1) = from_index(self.get_from());
         let (x2, y2) = {
-            // Print castling moves as if the king moves to the rook square
-            if self.is_castling() { from_index(self.get_target()) }
+            // Print castling moves as if the king moves to the rook square, and rifle captures as if the
+            // piece moves to the victim
+            if self.is_castling() || self.is_rifle_capture() { from_index(self.get_target()) }
             else { from_index(self.get_to()) }
         };
+        let intermediate = match self.get_intermediate() {
+            Some(index) => { let (x, y) = from_index(index); to_rank_file(x, y) },
+            // In free castling, also print where the king lands (like "e1g1h1")
+            None if self.is_free_castling() => { let (x, y) = from_index(self.get_to()); to_rank_file(x, y) },
+            None => "".to_string(),
+        };
         let suffix = {
-            if self.is_promotion() { format!("={}", self.promotion) }
+            if self.is_promotion() { format!("={}", self.promotion_id()) }
             else { "".to_string() }
         };
-        write!(f, "{}{}{}", to_rank_file(x1, y1), to_rank_file(x2, y2), suffix)
+        write!(f, "{}{}{}{}", to_rank_file(x1, y1), intermediate, to_rank_file(x2, y2), suffix)?;
+        self.fmt_neutral_move(f)
     }
 }
+impl Move {
+    // Writes the relocation of the neutral piece, if any (like ",@d5")
+    fn fmt_neutral_move(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(to) = self.get_neutral_to() {
+            let (x, y) = from_index(to);
+            write!(f, ",@{}", to_rank_file(x, y))?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Debug for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_null() {
             return write!(f, "[NULL]");
         }
+        if self.is_drop() {
+            return write!(f, "{self}");
+        }
         let (fx, fy) = from_index(self.get_from());
         let from = to_rank_file(fx, fy);
         let (to_x, to_y) = from_index(self.get_to());
@@ -178,12 +329,18 @@ impl fmt::Debug for Move {
         let (target_x, target_y) = from_index(self.get_target());
         let target = to_rank_file(target_x, target_y);
         let suffix = {
-            if self.is_promotion() { format!("={}", self.promotion) }
+            if self.is_promotion() { format!("={}", self.promotion_id()) }
             else if self.is_capture() { "x".to_string() }
             else if self.is_castling() { "(Castle)".to_string() }
             else { "".to_string() }
         };
-        write!(f, "{from}->{to}{suffix} [t:{target}]")
+        if let Some(index) = self.get_intermediate() {
+            let (x, y) = from_index(index);
+            write!(f, "{from}->{}->{to}{suffix} [t:{target}]", to_rank_file(x, y))?;
+        } else {
+            write!(f, "{from}->{to}{suffix} [t:{target}]")?;
+        }
+        self.fmt_neutral_move(f)
     }
 }
 
@@ -16,38 +16,92 @@ pub struct MoveInfo {
     pub from: (BCoord, BCoord),
     pub to: (BCoord, BCoord),
     pub promotion: Option<PieceId>,
+    // Piece placed from the hand on the `to` square (`from` is the same as `to`)
+    pub drop: Option<PieceId>,
+    // In multi-leg moves, the square where the first leg captures a piece before continuing to `to`.
+    // In free castling, the square where the king lands (`to` is the square of the partner)
+    pub intermediate: Option<(BCoord, BCoord)>,
+    // Square where a neutral piece is moved (or placed from the hand) after the move, like the duck in Duck chess
+    pub neutral_to: Option<(BCoord, BCoord)>,
 }
 
 impl From<Move> for MoveInfo {
     fn from(m: Move) -> Self {
         let from = from_index(m.get_from());
         let to = {
-            if m.is_castling() {
-                // Castling moves are stored as if the king moves to the rook's square
+            if m.is_castling() || m.is_rifle_capture() {
+                // Castling moves are stored as if the king moves to the rook's square, and rifle
+                // captures as if the piece moves to the victim
                 from_index(m.get_target())
             } else {
                 from_index(m.get_to())
             }
         };
-        MoveInfo { from, to, promotion: m.get_promotion_piece() }
+        MoveInfo {
+            from,
+            to,
+            promotion: m.get_promotion_piece(),
+            drop: m.get_drop_piece(),
+            intermediate: {
+                if m.is_free_castling() { Some(from_index(m.get_to())) }
+                else { m.get_intermediate().map(from_index) }
+            },
+            neutral_to: m.get_neutral_to().map(from_index),
+        }
     }
 }
 
-// Create a MoveInfo from a string like "e2e4" or "e7e8=Q"
+// Create a MoveInfo from a string like "e2e4", "e7e8=Q", "P@e4" or "e4f5f6" (multi-leg move through f5,
+// or a free castling move where the king lands on the middle square). Any of them can be followed by the
+// square where a neutral piece is moved, like "e2e4,@d5"
 impl TryFrom<&str> for MoveInfo {
     type Error = String;
     fn try_from(s: &str) -> wrap_res!(Self) {
+        if let Some((mv, neutral_to)) = s.trim().split_once(",@") {
+            err_assert!(Regex::new(r"^[a-p][0-9]+$").unwrap().is_match(neutral_to), "Invalid move format: '{s}'");
+            let (x, y) = match scan_fmt!(neutral_to, "{[a-p]}{d}", char, isize) {
+                Ok(parts) => parts,
+                Err(_) => err!("Invalid move format: '{s}'"),
+            };
+            err_assert!(y > 0 && y <= 16, "Invalid move format (rank must be between 1 and 16");
+            let mut info = MoveInfo::try_from(mv)?;
+            info.neutral_to = Some((x.to_digit(36).unwrap() as BCoord - 10, y as BCoord - 1));
+            return Ok(info);
+        }
         const EXPECTED_REGEX: &str = r"^[a-p][0-9]+[a-p][0-9]+(=.)?$";
+        const DROP_REGEX: &str = r"^.@[a-p][0-9]+$";
+        const MULTI_LEG_REGEX: &str = r"^[a-p][0-9]+[a-p][0-9]+[a-p][0-9]+(=.)?$";
         let s = s.trim();
-        err_assert!(Regex::new(EXPECTED_REGEX).unwrap().is_match(s), "Invalid move format: '{s}' (expected 'e2e4', 'e7e8=Q')");
+        if Regex::new(DROP_REGEX).unwrap().is_match(s) {
+            let (drop, to_x, to_y) = match scan_fmt!(s, "{}@{[a-p]}{d}", PieceId, char, isize) {
+                Ok(parts) => parts,
+                Err(_) => err!("Invalid move format: '{s}'"),
+            };
+            let to_x = to_x.to_digit(36).unwrap() as BCoord - 10;
+            err_assert!(to_y > 0 && to_y <= 16, "Invalid move format (rank must be between 1 and 16");
+            let to = (to_x, to_y as BCoord - 1);
+            return Ok(MoveInfo { from: to, to, promotion: None, drop: Some(drop), intermediate: None, neutral_to: None });
+        }
+        if Regex::new(MULTI_LEG_REGEX).unwrap().is_match(s) {
+            // Parse the move without the intermediate square, then add it back
+            let mid_start = s[1..].find(|c: char| c.is_ascii_lowercase()).unwrap() + 1;
+            let mid_end = s[mid_start + 1..].find(|c: char| !c.is_ascii_digit()).unwrap() + mid_start + 1;
+            let mid_x = s[mid_start..].chars().next().unwrap().to_digit(36).unwrap() as BCoord - 10;
+            let mid_y = match s[mid_start + 1..mid_end].parse::<isize>() {
+                Ok(rank) => rank,
+                Err(_) => err!("Invalid move format: '{s}'"),
+            };
+            err_assert!(mid_y > 0 && mid_y <= 16, "Invalid move format (rank must be between 1 and 16");
+            let mut info = MoveInfo::try_from(format!("{}{}", &s[..mid_start], &s[mid_end..]).as_str())?;
+            info.intermediate = Some((mid_x, mid_y as BCoord - 1));
+            return Ok(info);
+        }
+        err_assert!(Regex::new(EXPECTED_REGEX).unwrap().is_match(s), "Invalid move format: '{s}' (expected 'e2e4', 'e7e8=Q', 'P@e4', 'e4f5f6')");
         let (from_x, from_y, to_x, to_y) = match scan_fmt!(s, "{[a-p]}{d}{[a-p]}{d}", char, isize, char, isize) {
             Ok(parts) => parts,
             Err(_) => err!("Invalid move format: '{s}'"),
         };
-        let promotion = match scan_fmt!(s, "{*[a-p]}{*d}{*[a-p]}{*d}={}", PieceId) {
-            Ok(promo) => Some(promo),
-            Err(_) => None,
-        };
+        let promotion = scan_fmt!(s, "{*[a-p]}{*d}{*[a-p]}{*d}={}", PieceId).ok();
         // from_x, to_x are guaranteed to be between 'a' and 'p' (inclusive)
         let from_x = from_x.to_digit(36).unwrap() as BCoord - 10;
         let to_x = to_x.to_digit(36).unwrap() as BCoord - 10;
@@ -58,13 +112,23 @@ impl TryFrom<&str> for MoveInfo {
             from: (from_x, from_y as BCoord - 1),
             to: (to_x, to_y as BCoord - 1),
             promotion,
+            drop: None,
+            intermediate: None,
+            neutral_to: None,
         })
     }
 }
 
 impl PartialEq<Move> for MoveInfo {
     fn eq(&self, other: &Move) -> bool {
-        self == &MoveInfo::from(*other)
+        let other = MoveInfo::from(*other);
+        // Drops are often written with the uppercase id for both players (like "p@e4" for a white pawn)
+        let same_drop = match (self.drop, other.drop) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(&b),
+            (a, b) => a == b,
+        };
+        self.from == other.from && self.to == other.to && self.promotion == other.promotion && same_drop &&
+        self.intermediate == other.intermediate && self.neutral_to == other.neutral_to
     }
 }
 
@@ -72,9 +136,20 @@ impl PartialEq<Move> for MoveInfo {
 /// or check/checkmate indicators).
 impl std::fmt::Display for MoveInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", tuple_to_rank_file(self.from), tuple_to_rank_file(self.to))?;
-        if let Some(prom) = self.promotion {
-            write!(f, "={prom}")?;
+        if let Some(drop) = self.drop {
+            write!(f, "{drop}@{}", tuple_to_rank_file(self.to))?;
+        } else {
+            write!(f, "{}", tuple_to_rank_file(self.from))?;
+            if let Some(intermediate) = self.intermediate {
+                write!(f, "{}", tuple_to_rank_file(intermediate))?;
+            }
+            write!(f, "{}", tuple_to_rank_file(self.to))?;
+            if let Some(prom) = self.promotion {
+                write!(f, "={prom}")?;
+            }
+        }
+        if let Some(neutral_to) = self.neutral_to {
+            write!(f, ",@{}", tuple_to_rank_file(neutral_to))?;
         }
         Ok(())
     }
@@ -23,6 +23,12 @@ pub enum GameMode {
     RacingKings,
     ThreeCheck,
     FiveCheck,
+    Crazyhouse,
+    Duck,
+    Marseillais,
+    Progressive,
+    Monster,
+    Dark,
 }
 
 impl TryFrom<&str> for GameMode {
@@ -37,6 +43,12 @@ impl TryFrom<&str> for GameMode {
             "racingkings" => Ok(GameMode::RacingKings),
             "3check" => Ok(GameMode::ThreeCheck),
             "5check" => Ok(GameMode::FiveCheck),
+            "crazyhouse" => Ok(GameMode::Crazyhouse),
+            "duck" => Ok(GameMode::Duck),
+            "marseillais" => Ok(GameMode::Marseillais),
+            "progressive" => Ok(GameMode::Progressive),
+            "monster" => Ok(GameMode::Monster),
+            "dark" => Ok(GameMode::Dark),
             _ => err!("Invalid game mode '{value}'"),
         }
     }
@@ -53,6 +65,12 @@ impl std::fmt::Display for GameMode {
             GameMode::RacingKings => write!(f, "Racing Kings"),
             GameMode::ThreeCheck => write!(f, "Three-check"),
             GameMode::FiveCheck => write!(f, "Five-check"),
+            GameMode::Crazyhouse => write!(f, "Crazyhouse"),
+            GameMode::Duck => write!(f, "Duck"),
+            GameMode::Marseillais => write!(f, "Marseillais"),
+            GameMode::Progressive => write!(f, "Progressive"),
+            GameMode::Monster => write!(f, "Monster"),
+            GameMode::Dark => write!(f, "Dark"),
         }
     }
 }
\ No newline at end of file
@@ -1,3 +1,12 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::Sender;
+
+use instant::Duration;
+
+use crate::MoveInfo;
+use crate::searcher::GAME_OVER_SCORE;
+
 // Type for storing the value of a position, must allow negative values
 pub type Centipawns = i32;
 
@@ -21,3 +30,73 @@ impl std::fmt::Display for SearchTimeout {
     }
 }
 impl std::error::Error for SearchTimeout { }
+
+
+/// Limits and controls for a search. The search stops as soon as any of the limits is reached.
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    /// Maximum depth of the iterative deepening search
+    pub max_depth: Depth,
+    /// Maximum time to search. `None` means that there is no time limit
+    pub max_time: Option<Duration>,
+    /// Set this flag to `true` (from another thread) to stop the search as soon as possible.
+    /// The best move found so far is returned.
+    pub stop_flag: Option<Arc<AtomicBool>>,
+    /// If set, a `SearchInfo` is sent after each completed iteration of the iterative deepening search
+    pub info_sender: Option<Sender<SearchInfo>>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            max_depth: Depth::MAX,
+            max_time: None,
+            stop_flag: None,
+            info_sender: None,
+        }
+    }
+}
+
+
+/// Progress report of a search, produced after each completed iteration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// Depth of the completed iteration
+    pub depth: Depth,
+    /// Score of the position, from the point of view of the player to move
+    pub score: Centipawns,
+    /// Number of nodes searched since the start of the search
+    pub nodes: u64,
+    /// Time elapsed since the start of the search
+    pub elapsed: Duration,
+    /// Principal variation, starting with the best move
+    pub pv: Vec<MoveInfo>,
+}
+
+impl SearchInfo {
+    /// If the score is a forced game over, returns the number of moves until the game ends.
+    /// The value is positive if the player to move wins, and negative if they lose.
+    pub fn mate_in(&self) -> Option<i32> {
+        let plies = -(self.score.abs() + GAME_OVER_SCORE);
+        if plies < 200 {
+            let moves = (plies + 1) / 2;
+            Some(if self.score > 0 { moves } else { -moves })
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for SearchInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let score_str = {
+            if let Some(moves) = self.mate_in() { format!("MATE {moves}") }
+            else { format!("cp {:<4}", self.score) }
+        };
+        write!(f, "Depth {:<2} Score: {score_str} [nodes: {}] PV:", self.depth, self.nodes)?;
+        for mv in &self.pv {
+            write!(f, " {mv}")?;
+        }
+        Ok(())
+    }
+}
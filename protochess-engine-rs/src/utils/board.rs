@@ -38,3 +38,17 @@ pub fn distance_to_one<F>(x_start: BCoord, y_start: BCoord, board: &Bitboard, ge
     }
     isize::MAX
 }
+
+// Squares passed when moving in a straight line from one index to another (excluding both ends).
+// The steps are the shortest delta that divides the move, so jumping twice like a knight passes 1 square
+pub fn squares_between(from: BIndex, to: BIndex) -> impl Iterator<Item = BIndex> {
+    let (x1, y1) = from_index(from);
+    let (x2, y2) = from_index(to);
+    let (dx, dy) = (x2 as i16 - x1 as i16, y2 as i16 - y1 as i16);
+    let (mut a, mut b) = (dx.abs(), dy.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let steps = a;
+    (1..steps).map(move |k| to_index((x1 as i16 + k * dx / steps) as BCoord, (y1 as i16 + k * dy / steps) as BCoord))
+}
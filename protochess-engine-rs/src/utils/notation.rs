@@ -1,5 +1,5 @@
-use crate::types::{BCoord, Move, MoveType};
-use crate::Position;
+use crate::types::{BCoord, BIndex, Move, MoveType};
+use crate::{Position, MoveGen, wrap_res, err, err_assert};
 
 use super::from_index;
 
@@ -16,16 +16,41 @@ pub fn tuple_to_rank_file((x, y): (BCoord, BCoord)) -> String {
 /// Converts the move to user-friendly algebraic notation
 /// **IMPORTANT:** Call this **before** making the move
 pub fn get_algebraic_notation(pos: &mut Position, mv: Move, all_moves: &[Move]) -> String {
+    if let Some(neutral_to) = mv.get_neutral_to() {
+        // Write the move as if there were no neutral pieces, followed by the destination of the neutral piece,
+        // like "e4,@d5". The moves with the same neutral destination are generated one after another
+        let mut base_moves: Vec<Move> = all_moves.iter().map(|m| m.without_neutral_move()).collect();
+        base_moves.dedup();
+        let base = get_algebraic_notation(pos, mv.without_neutral_move(), &base_moves);
+        return format!("{base},@{}", tuple_to_rank_file(from_index(neutral_to)));
+    }
     if mv.is_castling() {
         return castling_notation(mv, all_moves);
     }
+    if let Some(piece_id) = mv.get_drop_piece() {
+        // Drops use the uppercase id for both players, like "P@e4" or "N@f3"
+        let to = tuple_to_rank_file(from_index(mv.get_to()));
+        return format!("{}@{to}", piece_id.to_ascii_uppercase());
+    }
     let piece = pos.piece_at(mv.get_from()).unwrap();
     let prefix = piece.get_notation_prefix();
-    let disamb = disambiguate(pos, mv, all_moves);
+    // Like pawns in standard chess, captures by pieces without prefix always include the origin file
+    let file_required = prefix.is_empty() && mv.is_capture();
+    let disamb = disambiguate(pos, mv, all_moves, file_required);
     
-    let capture = if mv.is_capture() { "x" } else { "" };
+    let capture = {
+        if let Some(intermediate) = mv.get_intermediate() {
+            // Multi-leg moves include the square of the first leg, like "Lxf5xf6" or "Lxf5-f6"
+            let second_leg = if mv.get_target() == mv.get_to() { "x" } else { "-" };
+            format!("x{}{second_leg}", tuple_to_rank_file(from_index(intermediate)))
+        } else if mv.is_capture() {
+            "x".to_string()
+        } else {
+            "".to_string()
+        }
+    };
     
-    let to = tuple_to_rank_file(from_index(mv.get_to()));
+    let to = tuple_to_rank_file(from_index(notation_destination(mv)));
     
     let promo = {
         if mv.is_promotion() {
@@ -41,6 +66,218 @@ pub fn get_algebraic_notation(pos: &mut Position, mv: Move, all_moves: &[Move])
     
     format!("{prefix}{disamb}{capture}{to}{promo}{ep}")
 }
+
+/// Converts a move in algebraic notation (SAN) to the matching legal move in the current position.
+/// Accepts the notation produced by `get_algebraic_notation()`, as well as the common variations:
+/// check and annotation suffixes (`+`, `#`, `!`, `?`), redundant disambiguation, castling with zeros
+/// (`0-0`) and promotions without `=` (`e8Q`). Castling is `O-O` (towards the east, or the north for pieces that
+/// castle vertically) or `O-O-O`, followed by the rank of the piece (`O-O(3)`) or the destination of the piece
+/// and the square of its partner (`O-O(g1,h1)`) when needed. Drops are written as `P@e4`, and multi-leg moves as
+/// `Lxf5xf6` (capturing on both legs) or `Lxf5-f6` (capturing only on the first leg). Rifle captures are written
+/// as if the piece moved to the square of the victim, like `Rxa8`. Moves that relocate a neutral piece end with its
+/// destination, like `e4,@d5`.
+pub fn parse_algebraic_notation(pos: &mut Position, notation: &str) -> wrap_res!(Move) {
+    let mut legal_moves = MoveGen::get_legal_moves(pos);
+    
+    let mut san = notation.trim();
+    if let Some(stripped) = san.strip_suffix("e.p.") {
+        san = stripped.trim_end();
+    }
+    san = san.trim_end_matches(['+', '#', '!', '?']);
+    if let Some((base, square)) = san.split_once(",@") {
+        let neutral_to = parse_square(&square[..square.len().min(1)], square.get(1..).unwrap_or(""))
+            .ok_or_else(|| format!("Invalid move notation: '{notation}'"))?;
+        legal_moves.retain(|mv| mv.get_neutral_to().is_some_and(|to| from_index(to) == neutral_to));
+        san = base;
+    } else {
+        legal_moves.retain(|mv| mv.get_neutral_to().is_none());
+    }
+    err_assert!(!san.is_empty(), "Invalid move notation: '{notation}'");
+    
+    let candidates: Vec<Move> = {
+        if let Some((piece, square)) = san.split_once('@') {
+            let to = parse_square(&square[..square.len().min(1)], square.get(1..).unwrap_or(""))
+                .ok_or_else(|| format!("Invalid move notation: '{notation}'"))?;
+            legal_moves.into_iter()
+                .filter(|mv| from_index(mv.get_to()) == to)
+                .filter(|mv| mv.get_drop_piece().is_some_and(|id| piece.eq_ignore_ascii_case(&id.to_string())))
+                .collect()
+        } else if let Some(castling) = parse_castling(san) {
+            let (move_type, disambiguation) = castling?;
+            legal_moves.into_iter()
+                .filter(|mv| mv.get_move_type() == move_type && disambiguation.matches(*mv))
+                .collect()
+        } else {
+            let san = SanMove::parse(san).ok_or_else(|| format!("Invalid move notation: '{notation}'"))?;
+            legal_moves.into_iter()
+                .filter(|mv| san.matches(pos, *mv))
+                .collect()
+        }
+    };
+    match candidates.len() {
+        0 => err!("Illegal move: '{notation}'"),
+        1 => Ok(candidates[0]),
+        _ => err!("Ambiguous move: '{notation}'"),
+    }
+}
+
+/// Distinguishes between several castling moves of the same type
+enum CastlingDisambiguation {
+    None,
+    /// Rank of the castling piece, like in `O-O(3)`
+    Rank(BCoord),
+    /// Destination of the castling piece and square of its partner, like in `O-O(g1,h1)`
+    Squares((BCoord, BCoord), (BCoord, BCoord)),
+}
+
+impl CastlingDisambiguation {
+    fn matches(&self, mv: Move) -> bool {
+        match self {
+            CastlingDisambiguation::None => true,
+            CastlingDisambiguation::Rank(rank) => from_index(mv.get_from()).1 == *rank,
+            CastlingDisambiguation::Squares(to, partner) => from_index(mv.get_to()) == *to && from_index(mv.get_target()) == *partner,
+        }
+    }
+}
+
+/// Returns `None` if the notation is not a castling move, or the type of castling and its disambiguation
+/// (for the `O-O(3)` and `O-O(g1,h1)` forms)
+fn parse_castling(san: &str) -> Option<wrap_res!(MoveType, CastlingDisambiguation)> {
+    let (base, disambiguation) = {
+        if let (Some(open), true) = (san.find('('), san.ends_with(')')) {
+            (&san[..open], Some(&san[open+1..san.len()-1]))
+        } else {
+            (san, None)
+        }
+    };
+    let move_type = match base.replace('0', "O").as_str() {
+        "O-O" => MoveType::KingsideCastle,
+        "O-O-O" => MoveType::QueensideCastle,
+        _ => return None,
+    };
+    let square = |sq: &str| parse_square(&sq[..sq.len().min(1)], sq.get(1..).unwrap_or(""));
+    let disambiguation = match disambiguation {
+        None => CastlingDisambiguation::None,
+        Some(squares) if squares.contains(',') => {
+            let (to, partner) = squares.split_once(',').unwrap();
+            match (square(to.trim()), square(partner.trim())) {
+                (Some(to), Some(partner)) => CastlingDisambiguation::Squares(to, partner),
+                _ => return Some(Err(format!("Invalid castling squares in '{san}'"))),
+            }
+        },
+        Some(rank) => match rank.parse::<BCoord>() {
+            Ok(r) if r >= 1 => CastlingDisambiguation::Rank(r - 1),
+            _ => return Some(Err(format!("Invalid castling rank in '{san}'"))),
+        },
+    };
+    Some(Ok((move_type, disambiguation)))
+}
+
+/// A non-castling move in algebraic notation, split into its parts
+struct SanMove<'a> {
+    /// Piece prefix followed by the (optional) disambiguation
+    head: &'a str,
+    is_capture: bool,
+    to: (BCoord, BCoord),
+    /// Square captured by the first leg of a multi-leg move
+    intermediate: Option<(BCoord, BCoord)>,
+    promotion: Option<&'a str>,
+}
+
+impl<'a> SanMove<'a> {
+    fn parse(san: &'a str) -> Option<Self> {
+        // Promotion: "e8=Q" or "e8Q"
+        let (body, promotion) = {
+            if let Some((body, promo)) = san.split_once('=') {
+                (body, Some(promo))
+            } else {
+                let last_digit = san.rfind(|c: char| c.is_ascii_digit())?;
+                let (body, promo) = san.split_at(last_digit + 1);
+                (body, if promo.is_empty() { None } else { Some(promo) })
+            }
+        };
+        if promotion == Some("") {
+            return None;
+        }
+        // Destination square: a file letter followed by the rank number
+        let rank_start = body.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let file_start = rank_start.checked_sub(1)?;
+        let to = parse_square(&body[file_start..rank_start], &body[rank_start..])?;
+        
+        let head = &body[..file_start];
+        let (head, is_capture, second_leg) = match (head.strip_suffix('x'), head.strip_suffix('-')) {
+            (Some(head), _) => (head, true, true),
+            (_, Some(head)) => (head, false, true),
+            _ => (head, false, false),
+        };
+        // Multi-leg moves: the first leg is a capture, like in "Lxf5xf6" or "Lxf5-f6"
+        let intermediate = {
+            let rank_start = head.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            let mid_start = rank_start.checked_sub(2).filter(|i| head[*i..].starts_with('x'));
+            match mid_start {
+                Some(i) => parse_square(&head[i+1..rank_start], &head[rank_start..]).map(|sq| (i, sq)),
+                None => None,
+            }
+        };
+        match intermediate {
+            Some((i, square)) => Some(SanMove { head: &head[..i], is_capture, to, intermediate: Some(square), promotion }),
+            None if second_leg && !is_capture => None,
+            None => Some(SanMove { head, is_capture, to, intermediate: None, promotion }),
+        }
+    }
+    
+    fn matches(&self, pos: &Position, mv: Move) -> bool {
+        if mv.is_castling() || mv.is_drop() || from_index(notation_destination(mv)) != self.to {
+            return false;
+        }
+        // In multi-leg moves, `is_capture` refers to the second leg
+        let is_capture = match mv.get_intermediate() {
+            Some(intermediate) => mv.get_target() != intermediate,
+            None => mv.is_capture(),
+        };
+        if is_capture != self.is_capture || mv.get_intermediate().map(from_index) != self.intermediate {
+            return false;
+        }
+        match (self.promotion, mv.get_promotion_piece()) {
+            (None, None) => {},
+            (Some(promo), Some(id)) => {
+                let prefix = pos.lookup_piece(id).map(|p| p.get_notation_prefix()).unwrap_or_default();
+                if prefix != promo && !promo.eq_ignore_ascii_case(&id.to_string()) {
+                    return false;
+                }
+            },
+            _ => return false,
+        }
+        let prefix = match pos.piece_at(mv.get_from()) {
+            Some(piece) => piece.get_notation_prefix(),
+            None => return false,
+        };
+        match self.head.strip_prefix(prefix.as_str()) {
+            Some(disamb) => disambiguation_matches(disamb, from_index(mv.get_from())),
+            None => false,
+        }
+    }
+}
+
+/// Returns `true` if the disambiguation (file and/or rank, possibly empty) matches the origin square
+fn disambiguation_matches(disamb: &str, from: (BCoord, BCoord)) -> bool {
+    let mut rank = disamb;
+    if let Some(file) = disamb.chars().next().filter(|c| c.is_ascii_lowercase()) {
+        if file as u32 != 'a' as u32 + from.0 as u32 {
+            return false;
+        }
+        rank = &disamb[1..];
+    }
+    rank.is_empty() || rank.parse::<BCoord>().ok() == Some(from.1 + 1)
+}
+
+/// Converts a file letter and a rank number (like `"e"` and `"4"`) to (x, y) coordinates
+fn parse_square(file: &str, rank: &str) -> Option<(BCoord, BCoord)> {
+    let file = file.chars().next().filter(|c| ('a'..='p').contains(c))?;
+    let rank = rank.parse::<BCoord>().ok().filter(|r| (1..=16).contains(r))?;
+    Some((file as BCoord - b'a', rank - 1))
+}
+
 pub fn add_suffix(mv: String, suf: &str) -> String {
     if mv.ends_with(" e.p.") {
         mv.replace(" e.p.", format!("{suf} e.p.").as_str())
@@ -50,38 +287,41 @@ pub fn add_suffix(mv: String, suf: &str) -> String {
 }
 
 fn castling_notation(mv: Move, all_moves: &[Move]) -> String {
-    let mut kingside_castles = 0;
-    let mut queenside_castles = 0;
-    for m in all_moves {
-        let ty = m.get_move_type();
-        if ty == MoveType::KingsideCastle {
-            kingside_castles += 1;
-        } else if ty == MoveType::QueensideCastle {
-            queenside_castles += 1;
-        }
-    }
-    
+    let base = match mv.get_move_type() {
+        MoveType::KingsideCastle => "O-O",
+        MoveType::QueensideCastle => "O-O-O",
+        _ => panic!("Not a castling move"),
+    };
+    let same_type: Vec<&Move> = all_moves.iter().filter(|m| m.get_move_type() == mv.get_move_type()).collect();
     let mv_rank = from_index(mv.get_from()).1;
-    if mv.get_move_type() == MoveType::KingsideCastle {
-        if kingside_castles > 1 { format!("O-O({})", mv_rank+1) }
-        else { "O-O".to_string() }
-    } else if mv.get_move_type() == MoveType::QueensideCastle {
-        if queenside_castles > 1 { format!("O-O-O({})", mv_rank+1) }
-        else { "O-O-O".to_string() }
+    if same_type.len() <= 1 {
+        base.to_string()
+    } else if same_type.iter().filter(|m| from_index(m.get_from()).1 == mv_rank).count() <= 1 {
+        // Several pieces can castle, but only one on this rank
+        format!("{base}({})", mv_rank + 1)
     } else {
-        panic!("Not a castling move");
+        // Several partners or destinations (like in free castling)
+        let to = tuple_to_rank_file(from_index(mv.get_to()));
+        let partner = tuple_to_rank_file(from_index(mv.get_target()));
+        format!("{base}({to},{partner})")
     }
 }
 
+/// Returns the square written as the destination of a move: the victim in rifle captures, where the piece doesn't move
+fn notation_destination(mv: Move) -> BIndex {
+    if mv.is_rifle_capture() { mv.get_target() } else { mv.get_to() }
+}
+
 /// Returns the necessary disambiguation for the move
-fn disambiguate(pos: &mut Position, mv: Move, all_moves: &[Move]) -> String {
+/// If `file_required` is `true`, the file of origin is always included
+fn disambiguate(pos: &mut Position, mv: Move, all_moves: &[Move], file_required: bool) -> String {
     let from = from_index(mv.get_from());
     let mv_piece = pos.piece_at(mv.get_from()).unwrap().get_piece_id();
     let mut print_rank = false;
-    let mut print_file = false;
+    let mut print_file = file_required;
     
     for m in all_moves {
-        if m.get_to() == mv.get_to() && m.get_from() != mv.get_from() 
+        if notation_destination(*m) == notation_destination(mv) && m.get_from() != mv.get_from() && !m.is_drop()
         && pos.piece_at(m.get_from()).unwrap().get_piece_id() == mv_piece {
             // Got a match, determine if we need to disambiguate rank or file
             let m_from = from_index(m.get_from());
@@ -16,12 +16,8 @@ pub fn perft(position: &mut Position, depth: Depth) -> usize {
         if !MoveGen::make_move_if_legal(mv, position) {
             continue;
         }
-        // Check for game over
-        if position.leader_is_captured() || position.piece_is_on_winning_square() {
-            position.unmake_move();
-            continue;
-        }
-        if position.global_rules.checks_to_lose != 0 && MoveGen::in_check(position) && position.increment_num_checks() {
+        // Check for game over (draws are ignored, like in standard perft)
+        if position.game_end(false, false, None).is_some() {
             position.unmake_move();
             continue;
         }
@@ -48,16 +44,21 @@ pub fn perft_divide(position: &mut Position, depth: Depth) -> usize {
             if let Some(p) = promo { p.to_string() }
             else { "".to_string() }
         };
+        let mv_str = {
+            if mv.is_drop() { mv.to_string() }
+            else { format!("{}{}{}", to_rank_file(x,y), to_rank_file(x2,y2), promo_str) }
+        };
         if depth == 1 {
             nodes += 1;
-            printing.push(format!("{}{}{}: 1", to_rank_file(x,y), to_rank_file(x2,y2), promo_str));
+            printing.push(format!("{mv_str}: 1"));
         } else {
             position.make_move(mv);
-            let plus = perft(position, depth - 1);
+            // Moves that end the game have no children, like in perft()
+            let plus = if position.game_end(false, false, None).is_some() { 0 } else { perft(position, depth - 1) };
             nodes += plus;
             position.unmake_move();
             //Print nodes
-            printing.push(format!("{}{}{}: {}", to_rank_file(x,y), to_rank_file(x2,y2), promo_str, plus));
+            printing.push(format!("{mv_str}: {plus}"));
         }
     }
     printing.sort();
@@ -0,0 +1,154 @@
+extern crate protochess_engine_rs;
+
+#[cfg(test)]
+mod betza {
+    use protochess_engine_rs::PieceDefinition;
+    use protochess_engine_rs::piece::PieceFactory;
+    use protochess_engine_rs::piece::betza::{to_betza, from_betza};
+    use protochess_engine_rs::utils::debug::eq_anyorder;
+
+    #[test]
+    fn standard_pieces_to_betza() {
+        let factory = PieceFactory::default();
+        assert_eq!(to_betza(&factory.make_king(8, 8)), "K");
+        assert_eq!(to_betza(&factory.make_queen()), "Q");
+        assert_eq!(to_betza(&factory.make_rook()), "R");
+        assert_eq!(to_betza(&factory.make_bishop()), "B");
+        assert_eq!(to_betza(&factory.make_knight()), "N");
+        // Black pawns move south, but "forward" is relative to the owner
+        assert_eq!(to_betza(&factory.make_pawn(true, 8, 8)), "fmWfcFifmnD");
+        assert_eq!(to_betza(&factory.make_pawn(false, 8, 8)), "fmWfcFifmnD");
+    }
+
+    #[test]
+    fn custom_pieces_to_betza() {
+        // Archbishop (bishop + knight)
+        let archbishop = PieceDefinition {
+            ids: [Some('A'), Some('a')],
+            attack_northeast: true, attack_northwest: true, attack_southeast: true, attack_southwest: true,
+            translate_northeast: true, translate_northwest: true, translate_southeast: true, translate_southwest: true,
+            attack_jump_deltas: vec![(1, 2), (1, -2), (-1, 2), (-1, -2), (2, 1), (2, -1), (-2, 1), (-2, -1)],
+            translate_jump_deltas: vec![(1, 2), (1, -2), (-1, 2), (-1, -2), (2, 1), (2, -1), (-2, 1), (-2, -1)],
+            ..Default::default()
+        };
+        assert_eq!(to_betza(&archbishop), "BN");
+        
+        // Moves like a rook, captures like a bishop. Also has a limited knightrider move forward
+        let ride = |dx: i8, dy: i8| (1..=3).map(|i| (i * dx, i * dy)).collect::<Vec<_>>();
+        let custom = PieceDefinition {
+            ids: [Some('X'), None],
+            translate_north: true, translate_south: true, translate_east: true, translate_west: true,
+            attack_northeast: true, attack_northwest: true, attack_southeast: true, attack_southwest: true,
+            translate_sliding_deltas: vec![ride(1, 2), ride(-1, 2)],
+            ..Default::default()
+        };
+        assert_eq!(to_betza(&custom), "mRcBffmN3");
+    }
+    
+    #[test]
+    fn parse_betza() {
+        // Moves like a wazir, captures like a pawn and has an initial double step
+        let piece = from_betza("WfcFifmnD", 0, 8, 8).unwrap();
+        assert!(eq_anyorder(&piece.translate_jump_deltas, &[(0, 1), (0, -1), (1, 0), (-1, 0)]));
+        assert!(eq_anyorder(&piece.attack_jump_deltas, &[(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (-1, 1)]));
+        assert_eq!(piece.double_jump_squares.len(), 16);
+        assert!(piece.double_jump_squares.contains(&(3, 1)));
+        assert!(!piece.can_slide_main_direction() && !piece.has_sliding_deltas());
+        
+        // Same piece for black: forward is south
+        let piece = from_betza("WfcFifmnD", 1, 8, 8).unwrap();
+        assert!(eq_anyorder(&piece.attack_jump_deltas, &[(0, 1), (0, -1), (1, 0), (-1, 0), (1, -1), (-1, -1)]));
+        assert!(piece.double_jump_squares.contains(&(3, 6)));
+        
+        // Nightrider
+        let piece = from_betza("NN", 0, 8, 8).unwrap();
+        assert_eq!(piece.translate_sliding_deltas.len(), 8);
+        assert_eq!(piece.attack_sliding_deltas.len(), 8);
+        assert!(piece.translate_sliding_deltas.iter().any(|run| run[0] == (1, 2) && run[2] == (3, 6)));
+        assert!(piece.translate_jump_deltas.is_empty() && piece.attack_jump_deltas.is_empty());
+        
+        // Moves forward like a rook, captures sideways like a bishop
+        let piece = from_betza("fmRcB", 0, 8, 8).unwrap();
+        assert!(piece.translate_north && !piece.translate_south && !piece.attack_north);
+        assert!(piece.attack_northeast && piece.attack_southwest && !piece.translate_northeast);
+        
+        // Short rook: the limited range uses the built-in slides, unless the moves and captures disagree
+        let piece = from_betza("W2", 0, 8, 8).unwrap();
+        assert!(piece.translate_north && piece.attack_west && piece.range_north == 2 && piece.range_west == 2);
+        assert!(!piece.has_sliding_deltas());
+        assert_eq!(to_betza(&piece), "W2");
+        let piece = from_betza("mW2cW3", 0, 8, 8).unwrap();
+        assert!(piece.translate_north && !piece.attack_north && piece.range_north == 2);
+        assert_eq!(piece.attack_sliding_deltas.len(), 4);
+        assert_eq!(to_betza(&piece), "mW2cW3");
+        // Initial moves that are not double steps are only available while the piece has not moved
+        let piece = from_betza("fmWfcFifmnH", 0, 8, 8).unwrap();
+        assert_eq!(piece.initial_moves, vec![vec![(0, 1), (0, 2), (0, 3)]]);
+        assert!(piece.double_jump_squares.is_empty());
+        assert_eq!(to_betza(&piece), "fmWfcFifmnH");
+        let piece = from_betza("KimN", 0, 8, 8).unwrap();
+        assert_eq!(piece.initial_moves.len(), 8);
+        assert_eq!(to_betza(&piece), "KimN");
+        assert!(from_betza("imnN", 0, 8, 8).is_err());
+        
+        assert!(from_betza("WpR", 0, 8, 8).is_err());
+        assert!(from_betza("X", 0, 8, 8).is_err());
+        assert!(from_betza("fm", 0, 8, 8).is_err());
+        assert!(from_betza("icD", 0, 8, 8).is_err());
+        
+        // Lion: leaps up to 2 squares, or captures an adjacent piece and makes another king step
+        let lion = from_betza("KNADcaK", 0, 8, 8).unwrap();
+        assert_eq!(lion.multi_leg_deltas.len(), 64);
+        assert!(lion.multi_leg_deltas.contains(&((1, 1), (-1, -1))));
+        assert_eq!(to_betza(&lion), "KDNAcaK");
+        assert_eq!(to_betza(&from_betza("fcaN", 0, 8, 8).unwrap()), "fcaN");
+        // Only multi-leg moves with a capturing first leg and an unmodified second leg are supported
+        let unsupported = |betza: &str| from_betza(betza, 0, 8, 8).unwrap_err().contains("not supported");
+        assert!(from_betza("maK", 0, 8, 8).unwrap_err().contains("capturing first leg"));
+        assert!(from_betza("aW", 0, 8, 8).unwrap_err().contains("capturing first leg"));
+        assert!(from_betza("caR", 0, 8, 8).unwrap_err().contains("only supported for leapers"));
+        assert!(unsupported("camK"));
+        assert!(unsupported("mafsW"));
+        assert!(unsupported("cafW"));
+    }
+    
+    #[test]
+    fn standard_pieces_round_trip() {
+        let factory = PieceFactory::default();
+        let pieces = [
+            (factory.make_king(8, 8), 0),
+            (factory.make_queen(), 0),
+            (factory.make_rook(), 0),
+            (factory.make_bishop(), 0),
+            (factory.make_knight(), 0),
+            (factory.make_pawn(true, 8, 8), 0),
+            (factory.make_pawn(false, 8, 8), 1),
+        ];
+        for (piece, player) in pieces {
+            let betza = to_betza(&piece);
+            let parsed = from_betza(&betza, player, 8, 8).unwrap();
+            // Copy the fields that are not related to movement
+            let parsed = PieceDefinition {
+                ids: piece.ids,
+                notation_prefix: piece.notation_prefix.clone(),
+                is_leader: piece.is_leader,
+                castle_files: piece.castle_files,
+                is_castle_rook: piece.is_castle_rook,
+                explode_on_capture: piece.explode_on_capture,
+                explosion_deltas: piece.explosion_deltas.clone(),
+                immune_to_explosion: piece.immune_to_explosion,
+                promotion_squares: piece.promotion_squares.clone(),
+                promo_vals: piece.promo_vals.clone(),
+                optional_promotion_squares: piece.optional_promotion_squares.clone(),
+                promote_to_captured_only: piece.promote_to_captured_only,
+                win_squares: piece.win_squares.clone(),
+                drop_squares: piece.drop_squares.clone(),
+                allowed_squares: piece.allowed_squares.clone(),
+                resets_move_clock: piece.resets_move_clock,
+                ..parsed
+            };
+            assert!(piece.eq_ignore_order(&parsed), "Round trip failed for '{}'", betza);
+            assert_eq!(to_betza(&parsed), betza);
+        }
+    }
+}
@@ -107,6 +107,24 @@ mod custom_pieces {
         }
     }
     
+    #[test]
+    fn limited_range_rook() {
+        let dims = BDimensions::new_without_walls(8, 8).unwrap();
+        let factory = PieceFactory::default();
+        let rook = Piece::new(factory.make_rook(), 0, &dims);
+        let mut short_rook = factory.make_rook();
+        short_rook.range_north = 2;
+        short_rook.range_south = 2;
+        short_rook.range_east = 2;
+        short_rook.range_west = 2;
+        let short_rook = Piece::new(short_rook, 0, &dims);
+        
+        assert!(short_rook.get_material_score() < rook.get_material_score());
+        assert!(short_rook.get_material_score() > 100);
+        // From d1, the short rook can't reach the center squares
+        assert!(short_rook.get_positional_score::<false>(to_index(3, 0)) < rook.get_positional_score::<false>(to_index(3, 0)));
+    }
+    
     #[test]
     fn piece_factory_queen() {
         let dims = BDimensions::new_without_walls(8, 8).unwrap();
@@ -1,9 +1,15 @@
 #[cfg(test)]
 mod move_generator_test {
+    use std::convert::TryFrom;
+
     use protochess_engine_rs::position::create::position_factory::PositionFactory;
-    use protochess_engine_rs::GameState;
+    use protochess_engine_rs::position::create::fen::FenData;
+    use protochess_engine_rs::{GameState, Piece, PieceDefinition, Position, MoveInfo, RoyaltyPolicy, MovesPerTurn, MakeMoveResultFlag, MakeMoveResultWinner};
     use protochess_engine_rs::move_generator::MoveGen;
-    use protochess_engine_rs::types::{Move, MoveType};
+    use protochess_engine_rs::types::{BDimensions, Move, MoveType};
+    use protochess_engine_rs::utils::{from_index, to_index};
+    use protochess_engine_rs::utils::notation::{parse_algebraic_notation, get_algebraic_notation};
+    use protochess_engine_rs::piece::betza::from_betza;
 
     #[test]
     fn capture_moves() {
@@ -30,6 +36,7 @@ mod move_generator_test {
         assert!(!mv.is_null());
         assert_eq!(mv.get_promotion_piece(), Some('y'));
         
+        assert_eq!(std::mem::size_of::<Move>(), 8);
         assert!(Move::null().is_null());
         assert!(Move::null().get_promotion_piece().is_none());
         
@@ -41,4 +48,898 @@ mod move_generator_test {
         assert!(Move::new(0, 0, 0, MoveType::PromotionCapture, None).is_capture());
         assert!(!Move::new(0, 0, 0, MoveType::Null, None).is_capture());
     }
+    
+    #[test]
+    fn hoppers() {
+        const ORTHOGONAL: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+        const DIAGONAL: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        // Xiangqi cannon: moves like a rook, captures by jumping over a piece
+        let cannon = PieceDefinition {
+            ids: [Some('C'), Some('c')],
+            translate_north: true, translate_south: true, translate_east: true, translate_west: true,
+            attack_hop_deltas: ORTHOGONAL.to_vec(),
+            ..Default::default()
+        };
+        // Grasshopper: moves and captures along queen lines, landing right behind the hurdle
+        let grasshopper = PieceDefinition {
+            ids: [Some('G'), Some('g')],
+            translate_hop_deltas: [ORTHOGONAL, DIAGONAL].concat(),
+            attack_hop_deltas: [ORTHOGONAL, DIAGONAL].concat(),
+            hop_lands_behind_hurdle: true,
+            ..Default::default()
+        };
+        let pieces = [cannon, grasshopper];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        
+        // The cannon needs exactly one piece between it and its target
+        let mut pos = load("k3r3/8/8/4p3/8/8/8/4C1K1 w - - 0 1");
+        assert!(has_move(&mut pos, (4, 0), (4, 7)));
+        assert!(has_move(&mut pos, (4, 0), (4, 3)));
+        assert!(!has_move(&mut pos, (4, 0), (4, 4)));
+        assert!(!has_move(&mut pos, (4, 0), (4, 5)));
+        // Check through a screen: moving the screen along the line doesn't help
+        let mut pos = load("4k3/8/8/4p3/8/8/8/4C1K1 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        assert!(!has_move(&mut pos, (4, 4), (4, 3)));
+        assert!(has_move(&mut pos, (4, 7), (3, 7)));
+        // The grasshopper lands right behind the hurdle
+        let mut pos = load("4k3/8/8/8/8/8/4P3/4G1K1 w - - 0 1");
+        assert!(has_move(&mut pos, (4, 0), (4, 2)));
+        assert!(!has_move(&mut pos, (4, 0), (4, 3)));
+        assert!(!has_move(&mut pos, (4, 0), (5, 1)));
+        let mut pos = load("4k3/4P3/8/8/8/8/8/4G1K1 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        assert!(has_move(&mut pos, (4, 7), (4, 6)));
+        
+        // Check detection agrees with the captures that the enemy can make
+        for fen in [
+            "r1c1kgc1/pp2p1pp/2n5/3G4/2C1P3/8/PP1P1PPP/R1B1KgNR w - - 0 1",
+            "4k3/2g5/1C2p3/8/2Gc4/8/4P3/R3K2R b - - 0 1",
+        ] {
+            assert_check_detection(&mut load(fen));
+        }
+    }
+    
+    #[test]
+    fn regions() {
+        let squares = |xs: std::ops::Range<u8>, ys: std::ops::Range<u8>| {
+            xs.flat_map(|x| ys.clone().map(move |y| (x, y))).collect::<Vec<_>>()
+        };
+        // Elephant: leaps 2 squares diagonally, cannot cross the middle of the board
+        let elephant = PieceDefinition {
+            ids: [Some('E'), Some('e')],
+            translate_jump_deltas: vec![(2, 2), (2, -2), (-2, 2), (-2, -2)],
+            attack_jump_deltas: vec![(2, 2), (2, -2), (-2, 2), (-2, -2)],
+            allowed_squares: [squares(0..8, 0..4), squares(0..8, 4..8)],
+            ..Default::default()
+        };
+        let mut state = GameState::default();
+        state.initial_state.piece_types.push(elephant);
+        // The king stays in the palace
+        for def in state.initial_state.piece_types.iter_mut().filter(|def| def.is_leader) {
+            def.allowed_squares = [squares(3..6, 0..3), squares(3..6, 5..8)];
+        }
+        state.initial_fen = Some("3k4/8/8/5e2/8/2EK4/8/8 w - - 0 1".to_string());
+        let mut pos = PositionFactory::default().set_state(state, None).unwrap().unwrap();
+        
+        assert_eq!(targets(&mut pos, (2, 2)), vec![(0, 0), (4, 0)]);
+        assert_eq!(targets(&mut pos, (3, 2)), vec![(3, 1), (4, 1), (4, 2)]);
+        // The black elephant cannot attack a square outside its region
+        assert!(!MoveGen::in_check(&mut pos));
+        for mv in MoveGen::get_legal_moves(&mut pos) {
+            pos.make_move(mv);
+            assert_eq!(MoveGen::in_check(&mut pos), leader_attacked(&mut pos), "{mv}");
+            pos.unmake_move();
+        }
+        // Pieces cannot be placed outside their region
+        assert!(pos.public_add_piece('E', to_index(2, 5), false).is_err());
+        assert!(pos.public_add_piece('E', to_index(2, 1), false).is_ok());
+        
+        // Slides cannot land outside the region, but can go through it
+        let chariot = PieceDefinition {
+            ids: [Some('C'), Some('c')],
+            attack_north: true, attack_south: true, attack_east: true, attack_west: true,
+            translate_north: true, translate_south: true, translate_east: true, translate_west: true,
+            allowed_squares: [[squares(0..8, 0..4), squares(0..8, 6..8)].concat(), squares(0..8, 4..8)],
+            ..Default::default()
+        };
+        let mut state = GameState::default();
+        state.initial_state.piece_types.push(chariot.clone());
+        state.initial_fen = Some("3k4/8/8/p7/8/8/8/C2K4 w - - 0 1".to_string());
+        let mut pos = PositionFactory::default().set_state(state, None).unwrap().unwrap();
+        assert_eq!(targets(&mut pos, (0, 0)), vec![(0, 1), (0, 2), (0, 3), (1, 0), (2, 0)]);
+        pos.public_remove_piece(to_index(0, 4)).unwrap();
+        assert_eq!(targets(&mut pos, (0, 0)), vec![(0, 1), (0, 2), (0, 3), (0, 6), (0, 7), (1, 0), (2, 0)]);
+        
+        // The material score and the piece-square tables don't depend on the region
+        let dims = BDimensions::new_without_walls(8, 8).unwrap();
+        let rook = Piece::new(PieceDefinition { allowed_squares: Default::default(), ..chariot.clone() }, 0, &dims);
+        let chariot = Piece::new(chariot, 0, &dims);
+        assert_eq!(chariot.get_material_score(), rook.get_material_score());
+        assert_eq!(chariot.get_positional_score::<false>(to_index(0, 0)), rook.get_positional_score::<false>(to_index(0, 0)));
+    }
+    
+    #[test]
+    fn lame_jumps() {
+        // Xiangqi horse: one step orthogonally, then one step diagonally outwards
+        let mut paths = Vec::new();
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            paths.push(vec![(dx, dy), (2 * dx - dy, 2 * dy + dx)]);
+            paths.push(vec![(dx, dy), (2 * dx + dy, 2 * dy - dx)]);
+        }
+        let horse = PieceDefinition {
+            ids: [Some('H'), Some('h')],
+            translate_lame_jumps: paths.clone(),
+            attack_lame_jumps: paths,
+            ..Default::default()
+        };
+        let pieces = [horse];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        
+        // The piece on d5 blocks the jumps to c6 and e6
+        let mut pos = load("7k/8/8/3P4/3H4/8/8/K7 w - - 0 1");
+        assert_eq!(targets(&mut pos, (3, 3)), vec![(1, 2), (1, 4), (2, 1), (4, 1), (5, 2), (5, 4)]);
+        // The attack on the king can be blocked as well
+        let mut pos = load("8/8/4k3/3p4/3H4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/4k3/8/3H4/8/8/K7 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        
+        // Check detection agrees with the captures that the enemy can make
+        assert_check_detection(&mut load("r1b1kh1r/pp1p1ppp/2h5/4p3/2BhP3/2H5/PPP2PPP/R3K1HR w - - 0 1"));
+    }
+    
+    #[test]
+    fn multi_leg_moves() {
+        let mut lion = from_betza("KNADcaK", 0, 8, 8).unwrap();
+        lion.ids = [Some('L'), Some('l')];
+        // Only captures by capturing an adjacent piece first, and then stepping again in the same direction
+        let mut piece = from_betza("mW", 0, 8, 8).unwrap();
+        piece.ids = [Some('X'), Some('x')];
+        piece.multi_leg_deltas = vec![((0, 1), (0, 1)), ((0, -1), (0, -1)), ((1, 0), (1, 0)), ((-1, 0), (-1, 0))];
+        let pieces = [lion, piece];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        
+        // Capture on both legs, then undo it
+        let mut pos = load("7k/8/4p3/4p3/3L4/8/8/K7 w - - 0 1");
+        let zobrist = pos.get_zobrist();
+        let mv = MoveInfo::try_from("d4e5e6").unwrap();
+        assert_eq!(mv.intermediate, Some((4, 4)));
+        assert_eq!(mv.to_string(), "d4e5e6");
+        let result = pos.pub_make_move(&mv);
+        assert_eq!(result.move_notation, Some("Lxe5xe6".to_string()));
+        assert!(pos.piece_at(to_index(4, 4)).is_none());
+        assert_eq!(pos.piece_at(to_index(4, 5)).unwrap().get_piece_id(), 'L');
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        assert_eq!(pos.piece_at(to_index(4, 4)).unwrap().get_piece_id(), 'p');
+        assert_eq!(pos.piece_at(to_index(4, 5)).unwrap().get_piece_id(), 'p');
+        // Capture and return to the starting square
+        let result = pos.pub_make_move(&MoveInfo::try_from("d4e5d4").unwrap());
+        assert_eq!(result.move_notation, Some("Lxe5-d4".to_string()));
+        assert!(pos.piece_at(to_index(4, 4)).is_none());
+        assert_eq!(pos.piece_at(to_index(3, 3)).unwrap().get_piece_id(), 'L');
+        pos.unmake_move();
+        // The notation can be parsed back
+        let mv = parse_algebraic_notation(&mut pos, "Lxe5-f6").unwrap();
+        assert_eq!(mv.get_intermediate(), Some(to_index(4, 4)));
+        assert_eq!(mv.get_to(), to_index(5, 5));
+        assert!(parse_algebraic_notation(&mut pos, "Lxe5xf6").is_err());
+        
+        // The first leg needs a piece to capture
+        let mut pos = load("3k4/8/8/8/3X4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("3k4/3p4/8/8/3X4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("8/3k4/3p4/8/3X4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/3k4/3p4/3X4/8/8/K7 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        // Capturing the leader in the first leg is also a check, if the second leg can be made
+        let mut pos = load("8/8/3P4/3k4/3X4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/8/3k4/3X4/8/8/K7 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        
+        // Check detection agrees with the captures that the enemy can make
+        for fen in [
+            "r3k2r/ppp2ppp/2n1xl2/3p4/3PP3/2NLX3/PPP2PPP/R3K2R w KQkq - 0 1",
+            "4k3/8/3pp3/2p1Lx2/3P4/8/8/4K3 b - - 0 1",
+            "8/8/4k3/4p3/8/x3X3/8/K7 w - - 0 1",
+        ] {
+            assert_check_detection(&mut load(fen));
+        }
+    }
+    
+    #[test]
+    fn bent_riders() {
+        // Griffon: one step diagonally, then rides orthogonally outwards
+        // Aanca: one step orthogonally, then rides diagonally outwards
+        let mut griffon_riders = Vec::new();
+        let mut aanca_riders = Vec::new();
+        for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            griffon_riders.push(((dx, dy), (dx, 0)));
+            griffon_riders.push(((dx, dy), (0, dy)));
+            aanca_riders.push(((dx, 0), (dx, dy)));
+            aanca_riders.push(((0, dy), (dx, dy)));
+        }
+        let griffon = PieceDefinition {
+            ids: [Some('G'), Some('g')],
+            translate_bent_riders: griffon_riders.clone(),
+            attack_bent_riders: griffon_riders,
+            ..Default::default()
+        };
+        let aanca = PieceDefinition {
+            ids: [Some('A'), Some('a')],
+            translate_bent_riders: aanca_riders.clone(),
+            attack_bent_riders: aanca_riders,
+            ..Default::default()
+        };
+        let pieces = [griffon, aanca];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        
+        // The steps are shared by two rides, but each square is only generated once
+        let mut pos = load("7k/8/8/8/3G4/8/8/K7 w - - 0 1");
+        assert_eq!(MoveGen::get_legal_moves(&mut pos).iter().filter(|mv| mv.get_from() == to_index(3, 3)).count(), 24);
+        // A piece on the step blocks both rides, and a piece on a ride blocks the rest of it
+        let mut pos = load("7k/8/8/4p3/3G4/8/8/K7 w - - 0 1");
+        let griffon_targets = targets(&mut pos, (3, 3));
+        assert_eq!(griffon_targets.len(), 18);
+        assert!(griffon_targets.contains(&(4, 4)));
+        let mut pos = load("7k/8/8/6p1/3G4/8/8/K7 w - - 0 1");
+        let griffon_targets = targets(&mut pos, (3, 3));
+        assert_eq!(griffon_targets.len(), 23);
+        assert!(griffon_targets.contains(&(6, 4)));
+        assert!(!griffon_targets.contains(&(7, 4)));
+        let mut pos = load("7k/8/8/8/3A4/8/8/K7 w - - 0 1");
+        assert_eq!(targets(&mut pos, (3, 3)).len(), 24);
+        
+        // Checks on the step and on the ride, which can be blocked on both
+        let mut pos = load("8/8/8/8/8/8/1k6/G6K b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/8/1k6/8/8/8/G6K b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/8/1k6/8/1P6/8/G6K b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/8/1k6/8/8/1p6/G6K b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        let mut pos = load("7G/8/8/8/8/8/8/K5k1 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        let mut pos = load("7G/8/8/8/8/6p1/8/K5k1 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        
+        // Check detection agrees with the captures that the enemy can make
+        for fen in [
+            "r1b1k1ar/pp1p1ppp/2g5/4p3/2BgP3/2G5/PPP2PPP/R3K1AR w - - 0 1",
+            "4k3/8/2p3g1/8/1A6/8/5G2/4K3 b - - 0 1",
+        ] {
+            assert_check_detection(&mut load(fen));
+        }
+    }
+    
+    #[test]
+    fn limited_range_riders() {
+        let mut short_rook = from_betza("W2", 0, 8, 8).unwrap();
+        short_rook.ids = [Some('S'), Some('s')];
+        // Queen with a different range in each direction
+        let mut piece = from_betza("Q", 0, 8, 8).unwrap();
+        piece.ids = [Some('X'), Some('x')];
+        (piece.range_north, piece.range_south, piece.range_east, piece.range_west) = (1, 2, 3, 0);
+        (piece.range_northeast, piece.range_northwest, piece.range_southeast, piece.range_southwest) = (3, 0, 1, 2);
+        let pieces = [short_rook, piece];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        
+        let mut pos = load("7k/8/8/8/3S4/8/8/K7 w - - 0 1");
+        assert_eq!(targets(&mut pos, (3, 3)), vec![(1, 3), (2, 3), (3, 1), (3, 2), (3, 4), (3, 5), (4, 3), (5, 3)]);
+        // Blocked slides still stop at the first piece
+        let mut pos = load("7k/8/8/3p4/3S4/3P4/8/K7 w - - 0 1");
+        assert_eq!(targets(&mut pos, (3, 3)), vec![(1, 3), (2, 3), (3, 4), (4, 3), (5, 3)]);
+        
+        // Checks only within the range
+        let mut pos = load("8/8/3k4/8/3S4/8/8/K7 b - - 0 1");
+        assert!(MoveGen::in_check(&mut pos));
+        let mut pos = load("8/3k4/8/8/3S4/8/8/K7 b - - 0 1");
+        assert!(!MoveGen::in_check(&mut pos));
+        
+        // Check detection agrees with the captures that the enemy can make
+        for fen in [
+            "r3k2r/pp3ppp/2s1x3/8/3S4/2X5/PPP2PPP/R3K2R w KQkq - 0 1",
+            "8/1k6/8/3x4/8/2S5/5X2/6K1 b - - 0 1",
+        ] {
+            assert_check_detection(&mut load(fen));
+        }
+    }
+    
+    #[test]
+    fn initial_moves() {
+        // Pawns with an initial triple step (like in Wildebeest), and a piece with the initial leap of the Courier king
+        let mut white_pawn = from_betza("fmWfcFifmnH", 0, 8, 8).unwrap();
+        white_pawn.ids = [Some('A'), None];
+        let mut black_pawn = from_betza("fmWfcFifmnH", 1, 8, 8).unwrap();
+        black_pawn.ids = [None, Some('a')];
+        let mut courier_king = from_betza("KimN", 0, 8, 8).unwrap();
+        courier_king.ids = [Some('C'), Some('c')];
+        assert_eq!(white_pawn.initial_moves, vec![vec![(0, 1), (0, 2), (0, 3)]]);
+        assert_eq!(courier_king.initial_moves.len(), 8);
+        let pieces = [white_pawn, black_pawn, courier_king];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        let find_move = |pos: &mut Position, from: (u8, u8), to: (u8, u8)| {
+            MoveGen::get_legal_moves(pos).into_iter()
+                .find(|mv| mv.get_from() == to_index(from.0, from.1) && mv.get_to() == to_index(to.0, to.1))
+                .unwrap()
+        };
+        
+        let start_fen = "4k2n/8/8/1a6/8/8/A7/C3K3 w (ALL) - 0 1";
+        let mut pos = load(start_fen);
+        assert_eq!(targets(&mut pos, (0, 1)), vec![(0, 2), (0, 4)]);
+        assert_eq!(targets(&mut pos, (0, 0)), vec![(1, 0), (1, 1), (1, 2), (2, 1)]);
+        // The path of the triple step must be empty
+        let mut blocked = load("4k2n/8/8/1a6/8/A7/A7/C3K3 w (ALL) - 0 1");
+        assert_eq!(targets(&mut blocked, (0, 1)), vec![]);
+        // Pieces that have moved cannot make their initial moves
+        let mut moved = load("4k2n/8/8/1a6/8/8/A7/C3K3 w (e1,e8) - 0 1");
+        assert_eq!(targets(&mut moved, (0, 1)), vec![(0, 2)]);
+        assert_eq!(targets(&mut moved, (0, 0)), vec![(1, 0), (1, 1)]);
+        
+        // The triple step can be captured en passant on the squares that it passes
+        let triple_step = find_move(&mut pos, (0, 1), (0, 4));
+        assert_eq!(triple_step.get_move_type(), MoveType::DoubleJump);
+        pos.make_move(triple_step);
+        assert_eq!(pos.get_ep_squares(), &[to_index(0, 2), to_index(0, 3)]);
+        assert_eq!(pos.get_ep_victim(), to_index(0, 4));
+        let en_passant = find_move(&mut pos, (1, 4), (0, 3));
+        assert!(en_passant.is_capture());
+        assert_eq!(en_passant.get_target(), to_index(0, 4));
+        pos.make_move(en_passant);
+        assert!(pos.piece_at(to_index(0, 4)).is_none());
+        pos.unmake_move();
+        pos.unmake_move();
+        
+        // Moving the piece and coming back removes its initial moves, which changes the zobrist key and the FEN
+        let start_zobrist = pos.get_zobrist();
+        for (from, to) in [((0, 0), (1, 0)), ((7, 7), (6, 5)), ((1, 0), (0, 0)), ((6, 5), (7, 7))] {
+            let mv = find_move(&mut pos, from, to);
+            pos.make_move(mv);
+        }
+        assert_eq!(targets(&mut pos, (0, 0)), vec![(1, 0), (1, 1)]);
+        assert_ne!(pos.get_zobrist(), start_zobrist);
+        let fen = FenData::from(&pos).to_string();
+        assert_ne!(fen, FenData::from(&load(start_fen)).to_string());
+        let mut reloaded = load(&fen);
+        assert_eq!(reloaded.get_zobrist(), pos.get_zobrist());
+        assert_eq!(FenData::from(&reloaded).to_string(), fen);
+        assert_eq!(targets(&mut reloaded, (0, 0)), vec![(1, 0), (1, 1)]);
+        assert_eq!(targets(&mut reloaded, (0, 1)), vec![(0, 2), (0, 4)]);
+        
+        // Making and unmaking the moves restores the zobrist key
+        let mut pos = load(start_fen);
+        for mv in MoveGen::get_legal_moves(&mut pos) {
+            let zobrist = pos.get_zobrist();
+            pos.make_move(mv);
+            for mv2 in MoveGen::get_legal_moves(&mut pos) {
+                let zobrist2 = pos.get_zobrist();
+                pos.make_move(mv2);
+                pos.unmake_move();
+                assert_eq!(pos.get_zobrist(), zobrist2, "{mv} {mv2}");
+            }
+            pos.unmake_move();
+            assert_eq!(pos.get_zobrist(), zobrist, "{mv}");
+        }
+    }
+    
+    #[test]
+    fn multiple_en_passant_squares() {
+        let mut white_pawn = from_betza("fmWfcFifmnH", 0, 8, 8).unwrap();
+        white_pawn.ids = [Some('A'), None];
+        let mut black_pawn = from_betza("fmWfcFifmnH", 1, 8, 8).unwrap();
+        black_pawn.ids = [None, Some('a')];
+        let pieces = [white_pawn, black_pawn];
+        let load = |fen: &str| load_with_pieces(&pieces, fen);
+        let en_passant_captures = |pos: &mut Position| {
+            let mut captures: Vec<_> = MoveGen::get_legal_moves(pos).iter()
+                .filter(|mv| mv.is_capture() && mv.get_target() != mv.get_to())
+                .map(|mv| (from_index(mv.get_from()), from_index(mv.get_to()), from_index(mv.get_target())))
+                .collect();
+            captures.sort_unstable();
+            captures
+        };
+        
+        // Every square skipped by the triple step is an en passant square
+        let mut pos = load("4k3/8/8/8/1a6/1a6/8/A3K3 w (ALL) - 0 1");
+        let triple_step = MoveGen::get_legal_moves(&mut pos).into_iter()
+            .find(|mv| mv.get_from() == to_index(0, 0) && mv.get_to() == to_index(0, 3))
+            .unwrap();
+        pos.make_move(triple_step);
+        assert_eq!(en_passant_captures(&mut pos), vec![((1, 2), (0, 1), (0, 3)), ((1, 3), (0, 2), (0, 3))]);
+        
+        // The squares are stored in the FEN, and loading it gives the same position
+        let fen = FenData::from(&pos).to_string();
+        assert!(fen.contains(" a2,a3(a4) "), "{}", fen);
+        let mut reloaded = load(&fen);
+        assert_eq!(reloaded.get_zobrist(), pos.get_zobrist());
+        assert_eq!(en_passant_captures(&mut reloaded), en_passant_captures(&mut pos));
+        for mv in MoveGen::get_legal_moves(&mut pos).into_iter().filter(|mv| mv.get_target() == to_index(0, 3)) {
+            pos.make_move(mv);
+            assert!(pos.piece_at(to_index(0, 3)).is_none());
+            pos.unmake_move();
+        }
+        
+        // Each en passant square changes the zobrist key
+        let fen = "4k3/8/8/8/Aa6/1a6/8/4K3 b - {} 0 1";
+        let zobrists: Vec<_> = ["-", "a2(a4)", "a3(a4)", "a2,a3(a4)"].iter()
+            .map(|ep| load(&fen.replace("{}", ep)).get_zobrist())
+            .collect();
+        for i in 0..zobrists.len() {
+            for j in 0..i {
+                assert_ne!(zobrists[i], zobrists[j]);
+            }
+        }
+        assert_eq!(load(&fen.replace("{}", "a3,a2(a4)")).get_zobrist(), zobrists[3]);
+        // The victim cannot be guessed with several squares
+        assert!(FenData::parse_fen(&fen.replace("{}", "a2,a3")).is_err());
+        assert!(FenData::parse_fen(&fen.replace("{}", "a2,(a4)")).is_err());
+    }
+    
+    #[test]
+    fn castling_rules() {
+        let load = |fen: &str, width: u8, edit_king: &dyn Fn(&mut PieceDefinition), free_castling: bool| {
+            let mut state = GameState::default();
+            state.initial_state.board_width = width;
+            state.initial_state.global_rules.free_castling = free_castling;
+            for def in state.initial_state.piece_types.iter_mut().filter(|def| def.is_leader) {
+                edit_king(def);
+            }
+            state.initial_fen = Some(fen.to_string());
+            PositionFactory::default().set_state(state, None).unwrap().unwrap()
+        };
+        let castles = |pos: &mut Position| {
+            let mut castles: Vec<_> = MoveGen::get_legal_moves(pos).into_iter()
+                .filter(|mv| mv.is_castling())
+                .map(|mv| (from_index(mv.get_to()), from_index(mv.get_target())))
+                .collect();
+            castles.sort_unstable();
+            castles
+        };
+        let find_castle = |pos: &mut Position, to: (u8, u8), partner: (u8, u8)| {
+            MoveGen::get_legal_moves(pos).into_iter()
+                .find(|mv| mv.is_castling() && from_index(mv.get_to()) == to && from_index(mv.get_target()) == partner)
+                .unwrap()
+        };
+        
+        // Capablanca chess: the king castles 3 squares on 10 files, and the rook lands next to it
+        let capablanca = |def: &mut PieceDefinition| {
+            def.castle_files = Some((2, 8));
+            def.castle_partner_files = Some((3, 7));
+        };
+        let mut pos = load("r4k3r/10/10/10/10/10/10/R4K3R w (ALL) - 0 1", 10, &capablanca, false);
+        assert_eq!(castles(&mut pos), vec![((2, 0), (0, 0)), ((8, 0), (9, 0))]);
+        let zobrist = pos.get_zobrist();
+        let kingside = find_castle(&mut pos, (8, 0), (9, 0));
+        assert_eq!(parse_algebraic_notation(&mut pos, "O-O").unwrap(), kingside);
+        pos.make_move(kingside);
+        assert!(FenData::from(&pos).to_string().starts_with("r4k3r/10/10/10/10/10/10/R6RK1 b "));
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        assert!(FenData::from(&pos).to_string().starts_with("r4k3r/10/10/10/10/10/10/R4K3R w "));
+        // The partner doesn't have to be next to the king, but the paths of both pieces must be empty
+        let mut pos = load("r4k3r/10/10/10/10/10/10/R1N2K3R w (ALL) - 0 1", 10, &capablanca, false);
+        assert_eq!(castles(&mut pos), vec![((8, 0), (9, 0))]);
+        let mut pos = load("r4k3r/10/10/10/10/10/10/RN3K3R w (ALL) - 0 1", 10, &capablanca, false);
+        assert_eq!(castles(&mut pos), vec![((8, 0), (9, 0))]);
+        
+        // Free castling: the king can land on any square up to the rook, which jumps over it
+        let mut pos = load("4k3/8/8/8/8/8/8/R3K2R w (ALL) - 0 1", 8, &|_| {}, true);
+        let king_destinations: Vec<_> = castles(&mut pos).into_iter().map(|(to, _)| to.0).collect();
+        assert_eq!(king_destinations, vec![0, 1, 2, 3, 5, 6, 7]);
+        let zobrist = pos.get_zobrist();
+        let mv = find_castle(&mut pos, (6, 0), (7, 0));
+        assert_eq!(MoveInfo::from(mv).to_string(), "e1g1h1");
+        assert_eq!(MoveInfo::try_from("e1g1h1").unwrap(), mv);
+        assert_eq!(parse_algebraic_notation(&mut pos, "O-O(g1,h1)").unwrap(), mv);
+        assert!(parse_algebraic_notation(&mut pos, "O-O").is_err());
+        let all_moves = MoveGen::get_legal_moves(&mut pos);
+        assert_eq!(get_algebraic_notation(&mut pos, mv, &all_moves), "O-O(g1,h1)");
+        let corner = find_castle(&mut pos, (7, 0), (7, 0));
+        pos.make_move(corner);
+        assert!(FenData::from(&pos).to_string().starts_with("4k3/8/8/8/8/8/8/R5RK b "));
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        
+        // Castling along the file, which cannot go through attacked squares
+        let vertical = |def: &mut PieceDefinition| {
+            def.castle_files = Some((2, 6));
+            def.castle_vertically = true;
+        };
+        let mut pos = load("R7/7k/8/K7/8/8/8/R7 w (ALL) - 0 1", 8, &vertical, false);
+        assert_eq!(castles(&mut pos), vec![((0, 2), (0, 0)), ((0, 6), (0, 7))]);
+        let kingside = find_castle(&mut pos, (0, 6), (0, 7));
+        pos.make_move(kingside);
+        assert!(FenData::from(&pos).to_string().starts_with("8/K6k/R7/8/8/8/8/R7 b "));
+        let mut pos = load("R7/7k/7r/K7/8/8/8/R7 w (ALL) - 0 1", 8, &vertical, false);
+        assert_eq!(castles(&mut pos), vec![((0, 2), (0, 0))]);
+        
+        // The partner files need castle files, and cannot be the same as them
+        let mut state = GameState::default();
+        state.initial_state.piece_types.iter_mut().find(|def| def.is_leader).unwrap().castle_partner_files = Some((3, 5));
+        state.initial_state.piece_types.iter_mut().find(|def| def.is_leader).unwrap().castle_files = Some((3, 6));
+        assert!(PositionFactory::default().set_state(state, None).is_err());
+    }
+    
+    #[test]
+    fn indirect_captures() {
+        let mut withdrawer = from_betza("mQ", 0, 8, 8).unwrap();
+        withdrawer.ids = [Some('W'), Some('w')];
+        withdrawer.capture_by_withdrawal = true;
+        let mut approacher = from_betza("mQ", 0, 8, 8).unwrap();
+        approacher.ids = [Some('A'), Some('a')];
+        approacher.capture_by_approach = true;
+        let mut pincer = from_betza("mR", 0, 8, 8).unwrap();
+        pincer.ids = [Some('X'), Some('x')];
+        pincer.custodian_capture = true;
+        let mut rifle_rook = from_betza("R", 0, 8, 8).unwrap();
+        rifle_rook.ids = [Some('Y'), Some('y')];
+        rifle_rook.rifle_capture = true;
+        let load = |fen: &str, captures_to_hand: bool| {
+            let mut state = GameState::default();
+            state.initial_state.piece_types.extend([withdrawer.clone(), approacher.clone(), pincer.clone(), rifle_rook.clone()]);
+            state.initial_state.global_rules.captures_to_hand = captures_to_hand;
+            state.initial_fen = Some(fen.to_string());
+            PositionFactory::default().set_state(state, None).unwrap().unwrap()
+        };
+        let captures_from = |pos: &mut Position, from: (u8, u8)| {
+            let from = to_index(from.0, from.1);
+            let mut captures: Vec<_> = MoveGen::get_legal_moves(pos).into_iter()
+                .filter(|mv| mv.get_from() == from && mv.is_capture())
+                .map(|mv| from_index(mv.get_to()))
+                .collect();
+            captures.sort_unstable();
+            captures
+        };
+        
+        // Withdrawal: moving straight away from the pawn captures it, and those moves are not quiet
+        let mut pos = load("4k3/8/8/8/3p4/3W4/8/K7 w - - 0 1", false);
+        assert_eq!(captures_from(&mut pos, (3, 2)), vec![(3, 0), (3, 1)]);
+        assert!(MoveGen::get_legal_moves(&mut pos).iter().all(|mv| mv.is_capture() || from_index(mv.get_to()) != (3, 0)));
+        let zobrist = pos.get_zobrist();
+        let result = pos.pub_make_move(&MoveInfo::try_from("d3d1").unwrap());
+        assert_eq!(result.move_notation, Some("Wxd1".to_string()));
+        assert!(pos.piece_at(to_index(3, 3)).is_none());
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        assert_eq!(pos.piece_at(to_index(3, 3)).unwrap().get_piece_id(), 'p');
+        
+        // Approach: moving straight to the square in front of the pawn captures it
+        let mut pos = load("4k3/8/3p4/8/8/3A4/8/K7 w - - 0 1", false);
+        assert_eq!(captures_from(&mut pos, (3, 2)), vec![(3, 4)]);
+        
+        // Custodian capture: both pawns are sandwiched at once, and both go to the hand
+        let mut pos = load("4k3/8/3X4/3p4/1Xp4X/8/8/K7 w - - 0 1", true);
+        let zobrist = pos.get_zobrist();
+        let result = pos.pub_make_move(&MoveInfo::try_from("h4d4").unwrap());
+        assert_eq!(result.move_notation, Some("Xxd4".to_string()));
+        assert!(pos.piece_at(to_index(2, 3)).is_none());
+        assert!(pos.piece_at(to_index(3, 4)).is_none());
+        assert_eq!(pos.pieces[0].lookup_piece('P').unwrap().get_num_in_hand(), 2);
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        assert_eq!(pos.pieces[0].lookup_piece('P').unwrap().get_num_in_hand(), 0);
+        assert_eq!(pos.piece_at(to_index(2, 3)).unwrap().get_piece_id(), 'p');
+        
+        // Rifle capture: the rook stays on its square
+        let mut pos = load("n3k3/8/8/8/8/8/8/Y3K3 w - - 0 1", false);
+        let zobrist = pos.get_zobrist();
+        let rifle_capture = MoveGen::get_legal_moves(&mut pos).into_iter().find(|mv| mv.is_capture()).unwrap();
+        assert_eq!(rifle_capture.get_to(), to_index(0, 0));
+        assert_eq!(MoveInfo::from(rifle_capture).to_string(), "a1a8");
+        let result = pos.pub_make_move(&MoveInfo::try_from("a1a8").unwrap());
+        assert_eq!(result.move_notation, Some("Yxa8".to_string()));
+        assert!(pos.piece_at(to_index(0, 7)).is_none());
+        assert_eq!(pos.piece_at(to_index(0, 0)).unwrap().get_piece_id(), 'Y');
+        pos.unmake_move();
+        assert_eq!(pos.get_zobrist(), zobrist);
+        assert_eq!(parse_algebraic_notation(&mut pos, "Yxa8").unwrap(), rifle_capture);
+        
+        // Indirect captures of the leader are checks
+        let mut pos = load("8/8/3X4/3k4/7X/8/8/K7 b - - 0 1", false);
+        assert!(MoveGen::in_check(&mut pos));
+        let mut pos = load("8/8/8/8/3k4/3W4/8/K7 b - - 0 1", false);
+        assert!(MoveGen::in_check(&mut pos));
+        // Check detection agrees with the captures that the enemy can make
+        for fen in [
+            "8/4k3/8/3w4/3K4/8/8/8 w - - 0 1",
+            "4k3/8/8/8/3Kx3/8/2x5/8 w - - 0 1",
+            "4k3/8/8/8/8/3K4/8/3a4 w - - 0 1",
+            "3xk3/8/2y5/8/3KW3/8/1x6/3a4 w - - 0 1",
+        ] {
+            assert_check_detection(&mut load(fen, false));
+        }
+        
+        // Pieces that explode cannot capture indirectly
+        let mut state = GameState::default();
+        let mut exploding_withdrawer = withdrawer.clone();
+        exploding_withdrawer.explode_on_capture = true;
+        state.initial_state.piece_types.push(exploding_withdrawer);
+        assert!(PositionFactory::default().set_state(state, None).is_err());
+    }
+    
+    #[test]
+    fn royalty_policies() {
+        let mut general = from_betza("K", 0, 8, 8).unwrap();
+        general.ids = [Some('G'), Some('g')];
+        general.is_leader = true;
+        let load = |fen: &str, royalty_policy: RoyaltyPolicy| {
+            let mut state = GameState::default();
+            state.initial_state.piece_types.push(general.clone());
+            state.initial_state.global_rules.royalty_policy = royalty_policy;
+            state.initial_fen = Some(fen.to_string());
+            PositionFactory::default().set_state(state, None).unwrap().unwrap()
+        };
+        let moves_from = |pos: &mut Position| {
+            let mut from: Vec<_> = MoveGen::get_legal_moves(pos).into_iter().map(|mv| from_index(mv.get_from())).collect();
+            from.sort_unstable();
+            from.dedup();
+            from
+        };
+        
+        // One of the two kings is attacked
+        let fen = "2r1k3/8/8/8/8/8/8/2K3K1 w - - 0 1";
+        for policy in [RoyaltyPolicy::AllCaptured, RoyaltyPolicy::AnyCapturedOrAllAttacked] {
+            let mut pos = load(fen, policy);
+            assert!(!MoveGen::in_check(&mut pos));
+            assert_eq!(moves_from(&mut pos), vec![(2, 0), (6, 0)]);
+        }
+        // Every king must be safe, so only the attacked king can move
+        let mut pos = load(fen, RoyaltyPolicy::EverySafe);
+        assert!(MoveGen::in_check(&mut pos));
+        assert_eq!(moves_from(&mut pos), vec![(2, 0)]);
+        
+        // Both kings are attacked
+        let fen = "2r1k1r1/8/8/8/8/8/8/2K3K1 w - - 0 1";
+        assert!(!MoveGen::in_check(&mut load(fen, RoyaltyPolicy::AllCaptured)));
+        assert!(MoveGen::in_check(&mut load(fen, RoyaltyPolicy::AnyCapturedOrAllAttacked)));
+        
+        // Capturing one of the kings only wins if any capture loses
+        let fen = "2r1k3/8/8/8/8/8/8/2K3K1 b - - 0 1";
+        let mut pos = load(fen, RoyaltyPolicy::AllCaptured);
+        assert_eq!(pos.pub_make_move(&MoveInfo::try_from("c8c1").unwrap()).flag, MakeMoveResultFlag::Ok);
+        for policy in [RoyaltyPolicy::AnyCapturedOrAllAttacked, RoyaltyPolicy::EverySafe] {
+            let mut pos = load(fen, policy);
+            assert_eq!(pos.pub_make_move(&MoveInfo::try_from("c8c1").unwrap()).flag, MakeMoveResultFlag::LeaderCaptured);
+            pos.unmake_move();
+            assert!(!pos.leader_is_captured());
+        }
+        
+        // Different piece types can be leaders
+        let fen = "4k1r1/8/8/8/8/8/8/4K1G1 w - - 0 1";
+        let mut pos = load(fen, RoyaltyPolicy::EverySafe);
+        assert!(pos.pieces[0].get_leader().is_none());
+        assert!(MoveGen::in_check(&mut pos));
+        assert_eq!(moves_from(&mut pos), vec![(6, 0)]);
+        assert!(!MoveGen::in_check(&mut load(fen, RoyaltyPolicy::AllCaptured)));
+    }
+
+    #[test]
+    fn extinction() {
+        let load = |fen: &str| {
+            let mut state = GameState::default();
+            for def in &mut state.initial_state.piece_types {
+                if def.ids[0] == Some('N') || def.ids[0] == Some('P') {
+                    def.lose_on_extinction = true;
+                }
+            }
+            state.initial_fen = Some(fen.to_string());
+            PositionFactory::default().set_state(state, None).unwrap().unwrap()
+        };
+
+        // Capturing the last knight wins
+        let mut pos = load("4k3/p7/8/8/8/R1n5/P7/4K1N1 w - - 0 1");
+        assert!(!pos.leader_is_captured());
+        let result = pos.pub_make_move(&MoveInfo::try_from("a3c3").unwrap());
+        assert_eq!(result.flag, MakeMoveResultFlag::Extinction);
+        assert_eq!(result.winner, MakeMoveResultWinner::White);
+        pos.unmake_move();
+        assert!(!pos.leader_is_captured());
+
+        // Promoting the last pawn is illegal, but promoting another pawn is not
+        let mut pos = load("4k3/P7/8/8/8/8/8/2n1K1N1 w - - 0 1");
+        assert!(MoveGen::get_legal_moves(&mut pos).iter().all(|mv| !mv.is_promotion()));
+        let mut pos = load("4k3/P7/8/8/8/8/P7/2n1K1N1 w - - 0 1");
+        assert!(MoveGen::get_legal_moves(&mut pos).iter().any(|mv| mv.is_promotion()));
+
+        // A position where the player to move has no pawns is already lost
+        assert!(load("4k3/8/8/8/8/8/8/2n1K1N1 w - - 0 1").leader_is_captured());
+    }
+
+    #[test]
+    fn neutral_pieces() {
+        let load = |fen: &str| PositionFactory::default().set_state(GameState::from_debug_fen(fen), None).unwrap().unwrap();
+        let placement = |pos: &Position| FenData::from(pos).to_string().split_whitespace().next().unwrap().to_string();
+
+        // Neutral pieces block both players, and they can't be captured
+        let mut pos = load("3k4/8/8/8/3@4/8/8/3RK3 w - - 0 1");
+        let rook_moves: Vec<Move> = MoveGen::get_legal_moves(&mut pos).into_iter()
+            .filter(|mv| mv.get_from() == to_index(3, 0))
+            .collect();
+        assert_eq!(rook_moves.len(), 5);
+        assert!(rook_moves.iter().all(|mv| !mv.is_capture() && mv.get_neutral_to().is_none()));
+
+        // In Duck chess, every move also moves the duck to another empty square
+        let mut pos = load("3k4/8/8/8/3@4/8/8/3RK3 w - - 0 1 duck");
+        let duck = to_index(3, 3);
+        let moves = MoveGen::get_legal_moves(&mut pos);
+        assert!(moves.iter().all(|mv| mv.get_neutral_to().is_some_and(|to| to != duck)));
+        // After Rd2, the duck can go to any of the 60 empty squares (including d1)
+        assert_eq!(moves.iter().filter(|mv| mv.get_from() == to_index(3, 0) && mv.get_to() == to_index(3, 1)).count(), 60);
+        let mv = parse_algebraic_notation(&mut pos, "Rd3,@d1").unwrap();
+        assert_eq!(get_algebraic_notation(&mut pos, mv, &moves), "Rd3,@d1");
+        assert_eq!(mv.to_string(), "d1d3,@d1");
+        assert_eq!(MoveInfo::try_from("d1d3,@d1").unwrap(), mv);
+        assert!(parse_algebraic_notation(&mut pos, "Rd3").is_err());
+        pos.make_move(mv);
+        assert_eq!(placement(&pos), "3k4/8/8/8/8/3R4/8/3@K3");
+        pos.unmake_move();
+        assert_eq!(placement(&pos), "3k4/8/8/8/3@4/8/8/3RK3");
+        
+        // The duck can go to every square that is empty after castling, en passant or promoting, and only there
+        let mut pos = load("4k3/1P6/8/3pP3/@7/8/8/R3K2R w KQ d6 0 1 duck");
+        let moves = MoveGen::get_legal_moves(&mut pos);
+        let mut base_moves: Vec<Move> = moves.iter().map(|mv| mv.without_neutral_move()).collect();
+        base_moves.dedup();
+        for base in base_moves {
+            pos.make_move(base);
+            let empty_squares = (0..8).flat_map(|x| (0..8).map(move |y| to_index(x, y)))
+                .filter(|index| pos.piece_at(*index).is_none() && !pos.get_neutral_pieces().get_bit(*index))
+                .count();
+            pos.unmake_move();
+            assert_eq!(moves.iter().filter(|mv| mv.without_neutral_move() == base).count(), empty_squares, "{base}");
+        }
+        assert!(moves.iter().any(|mv| mv.is_castling()));
+        assert!(moves.iter().any(|mv| mv.is_en_passant()));
+        assert!(moves.iter().any(|mv| mv.is_promotion()));
+
+        // The duck starts in the hand, and capturing the king wins
+        let mut pos = load("3k4/8/8/8/8/8/8/3RK3[@] w - - 0 1 duck");
+        assert!(MoveGen::get_legal_moves(&mut pos).iter().all(|mv| mv.get_neutral_to().is_some()));
+        let result = pos.pub_make_move(&MoveInfo::try_from("d1d8,@d4").unwrap());
+        assert_eq!(result.flag, MakeMoveResultFlag::Extinction);
+        assert_eq!(result.winner, MakeMoveResultWinner::White);
+        assert_eq!(result.move_notation.unwrap(), "Rxd8,@d4#");
+        assert_eq!(placement(&pos), "3R4/8/8/8/3@4/8/8/4K3");
+        
+        // The stalemated player wins, so blocking the last move of the opponent with the duck loses
+        let mut state = GameState::from_debug_fen("k7/8/8/8/8/8/8/4K3[@] w - - 0 1 duck");
+        state.initial_state.fen = "k7/**6/8/8/8/8/8/4K3".to_string();
+        let mut pos = PositionFactory::default().set_state(state, None).unwrap().unwrap();
+        assert_eq!(pos.pub_make_move(&MoveInfo::try_from("e1e2,@c8").unwrap()).flag, MakeMoveResultFlag::Ok);
+        pos.unmake_move();
+        let result = pos.pub_make_move(&MoveInfo::try_from("e1e2,@b8").unwrap());
+        assert_eq!(result.flag, MakeMoveResultFlag::Stalemate);
+        assert_eq!(result.winner, MakeMoveResultWinner::Black);
+    }
+
+    #[test]
+    fn mid_turn_checks() {
+        let make = |pos: &mut Position, mv: &str| pos.pub_make_move(&MoveInfo::try_from(mv).unwrap()).flag;
+        
+        // In Progressive chess, only the last move of the turn can give check (white has 3 moves)
+        let gs = GameState::from_debug_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 2 progressive");
+        let mut pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        assert_eq!(make(&mut pos, "a1a8"), MakeMoveResultFlag::IllegalMove);
+        assert_eq!(make(&mut pos, "a1a2"), MakeMoveResultFlag::Ok);
+        assert_eq!(make(&mut pos, "a2a7"), MakeMoveResultFlag::Ok);
+        assert_eq!(pos.whos_turn, 0);
+        let result = pos.pub_make_move(&MoveInfo::try_from("a7a8").unwrap());
+        assert_eq!(result.move_notation.unwrap(), "Ra8+");
+        assert_eq!(pos.whos_turn, 1);
+        
+        // In Monster chess, the king can be attacked in the middle of the turn if it can escape in the last move
+        let gs = GameState::from_debug_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 2 monster");
+        let mut pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        assert_eq!(make(&mut pos, "e1e2"), MakeMoveResultFlag::Ok);
+        assert_eq!(pos.whos_turn, 0);
+        assert!(MoveGen::in_check(&mut pos));
+        assert_eq!(make(&mut pos, "e2d3"), MakeMoveResultFlag::IllegalMove);
+        assert_eq!(make(&mut pos, "e2f1"), MakeMoveResultFlag::Ok);
+        assert_eq!(pos.whos_turn, 1);
+        // But not if the rest of the turn can't get it out of check
+        let gs = GameState::from_debug_fen("b2k2r1/8/8/8/8/7n/r5P1/7K w - - 0 2 monster");
+        let mut pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        assert_eq!(make(&mut pos, "g2h3"), MakeMoveResultFlag::IllegalMove);
+        let gs = GameState::from_debug_fen("b2k2r1/8/8/8/8/7n/r5P1/1R5K w - - 0 2 monster");
+        let mut pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        assert_eq!(make(&mut pos, "g2h3"), MakeMoveResultFlag::Ok);
+        assert_eq!(make(&mut pos, "b1b7"), MakeMoveResultFlag::Ok);
+        // Checks at the end of the turn need turns of at most 2 moves
+        let mut gs = GameState::from_debug_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 2 monster");
+        gs.initial_state.global_rules.moves_per_turn = MovesPerTurn::Fixed { moves: [3, 1], single_first_move: false };
+        assert!(PositionFactory::default().set_state(gs.clone(), None).is_err());
+        gs.initial_state.global_rules.moves_per_turn = MovesPerTurn::Progressive;
+        assert!(PositionFactory::default().set_state(gs, None).is_err());
+    }
+
+    #[test]
+    fn en_passant_in_multi_move_turns() {
+        let make = |pos: &mut Position, mv: &str| pos.pub_make_move(&MoveInfo::try_from(mv).unwrap()).flag;
+        let load = |any_move: bool| {
+            let mut gs = GameState::from_debug_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 2 marseillais");
+            gs.initial_state.global_rules.en_passant_any_move_of_turn = any_move;
+            PositionFactory::default().set_state(gs, None).unwrap().unwrap()
+        };
+        // By default, the double step can only be captured in the first move of the next turn
+        for any_move in [false, true] {
+            let mut pos = load(any_move);
+            assert_eq!(make(&mut pos, "e8d8"), MakeMoveResultFlag::Ok);
+            assert_eq!(make(&mut pos, "d7d5"), MakeMoveResultFlag::Ok);
+            assert_eq!(make(&mut pos, "e1e2"), MakeMoveResultFlag::Ok);
+            let expected = if any_move { MakeMoveResultFlag::Ok } else { MakeMoveResultFlag::IllegalMove };
+            assert_eq!(make(&mut pos, "e5d6"), expected);
+        }
+        // A double step in the first move of the turn can't be captured
+        let mut pos = load(true);
+        assert_eq!(make(&mut pos, "d7d5"), MakeMoveResultFlag::Ok);
+        assert_eq!(make(&mut pos, "e8d8"), MakeMoveResultFlag::Ok);
+        assert_eq!(make(&mut pos, "e5d6"), MakeMoveResultFlag::IllegalMove);
+    }
+
+    /// Loads a position of standard chess with additional piece types
+    fn load_with_pieces(pieces: &[PieceDefinition], fen: &str) -> Position {
+        let mut state = GameState::default();
+        state.initial_state.piece_types.extend(pieces.iter().cloned());
+        state.initial_fen = Some(fen.to_string());
+        PositionFactory::default().set_state(state, None).unwrap().unwrap()
+    }
+
+    /// Returns the sorted destinations of the legal moves of the piece on `from`
+    fn targets(pos: &mut Position, from: (u8, u8)) -> Vec<(u8, u8)> {
+        let from = to_index(from.0, from.1);
+        let mut targets: Vec<_> = MoveGen::get_legal_moves(pos).iter()
+            .filter(|mv| mv.get_from() == from)
+            .map(|mv| from_index(mv.get_to()))
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    fn has_move(pos: &mut Position, from: (u8, u8), to: (u8, u8)) -> bool {
+        let (from, to) = (to_index(from.0, from.1), to_index(to.0, to.1));
+        MoveGen::get_legal_moves(pos).iter().any(|mv| mv.get_from() == from && mv.get_to() == to)
+    }
+
+    /// Checks that the check detection agrees with the captures that the enemy can make, after every sequence of 2 moves
+    fn assert_check_detection(pos: &mut Position) {
+        for mv in MoveGen::get_legal_moves(pos) {
+            pos.make_move(mv);
+            assert_eq!(MoveGen::in_check(pos), leader_attacked(pos), "{mv}");
+            for mv2 in MoveGen::get_legal_moves(pos) {
+                pos.make_move(mv2);
+                assert_eq!(MoveGen::in_check(pos), leader_attacked(pos), "{mv} {mv2}");
+                pos.unmake_move();
+            }
+            pos.unmake_move();
+        }
+    }
+
+    /// Returns `true` if the enemy could capture the leader of the player to move
+    fn leader_attacked(pos: &mut Position) -> bool {
+        let leader = pos.pieces[pos.whos_turn as usize].get_leader().unwrap();
+        let index = leader.get_bitboard().lowest_one().unwrap();
+        let player = pos.whos_turn as usize;
+        pos.make_move(Move::null());
+        let attacked = MoveGen::get_pseudo_moves(pos, false).into_iter().any(|mv| {
+            if mv.get_target() == index || mv.get_intermediate() == Some(index) {
+                return true;
+            }
+            // Indirect captures can capture several pieces, and the target is only one of them
+            if !mv.is_indirect_capture() {
+                return false;
+            }
+            pos.make_move(mv);
+            let captured = pos.pieces[player].piece_at(index).is_none();
+            pos.unmake_move();
+            captured
+        });
+        pos.unmake_move();
+        attacked
+    }
 }
@@ -402,6 +402,77 @@ mod perft {
     }
     
     
+    #[test]
+    fn duck_start() {
+        // The duck is placed on one of the 32 empty squares after the first move
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[@] w KQkq - 0 1 DUCK";
+        test_perft(fen, vec![
+            640,
+            379440,
+        ]);
+    }
+    
+    
+    #[test]
+    fn marseillais_kiwipete() {
+        // Each player makes 2 moves per turn, unless the first one gives check. The counts match standard chess with
+        // a null move of the opponent between the 2 moves of each turn
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 2 MARSEILLAIS";
+        test_perft(fen, vec![
+            48,
+            2326,
+            97140,
+            4091573,
+        ]);
+    }
+    
+    #[test]
+    fn marseillais_checks() {
+        // Several moves give check, which ends the turn after the first move
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 0 3 MARSEILLAIS";
+        test_perft(fen, vec![
+            42,
+            1619,
+            47084,
+            1457553,
+        ]);
+    }
+    
+    #[test]
+    fn progressive_start() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 PROGRESSIVE";
+        test_perft(fen, vec![
+            20,
+            400,
+            8902,
+            196812,
+        ]);
+    }
+    
+    #[test]
+    fn monster_start() {
+        // White makes 2 moves per turn, with only a king and 4 pawns
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/2PPPP2/4K3 w kq - 0 1 MONSTER";
+        test_perft(fen, vec![
+            10,
+            99,
+            1976,
+        ]);
+    }
+    
+    #[test]
+    fn dark_start() {
+        // There is no check, so the counts only diverge from standard chess at depth 4
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 DARK";
+        test_perft(fen, vec![
+            20,
+            400,
+            8902,
+            197742,
+        ]);
+    }
+    
+    
     // https://github.com/niklasf/python-chess/blob/master/examples/perft/racingkings.perft
     
     #[test]
@@ -457,6 +528,51 @@ mod perft {
         ]);
     }
     
+    
+    // https://github.com/niklasf/python-chess/blob/master/examples/perft/crazyhouse.perft
+    
+    #[test]
+    fn crazyhouse_start() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 CRAZYHOUSE";
+        test_perft(fen, vec![
+            20,
+            400,
+            8902,
+            197281,
+            4888832,
+        ]);
+    }
+    
+    #[test]
+    fn crazyhouse_all_drop_types() {
+        let fen = "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1 CRAZYHOUSE";
+        test_perft(fen, vec![
+            301,
+            75353,
+        ]);
+    }
+    
+    #[test]
+    fn crazyhouse_middlegame() {
+        let fen = "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq - 0 1 CRAZYHOUSE";
+        test_perft(fen, vec![
+            42,
+            1347,
+            58057,
+        ]);
+    }
+    
+    #[test]
+    fn crazyhouse_promoted() {
+        let fen = "4k3/1Q~6/8/8/4b3/8/Kpp5/8 b - - 0 1 CRAZYHOUSE";
+        test_perft(fen, vec![
+            20,
+            360,
+            5445,
+            132758,
+        ]);
+    }
+    
     fn test_perft(fen: &str, results: Vec<usize>) {
         let gs = GameState::from_debug_fen(fen);        
         let mut engine = Engine::default();
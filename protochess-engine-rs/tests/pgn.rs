@@ -0,0 +1,246 @@
+#[cfg(test)]
+mod pgn_test {
+    use std::convert::TryFrom;
+
+    use protochess_engine_rs::{Engine, GameState, InitialState, MakeMoveResultFlag, MoveInfo};
+    use protochess_engine_rs::position::create::pgn::from_pgn;
+
+    #[test]
+    fn standard_game() {
+        let mut engine = Engine::default();
+        for mv in ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n"));
+        assert!(pgn.contains("[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n"));
+        assert!(!pgn.contains("[Variant"));
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.ends_with("\n\n1. e4 d5 2. exd5 Qxd5 3. Nc3 *\n"));
+    }
+
+    #[test]
+    fn checkmate_result() {
+        let mut engine = Engine::default();
+        for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Result \"0-1\"]"));
+        assert!(pgn.ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+
+        // After undoing the last move, the game is no longer over
+        engine.undo().unwrap();
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Result \"*\"]"));
+        assert!(pgn.ends_with("1. f3 e5 2. g4 *\n"));
+    }
+
+    #[test]
+    fn variant_and_fen_tags() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 atomic";
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        let _ = engine.make_move_str("g8f6").unwrap();
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Variant \"Atomic\"]\n"));
+        assert!(pgn.contains("[FEN \"rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"]\n[SetUp \"1\"]\n"));
+        // Black moves first
+        assert!(pgn.ends_with("1... Nf6 *\n"));
+    }
+
+    #[test]
+    fn fullmove_number_from_fen() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 20";
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        for mv in ["g1f3", "g8f6", "b1c3"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.ends_with("20. Nf3 Nf6 21. Nc3 *\n"));
+        assert_eq!(&from_pgn(&pgn, None).unwrap(), engine.get_state());
+        
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 7";
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        for mv in ["g8f6", "b1c3"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        assert!(engine.to_pgn().ends_with("7... Nf6 8. Nc3 *\n"));
+        
+        let pgn = "[FEN \"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 20\"]\n\n20. Nf3 Ke6";
+        let err = from_pgn(pgn, None).unwrap_err();
+        assert!(err.starts_with("Move 20... Ke6: Illegal move"), "{}", err);
+    }
+
+    #[test]
+    fn multi_move_turns() {
+        // The moves of a turn share the same number, and a check ends the turn in Marseillais chess
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 marseillais")).unwrap();
+        for mv in ["e2e4", "f7f5", "a7a6", "d1h5", "g7g6", "a6a5", "g1f3"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Variant \"Marseillais\"]\n"));
+        assert!(pgn.ends_with("\n\n1. e4 f5 a6 2. Qh5+ g6 a5 3. Nf3 *\n"), "{}", pgn);
+        assert_eq!(&from_pgn(&pgn, None).unwrap(), engine.get_state());
+        let mut engine2 = Engine::default();
+        let _ = engine2.load_pgn(&pgn).unwrap();
+        assert_eq!(engine2.to_pgn(), pgn);
+        
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 progressive")).unwrap();
+        for mv in ["e2e4", "e7e5", "d7d5", "e4d5", "g1f3", "b1c3", "g8f6"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Variant \"Progressive\"]\n"));
+        assert!(pgn.ends_with("\n\n1. e4 e5 d5 2. exd5 Nf3 Nc3 Nf6 *\n"), "{}", pgn);
+        assert_eq!(&from_pgn(&pgn, None).unwrap(), engine.get_state());
+        let mut engine2 = Engine::default();
+        let _ = engine2.load_pgn(&pgn).unwrap();
+        assert_eq!(engine2.to_pgn(), pgn);
+        
+        let err = from_pgn("[Variant \"Progressive\"]\n\n1. e4 e5 d5 2. exd5 Nf3 Ke6", None).unwrap_err();
+        assert!(err.starts_with("Move 2. Ke6: Illegal move"), "{}", err);
+        let err = from_pgn("[Variant \"Progressive\"]\n\n1. e4 e5 d5 2. exd5 Nf3 Nc3 Ke6", None).unwrap_err();
+        assert!(err.starts_with("Move 2... Ke6: Illegal move"), "{}", err);
+    }
+
+    #[test]
+    fn en_passant_capture() {
+        let mut engine = Engine::default();
+        for mv in ["e2e4", "a7a6", "e4e5", "d7d5", "e5d6"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        assert_eq!(engine.get_move_history().last().unwrap(), "exd6 e.p.");
+        assert!(engine.to_pgn().ends_with("1. e4 a6 2. e5 d5 3. exd6 *\n"));
+    }
+
+    #[test]
+    fn long_games_are_wrapped() {
+        let mut engine = Engine::default();
+        for _ in 0..20 {
+            for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let _ = engine.make_move_str(mv).unwrap();
+            }
+        }
+        let pgn = engine.to_pgn();
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn parse_san() {
+        let mut engine = Engine::default();
+        for mv in ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qd8", "Nf3", "Nf6", "Be2", "e6", "0-0", "Be7", "d4", "O-O"] {
+            let result = engine.make_move_san(mv).unwrap();
+            assert_eq!(result.flag, MakeMoveResultFlag::Ok);
+        }
+        // Disambiguation by file and by rank, and redundant disambiguation
+        let fen = "4k3/8/8/8/1N6/8/1N3N2/4K3 w - - 0 1";
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        assert!(engine.make_move_san("Nd3").is_err());
+        assert!(engine.make_move_san("Nbd3").is_err());
+        let _ = engine.make_move_san("Nfd3").unwrap();
+        engine.undo().unwrap();
+        let _ = engine.make_move_san("Nf2d3").unwrap();
+        engine.undo().unwrap();
+        let _ = engine.make_move_san("N4d3").unwrap();
+        assert_eq!(engine.get_move_history(), &vec!["Nb4d3"]);
+    }
+
+    #[test]
+    fn parse_san_promotion_and_en_passant() {
+        let fen = "1r2k3/P7/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        let _ = engine.make_move_san("exd6 e.p.").unwrap();
+        engine.undo().unwrap();
+        let _ = engine.make_move_san("exd6").unwrap();
+        let _ = engine.make_move_san("Kd8").unwrap();
+        // The promotion piece is required
+        assert!(engine.make_move_san("axb8").is_err());
+        assert!(engine.make_move_san("axb8=K").is_err());
+        let _ = engine.make_move_san("axb8=N").unwrap();
+        engine.undo().unwrap();
+        let _ = engine.make_move_san("axb8Q+").unwrap();
+        assert_eq!(engine.get_move_history(), &vec!["exd6 e.p.", "Kd8", "axb8=Q+"]);
+    }
+
+    #[test]
+    fn parse_san_custom_prefix() {
+        let mut initial_state = InitialState::default();
+        for def in &mut initial_state.piece_types {
+            if def.ids[0] == Some('N') {
+                def.notation_prefix = [Some("Kn".to_string()), Some("Kn".to_string())];
+            }
+        }
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState { initial_state, initial_fen: None, move_history: vec![] }).unwrap();
+        assert!(engine.make_move_san("Nf3").is_err());
+        let _ = engine.make_move_san("Knf3").unwrap();
+        let _ = engine.make_move_san("Knc6").unwrap();
+        assert_eq!(engine.get_move_history(), &vec!["Knf3", "Knc6"]);
+    }
+
+    #[test]
+    fn read_pgn() {
+        let pgn = "[Event \"Casual game\"]\n[Result \"1-0\"]\n\n\
+            1.e4 {Best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 a6; Ruy Lopez\n\
+            4. Ba4 Nf6 5. O-O Be7 1-0";
+        let state = from_pgn(pgn, None).unwrap();
+        assert_eq!(state.initial_state, InitialState::default());
+        assert_eq!(state.initial_fen, None);
+        assert_eq!(state.move_history.len(), 10);
+        assert_eq!(state.move_history[0], MoveInfo::try_from("e2e4").unwrap());
+        // Castling is stored as the king moving to the rook's square
+        assert_eq!(state.move_history[8], MoveInfo::try_from("e1h1").unwrap());
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 atomic";
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen(fen)).unwrap();
+        for mv in ["g8f6", "b1c3", "f6e4", "c3e4", "d7d5"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        let state = from_pgn(&pgn, None).unwrap();
+        assert_eq!(&state, engine.get_state());
+
+        let mut engine2 = Engine::default();
+        let _ = engine2.load_pgn(&pgn).unwrap();
+        assert_eq!(engine2.to_pgn(), pgn);
+    }
+
+    #[test]
+    fn crazyhouse_drops() {
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 crazyhouse")).unwrap();
+        for mv in ["e2e4", "d7d5", "e4d5", "d8d5", "P@e6", "p@e2"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let pgn = engine.to_pgn();
+        assert!(pgn.contains("[Variant \"Crazyhouse\"]\n"));
+        assert!(pgn.ends_with("1. e4 d5 2. exd5 Qxd5 3. P@e6 P@e2 *\n"));
+        let state = from_pgn(&pgn, None).unwrap();
+        assert_eq!(state.move_history[4], MoveInfo::try_from("P@e6").unwrap());
+        assert_eq!(state.move_history[5].drop, Some('p'));
+    }
+
+    #[test]
+    fn read_pgn_errors() {
+        let err = from_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Ke6", None).unwrap_err();
+        assert!(err.starts_with("Move 3... Ke6: Illegal move"), "{}", err);
+        let err = from_pgn("1. e4 e5 2. N3", None).unwrap_err();
+        assert!(err.starts_with("Move 2. N3: Invalid move notation"), "{}", err);
+        let err = from_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. e4", None).unwrap_err();
+        assert!(!err.is_empty());
+        let err = from_pgn("[Variant \"Unknown\"]\n\n1. e4", None).unwrap_err();
+        assert!(err.contains("Unknown variant"), "{}", err);
+    }
+}
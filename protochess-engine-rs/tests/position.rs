@@ -1,10 +1,11 @@
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod position_test {
 
     use std::convert::TryFrom;
 
     use protochess_engine_rs::position::create::position_factory::PositionFactory;
-    use protochess_engine_rs::{GameState, MoveInfo};
+    use protochess_engine_rs::{GameState, MoveInfo, Engine, MakeMoveResultFlag, MakeMoveResultWinner, GameEndRule};
     use protochess_engine_rs::types::Move;
     
     #[test]
@@ -323,6 +324,318 @@ mod position_test {
         assert_eq!(move_notation.len(), 2);
     }
     
+    #[test]
+    fn fen_move_clocks() {
+        let mut engine = Engine::default();
+        engine.load_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 5 20").unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 5 20"));
+        let _ = engine.make_move_str("g1f3").unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 6 20"));
+        let _ = engine.make_move_str("g8f6").unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 7 21"));
+        // Pawn moves reset the halfmove clock
+        let _ = engine.make_move_str("e2e4").unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" 0 21"));
+        engine.undo().unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 7 21"));
+        // Captures reset the halfmove clock
+        let _ = engine.make_move_str("f3e5").unwrap();
+        let _ = engine.make_move_str("f6e4").unwrap();
+        let _ = engine.make_move_str("e5f7").unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 0 22"));
+    }
+    
+    #[test]
+    fn fen_check_count() {
+        // The check count can be placed before or after the move clocks
+        let state = GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - +1+2 3check");
+        let mut engine = Engine::default();
+        let _ = engine.set_state(state).unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 0 1 +1+2"));
+        let state = GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3 4 +2+0 3check");
+        let _ = engine.set_state(state).unwrap();
+        assert!(engine.get_state_diff().fen.ends_with(" - 3 4 +2+0"));
+    }
+    
+    #[test]
+    fn n_move_rule() {
+        let mut engine = Engine::default();
+        engine.load_fen("7k/8/6K1/8/8/8/8/Rn6 w - - 99 80").unwrap();
+        let result = engine.make_move_str("a1a2").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::NMoveRule);
+        engine.undo().unwrap();
+        // Checkmate takes precedence
+        let result = engine.make_move_str("a1a8").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::Checkmate);
+        engine.undo().unwrap();
+        // Captures reset the clock
+        let result = engine.make_move_str("a1b1").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::Ok);
+        
+        // The rule can be disabled
+        let mut state = GameState::default();
+        state.initial_state.global_rules.n_move_rule = 0;
+        state.initial_fen = Some("7k/8/6K1/8/8/8/8/Rn6 w - - 99 80".to_string());
+        let _ = engine.set_state(state).unwrap();
+        let result = engine.make_move_str("a1a2").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::Ok);
+    }
+    
+    #[test]
+    fn insufficient_material() {
+        let result_after = |fen: &str, mv: &str| {
+            let mut engine = Engine::default();
+            engine.load_fen(fen).unwrap();
+            engine.make_move_str(mv).unwrap().flag
+        };
+        // Only the kings
+        assert_eq!(result_after("4k3/8/8/8/8/8/3n4/4K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::InsufficientMaterial);
+        // King and knight against king
+        assert_eq!(result_after("4k3/8/8/8/8/8/3r4/4KN2 w - - 0 1", "e1d2"), MakeMoveResultFlag::InsufficientMaterial);
+        assert_eq!(result_after("4k3/8/8/8/8/8/3n4/4KR2 w - - 0 1", "e1d2"), MakeMoveResultFlag::Ok);
+        // Bishops on the same colour, or on different colours
+        assert_eq!(result_after("5b2/4k3/8/8/8/8/3n4/2B1K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::InsufficientMaterial);
+        assert_eq!(result_after("2b5/4k3/8/8/8/8/3n4/2B1K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::Ok);
+        assert_eq!(result_after("4k3/8/8/8/8/8/1B1n1B2/B1B1K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::InsufficientMaterial);
+        // With more pieces the position is not checked, since it's almost never a draw
+        assert_eq!(result_after("4k3/8/8/8/8/8/1B1n1B1B/B1B1K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::Ok);
+        // Pawns can promote
+        assert_eq!(result_after("4k3/p7/8/8/8/8/3n4/4K3 w - - 0 1", "e1d2"), MakeMoveResultFlag::Ok);
+        
+        // The rook is surrounded by walls, and cannot reach the enemy king
+        let mut engine = Engine::default();
+        let mut state = GameState::default();
+        state.initial_state.fen = "4k3/8/8/8/8/8/**6/R*1nK3".to_string();
+        let _ = engine.set_state(state).unwrap();
+        let result = engine.make_move_str("e1d1").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::InsufficientMaterial);
+    }
+    
+    #[test]
+    fn game_end_rules() {
+        let load = |fen: &str, rule: GameEndRule| {
+            let mut engine = Engine::default();
+            let mut state = GameState::default();
+            state.initial_state.global_rules.game_end_rules.push(rule);
+            state.initial_fen = Some(fen.to_string());
+            let _ = engine.set_state(state).unwrap();
+            engine
+        };
+
+        // Any capture wins, and the search finds it
+        let mut engine = load("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1", GameEndRule::CapturedMaterial(1));
+        assert_eq!(engine.make_move_str("e1f1").unwrap().flag, MakeMoveResultFlag::Ok);
+        engine.undo().unwrap();
+        let (best_move, _) = engine.get_best_move(2).unwrap();
+        assert_eq!(best_move, MoveInfo::try_from("d1d5").unwrap());
+        let result = engine.make_move_str("d1d5").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::CapturedMaterial);
+        assert_eq!(result.winner, MakeMoveResultWinner::White);
+        assert_eq!(result.move_notation.unwrap(), "Rxd5#");
+
+        // Hold the center for 2 turns in a row
+        let center = vec![(3, 3), (4, 3), (3, 4), (4, 4)];
+        let rule = GameEndRule::HoldSquares { squares: [center.clone(), center], turns: 2 };
+        let mut engine = load("k7/8/8/8/8/4K3/8/8 w - - 0 1", rule.clone());
+        for mv in ["e3e4", "a8a7"] {
+            assert_eq!(engine.make_move_str(mv).unwrap().flag, MakeMoveResultFlag::Ok);
+        }
+        let result = engine.make_move_str("e4e5").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::SquaresHeld);
+        assert_eq!(result.winner, MakeMoveResultWinner::White);
+        // Leaving the squares resets the count
+        let mut engine = load("k7/8/8/8/8/4K3/8/8 w - - 0 1", rule.clone());
+        for mv in ["e3e4", "a8a7", "e4f4", "a7a8", "f4e4", "a8a7"] {
+            assert_eq!(engine.make_move_str(mv).unwrap().flag, MakeMoveResultFlag::Ok);
+        }
+        // Checking the rules again after the same move doesn't count the turn twice
+        let mut state = GameState::default();
+        state.initial_state.global_rules.game_end_rules.push(rule);
+        state.initial_fen = Some("k7/8/8/8/8/4K3/8/8 w - - 0 1".to_string());
+        let mut pos = PositionFactory::default().set_state(state, None).unwrap().unwrap();
+        assert_eq!(pos.pub_make_move(&MoveInfo::try_from("e3e4").unwrap()).flag, MakeMoveResultFlag::Ok);
+        let zobrist = pos.get_zobrist();
+        assert!(pos.game_end(true, true, None).is_none());
+        assert_eq!(pos.get_zobrist(), zobrist);
+
+        // The game is a draw after 2 moves by each player
+        let mut engine = load("k7/8/8/8/8/4K3/P7/8 w - - 0 1", GameEndRule::MoveLimit(2));
+        for mv in ["e3e4", "a8a7", "e4e5"] {
+            assert_eq!(engine.make_move_str(mv).unwrap().flag, MakeMoveResultFlag::Ok);
+        }
+        let result = engine.make_move_str("a7a8").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::MoveLimit);
+        assert_eq!(result.winner, MakeMoveResultWinner::None);
+
+        // Invalid rules
+        let mut state = GameState::default();
+        state.initial_state.global_rules.game_end_rules.push(GameEndRule::HoldSquares { squares: [vec![(8, 0)], vec![]], turns: 1 });
+        assert!(Engine::default().set_state(state).is_err());
+        let mut state = GameState::default();
+        state.initial_state.global_rules.stalemated_player_loses = true;
+        state.initial_state.global_rules.stalemated_player_wins = true;
+        assert!(Engine::default().set_state(state).is_err());
+    }
+
+    #[test]
+    fn crazyhouse_drops() {
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 CRAZYHOUSE")).unwrap();
+        for mv in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        // Each player has the pawn captured from the opponent
+        assert!(engine.get_state_diff().fen.starts_with("rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w "));
+        // Pawns cannot be dropped on the last rank
+        assert_eq!(engine.make_move_str("P@d8").unwrap().flag, MakeMoveResultFlag::IllegalMove);
+        let result = engine.make_move_str("P@e6").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::Ok);
+        assert_eq!(result.move_notation.unwrap(), "P@e6");
+        let fen = engine.get_state_diff().fen;
+        assert!(fen.starts_with("rnb1kbnr/ppp1pppp/4P3/3q4/8/8/PPPP1PPP/RNBQKBNR[p] b "));
+        // The hand is part of the zobrist key
+        let mut engine2 = Engine::default();
+        let _ = engine2.set_state(GameState::from_debug_fen(&format!("{fen} CRAZYHOUSE"))).unwrap();
+        assert_eq!(engine.get_zobrist(), engine2.get_zobrist());
+        
+        engine.undo().unwrap();
+        assert!(engine.get_state_diff().fen.starts_with("rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w "));
+        // Promoted pieces go back to the hand as pawns
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("4k3/1Q~6/8/8/4b3/8/K7/8 b - - 0 1 CRAZYHOUSE")).unwrap();
+        let _ = engine.make_move_str("e4b7").unwrap();
+        assert!(engine.get_state_diff().fen.starts_with("4k3/1b6/8/8/8/8/K7/8[p] w "));
+    }
+    
+    #[test]
+    fn optional_promotion() {
+        let mut engine = Engine::default();
+        let mut state = GameState::default();
+        for def in state.initial_state.piece_types.iter_mut().filter(|def| def.ids[0] == Some('P')) {
+            def.optional_promotion_squares = (0..8).map(|x| (x, 6)).collect();
+        }
+        state.initial_fen = Some("4k3/8/P7/8/8/8/8/4K3 w - - 0 1".to_string());
+        let _ = engine.set_state(state).unwrap();
+        // The pawn can choose whether to promote on the 7th rank
+        assert_eq!(engine.possible_promotions((0, 5), (0, 6)).len(), 4);
+        let result = engine.make_move_str("a6a7=N").unwrap();
+        assert_eq!(result.move_notation.unwrap(), "a7=N");
+        engine.undo().unwrap();
+        let result = engine.make_move_str("a6a7").unwrap();
+        assert_eq!(result.move_notation.unwrap(), "a7");
+        // But it must promote on the 8th rank
+        let _ = engine.make_move_str("e8d7").unwrap();
+        assert_eq!(engine.make_move_str("a7a8").unwrap().flag, MakeMoveResultFlag::IllegalMove);
+        assert_eq!(engine.make_move_str("a7a8=Q").unwrap().flag, MakeMoveResultFlag::Ok);
+    }
+    
+    #[test]
+    fn forced_promotion_from_movement() {
+        let mut engine = Engine::default();
+        let mut state = GameState::default();
+        for def in state.initial_state.piece_types.iter_mut() {
+            match def.ids[0] {
+                // Only an optional zone, but the pawn could never move again from the last rank
+                Some('P') => {
+                    def.promotion_squares.clear();
+                    def.optional_promotion_squares = (0..8).flat_map(|x| [(x, 6), (x, 7)]).collect();
+                },
+                // The knight can always move back, so it's never forced to promote
+                Some('N') => {
+                    def.optional_promotion_squares = (0..8).flat_map(|x| [(x, 6), (x, 7)]).collect();
+                    def.promo_vals = [vec!['Q'], vec!['q']];
+                },
+                _ => {},
+            }
+        }
+        state.initial_fen = Some("4k3/P7/1N6/8/8/8/8/4K3 w - - 0 1".to_string());
+        let _ = engine.set_state(state).unwrap();
+        assert_eq!(engine.make_move_str("a7a8").unwrap().flag, MakeMoveResultFlag::IllegalMove);
+        assert_eq!(engine.make_move_str("b6a8").unwrap().flag, MakeMoveResultFlag::Ok);
+        engine.undo().unwrap();
+        assert_eq!(engine.make_move_str("b6a8=Q").unwrap().flag, MakeMoveResultFlag::Ok);
+        engine.undo().unwrap();
+        assert_eq!(engine.possible_promotions((0, 6), (0, 7)).len(), 4);
+        assert_eq!(engine.make_move_str("a7a8=R").unwrap().flag, MakeMoveResultFlag::Ok);
+    }
+    
+    #[test]
+    fn multi_move_turns() {
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 MARSEILLAIS")).unwrap();
+        // The first turn of white has a single move
+        let result = engine.make_move_str("e2e4").unwrap();
+        assert!(!result.turn_continues);
+        let result = engine.make_move_str("e7e5").unwrap();
+        assert!(result.turn_continues);
+        assert_eq!(engine.player_to_move(), 1);
+        assert!(engine.get_state_diff().fen.ends_with(" b (a1,a8,e1,e8,h1,h8) e6(e5) 0 1 m1"));
+        let result = engine.make_move_str("d7d5").unwrap();
+        assert!(!result.turn_continues);
+        assert!(engine.get_state_diff().fen.ends_with(" w (a1,a8,e1,e8,h1,h8) d6(d5) 0 2"));
+        // Giving check ends the turn
+        let _ = engine.make_move_str("d2d4").unwrap();
+        let result = engine.make_move_str("f1b5").unwrap();
+        assert_eq!(result.move_notation.unwrap(), "Bb5+");
+        assert_eq!(engine.player_to_move(), 1);
+        engine.undo().unwrap();
+        engine.undo().unwrap();
+        assert_eq!(engine.player_to_move(), 0);
+        
+        // The sub-move counter is part of the FEN and the zobrist key
+        let _ = engine.make_move_str("g1f3").unwrap();
+        let fen = engine.get_state_diff().fen;
+        assert!(fen.ends_with(" 1 2 m1"));
+        let mut engine2 = Engine::default();
+        let _ = engine2.set_state(GameState::from_debug_fen(&format!("{fen} MARSEILLAIS"))).unwrap();
+        assert_eq!(engine.get_zobrist(), engine2.get_zobrist());
+        assert_eq!(engine2.player_to_move(), 0);
+        let _ = engine2.make_move_str("b1c3").unwrap();
+        assert_eq!(engine2.player_to_move(), 1);
+        assert!(engine2.set_state(GameState::from_debug_fen(&format!("{} m2 MARSEILLAIS", &fen[..fen.len() - 3]))).is_err());
+    }
+    
+    #[test]
+    fn dark_chess() {
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1 DARK")).unwrap();
+        // There is no check, the king can move to an attacked square and be captured
+        let result = engine.make_move_str("e1e2").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::Ok);
+        assert!(!engine.get_state_diff().in_check);
+        let result = engine.make_move_str("d2e2").unwrap();
+        assert_eq!(result.flag, MakeMoveResultFlag::LeaderCaptured);
+        assert_eq!(result.winner, MakeMoveResultWinner::Black);
+        // Castling through an attacked square is allowed
+        let _ = engine.set_state(GameState::from_debug_fen("4kr2/8/8/8/8/8/8/4K2R w K - 0 1 DARK")).unwrap();
+        assert_eq!(engine.make_move_san("O-O").unwrap().flag, MakeMoveResultFlag::Ok);
+    }
+    
+    #[test]
+    fn fog_of_war_view() {
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 DARK")).unwrap();
+        // Each player sees their own pieces and the 3rd and 4th ranks in front of them
+        let visible = engine.visible_squares(0).unwrap();
+        assert_eq!(visible.len(), 32);
+        assert!(visible.contains(&(4, 3)) && !visible.contains(&(4, 4)));
+        assert_eq!(engine.visible_squares(1).unwrap().len(), 32);
+        let state = engine.get_state_diff_for_player(0).unwrap();
+        assert!(state.fen.starts_with("8/8/8/8/8/8/PPPPPPPP/RNBQKBNR w (a1,e1,h1) - "), "{}", state.fen);
+        let state = engine.get_state_diff_for_player(1).unwrap();
+        assert!(state.fen.starts_with("rnbqkbnr/pppppppp/8/8/8/8/8/8 w (a8,e8,h8) - "), "{}", state.fen);
+        
+        // An enemy piece is visible if it can be captured
+        for mv in ["e2e4", "d7d5", "a2a3", "h7h6"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        let state = engine.get_state_diff_for_player(0).unwrap();
+        assert!(state.fen.starts_with("8/8/8/3p4/4P3/P7/1PPP1PPP/RNBQKBNR w "), "{}", state.fen);
+        // The full state is not affected
+        assert!(engine.get_state_diff().fen.starts_with("rnbqkbnr/ppp1ppp1/7p/3p4/4P3/P7/1PPP1PPP/RNBQKBNR w "));
+        assert!(engine.visible_squares(2).is_err());
+    }
+    
     
     fn build_move_history(moves: Vec<&str>) -> Vec<MoveInfo> {
         moves.iter().map(|mv| MoveInfo::try_from(*mv).unwrap()).collect()
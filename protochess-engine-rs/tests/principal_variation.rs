@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod principal_variation {
     use protochess_engine_rs::position::create::position_factory::PositionFactory;
-    use protochess_engine_rs::{GameState, MoveGen};
-    use protochess_engine_rs::searcher::Searcher;
+    use protochess_engine_rs::{GameState, MoveGen, RoyaltyPolicy};
+    use protochess_engine_rs::utils::to_index;
+    use protochess_engine_rs::searcher::{Searcher, GAME_OVER_SCORE};
     #[test]
     fn starting_position_1() {
         test_pv("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 1);
@@ -92,6 +93,88 @@ mod principal_variation {
     fn kiwipete_10() {
         test_pv("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 10);
     }
+    #[test]
+    fn n_move_rule_is_a_draw() {
+        // White is winning, but every move is a draw by the fifty-move rule
+        let gs = GameState::from_debug_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 99 60");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (_pv, score, _depth) = Searcher::get_best_move(&pos, 4, 1);
+        assert_eq!(score, 0);
+    }
+    #[test]
+    fn insufficient_material_is_a_draw() {
+        // White has an extra knight, but it cannot checkmate
+        let gs = GameState::from_debug_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        assert!(pos.draw_by_insufficient_material());
+        let (_pv, score, _depth) = Searcher::get_best_move(&pos, 4, 1);
+        assert_eq!(score, 0);
+    }
+    #[test]
+    fn capturing_any_leader_wins() {
+        // White has two kings, and losing either of them loses the game
+        let mut gs = GameState::from_debug_fen("2r1k3/8/8/8/8/8/8/2K3K1 b - - 0 1");
+        gs.initial_state.global_rules.royalty_policy = RoyaltyPolicy::AnyCapturedOrAllAttacked;
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (pv, _score, _depth) = Searcher::get_best_move(&pos, 2, 1);
+        assert_eq!(pv[0].get_to(), to_index(2, 0));
+    }
+    #[test]
+    fn crazyhouse_5() {
+        test_pv("r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq - 0 1 crazyhouse", 5);
+    }
+    #[test]
+    fn crazyhouse_drop_mate() {
+        // Only a drop can checkmate
+        let gs = GameState::from_debug_fen("k7/8/1K6/8/8/8/8/8[Q] w - - 0 1 crazyhouse");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (pv, _score, _depth) = Searcher::get_best_move(&pos, 3, 1);
+        assert_eq!(pv[0].get_drop_piece(), Some('Q'));
+    }
+    #[test]
+    fn duck_3() {
+        test_pv("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[@] w KQkq - 0 1 duck", 3);
+    }
+    #[test]
+    fn duck_captures_king() {
+        // There is no check in Duck chess, so the king is captured like any other piece
+        let gs = GameState::from_debug_fen("3k4/8/8/7@/8/8/8/3RK3 w - - 0 1 duck");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (pv, _score, _depth) = Searcher::get_best_move(&pos, 3, 1);
+        assert_eq!(pv[0].get_to(), to_index(3, 7));
+    }
+    #[test]
+    fn marseillais_3() {
+        test_pv("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 marseillais", 3);
+    }
+    #[test]
+    fn monster_4() {
+        test_pv("rnbqkbnr/pppppppp/8/8/8/8/2PPPP2/4K3 w kq - 0 1 monster", 4);
+    }
+    #[test]
+    fn marseillais_two_move_mate() {
+        // The knight blocks the rook, so the back rank mate takes both moves of the turn
+        let gs = GameState::from_debug_fen("7k/6pp/8/8/8/8/1N6/1R4K1 w - - 0 2 marseillais");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (_pv, score, _depth) = Searcher::get_best_move(&pos, 3, 1);
+        assert_eq!(score, -(GAME_OVER_SCORE + 2));
+    }
+    #[test]
+    fn dark_sampled_start() {
+        let gs = GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 dark");
+        let mut pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (mv, _score) = Searcher::get_best_move_sampled(&pos, 3, 4, 1).unwrap();
+        assert!(MoveGen::get_legal_moves(&mut pos).contains(&mv));
+    }
+    #[test]
+    fn dark_sampled_captures_king() {
+        // The black rook is hidden, but the king can be captured in every sample
+        let gs = GameState::from_debug_fen("r6k/8/8/8/8/8/8/K6R w - - 0 1 dark");
+        let pos = PositionFactory::default().set_state(gs, None).unwrap().unwrap();
+        let (mv, score) = Searcher::get_best_move_sampled(&pos, 3, 4, 1).unwrap();
+        assert_eq!(mv.get_to(), to_index(7, 7));
+        assert!(score > 0);
+    }
     
     
     fn test_pv(fen: &str, depth: u8) {
@@ -0,0 +1,189 @@
+extern crate protochess_engine_rs;
+
+// The front-end is a binary, so its source is included here to test its helper functions
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../src/bin/uci.rs"]
+mod uci_bin;
+
+#[cfg(test)]
+mod uci {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::thread;
+    use instant::Duration;
+
+    use protochess_engine_rs::{Engine, GameState};
+
+    use super::uci_bin::{parse_go, allocate_time};
+
+    #[test]
+    fn parse_go_params() {
+        let params = parse_go(&["wtime", "60000", "btime", "30000", "winc", "1000", "binc", "500", "movestogo", "20"]);
+        assert_eq!(params.wtime, Some(60000));
+        assert_eq!(params.btime, Some(30000));
+        assert_eq!(params.winc, Some(1000));
+        assert_eq!(params.binc, Some(500));
+        assert_eq!(params.movestogo, Some(20));
+        assert_eq!(params.depth, None);
+        assert!(!params.infinite);
+
+        let params = parse_go(&["depth", "7", "movetime", "250"]);
+        assert_eq!(params.depth, Some(7));
+        assert_eq!(params.movetime, Some(250));
+
+        let params = parse_go(&["infinite"]);
+        assert!(params.infinite);
+    }
+
+    #[test]
+    fn parse_go_invalid_values() {
+        // Negative times are clamped to 0, and invalid numbers are ignored
+        let params = parse_go(&["wtime", "-500", "btime", "abc", "depth", "100000"]);
+        assert_eq!(params.wtime, Some(0));
+        assert_eq!(params.btime, None);
+        assert_eq!(params.depth, Some(u8::MAX));
+        // Missing value at the end of the command
+        let params = parse_go(&["movetime"]);
+        assert_eq!(params.movetime, None);
+    }
+
+    #[test]
+    fn allocate_time_movetime() {
+        let params = parse_go(&["movetime", "1000", "wtime", "5000"]);
+        // The fixed move time has priority, minus the communication overhead
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(970)));
+        assert_eq!(allocate_time(&params, 1), Some(Duration::from_millis(970)));
+        // Never 0, even if the overhead is larger than the move time
+        let params = parse_go(&["movetime", "10"]);
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn allocate_time_uses_own_clock() {
+        let params = parse_go(&["wtime", "60000", "btime", "30000", "winc", "1000", "binc", "0", "movestogo", "20"]);
+        // 60000 / 20 + 1000 * 3/4
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(3750)));
+        // 30000 / 20
+        assert_eq!(allocate_time(&params, 1), Some(Duration::from_millis(1500)));
+        // Black has no clock
+        let params = parse_go(&["wtime", "60000"]);
+        assert_eq!(allocate_time(&params, 1), None);
+        assert_eq!(allocate_time(&parse_go(&["depth", "3"]), 0), None);
+    }
+
+    #[test]
+    fn allocate_time_low_on_time() {
+        // Without movestogo, 30 moves are assumed
+        let params = parse_go(&["wtime", "30000"]);
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(1000)));
+        // A large increment never uses more than half of the remaining time
+        let params = parse_go(&["wtime", "1030", "winc", "10000"]);
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(500)));
+        let params = parse_go(&["wtime", "0", "movestogo", "0"]);
+        assert_eq!(allocate_time(&params, 0), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn driver_go_depth() {
+        let output = run_uci(&["uci", "isready", "position startpos moves e2e4 e7e5", "go depth 3"], "bestmove");
+        assert!(output.iter().any(|l| l == "uciok"));
+        assert!(output.iter().any(|l| l == "readyok"));
+        assert!(output.iter().any(|l| l.starts_with("info depth 3 ")));
+        assert!(output.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn driver_stop_infinite_search() {
+        let output = run_uci(&["position startpos", "go infinite", "wait", "stop"], "bestmove");
+        let bestmove = output.last().unwrap();
+        assert!(bestmove.starts_with("bestmove ") && bestmove != "bestmove 0000");
+    }
+
+    #[test]
+    fn driver_variant_and_illegal_move() {
+        let output = run_uci(&[
+            "setoption name UCI_Variant value kingofthehill",
+            "position startpos moves e2e5",
+            "position fen 8/8/8/8/8/8/8/K1k5 w - - 0 1 moves a1a2",
+            "go movetime 100",
+        ], "bestmove");
+        assert!(output.iter().any(|l| l == "info string Invalid position: Illegal move: e2e5"));
+        assert!(output.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn driver_duck_moves() {
+        let output = run_uci(&[
+            "uci",
+            "setoption name UCI_Variant value duck",
+            "position startpos moves e2e4,@e6 d7d5,@e3",
+            "go depth 2",
+        ], "bestmove");
+        assert!(output.iter().any(|l| l.starts_with("option name UCI_Variant ") && l.contains(" var duck")));
+        assert!(!output.iter().any(|l| l.starts_with("info string Invalid position")));
+        let best_move = output.last().unwrap().split_whitespace().nth(1).unwrap();
+        assert!(best_move.contains(",@"), "{}", best_move);
+    }
+
+    #[test]
+    fn driver_several_moves_per_turn() {
+        // Black moves twice per turn, so it still has the move after its first move
+        let output = run_uci(&[
+            "uci",
+            "setoption name UCI_Variant value marseillais",
+            "position startpos moves e2e4 e7e5",
+            "go depth 2",
+        ], "bestmove");
+        assert!(output.iter().any(|l| l.starts_with("option name UCI_Variant ") && l.contains(" var marseillais")));
+        assert!(!output.iter().any(|l| l.starts_with("info string Invalid position")));
+        let best_move = output.last().unwrap().split_whitespace().nth(1).unwrap();
+        let mut engine = Engine::default();
+        let _ = engine.set_state(GameState::from_debug_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 marseillais")).unwrap();
+        for mv in ["e2e4", "e7e5"] {
+            let _ = engine.make_move_str(mv).unwrap();
+        }
+        assert_eq!(engine.player_to_move(), 1);
+        assert!(engine.make_move_str(best_move).is_ok(), "{}", best_move);
+    }
+
+    /// Runs the UCI binary with the given commands ("wait" sleeps for a short time) and returns the output lines
+    /// up to the first line that starts with `last_line_prefix`
+    fn run_uci(commands: &[&str], last_line_prefix: &str) -> Vec<String> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_uci"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        for command in commands {
+            if *command == "wait" {
+                thread::sleep(Duration::from_millis(300));
+            } else {
+                writeln!(stdin, "{command}").unwrap();
+            }
+        }
+        let mut output = Vec::new();
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).expect("The engine didn't answer");
+            let done = line.starts_with(last_line_prefix);
+            output.push(line);
+            if done {
+                break;
+            }
+        }
+        writeln!(stdin, "quit").unwrap();
+        child.wait().unwrap();
+        output
+    }
+}
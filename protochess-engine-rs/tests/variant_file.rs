@@ -0,0 +1,92 @@
+extern crate protochess_engine_rs;
+
+#[cfg(all(test, feature = "serde"))]
+mod variant_file {
+    use protochess_engine_rs::{Engine, GameState, InitialState, MakeMoveResultFlag, GameEndRule};
+    use protochess_engine_rs::position::create::variant_factory::VariantFactory;
+    use protochess_engine_rs::position::create::variant_file::*;
+    use protochess_engine_rs::types::GameMode;
+
+    #[test]
+    fn round_trip() {
+        for mode in [GameMode::Standard, GameMode::Atomic, GameMode::Horde, GameMode::RacingKings, GameMode::ThreeCheck] {
+            let state = VariantFactory::new(mode).make_initial_state();
+            let toml = variant_to_toml(&state).unwrap();
+            assert_eq!(variant_from_toml(&toml).unwrap(), state);
+            let json = variant_to_json(&state).unwrap();
+            assert_eq!(variant_from_json(&json).unwrap(), state);
+        }
+    }
+    
+    #[test]
+    fn example_file() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/variants/capablanca.toml");
+        let state = load_variant(&path).unwrap();
+        assert_eq!(state.board_width, 10);
+        assert_eq!(state.piece_types.len(), 9);
+        assert_eq!(state.global_rules, Default::default());
+        
+        let saved = std::env::temp_dir().join("protochess_capablanca.json");
+        save_variant(&state, &saved).unwrap();
+        assert_eq!(load_variant(&saved).unwrap(), state);
+    }
+    
+    #[test]
+    fn grand_chess_promotions() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/variants/grand.toml");
+        let initial_state = load_variant(&path).unwrap();
+        let mut engine = Engine::default();
+        let load = |engine: &mut Engine, fen: &str| {
+            let state = GameState { initial_state: initial_state.clone(), initial_fen: Some(fen.to_string()), move_history: vec![] };
+            let _ = engine.set_state(state).unwrap();
+        };
+        // No piece has been lost, so the pawn cannot promote or move to the last rank
+        load(&mut engine, "k9/4P5/10/10/10/10/10/10/1NBQKMCBN1/R8R w - - 0 1");
+        assert!(engine.possible_promotions((4, 8), (4, 9)).is_empty());
+        assert_eq!(engine.make_move_str("e9e10").unwrap().flag, MakeMoveResultFlag::IllegalMove);
+        // Only the lost queen is available
+        load(&mut engine, "k9/4P5/10/10/10/10/10/10/1NB1KMCBN1/R8R w - - 0 1");
+        assert_eq!(engine.possible_promotions((4, 8), (4, 9)), vec!['Q']);
+        let _ = engine.make_move_str("e9e10=Q").unwrap();
+        // After promoting, the queen is no longer available for the next pawn
+        load(&mut engine, "10/4P5/3P6/10/10/8k1/10/10/1NB1KMCBN1/R8R w - - 0 1");
+        assert_eq!(engine.possible_promotions((3, 7), (3, 8)), vec!['Q']);
+        let _ = engine.make_move_str("e9e10=Q").unwrap();
+        let _ = engine.make_move_str("i5h5").unwrap();
+        assert!(engine.possible_promotions((3, 7), (3, 8)).is_empty());
+        assert_eq!(engine.make_move_str("d8d9").unwrap().flag, MakeMoveResultFlag::Ok);
+    }
+    
+    #[test]
+    fn missing_fields_use_defaults() {
+        let state = variant_from_toml("player_to_move = 1").unwrap();
+        assert_eq!(state, InitialState { player_to_move: 1, ..Default::default() });
+        
+        let state = variant_from_json(r#"{ "global_rules": { "checks_to_lose": 3 } }"#).unwrap();
+        assert_eq!(state.global_rules.checks_to_lose, 3);
+        assert!(!state.global_rules.capturing_is_forced);
+        
+        let toml = "[global_rules]\ngame_end_rules = [{ captured_material = 1500 }, { hold_squares = { squares = [[[3, 3]], [[4, 4]]], turns = 3 } }]";
+        let state = variant_from_toml(toml).unwrap();
+        assert_eq!(state.global_rules.game_end_rules, vec![
+            GameEndRule::CapturedMaterial(1500),
+            GameEndRule::HoldSquares { squares: [vec![(3, 3)], vec![(4, 4)]], turns: 3 },
+        ]);
+    }
+    
+    #[test]
+    fn errors_point_to_field() {
+        let assert_error = |result: Result<InitialState, String>, field: &str| {
+            let err = result.unwrap_err();
+            assert!(err.starts_with(field), "Error '{}' does not point to '{}'", err, field);
+        };
+        assert_error(variant_from_toml("board_width = 20"), "board_width");
+        assert_error(variant_from_toml("board_height = 'x'"), "board_height");
+        assert_error(variant_from_toml("fen = '8/8/8'"), "fen");
+        assert_error(variant_from_json(r#"{ "global_rules": { "checks_to_loose": 3 } }"#), "global_rules");
+        assert_error(variant_from_json(r#"{ "piece_types": [{ "ids": { "white": "K" } }, { "is_leader": 1 }] }"#), "piece_types[1].is_leader");
+        assert_error(variant_from_json(r#"{ "piece_types": [{ "ids": { "white": "K" } }, { "ids": { "black": "K" } }] }"#), "piece_types[1].ids");
+        assert_error(variant_from_json(r#"{ "piece_types": [{ "ids": { "white": "Q" }, "promo_vals": [["Z"], []] }] }"#), "piece_types[0].promo_vals");
+        assert_error(variant_from_json(r#"{ "piece_types": [{ "ids": { "white": "P" }, "win_squares": [[8, 0]] }] }"#), "piece_types[0].win_squares");
+    }
+}
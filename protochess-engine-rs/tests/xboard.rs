@@ -0,0 +1,227 @@
+extern crate protochess_engine_rs;
+
+// The front-end is a binary, so its source is included here to test it without starting a process
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../src/bin/xboard.rs"]
+mod xboard_bin;
+
+#[cfg(test)]
+mod xboard {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc;
+    use std::thread;
+    use instant::Duration;
+
+    use protochess_engine_rs::GameState;
+    use protochess_engine_rs::position::create::variant_factory::VariantFactory;
+    use protochess_engine_rs::types::GameMode;
+
+    use super::xboard_bin::{XBoardEngine, Event, xboard_fen};
+
+    #[test]
+    fn allocate_time_fixed() {
+        let mut xboard = new_engine();
+        xboard.handle_command("st 2");
+        // Minus the communication overhead
+        assert_eq!(xboard.allocate_time(), Duration::from_millis(1950));
+    }
+
+    #[test]
+    fn invalid_times() {
+        let mut xboard = new_engine();
+        for command in ["st inf", "st NaN", "st 1e300", "st -5", "level 40 5 inf", "level 40 5 1e300", "time 99999999999999999"] {
+            xboard.handle_command(command);
+            assert!(xboard.allocate_time() <= Duration::from_secs(7 * 24 * 60 * 60));
+        }
+        xboard.handle_command("st -5");
+        assert_eq!(xboard.allocate_time(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn allocate_time_counts_own_moves() {
+        let mut xboard = new_engine();
+        xboard.handle_command("force");
+        xboard.handle_command("level 40 5 0");
+        xboard.handle_command("time 30000");
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(300) / 40);
+        for mv in ["e2e4", "e7e5", "g1f3"] {
+            xboard.handle_command(mv);
+        }
+        // Black has played 1 move
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(300) / 39);
+        xboard.handle_command("b8c6");
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(300) / 38);
+    }
+
+    #[test]
+    fn allocate_time_black_starts() {
+        let mut xboard = new_engine();
+        xboard.handle_command("force");
+        xboard.handle_command("level 10 1 0");
+        xboard.handle_command("setboard rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 7");
+        xboard.handle_command("e7e5");
+        // White hasn't played yet, even if the fullmove number has changed
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(60) / 10);
+        xboard.handle_command("g1f3");
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(60) / 9);
+    }
+
+    #[test]
+    fn allocate_time_several_moves_per_turn() {
+        let mut xboard = new_engine();
+        xboard.handle_command("force");
+        xboard.handle_command("level 40 5 0");
+        let initial_state = VariantFactory::new(GameMode::Marseillais).make_initial_state();
+        let state = GameState { initial_state, initial_fen: None, move_history: vec![] };
+        let _ = xboard.engine.set_state(state).unwrap();
+        // White plays 1 move in the first turn, then each player plays 2 moves per turn
+        for mv in ["e2e4", "e7e5", "d7d5", "d2d4", "g1f3", "b8c6", "g8f6"] {
+            xboard.handle_command(mv);
+        }
+        assert_eq!(xboard.engine.get_move_history().len(), 7);
+        // White has played 2 turns
+        assert_eq!(xboard.allocate_time(), Duration::from_secs(300) / 38);
+    }
+
+    #[test]
+    fn engine_plays_whole_turn() {
+        let (sender, receiver) = mpsc::channel();
+        let mut xboard = XBoardEngine::new(sender);
+        xboard.handle_command("sd 1");
+        let initial_state = VariantFactory::new(GameMode::Marseillais).make_initial_state();
+        let state = GameState { initial_state, initial_fen: None, move_history: vec![] };
+        let _ = xboard.engine.set_state(state).unwrap();
+        // The engine plays black, which has 2 moves per turn
+        xboard.handle_command("e2e4");
+        while xboard.engine.player_to_move() == 1 {
+            match receiver.recv_timeout(Duration::from_secs(30)).expect("The engine didn't move") {
+                Event::SearchDone(id, result) => xboard.search_done(id, result),
+                Event::Command(_) => unreachable!(),
+            }
+        }
+        assert_eq!(xboard.engine.get_move_history().len(), 3);
+    }
+
+    #[test]
+    fn fen_castling_rights() {
+        let mut xboard = new_engine();
+        xboard.handle_command("force");
+        assert!(xboard_fen(&mut xboard.engine).ends_with(" w KQkq - 0 1"));
+        for mv in ["e2e4", "e7e5", "e1e2", "h7h6", "g1f3", "h8h7"] {
+            xboard.handle_command(mv);
+        }
+        assert_eq!(xboard_fen(&mut xboard.engine), "rnbqkbn1/pppp1ppr/7p/4p3/4P3/5N2/PPPPKPPP/RNBQ1B1R w q - 2 4");
+        xboard.handle_command("setboard r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1");
+        assert!(xboard_fen(&mut xboard.engine).ends_with(" b Kq - 0 1"));
+        xboard.handle_command("setboard r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+        assert!(xboard_fen(&mut xboard.engine).ends_with(" w - - 0 1"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn load_variant_file() {
+        let mut xboard = new_engine();
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/variants/capablanca.toml");
+        xboard.handle_command(&format!("option VariantFile={}", path.display()));
+        assert_eq!(xboard.engine.get_state().initial_state.board_width, 10);
+        xboard.handle_command("variant normal");
+        assert_eq!(xboard.engine.get_state().initial_state.board_width, 8);
+        xboard.handle_command("variant capablanca");
+        assert_eq!(xboard.engine.get_state().initial_state.board_width, 10);
+        // "new" goes back to standard chess
+        xboard.handle_command("new");
+        assert_eq!(xboard.engine.get_state().initial_state.board_width, 8);
+
+        assert!(xboard.load_variant_file("missing.toml").is_err());
+    }
+
+    #[test]
+    fn driver_play_move() {
+        let output = run_xboard(&[], &["xboard", "protover 2", "new", "sd 2", "e2e4"], "move ");
+        assert!(output.iter().any(|l| l == "feature option=\"VariantFile -file \""));
+        assert!(output.iter().any(|l| l.starts_with("feature variants=\"normal,")));
+        assert!(output.last().unwrap().starts_with("move "));
+    }
+
+    #[test]
+    fn driver_illegal_move_and_variant() {
+        let output = run_xboard(&[], &["new", "force", "usermove e2e5", "variant duck", "ping 1"], "pong");
+        assert!(output.iter().any(|l| l == "Illegal move: e2e5"));
+        assert!(output.iter().any(|l| l.starts_with("setup (")));
+        assert_eq!(output.last().unwrap(), "pong 1");
+    }
+
+    #[test]
+    fn driver_several_moves_per_turn() {
+        // The engine plays both moves of black's turn
+        let commands = ["protover 2", "new", "variant marseillais", "sd 1", "e2e4"];
+        let output = run_xboard_until(&[], &commands, |output| output.iter().filter(|l| l.starts_with("move ")).count() == 2);
+        assert!(output.iter().any(|l| l.starts_with("feature variants=\"") && l.contains(",marseillais,progressive,monster")));
+        assert_eq!(output.iter().filter(|l| l.starts_with("move ")).count(), 2, "{:?}", output);
+    }
+
+    #[test]
+    fn driver_unsupported_variant() {
+        let output = run_xboard(&[], &["variant capablanca", "ping 2"], "pong");
+        assert_eq!(output, vec!["Error (unsupported variant): capablanca", "pong 2"]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn driver_variant_argument() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../docs/variants/capablanca.toml");
+        let path = path.to_str().unwrap();
+        let output = run_xboard(&["--variant", path], &["protover 2", "variant capablanca", "force", "go"], "move ");
+        assert!(output.iter().any(|l| l.starts_with("feature variants=\"") && l.ends_with(",capablanca\"")));
+        assert!(output.iter().any(|l| l.starts_with("setup (") && l.contains(" 10x8+0_fairy ")));
+        assert!(output.last().unwrap().starts_with("move "));
+    }
+
+    fn new_engine() -> XBoardEngine {
+        let (sender, _receiver) = mpsc::channel();
+        XBoardEngine::new(sender)
+    }
+
+    /// Runs the XBoard binary with the given arguments and commands, and returns the output lines
+    /// up to the first line that starts with `last_line_prefix`
+    fn run_xboard(args: &[&str], commands: &[&str], last_line_prefix: &str) -> Vec<String> {
+        run_xboard_until(args, commands, |output| output.last().is_some_and(|l| l.starts_with(last_line_prefix)))
+    }
+
+    /// Runs the XBoard binary with the given arguments and commands, and returns the output lines
+    /// until `done` returns `true`
+    fn run_xboard_until(args: &[&str], commands: &[&str], done: impl Fn(&[String]) -> bool) -> Vec<String> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_xboard"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        for command in commands {
+            writeln!(stdin, "{command}").unwrap();
+        }
+        let mut output = Vec::new();
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).expect("The engine didn't answer");
+            output.push(line);
+            if done(&output) {
+                break;
+            }
+        }
+        writeln!(stdin, "quit").unwrap();
+        child.wait().unwrap();
+        output
+    }
+}
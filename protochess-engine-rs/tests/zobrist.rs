@@ -337,6 +337,23 @@ mod zobrist_test {
         assert_eq!(engine1.get_zobrist(), engine2.get_zobrist());
     }
 
+    #[test]
+    fn neutral_pieces_affect_zobrist() {
+        let mut engine1 = build_engine_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[@] w KQkq - 0 1 DUCK");
+        let zob_start = engine1.get_zobrist();
+        assert_ne!(zob_start, build_engine_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 DUCK").get_zobrist());
+        for m in ["e2e4,@e6", "d7d5,@f3"] {
+            assert_eq!(engine1.make_move(&MoveInfo::try_from(m).unwrap()).flag, MakeMoveResultFlag::Ok);
+        }
+        let engine2 = build_engine_from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/5@2/PPPP1PPP/RNBQKBNR w KQkq d6 0 2 DUCK");
+        let engine3 = build_engine_from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/4@3/PPPP1PPP/RNBQKBNR w KQkq d6 0 2 DUCK");
+        assert_eq!(engine1.get_zobrist(), engine2.get_zobrist());
+        assert_ne!(engine1.get_zobrist(), engine3.get_zobrist());
+        engine1.undo().unwrap();
+        engine1.undo().unwrap();
+        assert_eq!(engine1.get_zobrist(), zob_start);
+    }
+    
     fn test_zobrist_sequence(moves: &[&str], expected_fen: &str) {
         let mut engine1 = Engine::default();
         let mut engine2 = Engine::default();
@@ -73,6 +73,11 @@ impl Protochess {
         let (best_move, eval, depth) = self.engine.get_best_move_timeout(time as u64)?;
         Ok(MoveInfoWithEvalDepthSer::to_js(best_move, eval, depth))
     }
+    #[wasm_bindgen(js_name = getBestMoveSampled)]
+    pub fn get_best_move_sampled(&mut self, depth: u8, num_samples: u32) -> Result<JsValue, String> {
+        let (best_move, eval) = self.engine.get_best_move_sampled(depth, num_samples)?;
+        Ok(MoveInfoWithEvalSer::to_js(best_move, eval))
+    }
     
     #[wasm_bindgen(js_name = setState)]
     pub fn set_state(&mut self, state: JsValue) -> Result<JsValue, String> {
@@ -98,6 +103,16 @@ impl Protochess {
         let state = self.engine.get_state_diff();
         StateDiffSer::to_js(state)
     }
+    #[wasm_bindgen(js_name = getStateDiffForPlayer)]
+    pub fn get_state_diff_for_player(&mut self, player: u8) -> Result<JsValue, String> {
+        let state = self.engine.get_state_diff_for_player(player)?;
+        Ok(StateDiffSer::to_js(state))
+    }
+    #[wasm_bindgen(js_name = visibleSquares)]
+    pub fn visible_squares(&mut self, player: u8) -> Result<JsValue, String> {
+        let squares: SerVec<(u8, u8)> = self.engine.visible_squares(player)?.into();
+        Ok(to_value(&squares).unwrap())
+    }
     
     #[wasm_bindgen(js_name = getMoveHistory)]
     pub fn get_move_history(&mut self) -> JsValue {
@@ -44,7 +44,10 @@ macro_rules! generate_wrapper {
 generate_wrapper!(MoveInfoSer, MoveInfo, [
     from, (u8, u8),
     to, (u8, u8),
-    promotion, Option<char>
+    promotion, Option<char>,
+    drop, Option<char>,
+    intermediate, Option<(u8, u8)>,
+    neutral_to, Option<(u8, u8)>
 ]);
 
 generate_wrapper!(MoveListSer, MoveList, [
@@ -58,7 +61,8 @@ generate_wrapper!(MakeMoveResultSer, MakeMoveResult, [
     flag, String,
     winner, String,
     exploded, SerVec<(u8, u8)>,
-    move_notation, Option<String>
+    move_notation, Option<String>,
+    turn_continues, bool
 ]);
 
 
@@ -101,13 +105,24 @@ generate_wrapper!(PieceDefinitionSer, PieceDefinition, [
     notation_prefix, [Option<String>; 2],
     is_leader, bool,
     castle_files, Option<(u8, u8)>,
+    castle_partner_files, Option<(u8, u8)>,
+    castle_vertically, bool,
     is_castle_rook, bool,
     explode_on_capture, bool,
     explosion_deltas, Vec<(i8, i8)>,
     immune_to_explosion, bool,
+    capture_by_withdrawal, bool,
+    capture_by_approach, bool,
+    custodian_capture, bool,
+    rifle_capture, bool,
     promotion_squares, Vec<(u8, u8)>,
     promo_vals, [Vec<char>; 2],
+    optional_promotion_squares, Vec<(u8, u8)>,
+    promote_to_captured_only, bool,
     double_jump_squares, Vec<(u8, u8)>,
+    initial_moves, Vec<Vec<(i8, i8)>>,
+    drop_squares, Vec<(u8, u8)>,
+    allowed_squares, [Vec<(u8, u8)>; 2],
     attack_sliding_deltas, Vec<Vec<(i8, i8)>>,
     attack_jump_deltas, Vec<(i8, i8)>,
     attack_north, bool,
@@ -128,18 +143,151 @@ generate_wrapper!(PieceDefinitionSer, PieceDefinition, [
     translate_northwest, bool,
     translate_southeast, bool,
     translate_southwest, bool,
-    win_squares, Vec<(u8, u8)>
+    range_north, u8,
+    range_south, u8,
+    range_east, u8,
+    range_west, u8,
+    range_northeast, u8,
+    range_northwest, u8,
+    range_southeast, u8,
+    range_southwest, u8,
+    translate_lame_jumps, Vec<Vec<(i8, i8)>>,
+    attack_lame_jumps, Vec<Vec<(i8, i8)>>,
+    translate_hop_deltas, Vec<(i8, i8)>,
+    attack_hop_deltas, Vec<(i8, i8)>,
+    hop_lands_behind_hurdle, bool,
+    multi_leg_deltas, Vec<((i8, i8), (i8, i8))>,
+    translate_bent_riders, Vec<((i8, i8), (i8, i8))>,
+    attack_bent_riders, Vec<((i8, i8), (i8, i8))>,
+    win_squares, Vec<(u8, u8)>,
+    lose_on_extinction, bool,
+    resets_move_clock, bool
 ]);
 
 generate_wrapper!(GlobalRulesSer, GlobalRules, [
     capturing_is_forced, bool,
     check_is_forbidden, bool,
+    ignore_checks, bool,
     stalemated_player_loses, bool,
+    stalemated_player_wins, bool,
     invert_win_conditions, bool,
     repetitions_draw, u8,
-    checks_to_lose, u8
+    checks_to_lose, u8,
+    n_move_rule, u16,
+    captures_to_hand, bool,
+    demote_promoted_pieces, bool,
+    free_castling, bool,
+    relocate_neutral_pieces, bool,
+    royalty_policy, RoyaltyPolicySer,
+    moves_per_turn, MovesPerTurnSer,
+    mid_turn_check, MidTurnCheckSer,
+    en_passant_any_move_of_turn, bool,
+    game_end_rules, SerVec<GameEndRuleSer>
 ]);
 
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GameEndRuleSer {
+    CapturedMaterial(i32),
+    #[serde(rename_all = "camelCase")]
+    HoldSquares { squares: [Vec<(u8, u8)>; 2], turns: u8 },
+    MoveLimit(u16),
+}
+impl From<GameEndRule> for GameEndRuleSer {
+    fn from(rule: GameEndRule) -> Self {
+        match rule {
+            GameEndRule::CapturedMaterial(material) => Self::CapturedMaterial(material),
+            GameEndRule::HoldSquares { squares, turns } => Self::HoldSquares { squares, turns },
+            GameEndRule::MoveLimit(limit) => Self::MoveLimit(limit),
+        }
+    }
+}
+impl From<GameEndRuleSer> for GameEndRule {
+    fn from(rule: GameEndRuleSer) -> Self {
+        match rule {
+            GameEndRuleSer::CapturedMaterial(material) => Self::CapturedMaterial(material),
+            GameEndRuleSer::HoldSquares { squares, turns } => Self::HoldSquares { squares, turns },
+            GameEndRuleSer::MoveLimit(limit) => Self::MoveLimit(limit),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoyaltyPolicySer {
+    AllCaptured,
+    AnyCapturedOrAllAttacked,
+    EverySafe,
+}
+impl From<RoyaltyPolicy> for RoyaltyPolicySer {
+    fn from(policy: RoyaltyPolicy) -> Self {
+        match policy {
+            RoyaltyPolicy::AllCaptured => Self::AllCaptured,
+            RoyaltyPolicy::AnyCapturedOrAllAttacked => Self::AnyCapturedOrAllAttacked,
+            RoyaltyPolicy::EverySafe => Self::EverySafe,
+        }
+    }
+}
+impl From<RoyaltyPolicySer> for RoyaltyPolicy {
+    fn from(policy: RoyaltyPolicySer) -> Self {
+        match policy {
+            RoyaltyPolicySer::AllCaptured => Self::AllCaptured,
+            RoyaltyPolicySer::AnyCapturedOrAllAttacked => Self::AnyCapturedOrAllAttacked,
+            RoyaltyPolicySer::EverySafe => Self::EverySafe,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MovesPerTurnSer {
+    #[serde(rename_all = "camelCase")]
+    Fixed { moves: [u8; 2], single_first_move: bool },
+    Progressive,
+}
+impl From<MovesPerTurn> for MovesPerTurnSer {
+    fn from(moves: MovesPerTurn) -> Self {
+        match moves {
+            MovesPerTurn::Fixed { moves, single_first_move } => Self::Fixed { moves, single_first_move },
+            MovesPerTurn::Progressive => Self::Progressive,
+        }
+    }
+}
+impl From<MovesPerTurnSer> for MovesPerTurn {
+    fn from(moves: MovesPerTurnSer) -> Self {
+        match moves {
+            MovesPerTurnSer::Fixed { moves, single_first_move } => Self::Fixed { moves, single_first_move },
+            MovesPerTurnSer::Progressive => Self::Progressive,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidTurnCheckSer {
+    EndsTurn,
+    OnlyLastMove,
+    EndOfTurn,
+}
+impl From<MidTurnCheck> for MidTurnCheckSer {
+    fn from(check: MidTurnCheck) -> Self {
+        match check {
+            MidTurnCheck::EndsTurn => Self::EndsTurn,
+            MidTurnCheck::OnlyLastMove => Self::OnlyLastMove,
+            MidTurnCheck::EndOfTurn => Self::EndOfTurn,
+        }
+    }
+}
+impl From<MidTurnCheckSer> for MidTurnCheck {
+    fn from(check: MidTurnCheckSer) -> Self {
+        match check {
+            MidTurnCheckSer::EndsTurn => Self::EndsTurn,
+            MidTurnCheckSer::OnlyLastMove => Self::OnlyLastMove,
+            MidTurnCheckSer::EndOfTurn => Self::EndOfTurn,
+        }
+    }
+}
+
 generate_wrapper!(InitialStateSer, InitialState, [
     fen, String,
     player_to_move, u8,